@@ -0,0 +1,729 @@
+//! The entries an org agenda shows for a range of days, gathered from one or
+//! more documents: scheduled items, deadlines and their warnings, and the
+//! active timestamps in headlines and their sections.
+//!
+//! Like org, items that are late, overdue or about to be due are only shown
+//! on today, so a range that doesn't contain today has none of them.
+//!
+//! An `Agenda` holds the entries of a range to write them out, as an org
+//! outline, an HTML fragment or JSON. An `AgendaIndex` builds them from the
+//! documents of a `DocumentSet`, filtered by file, category, tag and TODO
+//! keyword.
+
+use std::path::Path;
+
+use store::DocumentSet;
+use timestamp;
+use {Date, Document, Headline, Repeater, Time, TimeUnit, Timestamp};
+
+/// How many days before a deadline it shows up on today, unless its
+/// timestamp has a warning delay like `-3d`.
+pub const DEFAULT_WARNING_DAYS: i64 = 14;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+    /// On the day the headline is scheduled for.
+    Scheduled,
+    /// On today, for a headline scheduled this many days ago and not done.
+    ScheduledLate(i64),
+    /// On the day of the deadline.
+    Deadline,
+    /// On today, for a deadline this many days in the future.
+    DeadlineWarning(i64),
+    /// On today, for a deadline this many days ago that isn't done.
+    DeadlineOverdue(i64),
+    /// An active timestamp outside the planning line, on its day or on each
+    /// day of a date range.
+    Timestamp,
+}
+
+/// Something shown on one day of the agenda.
+#[derive(Debug, Clone)]
+pub struct Entry<'a> {
+    pub date: Date,
+    pub time: Option<Time>,
+    pub kind: EntryKind,
+    /// The timestamp as written in the document.
+    pub timestamp: Timestamp,
+    /// The index of the headline's document in those given to `agenda`.
+    pub document: usize,
+    pub headline: &'a Headline,
+    /// Whether the headline has one of its document's done keywords.
+    pub done: bool,
+}
+
+impl<'a> Entry<'a> {
+    /// What the entry is, like `Scheduled` or `Deadline in 3 days`, or
+    /// `None` for a plain timestamp.
+    pub fn label(&self) -> Option<String> {
+        let days = |n: i64| if n == 1 { "1 day".to_string() } else { format!("{} days", n) };
+        match self.kind {
+            EntryKind::Scheduled => Some("Scheduled".into()),
+            EntryKind::ScheduledLate(late) => Some(format!("Scheduled {} ago", days(late))),
+            EntryKind::Deadline => Some("Deadline".into()),
+            EntryKind::DeadlineWarning(until) => Some(format!("Deadline in {}", days(until))),
+            EntryKind::DeadlineOverdue(late) => Some(format!("Deadline {} ago", days(late))),
+            EntryKind::Timestamp => None,
+        }
+    }
+}
+
+/// The agenda for a range of days, to render it as a dated outline, a web
+/// page or JSON for other programs.
+#[derive(Debug, Clone)]
+pub struct Agenda<'a> {
+    start: Date,
+    end: Date,
+    entries: Vec<Entry<'a>>,
+}
+
+impl<'a> Agenda<'a> {
+    /// The agenda of `documents` from `start` to `end`, as `agenda` gives it.
+    pub fn new(documents: &[&'a Document], start: Date, end: Date, today: Date) -> Agenda<'a> {
+        Agenda { start, end, entries: agenda(documents, start, end, today) }
+    }
+
+    pub fn start(&self) -> Date {
+        self.start
+    }
+
+    pub fn end(&self) -> Date {
+        self.end
+    }
+
+    pub fn entries(&self) -> &[Entry<'a>] {
+        &self.entries
+    }
+
+    /// Each day from `start` to `end`, with its entries in order, days
+    /// without entries included.
+    pub fn days(&self) -> Vec<(Date, &[Entry<'a>])> {
+        let mut days = Vec::new();
+        let mut date = self.start;
+        let mut rest = &self.entries[..];
+        while date <= self.end {
+            let count = rest.iter().take_while(|entry| entry.date == date).count();
+            days.push((date, &rest[..count]));
+            rest = &rest[count..];
+            date = date.add_days(1);
+        }
+        days
+    }
+
+    /// The agenda as an org outline, with a headline for each day and under
+    /// it one for each entry, followed by what the entry is and its
+    /// timestamp, made inactive so the outline doesn't show up in agendas
+    /// itself.
+    pub fn to_org(&self) -> String {
+        let mut out = String::new();
+        for (date, entries) in self.days() {
+            out.push_str(&format!("* {} {}\n", date, timestamp::day_name(date)));
+            for entry in entries {
+                let headline = entry.headline;
+                out.push_str("** ");
+                if let Some(keyword) = headline.keyword() {
+                    out.push_str(keyword);
+                    out.push(' ');
+                }
+                out.push_str(headline.title());
+                if !headline.tags().is_empty() {
+                    let tags: Vec<&str> = headline.tags().iter().map(|tag| tag.as_str()).collect();
+                    out.push_str(&format!(" :{}:", tags.join(":")));
+                }
+                let mut timestamp = entry.timestamp.clone();
+                timestamp.set_active(false);
+                match entry.label() {
+                    Some(label) => out.push_str(&format!("\n   {}: {}\n", label, timestamp)),
+                    None => out.push_str(&format!("\n   {}\n", timestamp)),
+                }
+            }
+        }
+        out
+    }
+
+    /// The agenda as a JSON object with the range and a list of days, each
+    /// with its date and entries.
+    pub fn to_json(&self) -> String {
+        let days: Vec<String> = self.days().iter()
+            .map(|&(date, entries)| {
+                let entries: Vec<String> = entries.iter().map(json_entry).collect();
+                let list = if entries.is_empty() {
+                    "[]".to_string()
+                } else {
+                    format!("[\n{}\n    ]", entries.join(",\n"))
+                };
+                format!("    {{\"date\": \"{}\", \"entries\": {}}}", date, list)
+            })
+            .collect();
+        format!("{{\n  \"start\": \"{}\",\n  \"end\": \"{}\",\n  \"days\": [\n{}\n  ]\n}}\n",
+                self.start, self.end, days.join(",\n"))
+    }
+}
+
+fn json_entry(entry: &Entry) -> String {
+    let (kind, days) = match entry.kind {
+        EntryKind::Scheduled => ("scheduled", None),
+        EntryKind::ScheduledLate(days) => ("scheduled-late", Some(days)),
+        EntryKind::Deadline => ("deadline", None),
+        EntryKind::DeadlineWarning(days) => ("deadline-warning", Some(days)),
+        EntryKind::DeadlineOverdue(days) => ("deadline-overdue", Some(days)),
+        EntryKind::Timestamp => ("timestamp", None),
+    };
+    let optional = |value: Option<String>| value.unwrap_or_else(|| "null".into());
+    let headline = entry.headline;
+    let tags: Vec<String> = headline.tags().iter().map(|tag| quote(tag)).collect();
+    format!("      {{\"kind\": \"{}\", \"days\": {}, \"time\": {}, \"keyword\": {}, \
+             \"title\": {}, \"tags\": [{}], \"done\": {}, \"timestamp\": {}, \
+             \"document\": {}}}",
+            kind, optional(days.map(|days| days.to_string())),
+            optional(entry.time.map(|time| quote(&time.to_string()))),
+            optional(headline.keyword().map(quote)), quote(headline.title()), tags.join(", "),
+            entry.done, quote(&entry.timestamp.to_string()), entry.document)
+}
+
+/// `text` as a JSON string.
+fn quote(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// The agenda entries from `start` to `end`, inclusive, sorted by day. On
+/// each day entries with a time come first, in order of time, and the rest
+/// follow in document order.
+pub fn agenda<'a>(documents: &[&'a Document], start: Date, end: Date, today: Date)
+                  -> Vec<Entry<'a>> {
+    let index = AgendaIndex::build(documents.iter().map(|&document| (None, document)));
+    index.agenda(&AgendaFilter::new(), start, end, today).entries
+}
+
+/// Which headlines `AgendaIndex::agenda` shows. Each part left empty lets
+/// every headline through.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AgendaFilter {
+    files: Vec<String>,
+    categories: Vec<String>,
+    tags: Vec<String>,
+    excluded_tags: Vec<String>,
+    keywords: Vec<String>,
+}
+
+impl AgendaFilter {
+    pub fn new() -> AgendaFilter {
+        AgendaFilter::default()
+    }
+
+    /// Only the documents whose names match one of the patterns given, where
+    /// `*` matches anything but a `/`, `**` anything at all and `?` any one
+    /// character, like `work/*.org` or `**/projects.org`.
+    pub fn files<S: Into<String>>(mut self, pattern: S) -> Self {
+        self.files.push(pattern.into());
+        self
+    }
+
+    /// Only the headlines in one of the categories given, from their nearest
+    /// `CATEGORY` property, `#+CATEGORY:` or the name of their file.
+    pub fn category<S: Into<String>>(mut self, category: S) -> Self {
+        self.categories.push(category.into());
+        self
+    }
+
+    /// Only the headlines with every tag given, inherited ones included.
+    pub fn tag<S: Into<String>>(mut self, tag: S) -> Self {
+        self.tags.push(tag.into());
+        self
+    }
+
+    /// Leave out the headlines with `tag`, inherited ones included.
+    pub fn exclude_tag<S: Into<String>>(mut self, tag: S) -> Self {
+        self.excluded_tags.push(tag.into());
+        self
+    }
+
+    /// Only the headlines with one of the TODO keywords given.
+    pub fn keyword<S: Into<String>>(mut self, keyword: S) -> Self {
+        self.keywords.push(keyword.into());
+        self
+    }
+
+    fn matches(&self, item: &Item, name: Option<&str>) -> bool {
+        if !self.files.is_empty() {
+            let name: Vec<char> = name.unwrap_or("").chars().collect();
+            let matching = |pattern: &String| glob(&pattern.chars().collect::<Vec<_>>(), &name);
+            if !self.files.iter().any(matching) {
+                return false;
+            }
+        }
+        let category = item.category.as_ref();
+        let keyword = item.headline.keyword();
+        (self.categories.is_empty() || category.is_some_and(|c| self.categories.contains(c)))
+            && self.tags.iter().all(|tag| item.tags.contains(tag))
+            && !self.excluded_tags.iter().any(|tag| item.tags.contains(tag))
+            && (self.keywords.is_empty()
+                || keyword.is_some_and(|keyword| self.keywords.iter().any(|k| k == keyword)))
+    }
+}
+
+/// Whether `name` matches `pattern`, where `*` matches anything but a `/`,
+/// `**` anything at all, `**/` also nothing, and `?` any one character.
+fn glob(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some((&'*', rest)) if rest.first() == Some(&'*') => {
+            let rest = &rest[1..];
+            (0..=name.len()).any(|i| glob(rest, &name[i..]))
+                || (rest.first() == Some(&'/') && glob(&rest[1..], name))
+        }
+        Some((&'*', rest)) => {
+            (0..=name.len())
+                .take_while(|&i| i == 0 || name[i - 1] != '/')
+                .any(|i| glob(rest, &name[i..]))
+        }
+        Some((&'?', rest)) => !name.is_empty() && glob(rest, &name[1..]),
+        Some((&c, rest)) => name.first() == Some(&c) && glob(rest, &name[1..]),
+    }
+}
+
+/// The headlines of a `DocumentSet` that can show up in an agenda, with what
+/// an `AgendaFilter` looks at, so that agendas for other days and filters
+/// can be built without going through every document again.
+#[derive(Debug, Clone)]
+pub struct AgendaIndex<'a> {
+    names: Vec<Option<&'a str>>,
+    items: Vec<Item<'a>>,
+}
+
+/// A headline with a planning line or active timestamps.
+#[derive(Debug, Clone)]
+struct Item<'a> {
+    document: usize,
+    headline: &'a Headline,
+    done: bool,
+    /// Its tags, inherited ones included.
+    tags: Vec<String>,
+    category: Option<String>,
+    timestamps: Vec<Timestamp>,
+}
+
+impl<'a> AgendaIndex<'a> {
+    /// The index of `set`, whose documents `Entry::document` counts in the
+    /// order `DocumentSet::documents` gives them.
+    pub fn new(set: &'a DocumentSet) -> AgendaIndex<'a> {
+        AgendaIndex::build(set.documents().map(|(name, document)| (Some(name), document)))
+    }
+
+    fn build<I>(documents: I) -> AgendaIndex<'a>
+        where I: Iterator<Item = (Option<&'a str>, &'a Document)>
+    {
+        fn add<'a>(headlines: &'a [Headline], document: usize, done_keywords: &[String],
+                   inherited: &[String], category: Option<&str>, items: &mut Vec<Item<'a>>) {
+            for headline in headlines {
+                let mut tags = inherited.to_vec();
+                for tag in headline.tags() {
+                    if !tags.iter().any(|t| t == tag) {
+                        tags.push(tag.to_string());
+                    }
+                }
+                let category = headline.property("CATEGORY").or(category);
+                let timestamps = plain_timestamps(headline);
+                if headline.scheduled().is_some() || headline.deadline().is_some()
+                    || !timestamps.is_empty() {
+                    let done = headline.keyword()
+                        .is_some_and(|keyword| done_keywords.iter().any(|done| done == keyword));
+                    items.push(Item {
+                        document,
+                        headline,
+                        done,
+                        tags: tags.clone(),
+                        category: category.map(String::from),
+                        timestamps,
+                    });
+                }
+                add(headline.children(), document, done_keywords, &tags, category, items);
+            }
+        }
+
+        let mut names = Vec::new();
+        let mut items = Vec::new();
+        for (index, (name, document)) in documents.enumerate() {
+            let category = document.keywords().remove("CATEGORY").or_else(|| {
+                let stem = Path::new(name?).file_stem()?;
+                Some(stem.to_string_lossy().into_owned())
+            });
+            add(document.headlines(), index, document.done_keywords(), &document.file_tags(),
+                category.as_deref(), &mut items);
+            names.push(name);
+        }
+        AgendaIndex { names, items }
+    }
+
+    /// The agenda from `start` to `end` of the headlines `filter` lets
+    /// through, sorted as `agenda` sorts it.
+    pub fn agenda(&self, filter: &AgendaFilter, start: Date, end: Date, today: Date)
+                  -> Agenda<'a> {
+        let mut entries = Vec::new();
+        for item in &self.items {
+            if !filter.matches(item, self.names[item.document]) {
+                continue;
+            }
+            let headline = item.headline;
+            let mut days = Days {
+                start,
+                end,
+                today,
+                document: item.document,
+                headline,
+                done: item.done,
+                entries: &mut entries,
+            };
+            if let Some(scheduled) = headline.scheduled() {
+                days.scheduled(scheduled, item.done);
+            }
+            if let Some(deadline) = headline.deadline() {
+                days.deadline(deadline, item.done);
+            }
+            for timestamp in &item.timestamps {
+                days.timestamp(timestamp);
+            }
+        }
+        entries.sort_by_key(|entry| (entry.date, entry.time.is_none(), entry.time));
+        Agenda { start, end, entries }
+    }
+}
+
+/// Collects the entries of one headline.
+struct Days<'a, 'e> {
+    start: Date,
+    end: Date,
+    today: Date,
+    document: usize,
+    headline: &'a Headline,
+    done: bool,
+    entries: &'e mut Vec<Entry<'a>>,
+}
+
+impl<'a, 'e> Days<'a, 'e> {
+    fn push(&mut self, date: Date, kind: EntryKind, timestamp: &Timestamp) {
+        self.entries.push(Entry {
+            date,
+            time: timestamp.time(),
+            kind,
+            timestamp: timestamp.clone(),
+            document: self.document,
+            headline: self.headline,
+            done: self.done,
+        });
+    }
+
+    fn shows_today(&self) -> bool {
+        self.start <= self.today && self.today <= self.end
+    }
+
+    fn scheduled(&mut self, scheduled: &Timestamp, done: bool) {
+        let delay = scheduled.delay().map_or(0, |delay| days(delay.value, delay.unit));
+        let dates = occurrences(scheduled.date(), scheduled.repeater(), self.start, self.end);
+        if delay == 0 {
+            for &date in &dates {
+                self.push(date, EntryKind::Scheduled, scheduled);
+            }
+        }
+        let late = self.today.days_since(scheduled.date());
+        if !done && late > 0 && late >= delay && self.shows_today()
+            && (delay > 0 || !dates.contains(&self.today)) {
+            let today = self.today;
+            self.push(today, EntryKind::ScheduledLate(late), scheduled);
+        }
+    }
+
+    fn deadline(&mut self, deadline: &Timestamp, done: bool) {
+        let dates = occurrences(deadline.date(), deadline.repeater(), self.start, self.end);
+        for &date in &dates {
+            self.push(date, EntryKind::Deadline, deadline);
+        }
+        if done || !self.shows_today() || dates.contains(&self.today) {
+            return;
+        }
+        let warning = deadline.delay().map_or(DEFAULT_WARNING_DAYS, |d| days(d.value, d.unit));
+        let until = deadline.date().days_since(self.today);
+        let today = self.today;
+        if until < 0 {
+            self.push(today, EntryKind::DeadlineOverdue(-until), deadline);
+        } else if until <= warning {
+            self.push(today, EntryKind::DeadlineWarning(until), deadline);
+        }
+    }
+
+    fn timestamp(&mut self, timestamp: &Timestamp) {
+        match timestamp.end_date().filter(|&end| end > timestamp.date()) {
+            Some(last) => {
+                let mut date = timestamp.date().max(self.start);
+                while date <= last.min(self.end) {
+                    self.push(date, EntryKind::Timestamp, timestamp);
+                    date = date.add_days(1);
+                }
+            }
+            None => {
+                let repeater = timestamp.repeater();
+                for date in occurrences(timestamp.date(), repeater, self.start, self.end) {
+                    self.push(date, EntryKind::Timestamp, timestamp);
+                }
+            }
+        }
+    }
+}
+
+/// The active timestamps in the headline's title and section, leaving out
+/// its planning line.
+fn plain_timestamps(headline: &Headline) -> Vec<Timestamp> {
+    let mut timestamps = timestamp::find_timestamps(headline.title());
+    if let Some(section) = headline.section() {
+        let planning = headline.planning().span();
+        let text = section.text();
+        let base = section.span().start;
+        if planning.end > planning.start && section.span().contains(planning.start) {
+            let (before, after) = (planning.start - base, (planning.end - base).min(text.len()));
+            timestamps.extend(timestamp::find_timestamps(&text[..before]));
+            timestamps.extend(timestamp::find_timestamps(&text[after..]));
+        } else {
+            timestamps.extend(section.timestamps());
+        }
+    }
+    timestamps.retain(Timestamp::is_active);
+    timestamps
+}
+
+/// An interval in days. Hours round down, months count as 30 days and years
+/// as 365, as org does for warning delays.
+fn days(value: u32, unit: TimeUnit) -> i64 {
+    let value = value as i64;
+    match unit {
+        TimeUnit::Hour => value / 24,
+        TimeUnit::Day => value,
+        TimeUnit::Week => value * 7,
+        TimeUnit::Month => value * 30,
+        TimeUnit::Year => value * 365,
+    }
+}
+
+/// The days from `start` to `end` that a timestamp on `date` falls on,
+/// repeating from `date` onwards if it has a repeater. Hourly repeaters of
+/// less than a day don't repeat.
+fn occurrences(date: Date, repeater: Option<Repeater>, start: Date, end: Date) -> Vec<Date> {
+    let mut dates = Vec::new();
+    let repeater = match repeater.filter(|repeater| repeater.value > 0) {
+        Some(repeater) => repeater,
+        None => {
+            if start <= date && date <= end {
+                dates.push(date);
+            }
+            return dates;
+        }
+    };
+    let step = match repeater.unit {
+        TimeUnit::Month => Err(repeater.value as i64),
+        TimeUnit::Year => Err(repeater.value as i64 * 12),
+        unit => Ok(days(repeater.value, unit)),
+    };
+    match step {
+        Ok(0) => return occurrences(date, None, start, end),
+        Ok(step) => {
+            let skip = (start.days_since(date).max(0) + step - 1) / step;
+            let mut next = date.add_days(skip * step);
+            while next <= end {
+                dates.push(next);
+                next = next.add_days(step);
+            }
+        }
+        Err(months) => {
+            let mut count = 0;
+            let mut next = date;
+            while next <= end {
+                if next >= start {
+                    dates.push(next);
+                }
+                count += 1;
+                next = date.add_months(count * months);
+            }
+        }
+    }
+    dates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use DocumentParser;
+
+    fn day(day: u8) -> Date {
+        Date { year: 2024, month: 5, day }
+    }
+
+    fn summary(entries: &[Entry]) -> Vec<String> {
+        entries.iter()
+            .map(|entry| format!("{} {:?} {}", entry.date.day, entry.kind, entry.headline.title()))
+            .collect()
+    }
+
+    #[test]
+    fn test_agenda() {
+        let parser = DocumentParser::new().todo_keywords(vec!["TODO", "DONE"]);
+        let work = parser.parse("* TODO Report
+  DEADLINE: <2024-05-10 Fri -3d>
+* TODO Standup
+  SCHEDULED: <2024-05-01 Wed 09:30 +1w>
+* DONE Old deadline
+  DEADLINE: <2024-05-01 Wed>
+* TODO Taxes
+  DEADLINE: <2024-05-03 Fri>").unwrap();
+        let home = parser.parse("* TODO Water plants
+  SCHEDULED: <2024-05-05 Sun>
+* Trip
+<2024-05-08 Wed>--<2024-05-09 Thu>
+* Birthday
+Party on <2024-05-10 Fri 19:00>.").unwrap();
+        let entries = agenda(&[&work, &home], day(7), day(10), day(7));
+        assert_eq!(summary(&entries), [
+            "7 ScheduledLate(6) Standup",
+            "7 DeadlineWarning(3) Report",
+            "7 DeadlineOverdue(4) Taxes",
+            "7 ScheduledLate(2) Water plants",
+            "8 Scheduled Standup",
+            "8 Timestamp Trip",
+            "9 Timestamp Trip",
+            "10 Timestamp Birthday",
+            "10 Deadline Report",
+        ]);
+        assert_eq!(entries[0].time, Some(Time { hour: 9, minute: 30 }));
+        assert_eq!(entries[2].document, 0);
+        assert_eq!(entries[3].document, 1);
+
+        let entries = agenda(&[&work], day(7), day(7), day(4));
+        assert!(entries.is_empty());
+        let entries = agenda(&[&work], day(8), day(8), day(8));
+        assert_eq!(summary(&entries), [
+            "8 Scheduled Standup",
+            "8 DeadlineWarning(2) Report",
+            "8 DeadlineOverdue(5) Taxes",
+        ]);
+    }
+
+    #[test]
+    fn test_export() {
+        let parser = DocumentParser::new().todo_keywords(vec!["TODO", "DONE"]);
+        let doc = parser.parse("* TODO Standup :work:
+  SCHEDULED: <2024-05-01 Wed 09:30 +1w>
+* DONE Report \"final\"
+  DEADLINE: <2024-05-08 Wed>").unwrap();
+        let agenda = Agenda::new(&[&doc], day(7), day(8), day(7));
+        assert_eq!(agenda.to_org(), "* 2024-05-07 Tue
+** TODO Standup :work:
+   Scheduled 6 days ago: [2024-05-01 Wed 09:30 +1w]
+* 2024-05-08 Wed
+** TODO Standup :work:
+   Scheduled: [2024-05-01 Wed 09:30 +1w]
+** DONE Report \"final\"
+   Deadline: [2024-05-08 Wed]
+");
+        assert_eq!(agenda.to_html(), "<div class=\"org-agenda\">
+<h2 class=\"org-agenda-day\">2024-05-07 Tue</h2>
+<ul>
+<li><span class=\"org-agenda-time\">09:30</span> \
+<span class=\"org-agenda-label\">Scheduled 6 days ago</span> \
+<span class=\"org-todo\">TODO</span> Standup <span class=\"org-tag\">work</span></li>
+</ul>
+<h2 class=\"org-agenda-day\">2024-05-08 Wed</h2>
+<ul>
+<li><span class=\"org-agenda-time\">09:30</span> \
+<span class=\"org-agenda-label\">Scheduled</span> \
+<span class=\"org-todo\">TODO</span> Standup <span class=\"org-tag\">work</span></li>
+<li><span class=\"org-agenda-label\">Deadline</span> \
+<span class=\"org-done\">DONE</span> Report &quot;final&quot;</li>
+</ul>
+</div>
+");
+        let json = agenda.to_json();
+        assert!(json.starts_with("{\n  \"start\": \"2024-05-07\",\n  \"end\": \"2024-05-08\","));
+        assert!(json.contains("{\"kind\": \"scheduled-late\", \"days\": 6, \"time\": \"09:30\", \
+                               \"keyword\": \"TODO\", \"title\": \"Standup\", \
+                               \"tags\": [\"work\"], \"done\": false, \
+                               \"timestamp\": \"<2024-05-01 Wed 09:30 +1w>\", \
+                               \"document\": 0}"));
+        assert!(json.contains("\"days\": null, \"time\": null, \"keyword\": \"DONE\", \
+                               \"title\": \"Report \\\"final\\\"\""));
+    }
+
+    #[test]
+    fn test_index() {
+        let parser = DocumentParser::new().todo_keywords(vec!["TODO", "WAIT", "DONE"]);
+        let mut set = DocumentSet::new();
+        set.insert("work/projects.org", parser.parse("#+FILETAGS: :job:
+* TODO Report :urgent:
+  SCHEDULED: <2024-05-07 Tue>
+* Team
+  :PROPERTIES:
+  :CATEGORY: team
+  :END:
+** WAIT Review
+   SCHEDULED: <2024-05-07 Tue>
+* TODO Someday :someday:
+  SCHEDULED: <2024-05-07 Tue>").unwrap());
+        set.insert("home.org", parser.parse("#+CATEGORY: house
+* TODO Plants
+  SCHEDULED: <2024-05-07 Tue>").unwrap());
+        let index = AgendaIndex::new(&set);
+        let titles = |filter: AgendaFilter| -> Vec<&str> {
+            index.agenda(&filter, day(7), day(7), day(7)).entries().iter()
+                .map(|entry| entry.headline.title()).collect()
+        };
+        assert_eq!(titles(AgendaFilter::new()), ["Report", "Review", "Someday", "Plants"]);
+        assert_eq!(titles(AgendaFilter::new().files("work/*.org")),
+                   ["Report", "Review", "Someday"]);
+        assert_eq!(titles(AgendaFilter::new().files("**/home.org")), ["Plants"]);
+        assert_eq!(titles(AgendaFilter::new().category("team").category("house")),
+                   ["Review", "Plants"]);
+        assert_eq!(titles(AgendaFilter::new().category("projects")), ["Report", "Someday"]);
+        assert_eq!(titles(AgendaFilter::new().tag("job").exclude_tag("someday")),
+                   ["Report", "Review"]);
+        assert_eq!(titles(AgendaFilter::new().keyword("WAIT")), ["Review"]);
+        assert_eq!(index.agenda(&AgendaFilter::new(), day(8), day(8), day(8)).entries().len(), 4);
+    }
+
+    #[test]
+    fn test_glob() {
+        let glob = |pattern: &str, name: &str| {
+            super::glob(&pattern.chars().collect::<Vec<_>>(), &name.chars().collect::<Vec<_>>())
+        };
+        assert!(glob("*.org", "a.org"));
+        assert!(!glob("*.org", "dir/a.org"));
+        assert!(glob("**/*.org", "dir/sub/a.org"));
+        assert!(glob("**/*.org", "a.org"));
+        assert!(glob("notes-?.org", "notes-1.org"));
+        assert!(!glob("notes-?.org", "notes-12.org"));
+    }
+
+    #[test]
+    fn test_occurrences() {
+        let date = |month, day| Date { year: 2024, month, day };
+        let monthly = Repeater {
+            kind: ::RepeaterKind::Cumulative,
+            value: 1,
+            unit: TimeUnit::Month,
+        };
+        let dates = occurrences(date(1, 31), Some(monthly), date(2, 1), date(4, 30));
+        assert_eq!(dates, [date(2, 29), date(3, 31), date(4, 30)]);
+        let weekly = Repeater { kind: ::RepeaterKind::CatchUp, value: 2, unit: TimeUnit::Week };
+        assert_eq!(occurrences(day(1), Some(weekly), day(2), day(31)), [day(15), day(29)]);
+        assert!(occurrences(day(1), None, day(2), day(31)).is_empty());
+    }
+}
@@ -0,0 +1,217 @@
+//! Data of any type hung off the nodes of a document, like the overlays or
+//! cached export fragments of an editor, kept outside the tree so the node
+//! types don't need fields for it. After a reparse, `AnnotationMap::remap`
+//! moves the annotations of each headline to the headline it became, as
+//! `diff` matches them.
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+
+use diff;
+use {Content, Document, Headline, Section};
+
+/// A node of a document: a headline by its indices among its siblings from
+/// the top level down, like `kanban::Card::path`, one of the contents of its
+/// section, or the document itself and the contents of its first section
+/// for an empty path.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeKey {
+    headline: Vec<usize>,
+    content: Option<usize>,
+}
+
+impl NodeKey {
+    pub fn document() -> NodeKey {
+        NodeKey { headline: Vec::new(), content: None }
+    }
+
+    pub fn headline(path: Vec<usize>) -> NodeKey {
+        NodeKey { headline: path, content: None }
+    }
+
+    /// The key of `headline`, which must be one of the headlines of
+    /// `document` itself rather than a copy, or `None` if it isn't.
+    pub fn of_headline(document: &Document, headline: &Headline) -> Option<NodeKey> {
+        document.cursor(headline).map(|cursor| NodeKey::headline(cursor.path().to_vec()))
+    }
+
+    /// The key of the content at `index` in the section of this headline, or
+    /// of the first section for the document.
+    pub fn content(&self, index: usize) -> NodeKey {
+        NodeKey { headline: self.headline.clone(), content: Some(index) }
+    }
+
+    /// The path of the headline, empty for the document and its first
+    /// section.
+    pub fn path(&self) -> &[usize] {
+        &self.headline
+    }
+
+    /// The index of the content in its section, if the key is for one.
+    pub fn content_index(&self) -> Option<usize> {
+        self.content
+    }
+
+    /// The headline in `document` the key is for or that holds its content,
+    /// or `None` for the document and its first section.
+    pub fn resolve_headline<'a>(&self, document: &'a Document) -> Option<&'a Headline> {
+        document.cursor_at(&self.headline).map(|cursor| cursor.headline())
+    }
+
+    /// The content in `document` the key is for, if it is for one.
+    pub fn resolve_content<'a>(&self, document: &'a Document) -> Option<&'a Content> {
+        let index = self.content?;
+        self.section(document)?.contents().get(index)
+    }
+
+    fn section<'a>(&self, document: &'a Document) -> Option<&'a Section> {
+        if self.headline.is_empty() {
+            document.first_section()
+        } else {
+            self.resolve_headline(document)?.section()
+        }
+    }
+}
+
+/// Values of any type, at most one of each type per node.
+#[derive(Default)]
+pub struct AnnotationMap {
+    nodes: HashMap<NodeKey, HashMap<TypeId, Box<dyn Any>>>,
+}
+
+impl fmt::Debug for AnnotationMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map()
+            .entries(self.nodes.iter().map(|(key, values)| (key, values.len())))
+            .finish()
+    }
+}
+
+impl AnnotationMap {
+    pub fn new() -> AnnotationMap {
+        AnnotationMap::default()
+    }
+
+    /// Annotate `key` with `value`, returning the value of the same type it
+    /// replaces.
+    pub fn insert<T: Any>(&mut self, key: NodeKey, value: T) -> Option<T> {
+        self.nodes.entry(key).or_default()
+            .insert(TypeId::of::<T>(), Box::new(value))
+            .and_then(|old| old.downcast().ok())
+            .map(|old| *old)
+    }
+
+    pub fn get<T: Any>(&self, key: &NodeKey) -> Option<&T> {
+        self.nodes.get(key)?.get(&TypeId::of::<T>())?.downcast_ref()
+    }
+
+    pub fn get_mut<T: Any>(&mut self, key: &NodeKey) -> Option<&mut T> {
+        self.nodes.get_mut(key)?.get_mut(&TypeId::of::<T>())?.downcast_mut()
+    }
+
+    pub fn remove<T: Any>(&mut self, key: &NodeKey) -> Option<T> {
+        let values = self.nodes.get_mut(key)?;
+        let old = values.remove(&TypeId::of::<T>())?;
+        if values.is_empty() {
+            self.nodes.remove(key);
+        }
+        old.downcast().ok().map(|old| *old)
+    }
+
+    /// Remove every annotation of `key`.
+    pub fn clear(&mut self, key: &NodeKey) {
+        self.nodes.remove(key);
+    }
+
+    /// The annotated nodes, in no particular order.
+    pub fn keys(&self) -> impl Iterator<Item = &NodeKey> {
+        self.nodes.keys()
+    }
+
+    /// How many nodes have annotations.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Move the annotations from the nodes of `old` to those they became in
+    /// `new`, its reparse after an edit. Each headline's go to the headline
+    /// `diff` matches it with, and those of removed headlines are dropped.
+    /// The contents of a section keep their annotations only if its text
+    /// didn't change, since their indices may mean other contents after an
+    /// edit.
+    pub fn remap(&mut self, old: &Document, new: &Document) {
+        let mut paths: HashMap<Vec<usize>, Vec<usize>> = diff::matching_paths(old, new)
+            .into_iter()
+            .collect();
+        paths.insert(Vec::new(), Vec::new());
+        let nodes = std::mem::take(&mut self.nodes);
+        for (key, values) in nodes {
+            let path = match paths.get(&key.headline) {
+                Some(path) => path.clone(),
+                None => continue,
+            };
+            let new_key = NodeKey { headline: path, content: key.content };
+            if key.content.is_some() {
+                let text = |key: &NodeKey, document| key.section(document).map(Section::text);
+                if text(&key, old) != text(&new_key, new) {
+                    continue;
+                }
+            }
+            self.nodes.insert(new_key, values);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use DocumentParser;
+
+    #[derive(Debug, PartialEq)]
+    struct Folded(bool);
+
+    #[test]
+    fn test_annotations() {
+        let old = DocumentParser::new().parse("Intro.
+* Inbox
+** Call Sam
+Notes.
+* Projects
+Plans.
+").unwrap();
+        let mut annotations = AnnotationMap::new();
+        let call = NodeKey::of_headline(&old, &old.headlines()[0].children()[0]).unwrap();
+        assert_eq!(call.path(), [0, 0]);
+        annotations.insert(call.clone(), Folded(true));
+        annotations.insert(call.clone(), "overlay");
+        assert_eq!(annotations.insert(call.clone(), Folded(false)), Some(Folded(true)));
+        annotations.insert(NodeKey::headline(vec![1]).content(0), 42u32);
+        annotations.insert(NodeKey::document().content(0), "cached");
+        annotations.insert(NodeKey::headline(vec![0]), "removed");
+        assert_eq!(annotations.get::<&str>(&call), Some(&"overlay"));
+        assert_eq!(annotations.len(), 4);
+
+        let new = DocumentParser::new().parse("Intro.
+* Projects
+Plans.
+** Call Sam
+Notes, edited.
+").unwrap();
+        annotations.remap(&old, &new);
+        let call = NodeKey::headline(vec![0, 0]);
+        assert_eq!(call.resolve_headline(&new).unwrap().title(), "Call Sam");
+        assert_eq!(annotations.get::<Folded>(&call), Some(&Folded(false)));
+        let plans = NodeKey::headline(vec![0]).content(0);
+        assert!(plans.resolve_content(&new).is_some());
+        assert_eq!(annotations.get::<u32>(&plans), Some(&42));
+        assert_eq!(annotations.get::<&str>(&NodeKey::document().content(0)), Some(&"cached"));
+        assert_eq!(annotations.len(), 3);
+        assert_eq!(annotations.remove::<Folded>(&call), Some(Folded(false)));
+        assert_eq!(annotations.get::<Folded>(&call), None);
+    }
+}
@@ -0,0 +1,512 @@
+use block;
+use keyword::{Affiliate, Affiliated};
+use span::{self, Shift, Span};
+use {Block, BlockKind, Content, Document, Element, Headline};
+
+/// The header arguments org uses for every src block unless they are set,
+/// like `org-babel-default-header-args`.
+const DEFAULT_HEADER_ARGS: &str =
+    ":session none :results replace :exports code :cache no :noweb no :hlines no :tangle no";
+
+/// The groups of `:results` words, of which only one of each applies.
+const RESULTS_GROUPS: &[&[&str]] = &[
+    &["file", "list", "vector", "table", "scalar", "verbatim"],
+    &["raw", "html", "latex", "org", "code", "pp", "drawer", "link", "graphics"],
+    &["replace", "silent", "none", "discard", "append", "prepend"],
+    &["output", "value"],
+];
+
+/// Header arguments of a src block or call, like `:var x=1 :results
+/// output :tangle main.rs`, in order of precedence, so that later ones
+/// replace earlier ones with the same key.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct HeaderArgs {
+    args: Vec<(String, String)>,
+}
+
+/// Where `:tangle` says to write a src block.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum Tangle {
+    /// `:tangle no`, the default.
+    No,
+    /// `:tangle yes`, to the file named like the org file with the extension
+    /// of the block's language.
+    Yes,
+    /// `:tangle path`.
+    File(String),
+}
+
+/// When `:noweb` says to expand `<<name>>` references to other blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum Noweb {
+    /// `:noweb no`, the default.
+    No,
+    /// `:noweb yes`, always.
+    Yes,
+    /// `:noweb tangle`, only when tangling.
+    Tangle,
+    /// `:noweb no-export`, except when exporting.
+    NoExport,
+    /// `:noweb strip-export`, except when exporting, where references are
+    /// removed.
+    StripExport,
+    /// `:noweb strip-tangle`, except when tangling, where references are
+    /// removed.
+    StripTangle,
+    /// `:noweb eval`, only when evaluating.
+    Eval,
+}
+
+impl Noweb {
+    /// Whether references are expanded when tangling.
+    pub fn expands_on_tangle(self) -> bool {
+        matches!(self, Noweb::Yes | Noweb::Tangle | Noweb::NoExport | Noweb::StripExport)
+    }
+}
+
+/// Split `list` at the commas outside of quotes and brackets.
+fn split_top_level(list: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let (mut depth, mut quoted, mut start) = (0i32, false, 0);
+    for (i, c) in list.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '(' | '[' if !quoted => depth += 1,
+            ')' | ']' if !quoted => depth -= 1,
+            ',' if !quoted && depth == 0 => {
+                parts.push(list[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(list[start..].trim());
+    parts.into_iter().filter(|part| !part.is_empty()).collect()
+}
+
+impl HeaderArgs {
+    /// Parse `:key value` pairs, ignoring anything before the first key,
+    /// like the `-n` switch of `#+BEGIN_SRC rust -n :tangle main.rs`.
+    pub fn parse(text: &str) -> HeaderArgs {
+        let args = block::parse_plist(text).into_iter()
+            .filter_map(|(key, value)| {
+                let key = key.strip_prefix(':')?.to_ascii_lowercase();
+                Some((key, value)).filter(|(key, _)| !key.is_empty())
+            })
+            .collect();
+        HeaderArgs { args }
+    }
+
+    /// Org's defaults, which every src block starts from.
+    pub fn defaults() -> HeaderArgs {
+        HeaderArgs::parse(DEFAULT_HEADER_ARGS)
+    }
+
+    /// Add `other` after these, so that its arguments take precedence.
+    pub fn merge(&mut self, other: &HeaderArgs) {
+        self.args.extend(other.args.iter().cloned());
+    }
+
+    /// Every argument as `(key, value)` pairs, with keys in lowercase and
+    /// without their colon, in order of precedence.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.args.iter().map(|(k, v)| (&k[..], &v[..]))
+    }
+
+    /// The value of the last argument for `key`, like `dir` or `:dir`.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        let key = key.strip_prefix(':').unwrap_or(key);
+        self.args.iter().rev()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| &v[..])
+    }
+
+    /// The variables of every `:var`, like `:var x=1, y=table`, as names
+    /// and values as written. A later variable replaces an earlier one with
+    /// the same name.
+    pub fn vars(&self) -> Vec<(String, String)> {
+        let mut vars: Vec<(String, String)> = Vec::new();
+        let values = self.args.iter().filter(|(key, _)| key == "var").map(|(_, value)| value);
+        for assignment in values.flat_map(|value| split_top_level(value)) {
+            let (name, value) = match assignment.find('=') {
+                Some(equals) => (assignment[..equals].trim(), assignment[equals + 1..].trim()),
+                None => (assignment, ""),
+            };
+            vars.retain(|(existing, _)| existing != name);
+            vars.push((name.into(), value.into()));
+        }
+        vars
+    }
+
+    /// The words of every `:results`, like `output table replace`. A later
+    /// word replaces an earlier one of the same kind, so `:results output`
+    /// after `:results value silent` gives `silent output`.
+    pub fn results(&self) -> Vec<String> {
+        let mut words: Vec<String> = Vec::new();
+        let values = self.args.iter().filter(|(key, _)| key == "results").map(|(_, value)| value);
+        for word in values.flat_map(|value| value.split_whitespace()) {
+            let group = RESULTS_GROUPS.iter().find(|group| group.contains(&word));
+            words.retain(|existing| match group {
+                Some(group) => !group.contains(&&existing[..]),
+                None => existing != word,
+            });
+            words.push(word.into());
+        }
+        words
+    }
+
+    /// `code`, `results`, `both` or `none`.
+    pub fn exports(&self) -> Option<&str> {
+        self.get("exports")
+    }
+
+    /// The name of the `:session`, unless it is `none`.
+    pub fn session(&self) -> Option<&str> {
+        self.get("session").filter(|session| *session != "none")
+    }
+
+    pub fn tangle(&self) -> Tangle {
+        match self.get("tangle").map(|value| value.trim_matches('"')) {
+            None | Some("no") | Some("") => Tangle::No,
+            Some("yes") => Tangle::Yes,
+            Some(path) => Tangle::File(path.into()),
+        }
+    }
+
+    pub fn noweb(&self) -> Noweb {
+        match self.get("noweb") {
+            Some("yes") => Noweb::Yes,
+            Some("tangle") => Noweb::Tangle,
+            Some("no-export") => Noweb::NoExport,
+            Some("strip-export") => Noweb::StripExport,
+            Some("strip-tangle") => Noweb::StripTangle,
+            Some("eval") => Noweb::Eval,
+            _ => Noweb::No,
+        }
+    }
+
+    /// Whether `:cache yes` asks to reuse the results while the block is
+    /// unchanged.
+    pub fn cache(&self) -> bool {
+        self.get("cache") == Some("yes")
+    }
+}
+
+/// A call of a named src block, with the header arguments to use inside it
+/// and for its results:
+///
+/// ```ignore
+/// #+CALL: double[:session none](x=21)[:results silent]
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct BabelCall {
+    name: String,
+    inside_header: String,
+    arguments: String,
+    end_header: String,
+    affiliated: Affiliated,
+    span: Span,
+}
+
+impl BabelCall {
+    /// The `#+NAME:` of the src block called.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The header arguments in brackets after the name, used when running
+    /// the block.
+    pub fn inside_header(&self) -> &str {
+        &self.inside_header
+    }
+
+    /// The arguments in parentheses, like `x=21`, given to the block's
+    /// variables.
+    pub fn arguments(&self) -> &str {
+        &self.arguments
+    }
+
+    /// The header arguments in brackets at the end, used for the results.
+    pub fn end_header(&self) -> &str {
+        &self.end_header
+    }
+
+    /// The inside and end headers, and the arguments as `:var`s.
+    pub fn header_args(&self) -> HeaderArgs {
+        let mut args = HeaderArgs::parse(&self.inside_header);
+        if !self.arguments.is_empty() {
+            args.args.push(("var".into(), self.arguments.clone()));
+        }
+        args.merge(&HeaderArgs::parse(&self.end_header));
+        args
+    }
+
+    pub fn affiliated(&self) -> &Affiliated {
+        &self.affiliated
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+}
+
+impl Affiliate for BabelCall {
+    fn affiliated_mut(&mut self) -> &mut Affiliated {
+        &mut self.affiliated
+    }
+}
+
+impl Shift for BabelCall {
+    fn shift(&mut self, delta: isize) {
+        self.affiliated.shift(delta);
+        self.span.shift(delta);
+    }
+}
+
+/// The text inside the brackets `open` and `close` at the start of `text`,
+/// and the rest after them.
+fn bracketed(text: &str, open: char, close: char) -> Option<(&str, &str)> {
+    let rest = text.strip_prefix(open)?;
+    let mut depth = 1;
+    let end = rest.char_indices().find(|&(_, c)| {
+        if c == open {
+            depth += 1;
+        } else if c == close {
+            depth -= 1;
+        }
+        depth == 0
+    })?.0;
+    Some((&rest[..end], &rest[end + 1..]))
+}
+
+/// Try to parse a `#+CALL:` line at `lines[start]`. Returns it and the index
+/// of the line after it.
+pub fn parse_babel_call(lines: &[&str], offsets: &[usize], start: usize)
+                        -> Option<(BabelCall, usize)> {
+    let line = lines[start].trim();
+    let prefix = "#+call:";
+    if line.len() <= prefix.len()
+        || !line.is_char_boundary(prefix.len())
+        || !line[..prefix.len()].eq_ignore_ascii_case(prefix) {
+        return None;
+    }
+    let value = line[prefix.len()..].trim();
+    let name_len = value.find(['[', '(', ' ']).unwrap_or(value.len());
+    let name = &value[..name_len];
+    if name.is_empty() {
+        return None;
+    }
+    let mut rest = &value[name_len..];
+    let mut inside_header = "";
+    if let Some((header, after)) = bracketed(rest, '[', ']') {
+        inside_header = header;
+        rest = after;
+    }
+    let mut arguments = "";
+    if let Some((inside, after)) = bracketed(rest, '(', ')') {
+        arguments = inside;
+        rest = after;
+    }
+    let end_header = bracketed(rest.trim(), '[', ']').map_or("", |(header, _)| header);
+    Some((BabelCall {
+        name: name.into(),
+        inside_header: inside_header.trim().into(),
+        arguments: arguments.trim().into(),
+        end_header: end_header.trim().into(),
+        affiliated: Affiliated::default(),
+        span: span::lines_span(lines, offsets, start, start + 1),
+    }, start + 1))
+}
+
+/// Add the `header-args` and `header-args:language` values found by `get`
+/// to `args`, in that order.
+fn add_properties<'a, F>(args: &mut HeaderArgs, language: Option<&str>, get: F)
+    where F: Fn(&str) -> Option<&'a str> {
+    let mut keys = vec!["header-args".to_string()];
+    if let Some(language) = language {
+        keys.push(format!("header-args:{}", language));
+    }
+    for key in &keys {
+        if let Some(value) = get(key) {
+            args.merge(&HeaderArgs::parse(value));
+        }
+    }
+}
+
+fn add_src_blocks<'a>(contents: &'a [Content], blocks: &mut Vec<&'a Block>) {
+    for content in contents {
+        if let Content::Element(Element::Block(ref block)) = *content {
+            if *block.kind() == BlockKind::Src {
+                blocks.push(block);
+            }
+        }
+        let (children, items) = content.children();
+        add_src_blocks(children, blocks);
+        for item in items {
+            add_src_blocks(item.contents(), blocks);
+        }
+    }
+}
+
+impl Document {
+    /// The header arguments that apply to the src `block` of this document,
+    /// like org resolves them: org's defaults, then `#+PROPERTY:
+    /// header-args` lines, then the `header-args` properties of the
+    /// headlines above it from the outermost in, then the block's `#+HEADER:`
+    /// lines and then its `#+BEGIN_SRC` line. Each level takes the
+    /// `header-args:language` property for the block's language after the
+    /// general one.
+    pub fn header_args(&self, block: &Block) -> HeaderArgs {
+        let language = block.language();
+        let mut args = HeaderArgs::defaults();
+
+        let file = self.file_properties_accumulated();
+        add_properties(&mut args, language, |key| {
+            file.iter().find(|(k, _)| k.eq_ignore_ascii_case(key)).map(|(_, v)| &v[..])
+        });
+
+        let offset = block.span().start;
+        let mut headlines = &self.headlines[..];
+        while let Some(headline) = headlines.iter().find(|h| h.span.contains(offset)) {
+            add_properties(&mut args, language, |key| headline.property(key));
+            headlines = &headline.headlines;
+        }
+
+        for keyword in block.affiliated().keywords() {
+            if keyword.key().eq_ignore_ascii_case("HEADER") {
+                args.merge(&HeaderArgs::parse(keyword.value()));
+            }
+        }
+        args.merge(&HeaderArgs::parse(block.arguments()));
+        args
+    }
+
+    /// Every src block in the document, in order, with its header
+    /// arguments resolved by `header_args`.
+    pub fn src_blocks(&self) -> Vec<(&Block, HeaderArgs)> {
+        let mut blocks = Vec::new();
+        if let Some(section) = self.first_section() {
+            add_src_blocks(section.contents(), &mut blocks);
+        }
+        for headline in self.all_headlines() {
+            if let Some(section) = headline.section() {
+                add_src_blocks(section.contents(), &mut blocks);
+            }
+        }
+        blocks.into_iter().map(|block| (block, self.header_args(block))).collect()
+    }
+
+    /// The `#+CALL:` lines of the document, in order, with the headline
+    /// each is under.
+    pub fn babel_calls(&self) -> Vec<(Option<&Headline>, &BabelCall)> {
+        let mut calls = Vec::new();
+        let sections = self.first_section().map(|section| (None, section)).into_iter()
+            .chain(self.all_headlines()
+                   .filter_map(|h| h.section().map(|section| (Some(h), section))));
+        for (headline, section) in sections {
+            for content in section.contents() {
+                if let Content::Element(Element::BabelCall(ref call)) = *content {
+                    calls.push((headline, call));
+                }
+            }
+        }
+        calls
+    }
+
+    /// The `#+PROPERTY:` lines of the document, with the values of the
+    /// `key+` lines org adds to `key` added to it.
+    fn file_properties_accumulated(&self) -> Vec<(String, String)> {
+        let mut properties: Vec<(String, String)> = Vec::new();
+        let sections = self.first_section().into_iter()
+            .chain(self.all_headlines().filter_map(|headline| headline.section()));
+        for section in sections {
+            for content in section.contents() {
+                let keyword = match *content {
+                    Content::Element(Element::Keyword(ref keyword))
+                        if keyword.key().eq_ignore_ascii_case("PROPERTY") => keyword,
+                    _ => continue,
+                };
+                let mut parts = keyword.value().splitn(2, char::is_whitespace);
+                let key = parts.next().unwrap_or("");
+                let value = parts.next().unwrap_or("").trim();
+                let (key, add) = match key.strip_suffix('+') {
+                    Some(key) => (key, true),
+                    None => (key, false),
+                };
+                match properties.iter_mut().find(|(k, _)| k.eq_ignore_ascii_case(key)) {
+                    Some(existing) if add => {
+                        existing.1.push(' ');
+                        existing.1.push_str(value);
+                    }
+                    Some(existing) => existing.1 = value.into(),
+                    None if !key.is_empty() => properties.push((key.into(), value.into())),
+                    None => {}
+                }
+            }
+        }
+        properties
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use DocumentParser;
+
+    #[test]
+    fn test_header_args() {
+        let args = HeaderArgs::parse("-n :var x=1, y=\"a, b\" :results value silent \
+                                      :var x=2 :results output :tangle \"main.rs\"");
+        let vars: Vec<_> = args.vars().into_iter().map(|(n, v)| format!("{}={}", n, v)).collect();
+        assert_eq!(vars, ["y=\"a, b\"", "x=2"]);
+        assert_eq!(args.results(), ["silent", "output"]);
+        assert_eq!(args.tangle(), Tangle::File("main.rs".into()));
+        assert_eq!(HeaderArgs::defaults().tangle(), Tangle::No);
+        assert_eq!(HeaderArgs::defaults().session(), None);
+        assert!(!HeaderArgs::defaults().noweb().expands_on_tangle());
+        assert_eq!(args.get(":TANGLE"), Some("\"main.rs\""));
+    }
+
+    #[test]
+    fn test_resolved_header_args() {
+        let doc = DocumentParser::new().parse("#+PROPERTY: header-args :noweb yes
+#+PROPERTY: header-args+ :tangle yes
+#+CALL: double[:session calc](x=21)[:results silent]
+* Code
+:PROPERTIES:
+:header-args:rust: :tangle main.rs
+:END:
+#+HEADER: :results output
+#+BEGIN_SRC rust :exports both
+fn main() {}
+#+END_SRC
+#+BEGIN_SRC python
+print(1)
+#+END_SRC
+").unwrap();
+        let blocks = doc.src_blocks();
+        assert_eq!(blocks.len(), 2);
+        let (rust, ref args) = blocks[0];
+        assert_eq!(rust.language(), Some("rust"));
+        assert_eq!(args.tangle(), Tangle::File("main.rs".into()));
+        assert_eq!(args.noweb(), Noweb::Yes);
+        assert_eq!(args.exports(), Some("both"));
+        assert_eq!(args.results(), ["replace", "output"]);
+        assert_eq!(blocks[1].1.tangle(), Tangle::Yes);
+
+        let calls = doc.babel_calls();
+        let (headline, call) = calls[0];
+        assert!(headline.is_none());
+        assert_eq!(call.name(), "double");
+        assert_eq!(call.arguments(), "x=21");
+        let args = call.header_args();
+        assert_eq!(args.session(), Some("calc"));
+        assert_eq!(args.vars(), [("x".to_string(), "21".to_string())]);
+        assert_eq!(args.results(), ["silent"]);
+        assert!(parse_babel_call(&["#+CALL:"], &[0], 0).is_none());
+    }
+}
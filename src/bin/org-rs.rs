@@ -0,0 +1,210 @@
+//! `org-rs check [--format text|json|sarif] PATH...`
+//!
+//! Parses every `.org` file under the given files and directories, lints
+//! them, and prints what it found. Exits with status 1 if anything was
+//! found, so it can gate merges of org repositories.
+
+extern crate org;
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use org::lint::{Lint, lint};
+use org::{DocumentParser, decode};
+
+const USAGE: &str = "usage: org-rs check [--format text|json|sarif] PATH...";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Text,
+    Json,
+    Sarif,
+}
+
+/// A lint in a file, with one-based lines and columns in characters.
+struct Found {
+    file: String,
+    lint: Lint,
+    start: (usize, usize),
+    end: (usize, usize),
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(|arg| &arg[..]) != Some("check") {
+        eprintln!("{}", USAGE);
+        process::exit(2);
+    }
+    let mut format = Format::Text;
+    let mut paths = Vec::new();
+    let mut rest = args[1..].iter();
+    while let Some(arg) = rest.next() {
+        if arg == "--format" {
+            format = match rest.next().map(|format| &format[..]) {
+                Some("text") => Format::Text,
+                Some("json") => Format::Json,
+                Some("sarif") => Format::Sarif,
+                _ => {
+                    eprintln!("{}", USAGE);
+                    process::exit(2);
+                }
+            };
+        } else {
+            paths.push(PathBuf::from(arg));
+        }
+    }
+    if paths.is_empty() {
+        eprintln!("{}", USAGE);
+        process::exit(2);
+    }
+
+    let mut files = Vec::new();
+    for path in &paths {
+        if let Err(error) = collect_files(path, &mut files) {
+            eprintln!("org-rs: {}: {}", path.display(), error);
+            process::exit(2);
+        }
+    }
+    files.sort();
+
+    let parser = DocumentParser::new();
+    let mut found = Vec::new();
+    for file in &files {
+        let bytes = match fs::read(file) {
+            Ok(bytes) => bytes,
+            Err(error) => {
+                eprintln!("org-rs: {}: {}", file.display(), error);
+                process::exit(2);
+            }
+        };
+        // Bytes that don't decode are listed with the parse warnings.
+        let decoded = decode(&bytes);
+        let document = match parser.parse_bytes(&bytes) {
+            Ok(document) => document,
+            Err(error) => {
+                eprintln!("org-rs: {}: {}", file.display(), error);
+                process::exit(2);
+            }
+        };
+        let name = file.to_string_lossy().into_owned();
+        for lint in lint(&document, &decoded.text) {
+            let span = lint.diagnostic.span();
+            found.push(Found {
+                file: name.clone(),
+                start: position(&decoded.text, span.start),
+                end: position(&decoded.text, span.end),
+                lint,
+            });
+        }
+    }
+
+    print!("{}", match format {
+        Format::Text => text(&found),
+        Format::Json => json(&found, files.len()),
+        Format::Sarif => sarif(&found),
+    });
+    if !found.is_empty() {
+        process::exit(1);
+    }
+}
+
+/// Add `path` if it is a file, or the `.org` files under it if it is a
+/// directory, skipping hidden ones.
+fn collect_files(path: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    if !path.is_dir() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+    for entry in fs::read_dir(path)? {
+        let path = entry?.path();
+        let hidden = path.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.'));
+        if hidden {
+            continue;
+        }
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else if path.extension().is_some_and(|extension| extension == "org") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// The line and column of byte `offset` in `text`, from one.
+fn position(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset.min(text.len())];
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    (before.matches('\n').count() + 1, before[line_start..].chars().count() + 1)
+}
+
+fn severity(lint: &Lint) -> &'static str {
+    if lint.is_error() { "error" } else { "warning" }
+}
+
+fn text(found: &[Found]) -> String {
+    found.iter()
+        .map(|found| {
+            format!("{}:{}:{}: {}[{}]: {}\n", found.file, found.start.0, found.start.1,
+                    severity(&found.lint), found.lint.rule, found.lint.diagnostic.message())
+        })
+        .collect()
+}
+
+fn quote(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn json(found: &[Found], files: usize) -> String {
+    let diagnostics: Vec<_> = found.iter()
+        .map(|found| {
+            let span = found.lint.diagnostic.span();
+            format!("    {{\"file\": {}, \"rule\": {}, \"severity\": {}, \"message\": {}, \
+                     \"start\": {}, \"end\": {}, \"line\": {}, \"column\": {}, \
+                     \"end_line\": {}, \"end_column\": {}}}",
+                    quote(&found.file), quote(found.lint.rule), quote(severity(&found.lint)),
+                    quote(found.lint.diagnostic.message()), span.start, span.end,
+                    found.start.0, found.start.1, found.end.0, found.end.1)
+        })
+        .collect();
+    let list = if diagnostics.is_empty() {
+        "[]".to_string()
+    } else {
+        format!("[\n{}\n  ]", diagnostics.join(",\n"))
+    };
+    format!("{{\n  \"files\": {},\n  \"diagnostics\": {}\n}}\n", files, list)
+}
+
+fn sarif(found: &[Found]) -> String {
+    let results: Vec<_> = found.iter()
+        .map(|found| {
+            format!("{{\"ruleId\": {}, \"level\": {}, \"message\": {{\"text\": {}}}, \
+                     \"locations\": [{{\"physicalLocation\": {{\
+                     \"artifactLocation\": {{\"uri\": {}}}, \
+                     \"region\": {{\"startLine\": {}, \"startColumn\": {}, \
+                     \"endLine\": {}, \"endColumn\": {}}}}}}}]}}",
+                    quote(found.lint.rule), quote(severity(&found.lint)),
+                    quote(found.lint.diagnostic.message()), quote(&found.file.replace('\\', "/")),
+                    found.start.0, found.start.1, found.end.0, found.end.1)
+        })
+        .collect();
+    format!("{{\"version\": \"2.1.0\", \
+             \"$schema\": \"https://json.schemastore.org/sarif-2.1.0.json\", \
+             \"runs\": [{{\"tool\": {{\"driver\": {{\"name\": \"org-rs\"}}}}, \
+             \"results\": [{}]}}]}}\n", results.join(", "))
+}
@@ -0,0 +1,435 @@
+use keyword::{Affiliate, Affiliated};
+use object::Object;
+use span::{self, Shift, Span};
+use {Content, DocumentParser};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum BlockKind {
+    /// `#+BEGIN_CENTER`, contents are parsed.
+    Center,
+    /// `#+BEGIN_QUOTE`, contents are parsed.
+    Quote,
+    /// `#+BEGIN_COMMENT`, never exported.
+    Comment,
+    /// `#+BEGIN_EXAMPLE`, shown verbatim.
+    Example,
+    /// `#+BEGIN_EXPORT backend`, passed through to one export backend.
+    Export,
+    /// `#+BEGIN_SRC language switches header-args`, source code.
+    Src,
+    /// `#+BEGIN_VERSE`, line breaks and indentation are significant.
+    Verse,
+    /// Any other `#+BEGIN_NAME`, holding the name as written. Its contents
+    /// are parsed like those of a quote block.
+    Special(String),
+}
+
+impl BlockKind {
+    fn from_name(name: &str) -> BlockKind {
+        match &name.to_ascii_uppercase()[..] {
+            "CENTER" => BlockKind::Center,
+            "QUOTE" => BlockKind::Quote,
+            "COMMENT" => BlockKind::Comment,
+            "EXAMPLE" => BlockKind::Example,
+            "EXPORT" => BlockKind::Export,
+            "SRC" => BlockKind::Src,
+            "VERSE" => BlockKind::Verse,
+            _ => BlockKind::Special(name.into()),
+        }
+    }
+
+    /// Greater blocks hold elements, the others hold literal text.
+    pub fn is_greater(&self) -> bool {
+        matches!(*self, BlockKind::Center | BlockKind::Quote | BlockKind::Special(_))
+    }
+}
+
+/// The languages of blocks that are diagrams.
+const DIAGRAM_LANGUAGES: &[&str] = &["mermaid", "plantuml"];
+
+/// A block delimited by `#+BEGIN_NAME PARAMETERS` and `#+END_NAME` lines:
+///
+/// ```ignore
+/// #+BEGIN_SRC rust -n :results output
+/// fn main() {}
+/// #+END_SRC
+/// ```
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Block {
+    kind: BlockKind,
+    parameters: String,
+    text: String,
+    contents: Vec<Content>,
+    affiliated: Affiliated,
+    span: Span,
+}
+
+impl Block {
+    pub fn kind(&self) -> &BlockKind {
+        &self.kind
+    }
+
+    /// Everything after the block name on the `#+BEGIN_` line.
+    pub fn parameters(&self) -> &str {
+        &self.parameters
+    }
+
+    /// The language of a src block, or the backend of an export block.
+    pub fn language(&self) -> Option<&str> {
+        match self.kind {
+            BlockKind::Src | BlockKind::Export => self.parameters.split_whitespace().next(),
+            _ => None,
+        }
+    }
+
+    /// The switches and header arguments of a src block, following its
+    /// language.
+    pub fn arguments(&self) -> &str {
+        match self.language() {
+            Some(language) => {
+                let start = self.parameters.find(language).unwrap() + language.len();
+                self.parameters[start..].trim()
+            }
+            None => &self.parameters,
+        }
+    }
+
+    /// The body of the block. Lines that org escaped with a leading comma
+    /// (`,* not a headline`) have the comma removed.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// The diagram language of a `#+BEGIN_SRC mermaid` or `#+BEGIN_SRC
+    /// plantuml` block, or of a special block with one of those names, in
+    /// lowercase. Exporters render these as diagrams rather than as code.
+    pub fn diagram_language(&self) -> Option<&'static str> {
+        let name = match self.kind {
+            BlockKind::Src => self.language()?,
+            BlockKind::Special(ref name) => name,
+            _ => return None,
+        };
+        DIAGRAM_LANGUAGES.iter().cloned().find(|language| language.eq_ignore_ascii_case(name))
+    }
+
+    /// The lines of a verse block parsed into objects, each with how many
+    /// columns it is indented by past the indentation all its lines share,
+    /// which org keeps along with the line breaks. Empty for other blocks.
+    pub fn verse_lines(&self) -> Vec<(usize, Vec<Object>)> {
+        if self.kind != BlockKind::Verse {
+            return Vec::new();
+        }
+        let indent = |line: &str| line.chars().take_while(|c| c.is_whitespace()).count();
+        let common = self.text.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(indent)
+            .min()
+            .unwrap_or(0);
+        self.text.lines()
+            .map(|line| (indent(line).saturating_sub(common), ::parse_objects(line.trim())))
+            .collect()
+    }
+
+    /// The elements inside a greater block; empty for the others.
+    pub fn contents(&self) -> &[Content] {
+        &self.contents
+    }
+
+    /// The `#+NAME:`, `#+CAPTION:` and other keywords written above it.
+    pub fn affiliated(&self) -> &Affiliated {
+        &self.affiliated
+    }
+
+    /// Where the block is in the text, from its `#+BEGIN_` line to its
+    /// `#+END_` line.
+    pub fn span(&self) -> Span {
+        self.span
+    }
+}
+
+impl Affiliate for Block {
+    fn affiliated_mut(&mut self) -> &mut Affiliated {
+        &mut self.affiliated
+    }
+}
+
+impl Shift for Block {
+    fn shift(&mut self, delta: isize) {
+        self.affiliated.shift(delta);
+        self.span.shift(delta);
+        self.contents.shift(delta);
+    }
+}
+
+/// A dynamic block, whose contents are generated by a writer function named
+/// by the block, such as a clocktable:
+///
+/// ```ignore
+/// #+BEGIN: clocktable :maxlevel 2 :scope file
+/// ...
+/// #+END:
+/// ```
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct DynamicBlock {
+    name: String,
+    parameters: Vec<(String, String)>,
+    text: String,
+    contents: Vec<Content>,
+    affiliated: Affiliated,
+    span: Span,
+}
+
+impl DynamicBlock {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The `:key value` parameters in order, with keys including their colon.
+    pub fn parameters(&self) -> &[(String, String)] {
+        &self.parameters
+    }
+
+    /// Look up a parameter by key, like `:maxlevel`.
+    pub fn parameter(&self, key: &str) -> Option<&str> {
+        self.parameters.iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| &v[..])
+    }
+
+    /// The raw lines between the `#+BEGIN:` and `#+END:` lines.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn contents(&self) -> &[Content] {
+        &self.contents
+    }
+
+    /// The `#+NAME:`, `#+CAPTION:` and other keywords written above it.
+    pub fn affiliated(&self) -> &Affiliated {
+        &self.affiliated
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+}
+
+impl Affiliate for DynamicBlock {
+    fn affiliated_mut(&mut self) -> &mut Affiliated {
+        &mut self.affiliated
+    }
+}
+
+impl Shift for DynamicBlock {
+    fn shift(&mut self, delta: isize) {
+        self.affiliated.shift(delta);
+        self.span.shift(delta);
+        self.contents.shift(delta);
+    }
+}
+
+/// Split a `:key value :other value` property list into pairs. A value runs
+/// until the next word starting with a colon, so it may contain spaces.
+pub fn parse_plist(text: &str) -> Vec<(String, String)> {
+    let mut pairs: Vec<(String, String)> = Vec::new();
+    for word in text.split_whitespace() {
+        match pairs.last_mut() {
+            Some(&mut (_, ref mut value)) if !word.starts_with(':') => {
+                if !value.is_empty() {
+                    value.push(' ');
+                }
+                value.push_str(word);
+            }
+            _ => pairs.push((word.into(), String::new())),
+        }
+    }
+    pairs
+}
+
+/// Try to parse a dynamic block starting at `lines[start]`. Returns the block
+/// and the index of the first line after its `#+END:` line.
+pub fn parse_dynamic_block(parser: &DocumentParser, lines: &[&str], offsets: &[usize], start: usize)
+                           -> Option<(DynamicBlock, usize)> {
+    let line = lines[start].trim();
+    let prefix = "#+begin:";
+    if line.len() <= prefix.len()
+        || !line.is_char_boundary(prefix.len())
+        || !line[..prefix.len()].eq_ignore_ascii_case(prefix) {
+        return None;
+    }
+    let mut rest = line[prefix.len()..].trim().splitn(2, char::is_whitespace);
+    let name = rest.next().filter(|name| !name.is_empty())?;
+    let parameters = parse_plist(rest.next().unwrap_or(""));
+    let end = start + 1 + lines[start + 1..].iter()
+        .position(|line| line.trim().eq_ignore_ascii_case("#+end:"))?;
+    Some((DynamicBlock {
+        name: name.into(),
+        parameters,
+        text: lines[start + 1..end].join("\n"),
+        contents: ::parse_contents(parser, &lines[start + 1..end], &offsets[start + 1..end]),
+        affiliated: Affiliated::default(),
+        span: span::lines_span(lines, offsets, start, end + 1),
+    }, end + 1))
+}
+
+/// The name and parameters of a line like `#+BEGIN_SRC rust`.
+pub fn begin_line(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim();
+    let prefix = "#+begin_";
+    if line.len() <= prefix.len()
+        || !line.is_char_boundary(prefix.len())
+        || !line[..prefix.len()].eq_ignore_ascii_case(prefix) {
+        return None;
+    }
+    let rest = &line[prefix.len()..];
+    let name_len = rest.find(char::is_whitespace).unwrap_or(rest.len());
+    Some((&rest[..name_len], rest[name_len..].trim()))
+}
+
+pub fn is_end_line(line: &str, name: &str) -> bool {
+    let line = line.trim();
+    let prefix = "#+end_";
+    line.len() == prefix.len() + name.len()
+        && line.is_char_boundary(prefix.len())
+        && line[..prefix.len()].eq_ignore_ascii_case(prefix)
+        && line[prefix.len()..].eq_ignore_ascii_case(name)
+}
+
+/// What is wrong with `line` if it is a block delimiter left over after
+/// parsing, either because nothing closes it or because nothing opened it.
+pub fn stray_delimiter(line: &str) -> Option<String> {
+    let line = line.trim();
+    let starts_with = |prefix: &str| {
+        line.len() >= prefix.len()
+            && line.is_char_boundary(prefix.len())
+            && line[..prefix.len()].eq_ignore_ascii_case(prefix)
+    };
+    if let Some((name, _)) = begin_line(line) {
+        Some(format!("#+BEGIN_{} block is never closed", name))
+    } else if starts_with("#+begin:") {
+        Some("dynamic block is never closed".into())
+    } else if starts_with("#+end_") {
+        Some(format!("{} has no matching #+BEGIN_ line", line))
+    } else if starts_with("#+end:") {
+        Some("#+END: has no matching #+BEGIN: line".into())
+    } else {
+        None
+    }
+}
+
+/// Remove the comma org puts in front of lines starting with `*` or `#+`
+/// inside blocks.
+fn unescape_line(line: &str) -> String {
+    let indent = line.len() - line.trim_start().len();
+    let rest = &line[indent..];
+    if rest.starts_with(",*") || rest.starts_with(",#+") {
+        format!("{}{}", &line[..indent], &rest[1..])
+    } else {
+        line.into()
+    }
+}
+
+/// Try to parse a block starting at `lines[start]`. Returns the block and the
+/// index of the first line after its `#+END_` line.
+pub fn parse_block(parser: &DocumentParser, lines: &[&str], offsets: &[usize], start: usize)
+                   -> Option<(Block, usize)> {
+    let (name, parameters) = begin_line(lines[start])?;
+    let end = start + 1 + lines[start + 1..].iter()
+        .position(|line| is_end_line(line, name))?;
+    let body = &lines[start + 1..end];
+    let kind = BlockKind::from_name(name);
+    let (text, contents) = if kind.is_greater() {
+        (body.join("\n"), ::parse_contents(parser, body, &offsets[start + 1..end]))
+    } else {
+        let lines: Vec<_> = body.iter().map(|line| unescape_line(line)).collect();
+        (lines.join("\n"), Vec::new())
+    };
+    Some((Block {
+        kind,
+        parameters: parameters.into(),
+        text,
+        contents,
+        affiliated: Affiliated::default(),
+        span: span::lines_span(lines, offsets, start, end + 1),
+    }, end + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_block() {
+        let parser = DocumentParser::new();
+        let lines: Vec<_> = "#+BEGIN_SRC rust -n :tangle main.rs
+fn main() {
+    println!(\"hi\");
+}
+,* escaped
+#+end_src
+#+begin_quote
+- a list
+#+end_quote
+#+BEGIN_EXAMPLE
+never closed".lines().collect();
+        let offsets = span::line_offsets(&lines.join("\n"), 0);
+        let (src, next) = parse_block(&parser, &lines, &offsets, 0).unwrap();
+        assert_eq!(next, 6);
+        assert_eq!(*src.kind(), BlockKind::Src);
+        assert_eq!(src.language(), Some("rust"));
+        assert_eq!(src.arguments(), "-n :tangle main.rs");
+        assert_eq!(src.text(), "fn main() {\n    println!(\"hi\");\n}\n* escaped");
+
+        let (quote, next) = parse_block(&parser, &lines, &offsets, 6).unwrap();
+        assert_eq!(next, 9);
+        assert_eq!(*quote.kind(), BlockKind::Quote);
+        assert_eq!(quote.language(), None);
+        assert_eq!(quote.contents().len(), 1);
+
+        assert_eq!(quote.span(), Span::new(91, 125));
+        assert!(parse_block(&parser, &lines, &offsets, 9).is_none());
+        assert!(parse_block(&parser, &["#+BEGIN_NOTE", "#+END_NOTE"], &[0, 13], 0).is_some());
+        assert!(quote.verse_lines().is_empty());
+
+        let lines = ["#+BEGIN_VERSE", "  Great *clouds*", "    overhead", "", "  Tiny",
+                     "#+END_VERSE"];
+        let offsets = span::line_offsets(&lines.join("\n"), 0);
+        let (verse, _) = parse_block(&parser, &lines, &offsets, 0).unwrap();
+        assert!(verse.contents().is_empty());
+        assert_eq!(verse.verse_lines(), [
+            (0, vec![Object::Text("Great ".into()),
+                     Object::Bold(vec![Object::Text("clouds".into())])]),
+            (2, vec![Object::Text("overhead".into())]),
+            (0, vec![]),
+            (0, vec![Object::Text("Tiny".into())]),
+        ]);
+    }
+
+    #[test]
+    fn test_parse_dynamic_block() {
+        let parser = DocumentParser::new();
+        let lines: Vec<_> = "#+BEGIN: clocktable :maxlevel 2 :tstart \"<-1w>\" :formula %
+| Headline | Time |
+#+END:".lines().collect();
+        let offsets = span::line_offsets(&lines.join("\n"), 0);
+        let (block, next) = parse_dynamic_block(&parser, &lines, &offsets, 0).unwrap();
+        assert_eq!(next, 3);
+        assert_eq!(block.name(), "clocktable");
+        assert_eq!(block.parameter(":maxlevel"), Some("2"));
+        assert_eq!(block.parameter(":tstart"), Some("\"<-1w>\""));
+        assert_eq!(block.parameters().len(), 3);
+        assert_eq!(block.contents().len(), 1);
+
+        assert_eq!(parse_plist(":a 1 2 :b :c x"), [
+            (":a".to_string(), "1 2".to_string()),
+            (":b".to_string(), "".to_string()),
+            (":c".to_string(), "x".to_string()),
+        ]);
+        assert!(parse_dynamic_block(&parser, &["#+BEGIN:", "#+END:"], &[0, 9], 0).is_none());
+        assert!(parse_dynamic_block(&parser, &["#+BEGIN: x"], &[0], 0).is_none());
+    }
+}
@@ -0,0 +1,189 @@
+//! Using a document as a bookmark collection: its web links, each with the
+//! headline it is filed under.
+
+use object::{self, plain_text};
+use {Date, Document, Headline, Link, LinkType, Symbol, Timestamp};
+
+/// A web link found in a document.
+#[derive(Debug, Clone)]
+pub struct Bookmark<'a> {
+    link: Link,
+    headline: Option<&'a Headline>,
+}
+
+impl<'a> Bookmark<'a> {
+    pub fn link(&self) -> &Link {
+        &self.link
+    }
+
+    pub fn url(&self) -> &str {
+        self.link.destination()
+    }
+
+    /// The headline whose title or section holds the link, or `None` before
+    /// the first headline.
+    pub fn headline(&self) -> Option<&'a Headline> {
+        self.headline
+    }
+
+    /// The link description, or else the title of its headline, or else the
+    /// URL.
+    pub fn title(&self) -> String {
+        match (self.link.description(), self.headline) {
+            (Some(description), _) => plain_text(description),
+            (None, Some(headline)) if !headline.title().contains(self.url()) => {
+                plain_text(&headline.title_objects())
+            }
+            _ => self.url().into(),
+        }
+    }
+
+    /// The tags of its headline.
+    pub fn tags(&self) -> &'a [Symbol] {
+        self.headline.map_or(&[], |headline| headline.tags())
+    }
+
+    /// When the bookmark was added: the date in its headline's `ADDED` or
+    /// `CREATED` property, or else of the first inactive timestamp under the
+    /// headline.
+    pub fn added(&self) -> Option<Date> {
+        let headline = self.headline?;
+        ["ADDED", "CREATED"].iter()
+            .filter_map(|key| headline.property(key))
+            .filter_map(|value| {
+                Date::parse(value).or_else(|| Timestamp::parse(value).map(|ts| ts.date()))
+            })
+            .next()
+            .or_else(|| {
+                headline.timestamps().iter()
+                    .find(|timestamp| !timestamp.is_active())
+                    .map(|timestamp| timestamp.date())
+            })
+    }
+}
+
+/// Every web link in the document, in order.
+pub fn bookmarks(document: &Document) -> Vec<Bookmark<'_>> {
+    fn web_links<'a>(text: &str, headline: Option<&'a Headline>, out: &mut Vec<Bookmark<'a>>) {
+        out.extend(object::find_links(text).into_iter()
+            .filter(|link| link.link_type() == LinkType::Http)
+            .map(|link| Bookmark { link, headline }));
+    }
+
+    let mut bookmarks = Vec::new();
+    if let Some(section) = document.first_section() {
+        web_links(section.text(), None, &mut bookmarks);
+    }
+    for headline in document.all_headlines() {
+        web_links(headline.title(), Some(headline), &mut bookmarks);
+        if let Some(section) = headline.section() {
+            web_links(section.text(), Some(headline), &mut bookmarks);
+        }
+    }
+    bookmarks
+}
+
+/// A form of `url` for telling whether two links point at the same page.
+/// The scheme and host are lowercased, and `http` becomes `https`. A
+/// leading `www.`, the fragment, `utm_` tracking parameters and a trailing
+/// slash are dropped.
+pub fn normalize_url(url: &str) -> String {
+    let url = url.split('#').next().unwrap_or("");
+    let rest = url.find("://").map_or(url, |i| &url[i + 3..]);
+    let (address, query) = match rest.find('?') {
+        Some(i) => (&rest[..i], &rest[i + 1..]),
+        None => (rest, ""),
+    };
+    let (host, path) = match address.find('/') {
+        Some(i) => (&address[..i], &address[i..]),
+        None => (address, ""),
+    };
+    let host = host.to_ascii_lowercase();
+    let mut normalized = format!("https://{}{}", host.strip_prefix("www.").unwrap_or(&host),
+                                 path.trim_end_matches('/'));
+    let query: Vec<_> = query.split('&')
+        .filter(|param| !param.is_empty() && !param.starts_with("utm_"))
+        .collect();
+    if !query.is_empty() {
+        normalized.push('?');
+        normalized.push_str(&query.join("&"));
+    }
+    normalized
+}
+
+/// The bookmarks that point at the same page as an earlier one, grouped by
+/// page, in the order the pages first appear.
+pub fn duplicates<'b, 'a>(bookmarks: &'b [Bookmark<'a>]) -> Vec<Vec<&'b Bookmark<'a>>> {
+    let mut groups: Vec<(String, Vec<&Bookmark>)> = Vec::new();
+    for bookmark in bookmarks {
+        let url = normalize_url(bookmark.url());
+        match groups.iter_mut().find(|group| group.0 == url) {
+            Some(group) => group.1.push(bookmark),
+            None => groups.push((url, vec![bookmark])),
+        }
+    }
+    groups.into_iter().map(|(_, group)| group).filter(|group| group.len() > 1).collect()
+}
+
+/// A reading list of the document's bookmarks as an org list, oldest first
+/// and undated ones last. Only the first of several links to the same page
+/// is listed.
+pub fn reading_list(document: &Document) -> String {
+    let mut seen = Vec::new();
+    let mut entries: Vec<_> = bookmarks(document).into_iter()
+        .filter(|bookmark| {
+            let url = normalize_url(bookmark.url());
+            let new = !seen.contains(&url);
+            seen.push(url);
+            new
+        })
+        .map(|bookmark| (bookmark.added(), bookmark))
+        .collect();
+    entries.sort_by_key(|&(added, _)| (added.is_none(), added));
+    let mut out = String::new();
+    for (added, bookmark) in entries {
+        out.push_str("- ");
+        if let Some(date) = added {
+            out.push_str(&format!("[{}] ", date));
+        }
+        out.push_str(&format!("[[{}][{}]]", bookmark.url(), bookmark.title()));
+        if !bookmark.tags().is_empty() {
+            out.push_str(&format!(" :{}:", bookmark.tags().join(":")));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use DocumentParser;
+
+    #[test]
+    fn test_reading_list() {
+        let doc = DocumentParser::new().parse("See https://example.com/start
+* Rust book :rust:
+:PROPERTIES:
+:ADDED: 2024-03-01
+:END:
+[[https://doc.rust-lang.org/book/][The Book]]
+* [[http://www.Example.com/start/?utm_source=feed#top][Start again]]
+* Later :web:
+Saved [2024-01-15 Mon]: https://example.org/later
+").unwrap();
+        let bookmarks = bookmarks(&doc);
+        let titles: Vec<_> = bookmarks.iter().map(|b| b.title()).collect();
+        assert_eq!(titles, ["https://example.com/start", "The Book", "Start again", "Later"]);
+        assert_eq!(normalize_url(bookmarks[2].url()), "https://example.com/start");
+        let duplicates = duplicates(&bookmarks);
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates[0][1].title(), "Start again");
+
+        assert_eq!(reading_list(&doc), "\
+- [2024-01-15] [[https://example.org/later][Later]] :web:
+- [2024-03-01] [[https://doc.rust-lang.org/book/][The Book]] :rust:
+- [[https://example.com/start][https://example.com/start]]
+");
+    }
+}
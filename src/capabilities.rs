@@ -0,0 +1,106 @@
+/// What this build of the crate can parse and export, made by
+/// `capabilities`, so that an application can hide the parts of its
+/// interface the crate can't back instead of failing when they're used.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capabilities {
+    /// The elements the parser recognizes, greater elements included, by the
+    /// names `ComplexityReport::counts` uses.
+    pub elements: Vec<&'static str>,
+    /// The objects the parser recognizes in paragraphs, headline titles and
+    /// table cells, like `bold` and `footnote_reference`.
+    pub objects: Vec<&'static str>,
+    /// The names of the backends of `export::publish::Publisher::new`.
+    pub export_backends: Vec<&'static str>,
+    /// The optional features the crate was built with, out of `serde`,
+    /// `tracing` and `http`.
+    pub features: Vec<&'static str>,
+}
+
+impl Capabilities {
+    pub fn supports_element(&self, name: &str) -> bool {
+        self.elements.contains(&name)
+    }
+
+    pub fn supports_object(&self, name: &str) -> bool {
+        self.objects.contains(&name)
+    }
+
+    /// Whether there is a backend named `name`, matched ignoring case like
+    /// `EXPORT_BACKEND` properties are.
+    pub fn supports_backend(&self, name: &str) -> bool {
+        self.export_backends.iter().any(|backend| backend.eq_ignore_ascii_case(name))
+    }
+
+    pub fn has_feature(&self, name: &str) -> bool {
+        self.features.contains(&name)
+    }
+}
+
+/// What this build supports. Builds without the `http` feature can't include
+/// files by URL, and those without `serde` can't serialize the tree.
+pub fn capabilities() -> Capabilities {
+    let mut features = Vec::new();
+    if cfg!(feature = "serde") {
+        features.push("serde");
+    }
+    if cfg!(feature = "tracing") {
+        features.push("tracing");
+    }
+    if cfg!(feature = "http") {
+        features.push("http");
+    }
+    Capabilities {
+        elements: vec![
+            "greater_block", "drawer", "dynamic_block", "footnote_definition", "inlinetask",
+            "plain_list", "property_drawer", "table", "babel_call", "block", "clock",
+            "comment", "fixed_width", "horizontal_rule", "keyword", "latex_environment",
+            "paragraph", "planning", "raw",
+        ],
+        objects: vec![
+            "text", "bold", "italic", "underline", "strike_through", "verbatim", "code",
+            "link", "target", "radio_target", "entity", "subscript", "superscript",
+            "latex_fragment", "footnote_reference", "statistics_cookie", "timestamp", "raw",
+        ],
+        export_backends: vec!["html", "md", "markdown"],
+        features,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use export::publish::Publisher;
+    use DocumentParser;
+
+    #[test]
+    fn test_capabilities() {
+        let capabilities = capabilities();
+        let doc = DocumentParser::new().parse("#+TITLE: Notes
+* One
+- a
+| x |
+#+BEGIN_QUOTE
+Quoted.
+#+END_QUOTE
+").unwrap();
+        for kind in doc.complexity_report().counts.keys() {
+            if *kind != "headline" && *kind != "list_item" {
+                assert!(capabilities.supports_element(kind), "{}", kind);
+            }
+        }
+        assert!(capabilities.supports_object("footnote_reference"));
+        assert!(!capabilities.supports_object("inline_src_block"));
+
+        let doc = DocumentParser::new().parse("* Talk
+:PROPERTIES:
+:EXPORT_BACKEND: Markdown
+:END:
+").unwrap();
+        assert!(capabilities.supports_backend("Markdown"));
+        assert!(Publisher::new().publish(&doc, "talk.org").is_ok());
+        assert!(!capabilities.supports_backend("beamer"));
+
+        assert_eq!(capabilities.has_feature("http"), cfg!(feature = "http"));
+        assert_eq!(capabilities.has_feature("serde"), cfg!(feature = "serde"));
+    }
+}
@@ -0,0 +1,484 @@
+use span::{Shift, Span};
+use timestamp::{self, Date, Time, Timestamp};
+use todo;
+use {Content, Document, Element, Headline, Now};
+
+/// A clock line, usually found in a headline's `:LOGBOOK:` drawer:
+///
+/// ```ignore
+/// CLOCK: [2024-05-07 Tue 16:00]--[2024-05-07 Tue 17:30] =>  1:30
+/// CLOCK: [2024-05-08 Wed 09:15]
+/// ```
+///
+/// A clock without an end is still running.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Clock {
+    timestamp: Timestamp,
+    /// The `=> H:MM` duration as written, in minutes.
+    stated: Option<u32>,
+    span: Span,
+}
+
+impl Clock {
+    /// The inactive timestamp the clock was started at, a range once it has
+    /// been stopped.
+    pub fn timestamp(&self) -> &Timestamp {
+        &self.timestamp
+    }
+
+    pub fn start(&self) -> (Date, Option<Time>) {
+        (self.timestamp.date(), self.timestamp.time())
+    }
+
+    /// When the clock was stopped, or `None` while it is running.
+    pub fn end(&self) -> Option<(Date, Option<Time>)> {
+        Some((self.timestamp.end_date()?, self.timestamp.end_time()))
+    }
+
+    pub fn is_running(&self) -> bool {
+        !self.timestamp.is_range()
+    }
+
+    /// The clocked time in minutes, worked out from the start and end, or
+    /// the written `=> H:MM` if they lack times. `None` while running.
+    pub fn duration(&self) -> Option<u32> {
+        match self.interval() {
+            Some((start, end)) => Some((end - start).max(0) as u32),
+            None if self.is_running() => None,
+            None => self.stated,
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    /// The minutes clocked from `start` to `end`, both inclusive. A clock
+    /// running over midnight only counts the part inside the range. Without
+    /// times the whole duration counts if the clock started in the range.
+    pub fn duration_between(&self, start: Date, end: Date) -> u32 {
+        match self.interval() {
+            Some((from, to)) => {
+                let date = self.timestamp.date();
+                let midnight = Time { hour: 0, minute: 0 };
+                let range_start = minutes_since(date, start, midnight);
+                let range_end = minutes_since(date, end.add_days(1), midnight);
+                (to.min(range_end) - from.max(range_start)).max(0) as u32
+            }
+            None if self.timestamp.date() >= start && self.timestamp.date() <= end => {
+                self.duration().unwrap_or(0)
+            }
+            None => 0,
+        }
+    }
+
+    /// The start and end in minutes since midnight of the start date, if the
+    /// clock is stopped and both have times.
+    fn interval(&self) -> Option<(i64, i64)> {
+        let date = self.timestamp.date();
+        let start = minutes_since(date, date, self.timestamp.time()?);
+        let end = minutes_since(date, self.timestamp.end_date()?, self.timestamp.end_time()?);
+        Some((start, end))
+    }
+}
+
+impl Shift for Clock {
+    fn shift(&mut self, delta: isize) {
+        self.span.shift(delta);
+    }
+}
+
+fn minutes_since(origin: Date, date: Date, time: Time) -> i64 {
+    date.days_since(origin) * 24 * 60 + time.hour as i64 * 60 + time.minute as i64
+}
+
+/// Parse `line`, starting at `offset` in the text, as a clock line.
+pub fn parse_clock(line: &str, offset: usize) -> Option<Clock> {
+    let rest = line.trim().strip_prefix("CLOCK:")?.trim_start();
+    let (timestamp, len) = timestamp::parse_prefix(rest)?;
+    if timestamp.is_active() {
+        return None;
+    }
+    let rest = rest[len..].trim();
+    let stated = if rest.is_empty() {
+        None
+    } else {
+        let duration = rest.strip_prefix("=>")?.trim();
+        let colon = duration.find(':')?;
+        let hours: u32 = duration[..colon].parse().ok()?;
+        let minutes: u32 = duration[colon + 1..].parse().ok()?;
+        Some(hours * 60 + minutes)
+    };
+    Some(Clock { timestamp, stated, span: Span::new(offset, offset + line.len()) })
+}
+
+/// A line of a clock report, as made by `Document::clock_report`.
+#[derive(Debug, Clone, Copy)]
+pub struct ClockEntry<'a> {
+    pub headline: &'a Headline,
+    /// The minutes clocked on the headline itself.
+    pub own: u32,
+    /// The minutes clocked on the headline and all its descendants.
+    pub total: u32,
+}
+
+impl Headline {
+    /// The clock lines in the headline's section, including those in its
+    /// drawers, but not those of its children.
+    pub fn clocks(&self) -> Vec<&Clock> {
+        fn collect<'a>(contents: &'a [Content], clocks: &mut Vec<&'a Clock>) {
+            for content in contents {
+                if let Content::Element(Element::Clock(ref clock)) = *content {
+                    clocks.push(clock);
+                }
+                let (children, items) = content.children();
+                collect(children, clocks);
+                for item in items {
+                    collect(item.contents(), clocks);
+                }
+            }
+        }
+
+        let mut clocks = Vec::new();
+        if let Some(section) = self.section() {
+            collect(section.contents(), &mut clocks);
+        }
+        clocks
+    }
+
+    /// Start a clock at the time `now` gives, adding a running clock line to
+    /// the top of the `:LOGBOOK:` drawer, like `org-clock-in`. Does nothing,
+    /// returning `false`, if a clock is already running on the headline.
+    pub fn clock_in(&mut self, now: &dyn Now) -> bool {
+        self.clock_in_to(now, Some("LOGBOOK"))
+    }
+
+    /// Start a clock like `clock_in`, adding the clock line to `drawer`, or
+    /// straight into the section for `None`.
+    fn clock_in_to(&mut self, now: &dyn Now, drawer: Option<&str>) -> bool {
+        if self.clocks().iter().any(|clock| clock.is_running()) {
+            return false;
+        }
+        let (date, time) = now.now();
+        let line = format!("CLOCK: {}", Timestamp::new(false, date, Some(time)));
+        self.edit_lines(|lines, _| todo::insert_log_line(lines, line, drawer));
+        true
+    }
+
+    /// Stop the running clock at the time `now` gives, like
+    /// `org-clock-out`, returning the minutes it ran, or `None` if no clock
+    /// is running on the headline.
+    pub fn clock_out(&mut self, now: &dyn Now) -> Option<u32> {
+        let running = self.clocks().into_iter().find(|clock| clock.is_running())?.clone();
+        let (date, time) = now.now();
+        let (start_date, start_time) = running.start();
+        let midnight = Time { hour: 0, minute: 0 };
+        let minutes = (minutes_since(start_date, date, time)
+            - minutes_since(start_date, start_date, start_time.unwrap_or(midnight))).max(0) as u32;
+        let line = format!("CLOCK: {}--{} => {:>2}:{:02}", running.timestamp(),
+                           Timestamp::new(false, date, Some(time)), minutes / 60, minutes % 60);
+        self.edit_lines(|lines, offset| {
+            let mut start = offset;
+            for old in lines.iter_mut() {
+                if start == running.span().start {
+                    let indent = old.len() - old.trim_start().len();
+                    *old = format!("{}{}", &old[..indent], line);
+                    return;
+                }
+                start += old.len() + 1;
+            }
+        });
+        Some(minutes)
+    }
+
+    /// Rewrite the lines of the section text with `edit`, which is also
+    /// given the offset the section starts at, and parse it again.
+    fn edit_lines<F: FnOnce(&mut Vec<String>, usize)>(&mut self, edit: F) {
+        let (old, offset) = match self.section.take() {
+            Some(section) => (section.text, section.span.start),
+            None => (String::new(), self.body_start()),
+        };
+        let mut lines: Vec<String> = old.lines().map(String::from).collect();
+        edit(&mut lines, offset);
+        let mut text = lines.join("\n");
+        if !text.is_empty() && (old.is_empty() || old.ends_with('\n')) {
+            text.push('\n');
+        }
+        let parser = self.parser.clone();
+        self.set_body(&parser, &text, offset);
+    }
+
+    /// The minutes clocked on this headline and its descendants, only
+    /// counting time from `range.0` to `range.1` inclusive if a range is
+    /// given. Running clocks don't count.
+    pub fn clocked_minutes(&self, range: Option<(Date, Date)>) -> u32 {
+        own_minutes(self, range)
+            + self.children().iter().map(|child| child.clocked_minutes(range)).sum::<u32>()
+    }
+}
+
+/// The minutes clocked on `headline` itself, not counting its children.
+pub fn own_minutes(headline: &Headline, range: Option<(Date, Date)>) -> u32 {
+    headline.clocks().iter()
+        .map(|clock| match range {
+            Some((start, end)) => clock.duration_between(start, end),
+            None => clock.duration().unwrap_or(0),
+        })
+        .sum()
+}
+
+impl Document {
+    /// The clocked time of every headline with any, in document order, like
+    /// org's clocktable. Each entry's total includes the time of the
+    /// headline's descendants.
+    pub fn clock_report(&self, range: Option<(Date, Date)>) -> Vec<ClockEntry<'_>> {
+        fn report<'a>(headlines: &'a [Headline], range: Option<(Date, Date)>,
+                      entries: &mut Vec<ClockEntry<'a>>) -> u32 {
+            let mut sum = 0;
+            for headline in headlines {
+                let index = entries.len();
+                let own = own_minutes(headline, range);
+                entries.push(ClockEntry { headline, own, total: 0 });
+                let total = own + report(headline.children(), range, entries);
+                if total == 0 {
+                    entries.truncate(index);
+                } else {
+                    entries[index].total = total;
+                }
+                sum += total;
+            }
+            sum
+        }
+
+        let mut entries = Vec::new();
+        report(self.headlines(), range, &mut entries);
+        entries
+    }
+
+    /// The drawer clock lines of the headline at `path` go in, as org
+    /// decides it: from the `CLOCK_INTO_DRAWER` property the headline has
+    /// or inherits, or else the drawer of `log_drawer_at`, or else
+    /// `LOGBOOK`.
+    pub fn clock_drawer_at(&self, path: &[usize]) -> Option<String> {
+        match self.inherited_property(path, "CLOCK_INTO_DRAWER") {
+            Some(ref value) if value.trim() == "nil" => None,
+            Some(ref value) if value.trim() != "t" && value.trim().parse::<u32>().is_err() => {
+                Some(value.trim().into())
+            }
+            _ => Some(self.log_drawer_at(path, None).unwrap_or_else(|| "LOGBOOK".into())),
+        }
+    }
+
+    /// Start a clock on the headline at `path` like `Headline::clock_in`,
+    /// adding the clock line to the drawer `clock_drawer_at` gives. Returns
+    /// false if there is no headline at `path` or a clock is already running
+    /// on it.
+    pub fn clock_in_at(&mut self, path: &[usize], now: &dyn Now) -> bool {
+        let drawer = self.clock_drawer_at(path);
+        self.headline_at_mut(path)
+            .is_some_and(|headline| headline.clock_in_to(now, drawer.as_deref()))
+    }
+
+    /// Start a session of `minutes` on the headline at `path` at the time
+    /// `now` gives. Returns `None` if there is no headline at `path`.
+    pub fn start_session(&self, path: &[usize], minutes: u32, now: &dyn Now) -> Option<Session> {
+        self.cursor_at(path)?;
+        Some(Session { path: path.to_vec(), start: now.now(), minutes })
+    }
+
+    /// End `session` at the time `now` gives, adding a clock line for it to
+    /// the drawer `clock_drawer_at` gives and, if there is a `note`, a
+    /// `- Note taken on` entry to the drawer of `log_drawer_at`. Returns the
+    /// minutes clocked, or `None` if there is no headline at the session's
+    /// path any more.
+    pub fn finish_session(&mut self, session: &Session, now: &dyn Now, note: Option<&str>)
+                          -> Option<u32> {
+        let clock_drawer = self.clock_drawer_at(&session.path);
+        let note_drawer = self.log_drawer_at(&session.path, None);
+        let headline = self.headline_at_mut(&session.path)?;
+        let (date, time) = now.now();
+        let minutes = session.elapsed(now);
+        let (start_date, start_time) = session.start;
+        let clock = format!("CLOCK: {}--{} => {:>2}:{:02}",
+                            Timestamp::new(false, start_date, Some(start_time)),
+                            Timestamp::new(false, date, Some(time)), minutes / 60, minutes % 60);
+        let note = note.map(|note| {
+            format!("- Note taken on {}{}", Timestamp::new(false, date, Some(time)),
+                    todo::format_note(note))
+        });
+        headline.edit_lines(|lines, _| {
+            todo::insert_log_line(lines, clock, clock_drawer.as_deref());
+            if let Some(note) = note {
+                todo::insert_log_line(lines, note, note_drawer.as_deref());
+            }
+        });
+        Some(minutes)
+    }
+
+    /// The minutes clocked in the whole document.
+    pub fn clocked_minutes(&self, range: Option<(Date, Date)>) -> u32 {
+        self.headlines().iter().map(|headline| headline.clocked_minutes(range)).sum()
+    }
+}
+
+/// A timed stretch of work on a headline, like a pomodoro, made by
+/// `Document::start_session`. Nothing is written to the document until it
+/// is ended with `Document::finish_session`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Session {
+    path: Vec<usize>,
+    start: (Date, Time),
+    minutes: u32,
+}
+
+impl Session {
+    /// The path of the headline the session is on, see `Document::cursor_at`.
+    pub fn path(&self) -> &[usize] {
+        &self.path
+    }
+
+    pub fn start(&self) -> (Date, Time) {
+        self.start
+    }
+
+    /// The planned length in minutes.
+    pub fn minutes(&self) -> u32 {
+        self.minutes
+    }
+
+    /// The minutes since the session started, at the time `now` gives.
+    pub fn elapsed(&self, now: &dyn Now) -> u32 {
+        let (start_date, start_time) = self.start;
+        let (date, time) = now.now();
+        (minutes_since(start_date, date, time)
+            - minutes_since(start_date, start_date, start_time)).max(0) as u32
+    }
+
+    /// The minutes left of the planned length, or 0 once it has run out.
+    pub fn remaining(&self, now: &dyn Now) -> u32 {
+        self.minutes.saturating_sub(self.elapsed(now))
+    }
+
+    pub fn is_over(&self, now: &dyn Now) -> bool {
+        self.remaining(now) == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use DocumentParser;
+
+    #[test]
+    fn test_parse_clock() {
+        let clock = parse_clock(
+            "  CLOCK: [2024-05-07 Tue 23:30]--[2024-05-08 Wed 01:00] =>  1:30", 4).unwrap();
+        assert_eq!(clock.span(), Span::new(4, 68));
+        assert_eq!(clock.duration(), Some(90));
+        assert_eq!(clock.end().unwrap().0.day, 8);
+        let day = |day| Date { year: 2024, month: 5, day };
+        assert_eq!(clock.duration_between(day(8), day(8)), 60);
+        assert_eq!(clock.duration_between(day(1), day(7)), 30);
+        assert_eq!(clock.duration_between(day(9), day(9)), 0);
+
+        let running = parse_clock("CLOCK: [2024-05-08 Wed 09:15]", 0).unwrap();
+        assert!(running.is_running());
+        assert_eq!(running.duration(), None);
+
+        let stated = parse_clock("CLOCK: [2024-05-07 Tue]--[2024-05-07 Tue] =>  2:05", 0);
+        assert_eq!(stated.unwrap().duration(), Some(125));
+        assert!(parse_clock("CLOCK: <2024-05-07 Tue 10:00>", 0).is_none());
+        assert!(parse_clock("CLOCK: [2024-05-07 Tue 10:00] soon", 0).is_none());
+    }
+
+    #[test]
+    fn test_clock_report() {
+        let doc = DocumentParser::new().parse("* Project
+:LOGBOOK:
+CLOCK: [2024-05-06 Mon 09:00]--[2024-05-06 Mon 09:30] =>  0:30
+:END:
+** Design
+:LOGBOOK:
+CLOCK: [2024-05-07 Tue 10:00]--[2024-05-07 Tue 12:00] =>  2:00
+CLOCK: [2024-05-09 Thu 14:00]--[2024-05-09 Thu 14:45] =>  0:45
+:END:
+** Ideas
+* Running
+:LOGBOOK:
+CLOCK: [2024-05-09 Thu 16:00]
+:END:").unwrap();
+        let report: Vec<_> = doc.clock_report(None).iter()
+            .map(|entry| (entry.headline.title(), entry.own, entry.total))
+            .collect();
+        assert_eq!(report, [("Project", 30, 195), ("Design", 165, 165)]);
+        assert_eq!(doc.clocked_minutes(None), 195);
+
+        let day = |day| Date { year: 2024, month: 5, day };
+        let week = Some((day(7), day(8)));
+        let report: Vec<_> = doc.clock_report(week).iter()
+            .map(|entry| (entry.headline.title(), entry.own, entry.total))
+            .collect();
+        assert_eq!(report, [("Project", 0, 120), ("Design", 120, 120)]);
+        assert!(doc.headlines()[1].clocks()[0].is_running());
+    }
+
+    #[test]
+    fn test_clock_in_out() {
+        let mut doc = DocumentParser::new().parse("* Project
+SCHEDULED: <2024-05-08 Wed>
+:PROPERTIES:
+:EFFORT: 2:00
+:END:
+Notes.
+").unwrap();
+        let day = |day| Date { year: 2024, month: 5, day };
+        let project = &mut doc.headlines_mut()[0];
+        assert!(project.clock_in(&(day(8), Time { hour: 23, minute: 15 })));
+        assert!(!project.clock_in(&(day(8), Time { hour: 23, minute: 20 })));
+        let later = || (day(9), Time { hour: 1, minute: 5 });
+        assert_eq!(project.clock_out(&later), Some(110));
+        assert_eq!(project.clock_out(&later), None);
+        assert!(project.clock_in(&(day(9), Time { hour: 9, minute: 0 })));
+        assert_eq!(project.to_org_string(), "* Project
+SCHEDULED: <2024-05-08 Wed>
+:PROPERTIES:
+:EFFORT: 2:00
+:END:
+:LOGBOOK:
+CLOCK: [2024-05-09 Thu 09:00]
+CLOCK: [2024-05-08 Wed 23:15]--[2024-05-09 Thu 01:05] =>  1:50
+:END:
+Notes.
+");
+        assert_eq!(project.clocked_minutes(None), 110);
+        assert_eq!(project.property("EFFORT"), Some("2:00"));
+    }
+
+    #[test]
+    fn test_session() {
+        let mut doc = DocumentParser::new().parse("* Write
+:PROPERTIES:
+:LOG_INTO_DRAWER: t
+:END:
+** Chapter one
+").unwrap();
+        let at = |hour, minute| (Date { year: 2024, month: 5, day: 8 }, Time { hour, minute });
+        assert_eq!(doc.start_session(&[0, 1], 25, &at(9, 0)), None);
+        let session = doc.start_session(&[0, 0], 25, &at(9, 0)).unwrap();
+        assert_eq!((session.elapsed(&at(9, 10)), session.remaining(&at(9, 10))), (10, 15));
+        assert!(!session.is_over(&at(9, 24)));
+        assert!(session.is_over(&at(9, 30)));
+        assert_eq!(doc.to_org_string().matches("CLOCK").count(), 0);
+        assert_eq!(doc.finish_session(&session, &at(9, 25), Some("Drafted the opening.")),
+                   Some(25));
+        assert_eq!(doc.headlines()[0].children()[0].to_org_string(), "** Chapter one
+:LOGBOOK:
+- Note taken on [2024-05-08 Wed 09:25] \\\\
+  Drafted the opening.
+CLOCK: [2024-05-08 Wed 09:00]--[2024-05-08 Wed 09:25] =>  0:25
+:END:
+");
+        assert_eq!(doc.clocked_minutes(None), 25);
+    }
+}
@@ -0,0 +1,312 @@
+//! Column view: a table of property values, one row per headline, as
+//! `org-columns` shows it. Columns are written like the value of a
+//! `#+COLUMNS:` line:
+//!
+//! ```ignore
+//! %25ITEM %TODO %3PRIORITY %TAGS %CLOCKSUM %Effort(Estimate){:}
+//! ```
+//!
+//! Each column is a property, with an optional width before it, a title in
+//! parentheses and a summary type in braces. Besides the properties in
+//! drawers, `ITEM`, `TODO`, `PRIORITY`, `TAGS`, `ALLTAGS`, `LEVEL`,
+//! `CLOCKSUM`, `SCHEDULED`, `DEADLINE` and `CLOSED` are computed from the
+//! headline.
+
+use {Content, Document, Element, Headline};
+
+/// The columns used when none are given and the document has no `#+COLUMNS:`
+/// line, like `org-columns-default-format`.
+pub const DEFAULT_COLUMNS: &str = "%25ITEM %TODO %3PRIORITY %TAGS";
+
+/// One column of a view.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Column {
+    /// The property shown, in upper case for the special ones.
+    pub property: String,
+    pub title: Option<String>,
+    pub width: Option<usize>,
+    pub summary: Option<Summary>,
+}
+
+impl Column {
+    /// The title, or else the property name.
+    pub fn heading(&self) -> &str {
+        self.title.as_ref().unwrap_or(&self.property)
+    }
+}
+
+/// How a parent's value is computed from those of its children.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Summary {
+    /// `{+}`, the sum of numbers.
+    Sum,
+    /// `{:}`, the sum of `H:MM` durations.
+    Time,
+    /// Any other summary type, which is kept but not computed.
+    Other(String),
+}
+
+/// Parse a column spec, skipping anything that isn't a `%` column.
+pub fn parse_columns(spec: &str) -> Vec<Column> {
+    spec.split_whitespace().filter_map(parse_column).collect()
+}
+
+fn parse_column(word: &str) -> Option<Column> {
+    let rest = word.strip_prefix('%')?;
+    let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let width = rest[..digits].parse().ok();
+    let mut rest = &rest[digits..];
+    let mut summary = None;
+    if let Some(open) = rest.find('{').filter(|_| rest.ends_with('}')) {
+        summary = Some(match &rest[open + 1..rest.len() - 1] {
+            "+" => Summary::Sum,
+            ":" => Summary::Time,
+            other => Summary::Other(other.into()),
+        });
+        rest = &rest[..open];
+    }
+    let mut title = None;
+    if let Some(open) = rest.find('(').filter(|_| rest.ends_with(')')) {
+        title = Some(rest[open + 1..rest.len() - 1].to_string());
+        rest = &rest[..open];
+    }
+    if rest.is_empty() {
+        return None;
+    }
+    let property = if SPECIAL.iter().any(|special| special.eq_ignore_ascii_case(rest)) {
+        rest.to_ascii_uppercase()
+    } else {
+        rest.into()
+    };
+    Some(Column { property, title, width, summary })
+}
+
+const SPECIAL: [&str; 10] = ["ITEM", "TODO", "PRIORITY", "TAGS", "ALLTAGS", "LEVEL", "CLOCKSUM",
+                             "SCHEDULED", "DEADLINE", "CLOSED"];
+
+/// A column view made by `Document::column_view`.
+#[derive(Debug, Clone)]
+pub struct ColumnView<'a> {
+    pub columns: Vec<Column>,
+    pub rows: Vec<Row<'a>>,
+}
+
+/// The values of one headline, in the order of the columns. Missing
+/// properties are `None`.
+#[derive(Debug, Clone)]
+pub struct Row<'a> {
+    pub headline: &'a Headline,
+    pub values: Vec<Option<String>>,
+}
+
+impl Document {
+    /// The `#+PROPERTY:` lines of the document, by key, which every headline
+    /// inherits.
+    pub fn file_properties(&self) -> Vec<(String, String)> {
+        let mut properties: Vec<(String, String)> = Vec::new();
+        let sections = self.first_section().into_iter()
+            .chain(self.all_headlines().filter_map(|headline| headline.section()));
+        for section in sections {
+            for content in section.contents() {
+                let keyword = match *content {
+                    Content::Element(Element::Keyword(ref keyword)) => keyword,
+                    _ => continue,
+                };
+                if !keyword.key().eq_ignore_ascii_case("PROPERTY") {
+                    continue;
+                }
+                let mut parts = keyword.value().splitn(2, char::is_whitespace);
+                let key = parts.next().unwrap_or("");
+                let value = parts.next().unwrap_or("").trim();
+                match properties.iter_mut().find(|(k, _)| k.eq_ignore_ascii_case(key)) {
+                    Some(existing) => existing.1 = value.into(),
+                    None if !key.is_empty() => properties.push((key.into(), value.into())),
+                    None => {}
+                }
+            }
+        }
+        properties
+    }
+
+    /// A row for every headline, depth-first, with the values of `columns`,
+    /// or of the document's `#+COLUMNS:` line, or of `DEFAULT_COLUMNS`.
+    ///
+    /// Properties not set on a headline are inherited from its ancestors and
+    /// then from `#+PROPERTY:` lines. Columns with a `{+}` or `{:}` summary
+    /// show, for headlines with children, the sum over the children instead.
+    pub fn column_view(&self, columns: Option<&str>) -> ColumnView<'_> {
+        let spec = match columns {
+            Some(spec) => spec.to_string(),
+            None => self.keywords().get("COLUMNS").cloned()
+                .unwrap_or_else(|| DEFAULT_COLUMNS.into()),
+        };
+        let columns = parse_columns(&spec);
+        let mut view = ColumnView { columns, rows: Vec::new() };
+        let mut inherited = self.file_properties();
+        let file_tags = self.file_tags();
+        for headline in &self.headlines {
+            add_rows(headline, &mut inherited, &file_tags, &mut view);
+        }
+        view
+    }
+}
+
+/// Add the rows of `headline` and its descendants to `view`, returning the
+/// values of `headline`.
+fn add_rows<'a>(headline: &'a Headline, inherited: &mut Vec<(String, String)>,
+                tags: &[String], view: &mut ColumnView<'a>) -> Vec<Option<String>> {
+    let before = inherited.len();
+    for (key, value) in headline.properties().iter() {
+        inherited.push((key.into(), value.into()));
+    }
+    let mut all_tags = tags.to_vec();
+    for tag in headline.tags() {
+        if !all_tags.iter().any(|t| t == tag) {
+            all_tags.push(tag.to_string());
+        }
+    }
+    let index = view.rows.len();
+    let values = view.columns.iter()
+        .map(|column| value(headline, column, inherited, &all_tags))
+        .collect();
+    view.rows.push(Row { headline, values });
+    let children: Vec<_> = headline.children().iter()
+        .map(|child| add_rows(child, inherited, &all_tags, view))
+        .collect();
+    inherited.truncate(before);
+
+    if !children.is_empty() {
+        for (i, column) in view.columns.iter().enumerate() {
+            if let Some(sum) = column.summary.as_ref()
+                .and_then(|summary| summarize(summary, children.iter().map(|values| &values[i]))) {
+                view.rows[index].values[i] = Some(sum);
+            }
+        }
+    }
+    view.rows[index].values.clone()
+}
+
+fn value(headline: &Headline, column: &Column, inherited: &[(String, String)],
+         all_tags: &[String]) -> Option<String> {
+    let tags = |tags: &[String]| if tags.is_empty() {
+        None
+    } else {
+        Some(format!(":{}:", tags.join(":")))
+    };
+    match &column.property[..] {
+        "ITEM" => Some(headline.title().into()),
+        "TODO" => headline.keyword().map(String::from),
+        "PRIORITY" => headline.priority().map(|priority| priority.to_string()),
+        "TAGS" => {
+            let own: Vec<_> = headline.tags().iter().map(|tag| tag.to_string()).collect();
+            tags(&own)
+        }
+        "ALLTAGS" => tags(all_tags),
+        "LEVEL" => Some(headline.level().to_string()),
+        "CLOCKSUM" => match headline.clocked_minutes(None) {
+            0 => None,
+            minutes => Some(format_minutes(minutes)),
+        },
+        "SCHEDULED" => headline.scheduled().map(|timestamp| timestamp.to_string()),
+        "DEADLINE" => headline.deadline().map(|timestamp| timestamp.to_string()),
+        "CLOSED" => headline.closed().map(|timestamp| timestamp.to_string()),
+        key => inherited.iter().rev()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, value)| value.clone()),
+    }
+}
+
+/// The sum of the values with `summary`, or `None` if none of them count.
+fn summarize<'a, I>(summary: &Summary, values: I) -> Option<String>
+    where I: Iterator<Item = &'a Option<String>> {
+    let values = values.filter_map(|value| value.as_ref());
+    match *summary {
+        Summary::Sum => {
+            let numbers: Vec<f64> = values.filter_map(|value| value.parse().ok()).collect();
+            if numbers.is_empty() {
+                return None;
+            }
+            Some(numbers.iter().sum::<f64>().to_string())
+        }
+        Summary::Time => {
+            let minutes: Vec<u32> = values.filter_map(|value| parse_minutes(value)).collect();
+            if minutes.is_empty() {
+                return None;
+            }
+            Some(format_minutes(minutes.iter().sum()))
+        }
+        Summary::Other(_) => None,
+    }
+}
+
+fn parse_minutes(value: &str) -> Option<u32> {
+    let mut parts = value.trim().splitn(2, ':');
+    let hours: u32 = parts.next()?.parse().ok()?;
+    let minutes: u32 = parts.next().map_or(Some(0), |minutes| minutes.parse().ok())?;
+    Some(hours * 60 + minutes)
+}
+
+fn format_minutes(minutes: u32) -> String {
+    format!("{}:{:02}", minutes / 60, minutes % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use DocumentParser;
+
+    #[test]
+    fn test_parse_columns() {
+        let columns = parse_columns("%25ITEM %todo %Effort(Estimate){:} junk %Cost{+}");
+        assert_eq!(columns.len(), 4);
+        assert_eq!(columns[0].width, Some(25));
+        assert_eq!(columns[1].property, "TODO");
+        assert_eq!(columns[2], Column {
+            property: "Effort".into(),
+            title: Some("Estimate".into()),
+            width: None,
+            summary: Some(Summary::Time),
+        });
+        assert_eq!(columns[2].heading(), "Estimate");
+        assert_eq!(columns[3].summary, Some(Summary::Sum));
+    }
+
+    #[test]
+    fn test_column_view() {
+        let parser = DocumentParser::new().todo_keywords(vec!["TODO", "DONE"]);
+        let doc = parser.parse("#+PROPERTY: Owner sam
+#+COLUMNS: %ITEM %TODO %Owner %Effort{:} %CLOCKSUM
+* Release :work:
+:PROPERTIES:
+:Owner: kim
+:END:
+** TODO [#A] Tag it
+:PROPERTIES:
+:Effort: 0:30
+:END:
+** DONE Build
+CLOSED: [2024-05-07 Tue]
+:PROPERTIES:
+:Effort: 1:15
+:END:
+CLOCK: [2024-05-07 Tue 10:00]--[2024-05-07 Tue 11:00] =>  1:00
+* Notes").unwrap();
+        let view = doc.column_view(None);
+        let rows: Vec<Vec<_>> = view.rows.iter()
+            .map(|row| row.values.iter().map(|value| value.as_ref().map(|v| &v[..])).collect())
+            .collect();
+        assert_eq!(rows, [
+            vec![Some("Release"), None, Some("kim"), Some("1:45"), Some("1:00")],
+            vec![Some("Tag it"), Some("TODO"), Some("kim"), Some("0:30"), None],
+            vec![Some("Build"), Some("DONE"), Some("kim"), Some("1:15"), Some("1:00")],
+            vec![Some("Notes"), None, Some("sam"), None, None],
+        ]);
+
+        let view = doc.column_view(Some("%PRIORITY %ALLTAGS %LEVEL %CLOSED"));
+        let values = &view.rows[2].values;
+        assert_eq!(values[1].as_ref().unwrap(), ":work:");
+        assert_eq!(values[2].as_ref().unwrap(), "2");
+        assert_eq!(values[3].as_ref().unwrap(), "[2024-05-07 Tue]");
+        assert_eq!(view.rows[1].values[0].as_ref().unwrap(), "A");
+    }
+}
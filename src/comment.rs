@@ -0,0 +1,178 @@
+use span::{self, Shift, Span};
+use {BlockKind, Content, Document, Element, Headline};
+
+/// Consecutive comment lines, each a `#` followed by a space or nothing:
+///
+/// ```ignore
+/// # Not exported.
+/// #
+/// # Still the same comment.
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Comment {
+    text: String,
+    span: Span,
+}
+
+impl Comment {
+    /// The lines without their `# ` markers.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+}
+
+impl Shift for Comment {
+    fn shift(&mut self, delta: isize) {
+        self.span.shift(delta);
+    }
+}
+
+/// Comment lines or a `#+BEGIN_COMMENT` block, found by
+/// `Document::comments`. Exporters leave both out.
+#[derive(Debug, Clone, Copy)]
+pub struct CommentEntry<'a> {
+    /// The headline the comment is under, or `None` before the first one.
+    pub headline: Option<&'a Headline>,
+    /// The text without its `# ` markers or block delimiters.
+    pub text: &'a str,
+    pub span: Span,
+    /// Whether it is a `#+BEGIN_COMMENT` block rather than comment lines.
+    pub block: bool,
+}
+
+impl<'a> CommentEntry<'a> {
+    /// The `@Marker: note` lines of the comment, like `@Todo: Handle tabs`,
+    /// as the marker without its `@` and the rest of the line after it.
+    pub fn markers(&self) -> Vec<(&'a str, &'a str)> {
+        self.text.lines()
+            .filter_map(|line| {
+                let rest = line.trim_start().strip_prefix('@')?;
+                let end = rest.find(|c: char| !c.is_alphanumeric() && c != '_' && c != '-')
+                    .unwrap_or(rest.len());
+                if end == 0 {
+                    return None;
+                }
+                let note = rest[end..].strip_prefix(':').unwrap_or(&rest[end..]);
+                Some((&rest[..end], note.trim()))
+            })
+            .collect()
+    }
+}
+
+impl Document {
+    /// Every run of comment lines and every `#+BEGIN_COMMENT` block in the
+    /// document, including those inside lists and drawers, in order.
+    pub fn comments(&self) -> Vec<CommentEntry<'_>> {
+        fn collect<'a>(headline: Option<&'a Headline>, contents: &'a [Content],
+                       comments: &mut Vec<CommentEntry<'a>>) {
+            for content in contents {
+                match *content {
+                    Content::Element(Element::Comment(ref comment)) => {
+                        comments.push(CommentEntry {
+                            headline,
+                            text: comment.text(),
+                            span: comment.span(),
+                            block: false,
+                        });
+                    }
+                    Content::Element(Element::Block(ref block))
+                        if *block.kind() == BlockKind::Comment => {
+                        comments.push(CommentEntry {
+                            headline,
+                            text: block.text(),
+                            span: block.span(),
+                            block: true,
+                        });
+                    }
+                    _ => {}
+                }
+                let (children, items) = content.children();
+                collect(headline, children, comments);
+                for item in items {
+                    collect(headline, item.contents(), comments);
+                }
+            }
+        }
+
+        let mut comments = Vec::new();
+        if let Some(section) = self.first_section() {
+            collect(None, section.contents(), &mut comments);
+        }
+        for headline in self.all_headlines() {
+            if let Some(section) = headline.section() {
+                collect(Some(headline), section.contents(), &mut comments);
+            }
+        }
+        comments
+    }
+}
+
+/// The text of `line` after its `#` marker, if it is a comment line.
+fn comment_line(line: &str) -> Option<&str> {
+    let rest = line.trim_start().strip_prefix('#')?;
+    if rest.is_empty() {
+        Some(rest)
+    } else {
+        rest.strip_prefix(' ')
+    }
+}
+
+/// Try to parse a comment starting at `lines[start]`. Returns it and the
+/// index of the first line after it.
+pub fn parse_comment(lines: &[&str], offsets: &[usize], start: usize) -> Option<(Comment, usize)> {
+    comment_line(lines[start])?;
+    let end = (start..lines.len())
+        .find(|&i| comment_line(lines[i]).is_none())
+        .unwrap_or(lines.len());
+    let text: Vec<_> = lines[start..end].iter().filter_map(|line| comment_line(line)).collect();
+    Some((Comment {
+        text: text.join("\n"),
+        span: span::lines_span(lines, offsets, start, end),
+    }, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_comment() {
+        let lines = ["# one", "  #", "# two", "#+TITLE: x", "#not a comment"];
+        let offsets = span::line_offsets(&lines.join("\n"), 0);
+        let (comment, next) = parse_comment(&lines, &offsets, 0).unwrap();
+        assert_eq!(next, 3);
+        assert_eq!(comment.text(), "one\n\ntwo");
+        assert_eq!(comment.span(), Span::new(0, 15));
+        assert!(parse_comment(&lines, &offsets, 3).is_none());
+        assert!(parse_comment(&lines, &offsets, 4).is_none());
+    }
+
+    #[test]
+    fn test_comments() {
+        let doc = ::DocumentParser::new().parse("# @Todo: Intro
+* Parser
+- item
+  # @Fixme handle tabs
+#+BEGIN_COMMENT
+Not exported either.
+@Todo: Implement greater elements
+#+END_COMMENT
+").unwrap();
+        let comments = doc.comments();
+        let found: Vec<_> = comments.iter()
+            .map(|c| (c.headline.map(|h| h.title()), c.block, c.markers()))
+            .collect();
+        assert_eq!(found, [
+            (None, false, vec![("Todo", "Intro")]),
+            (Some("Parser"), false, vec![("Fixme", "handle tabs")]),
+            (Some("Parser"), true, vec![("Todo", "Implement greater elements")]),
+        ]);
+        assert_eq!(comments[2].text, "Not exported either.\n@Todo: Implement greater elements");
+        assert!(!::export::html::to_html(&doc).contains("exported"));
+    }
+}
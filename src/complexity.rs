@@ -0,0 +1,161 @@
+use std::collections::BTreeMap;
+
+use span::Span;
+use {Content, Diagnostic, Document, Element, GreaterElement, Headline, ListItem, TableRow,
+     Visitor};
+
+/// A summary of the shape of a document, made by
+/// `Document::complexity_report`, to see why a file is slow to work with or
+/// comes out wrong.
+#[derive(Debug, Clone, Default)]
+pub struct ComplexityReport {
+    /// How deeply headlines nest, 1 if there are only top-level ones.
+    pub max_depth: usize,
+    /// How deeply elements nest inside each other, counting list items.
+    pub max_nesting: usize,
+    /// The table with the most cells, and how many it has.
+    pub largest_table: Option<(Span, usize)>,
+    /// The longest section, and its length in bytes.
+    pub longest_section: Option<(Span, usize)>,
+    /// How many nodes there are of each kind, by names like `headline`,
+    /// `plain_list` and `paragraph`.
+    pub counts: BTreeMap<&'static str, usize>,
+    /// The malformed constructs the parser read as plain text.
+    pub recovered: Vec<Diagnostic>,
+}
+
+impl Document {
+    /// Measure the document. Only headlines and elements are counted, not
+    /// the objects in them.
+    pub fn complexity_report(&self) -> ComplexityReport {
+        let mut counter = Counter { report: ComplexityReport::default(), depth: 0, nesting: 0 };
+        self.walk(&mut counter);
+        let mut report = counter.report;
+        let sections = self.first_section().into_iter()
+            .chain(self.all_headlines().filter_map(|headline| headline.section()));
+        for section in sections {
+            let len = section.text().len();
+            if report.longest_section.is_none_or(|(_, longest)| len > longest) {
+                report.longest_section = Some((section.span(), len));
+            }
+        }
+        report.recovered = self.warnings().to_vec();
+        report
+    }
+}
+
+struct Counter {
+    report: ComplexityReport,
+    depth: usize,
+    nesting: usize,
+}
+
+impl Counter {
+    fn count(&mut self, kind: &'static str) {
+        *self.report.counts.entry(kind).or_insert(0) += 1;
+    }
+
+    fn enter(&mut self) {
+        self.nesting += 1;
+        self.report.max_nesting = self.report.max_nesting.max(self.nesting);
+    }
+}
+
+impl Visitor for Counter {
+    fn enter_headline(&mut self, _: &Headline) {
+        self.count("headline");
+        self.depth += 1;
+        self.report.max_depth = self.report.max_depth.max(self.depth);
+    }
+
+    fn exit_headline(&mut self, _: &Headline) {
+        self.depth -= 1;
+    }
+
+    fn enter_content(&mut self, content: &Content) {
+        self.count(kind(content));
+        self.enter();
+        if let Content::Greater(GreaterElement::Table(ref table)) = *content {
+            let cells = table.rows().iter()
+                .map(|row| match *row {
+                    TableRow::Standard(ref cells) => cells.len(),
+                    TableRow::Rule => 0,
+                })
+                .sum();
+            if self.report.largest_table.is_none_or(|(_, largest)| cells > largest) {
+                self.report.largest_table = Some((table.span(), cells));
+            }
+        }
+    }
+
+    fn exit_content(&mut self, _: &Content) {
+        self.nesting -= 1;
+    }
+
+    fn enter_list_item(&mut self, _: &ListItem) {
+        self.count("list_item");
+        self.enter();
+    }
+
+    fn exit_list_item(&mut self, _: &ListItem) {
+        self.nesting -= 1;
+    }
+}
+
+fn kind(content: &Content) -> &'static str {
+    match *content {
+        Content::Greater(ref greater) => match *greater {
+            GreaterElement::Block(_) => "greater_block",
+            GreaterElement::Drawer(_) => "drawer",
+            GreaterElement::DynamicBlock(_) => "dynamic_block",
+            GreaterElement::Footnote(_) => "footnote_definition",
+            GreaterElement::Inlinetask(_) => "inlinetask",
+            GreaterElement::PlainList(_) => "plain_list",
+            GreaterElement::PropertyDrawer(_) => "property_drawer",
+            GreaterElement::Table(_) => "table",
+        },
+        Content::Element(ref element) => match *element {
+            Element::BabelCall(_) => "babel_call",
+            Element::Block(_) => "block",
+            Element::Clock(_) => "clock",
+            Element::Comment(_) => "comment",
+            Element::FixedWidth(_) => "fixed_width",
+            Element::HorizontalRule(_) => "horizontal_rule",
+            Element::Keyword(_) => "keyword",
+            Element::LatexEnvironment(_) => "latex_environment",
+            Element::Paragraph(_) => "paragraph",
+            Element::Planning(_) => "planning",
+            Element::Raw(_) => "raw",
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use DocumentParser;
+
+    #[test]
+    fn test_complexity_report() {
+        let doc = DocumentParser::new().parse("Intro.
+* One
+- a
+  - b
+    | x | y |
+** Two
+| 1 | 2 | 3 |
+|---+---+---|
+| 4 | 5 | 6 |
+*** Three
+#+BEGIN_SRC sh
+never closed").unwrap();
+        let report = doc.complexity_report();
+        assert_eq!(report.max_depth, 3);
+        assert_eq!(report.max_nesting, 5);
+        assert_eq!(report.largest_table.unwrap().1, 6);
+        assert_eq!(report.longest_section.unwrap().1, 42);
+        assert_eq!(report.counts["headline"], 3);
+        assert_eq!(report.counts["list_item"], 2);
+        assert_eq!(report.counts["paragraph"], 4);
+        assert_eq!(report.recovered.len(), 1);
+    }
+}
@@ -0,0 +1,205 @@
+//! Address books following org-contacts' conventions: a headline is a contact
+//! when it has any of the `EMAIL`, `PHONE`, `BIRTHDAY` or `ADDRESS`
+//! properties.
+//!
+//! ```ignore
+//! * Ada Lovelace
+//! :PROPERTIES:
+//! :EMAIL: ada@example.com ada.l@example.org
+//! :PHONE: +44 20 7946 0000
+//! :BIRTHDAY: 1815-12-10
+//! :END:
+//! ```
+
+use {Date, Document, Headline, Timestamp};
+
+const CONTACT_PROPERTIES: [&str; 4] = ["EMAIL", "PHONE", "BIRTHDAY", "ADDRESS"];
+
+/// A contact, read from its headline.
+#[derive(Debug, Clone, Copy)]
+pub struct Contact<'a> {
+    headline: &'a Headline,
+}
+
+impl<'a> Contact<'a> {
+    /// The contact stored in `headline`, if it is one.
+    pub fn from_headline(headline: &'a Headline) -> Option<Contact<'a>> {
+        if CONTACT_PROPERTIES.iter().any(|key| headline.property(key).is_some()) {
+            Some(Contact { headline })
+        } else {
+            None
+        }
+    }
+
+    pub fn headline(&self) -> &'a Headline {
+        self.headline
+    }
+
+    /// The headline title.
+    pub fn name(&self) -> &'a str {
+        self.headline.title()
+    }
+
+    /// The addresses in `EMAIL`, which may list several separated by spaces
+    /// or commas.
+    pub fn emails(&self) -> Vec<&'a str> {
+        self.headline.property("EMAIL")
+            .map(|emails| {
+                emails.split(|c: char| c == ',' || c.is_whitespace())
+                    .filter(|email| !email.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// The numbers in `PHONE`, which may list several separated by commas.
+    pub fn phones(&self) -> Vec<&'a str> {
+        self.headline.property("PHONE")
+            .map(|phones| {
+                phones.split(',').map(str::trim).filter(|phone| !phone.is_empty()).collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// `BIRTHDAY`, written as a date like `1815-12-10` or as a timestamp.
+    pub fn birthday(&self) -> Option<Date> {
+        let birthday = self.headline.property("BIRTHDAY")?;
+        Date::parse(birthday).or_else(|| Timestamp::parse(birthday).map(|ts| ts.date()))
+    }
+
+    pub fn address(&self) -> Option<&'a str> {
+        self.headline.property("ADDRESS")
+    }
+
+    pub fn nickname(&self) -> Option<&'a str> {
+        self.headline.property("NICKNAME")
+    }
+
+    pub fn note(&self) -> Option<&'a str> {
+        self.headline.property("NOTE")
+    }
+
+    /// The first birthday on or after `date`.
+    pub fn next_birthday(&self, date: Date) -> Option<Date> {
+        let born = self.birthday()?;
+        let birthday = born.with_year(date.year);
+        if birthday >= date {
+            Some(birthday)
+        } else {
+            Some(born.with_year(date.year + 1))
+        }
+    }
+
+    /// The contact as a vCard 3.0.
+    pub fn to_vcard(&self) -> String {
+        let mut card = String::from("BEGIN:VCARD\r\nVERSION:3.0\r\n");
+        card.push_str(&format!("FN:{}\r\n", escape(self.name())));
+        if let Some(nickname) = self.nickname() {
+            card.push_str(&format!("NICKNAME:{}\r\n", escape(nickname)));
+        }
+        for email in self.emails() {
+            card.push_str(&format!("EMAIL:{}\r\n", escape(email)));
+        }
+        for phone in self.phones() {
+            card.push_str(&format!("TEL:{}\r\n", escape(phone)));
+        }
+        if let Some(address) = self.address() {
+            card.push_str(&format!("ADR:;;{};;;;\r\n", escape(address)));
+        }
+        if let Some(birthday) = self.birthday() {
+            card.push_str(&format!("BDAY:{}\r\n", birthday));
+        }
+        if let Some(note) = self.note() {
+            card.push_str(&format!("NOTE:{}\r\n", escape(note)));
+        }
+        card.push_str("END:VCARD\r\n");
+        card
+    }
+}
+
+/// Escape a vCard text value.
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '\\' | ',' | ';' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Every contact in the document, in order.
+pub fn contacts(document: &Document) -> Vec<Contact<'_>> {
+    document.all_headlines().filter_map(Contact::from_headline).collect()
+}
+
+/// The birthdays from `start` to `end`, inclusive, each with its contact,
+/// sorted by date.
+pub fn birthdays<'a>(contacts: &[Contact<'a>], start: Date, end: Date)
+                     -> Vec<(Date, Contact<'a>)> {
+    let mut birthdays = Vec::new();
+    for contact in contacts {
+        let mut date = start;
+        while let Some(birthday) = contact.next_birthday(date).filter(|&day| day <= end) {
+            birthdays.push((birthday, *contact));
+            date = birthday.add_days(1);
+        }
+    }
+    birthdays.sort_by_key(|&(date, _)| date);
+    birthdays
+}
+
+/// All contacts in the document as one vCard file.
+pub fn to_vcards(document: &Document) -> String {
+    contacts(document).iter().map(Contact::to_vcard).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use DocumentParser;
+
+    #[test]
+    fn test_contacts() {
+        let doc = DocumentParser::new().parse("* Friends
+** Ada Lovelace
+:PROPERTIES:
+:EMAIL: ada@example.com, ada.l@example.org
+:PHONE: +44 20 7946 0000
+:BIRTHDAY: 1815-12-10
+:NOTE: Analyst; poet
+:END:
+** Leap Day
+:PROPERTIES:
+:BIRTHDAY: <2000-02-29 Tue>
+:END:").unwrap();
+        let contacts = contacts(&doc);
+        let names: Vec<_> = contacts.iter().map(|c| c.name()).collect();
+        assert_eq!(names, ["Ada Lovelace", "Leap Day"]);
+        assert_eq!(contacts[0].emails(), ["ada@example.com", "ada.l@example.org"]);
+
+        let start = Date { year: 2024, month: 12, day: 1 };
+        let end = Date { year: 2025, month: 12, day: 31 };
+        let dates: Vec<_> = birthdays(&contacts, start, end).iter()
+            .map(|&(date, contact)| format!("{} {}", date, contact.name()))
+            .collect();
+        assert_eq!(dates, ["2024-12-10 Ada Lovelace", "2025-02-28 Leap Day",
+                           "2025-12-10 Ada Lovelace"]);
+
+        assert_eq!(contacts[0].to_vcard(), "BEGIN:VCARD\r
+VERSION:3.0\r
+FN:Ada Lovelace\r
+EMAIL:ada@example.com\r
+EMAIL:ada.l@example.org\r
+TEL:+44 20 7946 0000\r
+BDAY:1815-12-10\r
+NOTE:Analyst\\; poet\r
+END:VCARD\r
+");
+    }
+}
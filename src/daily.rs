@@ -0,0 +1,181 @@
+//! Daily notes: one file per day in a notes directory, made from a template,
+//! as org-roam-dailies keeps them. Creating a note links it with the day
+//! before and with the notes that already link to it.
+
+use std::path::Path;
+
+use store::DocumentSet;
+use {Date, DocumentParser};
+
+/// Where daily notes go and what a new one starts with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DailyNotes {
+    directory: String,
+    file_name: String,
+    template: String,
+}
+
+impl DailyNotes {
+    /// Daily notes in `directory`, named like `2024-05-08.org`, starting with
+    /// a `#+TITLE:` of the date.
+    pub fn new<S: Into<String>>(directory: S) -> DailyNotes {
+        DailyNotes {
+            directory: directory.into(),
+            file_name: "%Y-%m-%d.org".into(),
+            template: "#+TITLE: %Y-%m-%d\n".into(),
+        }
+    }
+
+    /// The file name of a note, where `%Y`, `%m` and `%d` stand for the year,
+    /// month and day of its date, `%a` for the day of the week like `Wed`,
+    /// and `%%` for a percent sign.
+    pub fn file_name<S: Into<String>>(mut self, pattern: S) -> Self {
+        self.file_name = pattern.into();
+        self
+    }
+
+    /// The text a new note starts with, with the same placeholders as
+    /// `file_name`.
+    pub fn template<S: Into<String>>(mut self, template: S) -> Self {
+        self.template = template.into();
+        self
+    }
+
+    /// The name of the note for `date` in a `DocumentSet`: its path.
+    pub fn name(&self, date: Date) -> String {
+        let file_name = expand(&self.file_name, date);
+        Path::new(&self.directory).join(file_name).to_string_lossy().into_owned()
+    }
+
+    /// Link text for `name` from a note: its path relative to the directory.
+    fn relative<'a>(&self, name: &'a str) -> &'a str {
+        Path::new(name).strip_prefix(&self.directory).ok()
+            .and_then(|path| path.to_str())
+            .unwrap_or(name)
+    }
+}
+
+/// Expand the placeholders in `pattern` for `date`.
+fn expand(pattern: &str, date: Date) -> String {
+    let mut out = String::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('Y') => out.push_str(&format!("{:04}", date.year)),
+            Some('m') => out.push_str(&format!("{:02}", date.month)),
+            Some('d') => out.push_str(&format!("{:02}", date.day)),
+            Some('a') => out.push_str(::timestamp::day_name(date)),
+            Some('%') => out.push('%'),
+            Some(other) => {
+                out.push('%');
+                out.push(other);
+            }
+            None => out.push('%'),
+        }
+    }
+    out
+}
+
+/// The title of the note `name` in `set`: its `#+TITLE:`, or else its file
+/// stem.
+fn title(set: &DocumentSet, name: &str) -> String {
+    set.get(name).and_then(|document| document.keywords().get("TITLE").cloned())
+        .filter(|title| !title.is_empty())
+        .or_else(|| Path::new(name).file_stem().map(|stem| stem.to_string_lossy().into_owned()))
+        .unwrap_or_else(|| name.into())
+}
+
+impl DocumentSet {
+    /// Add the daily note for `date`, parsed with `parser`, and return its
+    /// name, or `None` if the set already has it. Notes are only made in
+    /// memory; write them out with `DocumentSet::save`.
+    ///
+    /// Below the template, the note gets a `Links` headline listing the most
+    /// recent earlier daily note within a year, and every note that already
+    /// links to the new one. The earlier note gets a link to the new one in
+    /// return.
+    pub fn create_daily_note(&mut self, parser: &DocumentParser, notes: &DailyNotes, date: Date)
+                             -> Option<String> {
+        let name = notes.name(date);
+        if self.get(&name).is_some() {
+            return None;
+        }
+        let previous = (1..=366)
+            .map(|days| (date.add_days(-days), notes.name(date.add_days(-days))))
+            .find(|(_, name)| self.get(name).is_some());
+        let mut sources: Vec<&str> = Vec::new();
+        for (from, _) in self.backlinks(&name) {
+            if !sources.contains(&from) {
+                sources.push(from);
+            }
+        }
+
+        let mut links = Vec::new();
+        if let Some((day, ref previous)) = previous {
+            links.push(format!("- Previous: [[file:{}][{}]]", notes.relative(previous), day));
+        }
+        for &source in &sources {
+            links.push(format!("- [[file:{}][{}]]", notes.relative(source), title(self, source)));
+        }
+        let mut text = expand(&notes.template, date);
+        if !links.is_empty() {
+            if !text.is_empty() && !text.ends_with('\n') {
+                text.push('\n');
+            }
+            text.push_str("* Links\n");
+            text.push_str(&links.join("\n"));
+            text.push('\n');
+        }
+
+        if let Some((_, previous)) = previous {
+            let document = self.get(&previous)?;
+            let mut text = document.to_org_string();
+            if !text.is_empty() && !text.ends_with('\n') {
+                text.push('\n');
+            }
+            text.push_str(&format!("- Next: [[file:{}][{}]]\n", notes.relative(&name), date));
+            let encoding = document.encoding();
+            let mut document = parser.parse(&text).ok()?;
+            document.set_encoding(encoding);
+            self.insert(previous, document);
+        }
+        self.insert(name.clone(), parser.parse(&text).ok()?);
+        Some(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_daily_note() {
+        let parser = DocumentParser::new();
+        let notes = DailyNotes::new("notes/daily").template("#+TITLE: %a %Y-%m-%d\n* Today\n");
+        let mut set = DocumentSet::new();
+        set.insert("notes/daily/2024-05-06.org", parser.parse("#+TITLE: Monday").unwrap());
+        set.insert("notes/daily/project.org", parser.parse("#+TITLE: Project
+* Review on [[file:2024-05-08.org]]").unwrap());
+
+        let day = Date { year: 2024, month: 5, day: 8 };
+        let name = set.create_daily_note(&parser, &notes, day).unwrap();
+        assert_eq!(name, "notes/daily/2024-05-08.org");
+        assert_eq!(set.get(&name).unwrap().to_org_string(), "#+TITLE: Wed 2024-05-08
+* Today
+* Links
+- Previous: [[file:2024-05-06.org][2024-05-06]]
+- [[file:project.org][Project]]
+");
+        let monday = set.get("notes/daily/2024-05-06.org").unwrap().to_org_string();
+        assert_eq!(monday, "#+TITLE: Monday
+- Next: [[file:2024-05-08.org][2024-05-08]]
+");
+        assert_eq!(set.backlinks(&name).len(), 2);
+        assert!(set.create_daily_note(&parser, &notes, day).is_none());
+        assert_eq!(expand("%Y%%%q", day), "2024%%q");
+    }
+}
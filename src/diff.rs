@@ -0,0 +1,789 @@
+//! Structural diffs and merges of documents by headline. Headlines are
+//! matched by their `ID` property, then by `CUSTOM_ID`, then by title, so a
+//! subtree that moved or had its text rewritten is still recognized as the
+//! same headline.
+
+use {Document, Headline, TimestampStyle};
+
+/// A part of a headline that changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Keyword,
+    Priority,
+    Title,
+    Tags,
+    Planning,
+    Properties,
+    /// The section text, apart from the planning line and property drawer.
+    Body,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    /// A subtree only in the new document. Its descendants aren't listed
+    /// separately.
+    Added,
+    /// A subtree only in the old document. Its descendants aren't listed
+    /// separately.
+    Removed,
+    /// A headline with another parent or at another level.
+    Moved,
+    /// A headline that changed in place.
+    Modified,
+}
+
+/// One difference found by `diff`.
+#[derive(Debug, Clone)]
+pub struct Change<'a> {
+    pub kind: ChangeKind,
+    pub old: Option<&'a Headline>,
+    pub new: Option<&'a Headline>,
+    /// What changed on the headline itself, not counting its children.
+    /// Moved headlines may have changed too.
+    pub fields: Vec<Field>,
+}
+
+struct Node<'a> {
+    headline: &'a Headline,
+    parent: Option<usize>,
+}
+
+fn flatten<'a>(headlines: &'a [Headline], parent: Option<usize>, nodes: &mut Vec<Node<'a>>) {
+    for headline in headlines {
+        let index = nodes.len();
+        nodes.push(Node { headline, parent });
+        flatten(headline.children(), Some(index), nodes);
+    }
+}
+
+/// The key a headline is matched by in the given pass: its ID, its custom
+/// ID, or its title.
+fn match_key(headline: &Headline, pass: usize) -> Option<String> {
+    match pass {
+        0 => headline.property("ID").map(String::from),
+        1 => headline.property("CUSTOM_ID").map(String::from),
+        _ => Some(headline.title().to_string()),
+    }
+}
+
+/// Pair each of `old` with one of `new`, in document order.
+fn pair(old: &[Node], new: &[Node]) -> Vec<Option<usize>> {
+    let mut pairs = vec![None; old.len()];
+    let mut taken = vec![false; new.len()];
+    // Titles are first only matched under the same parent.
+    for pass in 0..4 {
+        for (i, node) in old.iter().enumerate() {
+            if pairs[i].is_some() {
+                continue;
+            }
+            let key = match match_key(node.headline, pass) {
+                Some(key) => key,
+                None => continue,
+            };
+            let parent = node.parent.map(|parent| pairs[parent]);
+            let found = (0..new.len()).find(|&j| {
+                !taken[j]
+                    && match_key(new[j].headline, pass).as_ref() == Some(&key)
+                    && (pass != 2 || parent.map_or(new[j].parent.is_none(),
+                                                   |parent| parent.is_some()
+                                                       && parent == new[j].parent))
+            });
+            if let Some(j) = found {
+                pairs[i] = Some(j);
+                taken[j] = true;
+            }
+        }
+    }
+    pairs
+}
+
+/// The section text of `headline` without its planning line and property
+/// drawer.
+fn body(headline: &Headline) -> String {
+    let section = match headline.section() {
+        Some(section) => section,
+        None => return String::new(),
+    };
+    let base = section.span().start;
+    let mut text = section.text().to_string();
+    let mut spans = Vec::new();
+    if !headline.planning().is_empty() {
+        spans.push(headline.planning().span());
+    }
+    if !headline.properties().is_empty() {
+        spans.push(headline.properties().span());
+    }
+    spans.sort_by_key(|span| std::cmp::Reverse(span.start));
+    for span in spans {
+        if span.start >= base && span.end - base <= text.len() {
+            text.replace_range(span.start - base..span.end - base, "");
+        }
+    }
+    text.trim().to_string()
+}
+
+/// The parts of the headline itself that differ between `old` and `new`.
+pub fn changed_fields(old: &Headline, new: &Headline) -> Vec<Field> {
+    let mut fields = Vec::new();
+    if old.keyword() != new.keyword() {
+        fields.push(Field::Keyword);
+    }
+    if old.priority() != new.priority() {
+        fields.push(Field::Priority);
+    }
+    if old.title() != new.title() {
+        fields.push(Field::Title);
+    }
+    if old.tags() != new.tags() {
+        fields.push(Field::Tags);
+    }
+    if (old.scheduled(), old.deadline(), old.closed())
+        != (new.scheduled(), new.deadline(), new.closed()) {
+        fields.push(Field::Planning);
+    }
+    if !old.properties().iter().eq(new.properties().iter()) {
+        fields.push(Field::Properties);
+    }
+    if body(old) != body(new) {
+        fields.push(Field::Body);
+    }
+    fields
+}
+
+/// The headlines added, removed, moved and modified from `old` to `new`.
+/// Removed subtrees come first, in the order of `old`, and then the other
+/// changes in the order of `new`.
+pub fn diff<'a>(old: &'a Document, new: &'a Document) -> Vec<Change<'a>> {
+    let mut old_nodes = Vec::new();
+    flatten(old.headlines(), None, &mut old_nodes);
+    let mut new_nodes = Vec::new();
+    flatten(new.headlines(), None, &mut new_nodes);
+    let pairs = pair(&old_nodes, &new_nodes);
+    let mut reverse = vec![None; new_nodes.len()];
+    for (i, pair) in pairs.iter().enumerate() {
+        if let Some(j) = *pair {
+            reverse[j] = Some(i);
+        }
+    }
+
+    let mut changes = Vec::new();
+    for (i, node) in old_nodes.iter().enumerate() {
+        if pairs[i].is_none() && node.parent.is_none_or(|parent| pairs[parent].is_some()) {
+            changes.push(Change {
+                kind: ChangeKind::Removed,
+                old: Some(node.headline),
+                new: None,
+                fields: Vec::new(),
+            });
+        }
+    }
+    for (j, node) in new_nodes.iter().enumerate() {
+        let i = match reverse[j] {
+            Some(i) => i,
+            None => {
+                if node.parent.is_none_or(|parent| reverse[parent].is_some()) {
+                    changes.push(Change {
+                        kind: ChangeKind::Added,
+                        old: None,
+                        new: Some(node.headline),
+                        fields: Vec::new(),
+                    });
+                }
+                continue;
+            }
+        };
+        let old = old_nodes[i].headline;
+        let fields = changed_fields(old, node.headline);
+        let moved = old_nodes[i].parent.map(|parent| pairs[parent]) != node.parent.map(Some)
+            || old.level() != node.headline.level();
+        let kind = if moved {
+            ChangeKind::Moved
+        } else if !fields.is_empty() {
+            ChangeKind::Modified
+        } else {
+            continue;
+        };
+        changes.push(Change { kind, old: Some(old), new: Some(node.headline), fields });
+    }
+    changes
+}
+
+/// The headlines of `old` that are still in `new`, each paired with the one
+/// it became, both as their indices among their siblings from the top level
+/// down, like `kanban::Card::path`. They are matched like `diff` matches
+/// them, so this follows headlines across edits and moves.
+pub fn matching_paths(old: &Document, new: &Document) -> Vec<(Vec<usize>, Vec<usize>)> {
+    fn paths(headlines: &[Headline], path: &mut Vec<usize>, found: &mut Vec<Vec<usize>>) {
+        for (i, headline) in headlines.iter().enumerate() {
+            path.push(i);
+            found.push(path.clone());
+            paths(headline.children(), path, found);
+            path.pop();
+        }
+    }
+
+    let mut old_nodes = Vec::new();
+    flatten(old.headlines(), None, &mut old_nodes);
+    let mut new_nodes = Vec::new();
+    flatten(new.headlines(), None, &mut new_nodes);
+    let (mut old_paths, mut new_paths) = (Vec::new(), Vec::new());
+    paths(old.headlines(), &mut Vec::new(), &mut old_paths);
+    paths(new.headlines(), &mut Vec::new(), &mut new_paths);
+    pair(&old_nodes, &new_nodes).into_iter().zip(old_paths)
+        .filter_map(|(pair, old_path)| Some((old_path, new_paths[pair?].clone())))
+        .collect()
+}
+
+/// The result of `merge`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Merge {
+    /// The merged document as org text.
+    pub text: String,
+    /// The outline paths, like `Projects/Garden`, of the headlines both
+    /// sides changed differently. The text has both versions of each
+    /// between conflict markers, ours first.
+    pub conflicts: Vec<String>,
+}
+
+/// Merge the changes made to `base` in `ours` and `theirs`, subtree by
+/// subtree: a headline line or section changed on one side takes that
+/// side's version, subtrees added on either side are kept, and subtrees
+/// deleted on one side are dropped unless the other side changed them.
+/// Headlines are matched among their siblings, so a subtree moved to
+/// another parent counts as deleted and added.
+pub fn merge(base: &Document, ours: &Document, theirs: &Document) -> Merge {
+    let mut merge = Merge { text: String::new(), conflicts: Vec::new() };
+    let preamble = |document: &Document| {
+        document.first_section().map_or(String::new(), |section| terminated(section.text()))
+    };
+    let (base_text, our_text, their_text) = (preamble(base), preamble(ours), preamble(theirs));
+    merge_text(&base_text, &our_text, &their_text, "", &mut merge);
+    merge_siblings(base.headlines(), ours.headlines(), theirs.headlines(), "", &mut merge);
+    merge
+}
+
+fn terminated(text: &str) -> String {
+    let mut text = text.to_string();
+    if !text.is_empty() && !text.ends_with('\n') {
+        text.push('\n');
+    }
+    text
+}
+
+/// The headline line and section of `headline`, without its children.
+fn own_text(headline: &Headline) -> (String, String) {
+    let mut line = headline.clone();
+    line.headlines.clear();
+    line.section = None;
+    let section = headline.section().map_or(String::new(), |section| terminated(section.text()));
+    (line.to_org_string(), section)
+}
+
+fn subtree_text(headline: &Headline) -> String {
+    terminated(&headline.to_org_string())
+}
+
+/// Add the three-way merge of one piece of text to `merge`.
+fn merge_text(base: &str, ours: &str, theirs: &str, path: &str, merge: &mut Merge) {
+    if ours == theirs || base == theirs {
+        merge.text.push_str(ours);
+    } else if base == ours {
+        merge.text.push_str(theirs);
+    } else {
+        merge.text.push_str("<<<<<<< ours\n");
+        merge.text.push_str(ours);
+        merge.text.push_str("=======\n");
+        merge.text.push_str(theirs);
+        merge.text.push_str(">>>>>>> theirs\n");
+        if !merge.conflicts.iter().any(|conflict| conflict == path) {
+            merge.conflicts.push(path.into());
+        }
+    }
+}
+
+/// Pair each of `a` with the first unpaired sibling in `b` with the same key.
+fn pair_siblings(a: &[Headline], b: &[Headline]) -> Vec<Option<usize>> {
+    let mut pairs = vec![None; a.len()];
+    let mut taken = vec![false; b.len()];
+    for pass in 0..3 {
+        for (i, headline) in a.iter().enumerate() {
+            let key = match match_key(headline, pass) {
+                Some(key) if pairs[i].is_none() => key,
+                _ => continue,
+            };
+            let found = (0..b.len())
+                .find(|&j| !taken[j] && match_key(&b[j], pass).as_ref() == Some(&key));
+            if let Some(j) = found {
+                pairs[i] = Some(j);
+                taken[j] = true;
+            }
+        }
+    }
+    pairs
+}
+
+fn merge_siblings(base: &[Headline], ours: &[Headline], theirs: &[Headline], parent: &str,
+                  merge: &mut Merge) {
+    let our_base = pair_siblings(ours, base);
+    let our_theirs = pair_siblings(ours, theirs);
+    let their_base = pair_siblings(theirs, base);
+    let mut their_ours = vec![None; theirs.len()];
+    for (i, pair) in our_theirs.iter().enumerate() {
+        if let Some(j) = *pair {
+            their_ours[j] = Some(i);
+        }
+    }
+
+    // Our order, with the headlines only they have after the one before
+    // them on their side.
+    let mut order: Vec<(Option<usize>, Option<usize>)> = Vec::new();
+    let mut pending = Vec::new();
+    for (j, pair) in their_ours.iter().enumerate() {
+        if pair.is_none() {
+            pending.push(j);
+        } else {
+            break;
+        }
+    }
+    order.extend(pending.into_iter().map(|j| (None, Some(j))));
+    for (i, pair) in our_theirs.iter().enumerate() {
+        order.push((Some(i), *pair));
+        if let Some(j) = *pair {
+            order.extend(((j + 1)..theirs.len())
+                .take_while(|&k| their_ours[k].is_none())
+                .map(|k| (None, Some(k))));
+        }
+    }
+
+    for (our, their) in order {
+        let headline = our.map_or_else(|| &theirs[their.unwrap()], |i| &ours[i]);
+        let path = if parent.is_empty() {
+            headline.title().to_string()
+        } else {
+            format!("{}/{}", parent, headline.title())
+        };
+        let original = match our {
+            Some(i) => our_base[i],
+            None => their_base[their.unwrap()],
+        }.map(|k| &base[k]);
+        match (our.map(|i| &ours[i]), their.map(|j| &theirs[j])) {
+            (Some(our), Some(their)) => {
+                let (base_line, base_section) = original.map_or_else(Default::default, own_text);
+                let (our_line, our_section) = own_text(our);
+                let (their_line, their_section) = own_text(their);
+                merge_text(&base_line, &our_line, &their_line, &path, merge);
+                merge_text(&base_section, &our_section, &their_section, &path, merge);
+                let base_children = original.map_or(&[][..], |original| original.children());
+                merge_siblings(base_children, our.children(), their.children(), &path, merge);
+            }
+            (Some(kept), None) | (None, Some(kept)) => match original {
+                None => merge.text.push_str(&subtree_text(kept)),
+                // Deleted on the other side, and unchanged on this one.
+                Some(original) if subtree_text(original) == subtree_text(kept) => {}
+                Some(_) => {
+                    merge.text.push_str(&subtree_text(kept));
+                    merge.conflicts.push(path);
+                }
+            },
+            (None, None) => {}
+        }
+    }
+}
+
+/// A run of text in a word diff, holding org markup.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Run {
+    Unchanged(String),
+    Inserted(String),
+    Deleted(String),
+}
+
+impl Run {
+    pub fn text(&self) -> &str {
+        match *self {
+            Run::Unchanged(ref text) | Run::Inserted(ref text) | Run::Deleted(ref text) => text,
+        }
+    }
+}
+
+/// Split `text` into words and the whitespace between them.
+fn tokens(text: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    let mut space = None;
+    for (i, c) in text.char_indices() {
+        let is_space = c.is_whitespace();
+        if space.is_some_and(|space| space != is_space) {
+            tokens.push(&text[start..i]);
+            start = i;
+        }
+        space = Some(is_space);
+    }
+    if start < text.len() {
+        tokens.push(&text[start..]);
+    }
+    tokens
+}
+
+/// The words deleted from `old` and inserted into `new`, as runs that
+/// spell out `old` when the insertions are left out and `new` when the
+/// deletions are. Deletions come before the insertions replacing them.
+pub fn word_diff(old: &str, new: &str) -> Vec<Run> {
+    let (old, new) = (tokens(old), tokens(new));
+    // The length of the longest common subsequence of old[i..] and new[j..].
+    let mut lengths = vec![vec![0u32; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = if old[i] == new[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+    let mut runs = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            push(&mut runs, Run::Unchanged(old[i].into()));
+            i += 1;
+            j += 1;
+        } else if j == new.len() || i < old.len() && lengths[i + 1][j] >= lengths[i][j + 1] {
+            push(&mut runs, Run::Deleted(old[i].into()));
+            i += 1;
+        } else {
+            push(&mut runs, Run::Inserted(new[j].into()));
+            j += 1;
+        }
+    }
+    runs
+}
+
+/// Add `run` to `runs`, joining it with the last run if it is the same kind.
+fn push(runs: &mut Vec<Run>, run: Run) {
+    if let Some(last) = runs.last_mut() {
+        if ::std::mem::discriminant(last) == ::std::mem::discriminant(&run) {
+            match *last {
+                Run::Unchanged(ref mut text) | Run::Inserted(ref mut text)
+                    | Run::Deleted(ref mut text) => text.push_str(run.text()),
+            }
+            return;
+        }
+    }
+    runs.push(run);
+}
+
+/// The word diff of one headline in a subtree, see `subtree_diff`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeadlineDiff {
+    /// The level of the new headline, or of the old one if it was removed.
+    pub level: u32,
+    /// The TODO keyword and title.
+    pub title: Vec<Run>,
+    /// The section text without the planning line and property drawer.
+    pub body: Vec<Run>,
+}
+
+/// A word diff of the titles and text of `old` and `new` and their
+/// descendants, one per headline in document order. Children are matched
+/// like in `merge`; those only in `old` are all deletions, and those only in
+/// `new` all insertions.
+pub fn subtree_diff(old: &Headline, new: &Headline) -> Vec<HeadlineDiff> {
+    let mut diffs = Vec::new();
+    add_diffs(Some(old), Some(new), &mut diffs);
+    diffs
+}
+
+fn add_diffs(old: Option<&Headline>, new: Option<&Headline>, diffs: &mut Vec<HeadlineDiff>) {
+    let title = |headline: Option<&Headline>| headline.map_or(String::new(), |headline| {
+        match headline.keyword() {
+            Some(keyword) => format!("{} {}", keyword, headline.title()),
+            None => headline.title().to_string(),
+        }
+    });
+    let level = new.or(old).map_or(1, |headline| headline.level());
+    diffs.push(HeadlineDiff {
+        level,
+        title: word_diff(&title(old), &title(new)),
+        body: word_diff(&old.map_or(String::new(), body), &new.map_or(String::new(), body)),
+    });
+    let old_children = old.map_or(&[][..], |old| old.children());
+    let new_children = new.map_or(&[][..], |new| new.children());
+    let pairs = pair_siblings(old_children, new_children);
+    let mut reverse = vec![None; new_children.len()];
+    for (k, pair) in pairs.iter().enumerate() {
+        if let Some(j) = *pair {
+            reverse[j] = Some(k);
+        }
+    }
+    // Removed children go before the first child after them kept in both.
+    let mut removed = (0..old_children.len()).filter(|&k| pairs[k].is_none()).peekable();
+    for (child, pair) in new_children.iter().zip(&reverse) {
+        if let Some(k) = *pair {
+            while let Some(gone) = removed.next_if(|&gone| gone < k) {
+                add_diffs(Some(&old_children[gone]), None, diffs);
+            }
+        }
+        add_diffs(pair.map(|k| &old_children[k]), Some(child), diffs);
+    }
+    for gone in removed {
+        add_diffs(Some(&old_children[gone]), None, diffs);
+    }
+}
+
+/// 64-bit FNV-1a, which unlike `DefaultHasher` gives the same hash on every
+/// platform and Rust release.
+struct Fnv(u64);
+
+impl Fnv {
+    fn write(&mut self, text: &str) {
+        for &byte in text.as_bytes().iter().chain(&[0xff]) {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(0x100_0000_01b3);
+        }
+    }
+
+    /// Write `text` without trailing spaces, with runs of blank lines written
+    /// as one.
+    fn write_text(&mut self, text: &str) {
+        let mut blank = false;
+        for line in text.lines().map(str::trim_end) {
+            if line.is_empty() {
+                blank = true;
+                continue;
+            }
+            if blank {
+                self.write("");
+                blank = false;
+            }
+            self.write(line);
+        }
+    }
+}
+
+impl Headline {
+    /// A hash of the headline and its subtree that stays the same across
+    /// runs, platforms and releases, so it can be stored to see later which
+    /// subtrees changed. The whitespace `WriterConfig` options change isn't
+    /// counted: tag alignment and order, blank lines, trailing spaces,
+    /// property order and how planning timestamps are written.
+    pub fn content_hash(&self) -> u64 {
+        let mut hasher = Fnv(0xcbf2_9ce4_8422_2325);
+        self.hash_content(&mut hasher);
+        hasher.0
+    }
+
+    fn hash_content(&self, hasher: &mut Fnv) {
+        hasher.write(&self.level().to_string());
+        hasher.write(self.keyword().unwrap_or(""));
+        hasher.write(&self.priority().map_or(String::new(), String::from));
+        hasher.write(if self.is_commented() { "COMMENT" } else { "" });
+        hasher.write(self.title().trim());
+        let mut tags: Vec<&str> = self.tags().iter().map(|tag| &tag[..]).collect();
+        tags.sort_unstable();
+        hasher.write(&tags.join(":"));
+        for timestamp in &[self.closed(), self.deadline(), self.scheduled()] {
+            hasher.write(&timestamp.map_or(String::new(), |timestamp| {
+                timestamp.format(TimestampStyle::org())
+            }));
+        }
+        // Sorting is stable, so `KEY+` values stay in order.
+        let mut properties: Vec<_> = self.properties().iter().collect();
+        properties.sort_by_key(|&(key, _)| key.trim_end_matches('+').to_lowercase());
+        for (key, value) in properties {
+            hasher.write(key);
+            hasher.write(value.trim());
+        }
+        hasher.write_text(&body(self));
+        hasher.write(&self.children().len().to_string());
+        for child in self.children() {
+            child.hash_content(hasher);
+        }
+    }
+}
+
+impl Document {
+    /// A hash of the text before the first headline and of every subtree,
+    /// like `Headline::content_hash`.
+    pub fn content_hash(&self) -> u64 {
+        let mut hasher = Fnv(0xcbf2_9ce4_8422_2325);
+        hasher.write_text(self.first_section().map_or("", |section| section.text()).trim());
+        for headline in self.headlines() {
+            headline.hash_content(&mut hasher);
+        }
+        hasher.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use DocumentParser;
+
+    #[test]
+    fn test_diff() {
+        let parser = DocumentParser::new().todo_keywords(vec!["TODO", "DONE"]);
+        let old = parser.parse("* Projects
+** TODO Garden
+:PROPERTIES:
+:ID: garden
+:END:
+Dig beds.
+** Notes
+* Archive
+** Old
+* Inbox
+").unwrap();
+        let new = parser.parse("* Projects
+** Notes
+More notes.
+** New
+* Archive
+** DONE Yard
+:PROPERTIES:
+:ID: garden
+:END:
+Dig beds.
+* Inbox :home:
+").unwrap();
+        let changes: Vec<_> = diff(&old, &new).iter()
+            .map(|change| {
+                let title = change.new.or(change.old).unwrap().title();
+                (change.kind, title, change.fields.clone())
+            })
+            .collect();
+        assert_eq!(changes, [
+            (ChangeKind::Removed, "Old", vec![]),
+            (ChangeKind::Modified, "Notes", vec![Field::Body]),
+            (ChangeKind::Added, "New", vec![]),
+            (ChangeKind::Moved, "Yard", vec![Field::Keyword, Field::Title]),
+            (ChangeKind::Modified, "Inbox", vec![Field::Tags]),
+        ]);
+    }
+
+    #[test]
+    fn test_merge() {
+        let parser = DocumentParser::new().todo_keywords(vec!["TODO", "DONE"]);
+        let base = parser.parse("#+TITLE: Notes
+* TODO Shopping
+Milk.
+* Work
+** Report
+Draft.
+** Meeting
+* Old
+").unwrap();
+        let ours = parser.parse("#+TITLE: Notes
+* DONE Shopping
+Milk.
+* Work
+** Report
+Final.
+** Meeting
+** Plan
+").unwrap();
+        let theirs = parser.parse("#+TITLE: My notes
+* TODO Shopping
+Milk and eggs.
+* Work
+** Report
+Reviewed.
+** Call
+* Old
+").unwrap();
+        let merged = merge(&base, &ours, &theirs);
+        assert_eq!(merged.conflicts, ["Work/Report"]);
+        assert_eq!(merged.text, "#+TITLE: My notes
+* DONE Shopping
+Milk and eggs.
+* Work
+** Report
+<<<<<<< ours
+Final.
+=======
+Reviewed.
+>>>>>>> theirs
+** Call
+** Plan
+");
+        assert!(merge(&base, &base, &base).text == base.to_org_string());
+    }
+
+    #[test]
+    fn test_word_diff() {
+        let runs = word_diff("Buy milk and bread today.", "Buy oat milk and rolls today.");
+        assert_eq!(runs, [
+            Run::Unchanged("Buy ".into()),
+            Run::Inserted("oat ".into()),
+            Run::Unchanged("milk and ".into()),
+            Run::Deleted("bread".into()),
+            Run::Inserted("rolls".into()),
+            Run::Unchanged(" today.".into()),
+        ]);
+        assert!(word_diff("", "").is_empty());
+
+        let parser = DocumentParser::new().todo_keywords(vec!["TODO", "DONE"]);
+        let old = parser.parse("* TODO Trip\nPack bags.\n** Hotel\n** Train\nBook seats.\n")
+            .unwrap();
+        let new = parser.parse("* DONE Trip\nPack bags.\n** Train\nBook two seats.\n** Car\n")
+            .unwrap();
+        let diffs = subtree_diff(&old.headlines()[0], &new.headlines()[0]);
+        let titles: Vec<_> = diffs.iter().map(|diff| (diff.level, &diff.title[..])).collect();
+        assert_eq!(titles, [
+            (1, &[Run::Deleted("TODO".into()), Run::Inserted("DONE".into()),
+                  Run::Unchanged(" Trip".into())][..]),
+            (2, &[Run::Deleted("Hotel".into())][..]),
+            (2, &[Run::Unchanged("Train".into())][..]),
+            (2, &[Run::Inserted("Car".into())][..]),
+        ]);
+        assert_eq!(diffs[2].body, [
+            Run::Unchanged("Book ".into()),
+            Run::Inserted("two ".into()),
+            Run::Unchanged("seats.".into()),
+        ]);
+    }
+
+    #[test]
+    fn test_content_hash() {
+        let parser = DocumentParser::new().todo_keywords(vec!["TODO"]);
+        let doc = parser.parse("* TODO Plan :work:home:
+SCHEDULED: <2024-03-01>
+:PROPERTIES:
+:ID: abc
+:EFFORT: 1:00
+:END:
+Body.  
+
+
+More.
+** Child
+Text.
+").unwrap();
+        let hash = doc.headlines()[0].content_hash();
+        let config = ::WriterConfig::profile("doom").unwrap().sort_tags(true);
+        for config in &[config, ::WriterConfig::profile("compact").unwrap()] {
+            let rewritten = parser.parse(&doc.to_org_string_with(config)).unwrap();
+            assert_eq!(rewritten.headlines()[0].content_hash(), hash);
+        }
+        let tidied = parser.parse("* TODO Plan   :home:work:
+SCHEDULED: <2024-03-01 Fri>
+:PROPERTIES:
+:EFFORT: 1:00
+:ID: abc
+:END:
+
+Body.
+
+More.
+
+** Child
+Text.
+").unwrap();
+        assert_eq!(tidied.headlines()[0].content_hash(), hash);
+        let edited = parser.parse(&doc.to_org_string().replace("Text.", "Text!")).unwrap();
+        assert_ne!(edited.headlines()[0].content_hash(), hash);
+        assert_eq!(edited.headlines()[0].children()[0].content_hash(),
+                   parser.parse("** Child\nText!\n").unwrap().headlines()[0].content_hash());
+        // The hash is stored, so it must not change between releases.
+        assert_eq!(::Headline::new(1, "Same").content_hash(), 0x6483_e017_6c21_209e);
+    }
+}
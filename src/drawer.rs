@@ -0,0 +1,329 @@
+use keyword::{Affiliate, Affiliated};
+use span::{self, Shift, Span};
+use {Content, DocumentParser, Symbol};
+
+/// The property drawer of a headline, placed right after the headline and its
+/// planning line:
+///
+/// ```ignore
+/// * Headline
+///   :PROPERTIES:
+///   :CUSTOM_ID: intro
+///   :header-args: :results output
+///   :header-args+: :exports both
+///   :END:
+/// ```
+///
+/// A `KEY+` entry appends its value to an earlier `KEY`, separated by a space.
+/// Keys are matched case-insensitively.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct PropertyDrawer {
+    properties: Vec<(Symbol, String)>,
+    span: Span,
+}
+
+impl PropertyDrawer {
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.properties.iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| &v[..])
+    }
+
+    /// Iterate over the properties as `(key, value)` pairs in the order they
+    /// first appear.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.properties.iter().map(|(k, v)| (&k[..], &v[..]))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.properties.is_empty()
+    }
+
+    /// Where the drawer is in the text, or an empty span for a drawer that
+    /// wasn't parsed.
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    fn get_mut(&mut self, key: &str) -> Option<&mut String> {
+        self.properties.iter_mut()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v)
+    }
+
+    /// Set a property, replacing any value it had.
+    pub fn insert(&mut self, key: &str, value: &str) {
+        match self.get_mut(key) {
+            Some(existing) => *existing = value.into(),
+            None => self.properties.push((Symbol::new(key), value.into())),
+        }
+    }
+
+    /// Remove a property, returning its value.
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        let index = self.properties.iter().position(|(k, _)| k.eq_ignore_ascii_case(key))?;
+        Some(self.properties.remove(index).1)
+    }
+
+    fn append(&mut self, key: &str, value: &str) {
+        match self.get_mut(key) {
+            Some(existing) => {
+                if !existing.is_empty() {
+                    existing.push(' ');
+                }
+                existing.push_str(value);
+            }
+            None => self.properties.push((Symbol::new(key), value.into())),
+        }
+    }
+}
+
+impl Shift for PropertyDrawer {
+    fn shift(&mut self, delta: isize) {
+        self.span.shift(delta);
+    }
+}
+
+/// A drawer groups lines under a name, hiding them from the normal outline
+/// view:
+///
+/// ```ignore
+/// :LOGBOOK:
+/// - State "DONE"       from "TODO"       [2024-05-07 Tue 17:12]
+/// CLOCK: [2024-05-07 Tue 16:00]--[2024-05-07 Tue 17:00] =>  1:00
+/// :END:
+/// ```
+///
+/// Property drawers have their own type, see `PropertyDrawer`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Drawer {
+    name: String,
+    text: String,
+    contents: Vec<Content>,
+    affiliated: Affiliated,
+    span: Span,
+}
+
+impl Drawer {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Whether this is the `:LOGBOOK:` drawer org records state changes and
+    /// clocked time in.
+    pub fn is_logbook(&self) -> bool {
+        self.name.eq_ignore_ascii_case("LOGBOOK")
+    }
+
+    /// The raw lines between the opening line and `:END:`.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// The elements parsed from the drawer's text, or none for the drawers
+    /// `DocumentParser::opaque_drawers` names.
+    pub fn contents(&self) -> &[Content] {
+        &self.contents
+    }
+
+    /// The `#+NAME:`, `#+CAPTION:` and other keywords written above it.
+    pub fn affiliated(&self) -> &Affiliated {
+        &self.affiliated
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+}
+
+impl Affiliate for Drawer {
+    fn affiliated_mut(&mut self) -> &mut Affiliated {
+        &mut self.affiliated
+    }
+}
+
+impl Shift for Drawer {
+    fn shift(&mut self, delta: isize) {
+        self.affiliated.shift(delta);
+        self.span.shift(delta);
+        self.contents.shift(delta);
+    }
+}
+
+/// What is wrong with `line` if it is a drawer delimiter left over after
+/// parsing, either because no `:END:` closes it or because it is a stray
+/// `:END:`.
+pub fn stray_delimiter(line: &str) -> Option<String> {
+    match drawer_name(line)? {
+        name if name.eq_ignore_ascii_case("END") => Some(":END: has no drawer to close".into()),
+        name => Some(format!(":{}: drawer is never closed", name)),
+    }
+}
+
+/// Split a `:NAME:` drawer delimiter line into its name.
+pub fn drawer_name(line: &str) -> Option<&str> {
+    let line = line.trim();
+    if line.len() < 3 || !line.starts_with(':') || !line.ends_with(':') {
+        return None;
+    }
+    let name = &line[1..line.len() - 1];
+    if name.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-') {
+        Some(name)
+    } else {
+        None
+    }
+}
+
+/// Try to parse a property drawer starting at `lines[start]`. Returns the
+/// drawer and the index of the first line after its `:END:`.
+pub fn parse_property_drawer(lines: &[&str], offsets: &[usize], start: usize)
+                             -> Option<(PropertyDrawer, usize)> {
+    if !drawer_name(lines.get(start)?)?.eq_ignore_ascii_case("PROPERTIES") {
+        return None;
+    }
+    let mut drawer = PropertyDrawer::default();
+    for (i, line) in lines.iter().enumerate().skip(start + 1) {
+        let line = line.trim();
+        if line.eq_ignore_ascii_case(":END:") {
+            drawer.span = span::lines_span(lines, offsets, start, i + 1);
+            return Some((drawer, i + 1));
+        }
+        // Keys like `header-args:rust` hold colons, so the key ends at the
+        // first colon followed by a space.
+        let key_end = |rest: &str| {
+            rest.match_indices(':')
+                .map(|(colon, _)| colon)
+                .find(|&colon| rest[colon + 1..].chars().next().is_none_or(char::is_whitespace))
+                .or_else(|| rest.find(':'))
+        };
+        let (key, value) = match line.get(1..).and_then(key_end) {
+            Some(colon) if line.starts_with(':') && colon > 0 => {
+                (&line[1..colon + 1], line[colon + 2..].trim())
+            }
+            _ => return None,
+        };
+        if key.contains(char::is_whitespace) {
+            return None;
+        }
+        match key.strip_suffix('+') {
+            Some(key) => drawer.append(key, value),
+            None => drawer.insert(key, value),
+        }
+    }
+    None
+}
+
+/// Try to parse a drawer other than a property drawer starting at
+/// `lines[start]`. Returns the drawer and the index of the first line after
+/// its `:END:`.
+pub fn parse_drawer(parser: &DocumentParser, lines: &[&str], offsets: &[usize], start: usize)
+                    -> Option<(Drawer, usize)> {
+    let name = drawer_name(lines[start])?;
+    if name.eq_ignore_ascii_case("END") || name.eq_ignore_ascii_case("PROPERTIES") {
+        return None;
+    }
+    let end = start + 1 + lines[start + 1..].iter()
+        .position(|line| line.trim().eq_ignore_ascii_case(":END:"))?;
+    let contents = if parser.is_opaque_drawer(name) {
+        Vec::new()
+    } else {
+        ::parse_contents(parser, &lines[start + 1..end], &offsets[start + 1..end])
+    };
+    Some((Drawer {
+        name: name.into(),
+        text: lines[start + 1..end].join("\n"),
+        contents,
+        affiliated: Affiliated::default(),
+        span: span::lines_span(lines, offsets, start, end + 1),
+    }, end + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {Element, GreaterElement};
+
+    #[test]
+    fn test_parse_property_drawer() {
+        let lines: Vec<_> = "  :PROPERTIES:
+  :CUSTOM_ID: intro
+  :header-args: :results output
+  :Empty:
+  :HEADER-ARGS+: :exports both
+  :END:
+after".lines().collect();
+        let offsets = span::line_offsets(&lines.join("\n"), 0);
+        let (drawer, next) = parse_property_drawer(&lines, &offsets, 0).unwrap();
+        assert_eq!(next, 6);
+        assert_eq!(drawer.span(), Span::new(0, offsets[5] + lines[5].len()));
+        assert_eq!(drawer.get("custom_id"), Some("intro"));
+        assert_eq!(drawer.get("header-args"), Some(":results output :exports both"));
+        assert_eq!(drawer.get("Empty"), Some(""));
+        assert_eq!(drawer.get("missing"), None);
+        let keys: Vec<_> = drawer.iter().map(|(k, _)| k).collect();
+        assert_eq!(keys, ["CUSTOM_ID", "header-args", "Empty"]);
+
+        assert!(parse_property_drawer(&lines[..5], &offsets, 0).is_none());
+        assert!(parse_property_drawer(&[":LOGBOOK:", ":END:"], &[0, 10], 0).is_none());
+        let lines = [":PROPERTIES:", "not a property", ":END:"];
+        assert!(parse_property_drawer(&lines, &[0, 13, 28], 0).is_none());
+    }
+
+    #[test]
+    fn test_parse_drawer() {
+        let parser = DocumentParser::new();
+        let lines: Vec<_> = ":LOGBOOK:
+- State \"DONE\"       from \"TODO\"       [2024-05-07 Tue 17:12]
+CLOCK: [2024-05-07 Tue 16:00]--[2024-05-07 Tue 17:00] =>  1:00
+:END:
+  :notes:
+  unterminated".lines().collect();
+        let offsets = span::line_offsets(&lines.join("\n"), 0);
+        let (drawer, next) = parse_drawer(&parser, &lines, &offsets, 0).unwrap();
+        assert_eq!(next, 4);
+        assert!(drawer.is_logbook());
+        assert!(drawer.text().ends_with("=>  1:00"));
+        match drawer.contents() {
+            [Content::Greater(GreaterElement::PlainList(list)),
+             Content::Element(Element::Clock(clock))] => {
+                assert_eq!(list.items().len(), 1);
+                assert_eq!(clock.duration(), Some(60));
+            }
+            other => panic!("expected a list and a clock, got {:?}", other),
+        }
+        assert!(parse_drawer(&parser, &lines, &offsets, 4).is_none());
+        assert!(parse_drawer(&parser, &[":END:", ":END:"], &[0, 6], 0).is_none());
+    }
+
+    #[test]
+    fn test_opaque_drawers() {
+        let text = "* Sensor
+:LOGBOOK:
+CLOCK: [2024-05-07 Tue 16:00]--[2024-05-07 Tue 17:00] =>  1:00
+:END:
+:rawdata:
+3. | 0x1f | <2024-05-07>
+1. | 0x20 |
+:END:
+";
+        let mut doc = DocumentParser::new()
+            .drawers(vec!["NOTES"])
+            .opaque_drawers(vec!["RAWDATA"])
+            .parse(text)
+            .unwrap();
+        doc.renumber_lists();
+        assert_eq!(doc.to_org_string(), text);
+        match doc.headlines()[0].section().unwrap().contents() {
+            [Content::Greater(GreaterElement::Drawer(logbook)),
+             Content::Greater(GreaterElement::Drawer(raw))] => {
+                assert_eq!(logbook.contents().len(), 1);
+                assert_eq!(raw.name(), "rawdata");
+                assert!(raw.contents().is_empty());
+                assert_eq!(raw.text(), "3. | 0x1f | <2024-05-07>\n1. | 0x20 |");
+            }
+            other => panic!("expected two drawers, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,153 @@
+//! Spaced repetition following org-drill's conventions: headlines tagged
+//! `:drill:` are cards, scheduled with the SM-2 algorithm through `DRILL_*`
+//! properties.
+
+use {Date, Document, Headline, Timestamp};
+
+/// Where a card is in its SM-2 schedule, as stored in its properties.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Schedule {
+    /// `DRILL_EASE`, how quickly the interval grows. Starts at 2.5.
+    pub ease: f64,
+    /// `DRILL_LAST_INTERVAL`, the days between the last review and the next.
+    pub interval: u32,
+    /// `DRILL_REPEATS_SINCE_FAIL`, the successful reviews in a row.
+    pub repeats: u32,
+    /// `DRILL_TOTAL_REPEATS`.
+    pub total_repeats: u32,
+    /// `DRILL_FAILURE_COUNT`.
+    pub failures: u32,
+    /// The date in `DRILL_LAST_REVIEWED`, or `None` for a new card.
+    pub last_reviewed: Option<Date>,
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Schedule {
+            ease: 2.5,
+            interval: 0,
+            repeats: 0,
+            total_repeats: 0,
+            failures: 0,
+            last_reviewed: None,
+        }
+    }
+}
+
+impl Schedule {
+    /// Read the schedule from a headline's properties. Missing or malformed
+    /// properties keep their default.
+    pub fn of(headline: &Headline) -> Schedule {
+        let mut schedule = Schedule::default();
+        let number = |key| headline.property(key).and_then(|value| value.parse::<f64>().ok());
+        if let Some(ease) = number("DRILL_EASE") {
+            schedule.ease = ease;
+        }
+        schedule.interval = number("DRILL_LAST_INTERVAL").map_or(0, |days| days.round() as u32);
+        schedule.repeats = number("DRILL_REPEATS_SINCE_FAIL").map_or(0, |n| n as u32);
+        schedule.total_repeats = number("DRILL_TOTAL_REPEATS").map_or(0, |n| n as u32);
+        schedule.failures = number("DRILL_FAILURE_COUNT").map_or(0, |n| n as u32);
+        schedule.last_reviewed = headline.property("DRILL_LAST_REVIEWED")
+            .and_then(Timestamp::parse)
+            .map(|timestamp| timestamp.date());
+        schedule
+    }
+
+    /// The day the card is next due, or `None` if it was never reviewed.
+    pub fn due(&self) -> Option<Date> {
+        self.last_reviewed.map(|date| date.add_days(self.interval as i64))
+    }
+
+    /// The schedule after a review on `today` with a `quality` from 0 (no
+    /// idea) to 5 (perfect). Below 3 counts as a failure and starts over.
+    pub fn review(&self, quality: u8, today: Date) -> Schedule {
+        let quality = quality.min(5);
+        let mut next = *self;
+        next.total_repeats += 1;
+        next.last_reviewed = Some(today);
+        if quality < 3 {
+            next.repeats = 0;
+            next.failures += 1;
+            next.interval = 1;
+            return next;
+        }
+        next.repeats += 1;
+        next.interval = match next.repeats {
+            1 => 1,
+            2 => 6,
+            _ => (self.interval as f64 * self.ease).round() as u32,
+        };
+        let miss = (5 - quality) as f64;
+        next.ease = (self.ease + 0.1 - miss * (0.08 + miss * 0.02)).max(1.3);
+        next
+    }
+
+    /// Store the schedule in `headline`'s properties.
+    pub fn write(&self, headline: &mut Headline) {
+        headline.set_property("DRILL_LAST_INTERVAL", &self.interval.to_string());
+        headline.set_property("DRILL_REPEATS_SINCE_FAIL", &self.repeats.to_string());
+        headline.set_property("DRILL_TOTAL_REPEATS", &self.total_repeats.to_string());
+        headline.set_property("DRILL_FAILURE_COUNT", &self.failures.to_string());
+        headline.set_property("DRILL_EASE", &format!("{:.2}", self.ease));
+        if let Some(date) = self.last_reviewed {
+            let timestamp = Timestamp::new(false, date, None);
+            headline.set_property("DRILL_LAST_REVIEWED", &timestamp.to_string());
+        }
+    }
+}
+
+/// Whether the headline is a card, which org-drill marks with a `drill` tag.
+pub fn is_card(headline: &Headline) -> bool {
+    headline.tags().iter().any(|tag| tag == "drill")
+}
+
+/// The cards due for review on `today`, new ones included, in document
+/// order.
+pub fn due_cards(document: &Document, today: Date) -> Vec<&Headline> {
+    document.all_headlines()
+        .filter(|headline| is_card(headline))
+        .filter(|headline| Schedule::of(headline).due().is_none_or(|due| due <= today))
+        .collect()
+}
+
+/// Record a review of the card on `today` with a `quality` from 0 to 5, and
+/// schedule its next one.
+pub fn review(headline: &mut Headline, quality: u8, today: Date) {
+    Schedule::of(headline).review(quality, today).write(headline);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use DocumentParser;
+
+    #[test]
+    fn test_review() {
+        let mut doc = DocumentParser::new().parse("* Capital of France :drill:
+** Answer
+Paris
+* Not a card
+* Seen card :drill:
+:PROPERTIES:
+:DRILL_LAST_INTERVAL: 6
+:DRILL_REPEATS_SINCE_FAIL: 2
+:DRILL_EASE: 2.5
+:DRILL_LAST_REVIEWED: [2024-05-01 Wed 10:00]
+:END:").unwrap();
+        let today = Date { year: 2024, month: 5, day: 6 };
+        let titles: Vec<_> = due_cards(&doc, today).iter().map(|h| h.title()).collect();
+        assert_eq!(titles, ["Capital of France"]);
+        assert_eq!(due_cards(&doc, today.add_days(1)).len(), 2);
+
+        let card = &mut doc.headlines_mut()[2];
+        review(card, 4, today);
+        let schedule = Schedule::of(card);
+        assert_eq!((schedule.interval, schedule.repeats, schedule.total_repeats), (15, 3, 1));
+        assert_eq!(schedule.due(), Some(Date { year: 2024, month: 5, day: 21 }));
+        assert_eq!(card.property("DRILL_LAST_REVIEWED"), Some("[2024-05-06 Mon]"));
+
+        review(card, 1, today);
+        let schedule = Schedule::of(card);
+        assert_eq!((schedule.interval, schedule.repeats, schedule.failures), (1, 0, 1));
+    }
+}
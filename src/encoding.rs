@@ -0,0 +1,242 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use span::Span;
+use {Diagnostic, Document, DocumentParser};
+
+/// The encoding a document was read in, kept so that it can be written back
+/// the same way with `Document::to_encoded_bytes`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    /// UTF-8 starting with a byte order mark.
+    Utf8Bom,
+    /// UTF-16, little endian, with or without a byte order mark.
+    Utf16Le {
+        bom: bool,
+    },
+    /// UTF-16, big endian, with or without a byte order mark.
+    Utf16Be {
+        bom: bool,
+    },
+    /// ISO-8859-1, as read for legacy files that aren't valid UTF-8.
+    Latin1,
+}
+
+/// Text decoded by `Encoding::decode`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decoded {
+    pub text: String,
+    pub encoding: Encoding,
+    /// Where `U+FFFD` replacement characters were put in `text` for bytes
+    /// that couldn't be decoded.
+    pub replaced: Vec<Span>,
+}
+
+impl Encoding {
+    /// Guess the encoding of `bytes`: from the byte order mark if there is
+    /// one, then UTF-16 if every other byte is mostly zero, then UTF-8 if
+    /// they are valid UTF-8, and Latin-1 otherwise.
+    pub fn detect(bytes: &[u8]) -> Encoding {
+        if bytes.starts_with(b"\xEF\xBB\xBF") {
+            return Encoding::Utf8Bom;
+        } else if bytes.starts_with(b"\xFF\xFE") {
+            return Encoding::Utf16Le { bom: true };
+        } else if bytes.starts_with(b"\xFE\xFF") {
+            return Encoding::Utf16Be { bom: true };
+        }
+        let pairs = bytes.len() / 2;
+        let zeros = |parity: usize| {
+            bytes.chunks(2).filter(|pair| pair.len() == 2 && pair[parity] == 0).count()
+        };
+        if pairs > 0 && zeros(1) * 4 >= pairs * 3 {
+            Encoding::Utf16Le { bom: false }
+        } else if pairs > 0 && zeros(0) * 4 >= pairs * 3 {
+            Encoding::Utf16Be { bom: false }
+        } else if ::std::str::from_utf8(bytes).is_ok() {
+            Encoding::Utf8
+        } else {
+            Encoding::Latin1
+        }
+    }
+
+    /// The name of the encoding, like `UTF-8`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Encoding::Utf8 | Encoding::Utf8Bom => "UTF-8",
+            Encoding::Utf16Le { .. } => "UTF-16LE",
+            Encoding::Utf16Be { .. } => "UTF-16BE",
+            Encoding::Latin1 => "ISO-8859-1",
+        }
+    }
+
+    /// Decode `bytes` as this encoding, skipping its byte order mark.
+    pub fn decode(self, bytes: &[u8]) -> Decoded {
+        let mut text = String::with_capacity(bytes.len());
+        let mut replaced = Vec::new();
+        let mut replace = |text: &mut String| {
+            replaced.push(Span::new(text.len(), text.len() + '\u{FFFD}'.len_utf8()));
+            text.push('\u{FFFD}');
+        };
+        match self {
+            Encoding::Utf8 | Encoding::Utf8Bom => {
+                let mut rest = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+                loop {
+                    match ::std::str::from_utf8(rest) {
+                        Ok(valid) => {
+                            text.push_str(valid);
+                            break;
+                        }
+                        Err(error) => {
+                            let (valid, after) = rest.split_at(error.valid_up_to());
+                            text.push_str(::std::str::from_utf8(valid).unwrap_or(""));
+                            replace(&mut text);
+                            rest = &after[error.error_len().unwrap_or(after.len())..];
+                        }
+                    }
+                }
+            }
+            Encoding::Utf16Le { .. } | Encoding::Utf16Be { .. } => {
+                let little = matches!(self, Encoding::Utf16Le { .. });
+                let bom: &[u8] = if little { b"\xFF\xFE" } else { b"\xFE\xFF" };
+                let rest = bytes.strip_prefix(bom).unwrap_or(bytes);
+                let units = rest.chunks(2).filter(|pair| pair.len() == 2).map(|pair| {
+                    if little {
+                        u16::from_le_bytes([pair[0], pair[1]])
+                    } else {
+                        u16::from_be_bytes([pair[0], pair[1]])
+                    }
+                });
+                for c in char::decode_utf16(units) {
+                    match c {
+                        Ok(c) => text.push(c),
+                        Err(_) => replace(&mut text),
+                    }
+                }
+                if rest.len() % 2 == 1 {
+                    replace(&mut text);
+                }
+            }
+            Encoding::Latin1 => text.extend(bytes.iter().map(|&b| b as char)),
+        }
+        Decoded { text, encoding: self, replaced }
+    }
+
+    /// Encode `text` in this encoding, with a byte order mark if it had one.
+    /// Characters Latin-1 has no byte for are written as `?`.
+    pub fn encode(self, text: &str) -> Vec<u8> {
+        match self {
+            Encoding::Utf8 => text.as_bytes().to_vec(),
+            Encoding::Utf8Bom => {
+                let mut bytes = b"\xEF\xBB\xBF".to_vec();
+                bytes.extend_from_slice(text.as_bytes());
+                bytes
+            }
+            Encoding::Utf16Le { bom } | Encoding::Utf16Be { bom } => {
+                let little = matches!(self, Encoding::Utf16Le { .. });
+                let bom = if bom { Some('\u{FEFF}') } else { None };
+                bom.into_iter().chain(text.chars())
+                    .flat_map(|c| c.encode_utf16(&mut [0; 2]).to_vec())
+                    .flat_map(|unit| if little { unit.to_le_bytes() } else { unit.to_be_bytes() })
+                    .collect()
+            }
+            Encoding::Latin1 => {
+                text.chars().map(|c| if (c as u32) < 0x100 { c as u8 } else { b'?' }).collect()
+            }
+        }
+    }
+}
+
+/// Detect the encoding of `bytes` and decode them.
+pub fn decode(bytes: &[u8]) -> Decoded {
+    Encoding::detect(bytes).decode(bytes)
+}
+
+impl DocumentParser {
+    /// Parse a document from `bytes` in any encoding `Encoding::detect`
+    /// knows. The encoding is kept as `Document::encoding`, and bytes that
+    /// couldn't be decoded are listed in `Document::warnings`.
+    pub fn parse_bytes(&self, bytes: &[u8]) -> Result<Document, ::ParseError> {
+        let decoded = decode(bytes);
+        let mut document = self.parse(&decoded.text)?;
+        document.encoding = decoded.encoding;
+        if !decoded.replaced.is_empty() {
+            document.warnings.extend(decoded.replaced.into_iter()
+                .map(|span| Diagnostic::new("undecodable bytes replaced", span)));
+            document.warnings.sort_by_key(|warning| warning.span().start);
+        }
+        Ok(document)
+    }
+
+    /// Read and parse the file at `path`, like `parse_bytes`.
+    pub fn parse_file<P: AsRef<Path>>(&self, path: P) -> io::Result<Document> {
+        let bytes = fs::read(path)?;
+        self.parse_bytes(&bytes).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+}
+
+impl Document {
+    /// The encoding the document was read in, UTF-8 unless it came from
+    /// `DocumentParser::parse_bytes` or `parse_file`.
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    /// Change the encoding `to_encoded_bytes` writes.
+    pub fn set_encoding(&mut self, encoding: Encoding) {
+        self.encoding = encoding;
+    }
+
+    /// Write the document back out as org text in its encoding.
+    pub fn to_encoded_bytes(&self) -> Vec<u8> {
+        self.encoding.encode(&self.to_org_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_and_decode() {
+        assert_eq!(Encoding::detect(b"* Caf\xC3\xA9"), Encoding::Utf8);
+        assert_eq!(Encoding::detect(b"\xEF\xBB\xBF* x"), Encoding::Utf8Bom);
+        assert_eq!(Encoding::detect(b"* Caf\xE9"), Encoding::Latin1);
+        assert_eq!(Encoding::detect(b"*\0 \0x\0"), Encoding::Utf16Le { bom: false });
+        assert_eq!(Encoding::detect(b"\xFE\xFF\0*"), Encoding::Utf16Be { bom: true });
+
+        let decoded = decode(b"\xFF\xFE*\0 \0\x00\xD8x\0");
+        assert_eq!(decoded.text, "* \u{FFFD}x");
+        assert_eq!(decoded.replaced, [Span::new(2, 5)]);
+        let decoded = Encoding::Utf8Bom.decode(b"\xEF\xBB\xBFa\xFFb");
+        assert_eq!(decoded.text, "a\u{FFFD}b");
+        assert_eq!(decoded.replaced, [Span::new(1, 4)]);
+
+        for &bytes in &[&b"\xEF\xBB\xBF* x\n"[..], b"\xFE\xFF\0*\0 \0\xE9", b"* Caf\xE9\n"] {
+            let encoding = Encoding::detect(bytes);
+            assert_eq!(encoding.encode(&encoding.decode(bytes).text), bytes);
+        }
+        assert_eq!(Encoding::Latin1.encode("\u{E9}\u{2013}"), b"\xE9?");
+    }
+
+    #[test]
+    fn test_parse_bytes() {
+        let parser = DocumentParser::new();
+        let mut doc = parser.parse_bytes(b"* Caf\xE9\n#+BEGIN_SRC\n").unwrap();
+        assert_eq!(doc.encoding(), Encoding::Latin1);
+        assert_eq!(doc.headlines()[0].title(), "Caf\u{E9}");
+        assert_eq!(doc.to_encoded_bytes(), b"* Caf\xE9\n#+BEGIN_SRC\n");
+        doc.set_encoding(Encoding::Utf8);
+        assert_eq!(doc.to_encoded_bytes(), "* Caf\u{E9}\n#+BEGIN_SRC\n".as_bytes());
+
+        let doc = parser.parse_bytes(b"\xEF\xBB\xBF* A \xFF\n").unwrap();
+        assert_eq!(doc.encoding(), Encoding::Utf8Bom);
+        let messages: Vec<_> = doc.warnings().iter().map(|warning| warning.message()).collect();
+        assert_eq!(messages, ["undecodable bytes replaced"]);
+        assert_eq!(doc.warnings()[0].span(), Span::new(4, 7));
+        assert_eq!(parser.parse(&decode(b"* A").text).unwrap().encoding(), Encoding::Utf8);
+    }
+}
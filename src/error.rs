@@ -0,0 +1,61 @@
+use std::error::Error;
+use std::fmt;
+
+use span::{Shift, Span};
+
+/// A malformed construct found while parsing, like a block that is never
+/// closed. The parser reads such text as a paragraph and carries on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Diagnostic {
+    message: String,
+    span: Span,
+}
+
+impl Diagnostic {
+    pub fn new<S: Into<String>>(message: S, span: Span) -> Diagnostic {
+        Diagnostic { message: message.into(), span }
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// The text the diagnostic is about.
+    pub fn span(&self) -> Span {
+        self.span
+    }
+}
+
+impl Shift for Diagnostic {
+    fn shift(&mut self, delta: isize) {
+        self.span.shift(delta);
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at byte {}", self.message, self.span.start)
+    }
+}
+
+/// Why a document could not be parsed at all.
+///
+/// Parsing currently always recovers from malformed text, reporting it through
+/// `Document::warnings` instead, so this is never returned yet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError(Diagnostic);
+
+impl ParseError {
+    pub fn diagnostic(&self) -> &Diagnostic {
+        &self.0
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl Error for ParseError {}
@@ -0,0 +1,269 @@
+use std::collections::VecDeque;
+use std::io::{self, BufRead};
+
+use regex::Regex;
+
+use {block, drawer, inlinetask, keyword, planning};
+use {DocumentParser, Headline, Keyword, Planning, Priorities, Span};
+
+/// Something found while reading a document line by line with
+/// `DocumentParser::events`.
+#[derive(Debug, Clone)]
+pub enum Event {
+    /// A headline line. The headline has neither a section nor children, and
+    /// its span covers only the line.
+    HeadlineStart(Box<Headline>),
+    /// The end of the subtree of the innermost headline still open.
+    HeadlineEnd,
+    /// The headline line of an inline task. Its body follows as usual, up to
+    /// an `InlinetaskEnd` if the task has an END line.
+    InlinetaskStart(Box<Headline>),
+    InlinetaskEnd,
+    Keyword(Keyword),
+    Planning(Planning),
+    /// A `#+BEGIN_NAME PARAMETERS` line. The lines up to the matching
+    /// `#+END_NAME` follow as `Text`, without any unescaping.
+    BlockStart { name: String, parameters: String },
+    BlockEnd,
+    /// A `:NAME:` line. The lines up to `:END:` follow as `Text`.
+    DrawerStart(String),
+    DrawerEnd,
+    ParagraphStart,
+    ParagraphEnd,
+    /// A line of a paragraph, block or drawer, without its line ending.
+    Text(String),
+}
+
+/// Where in the document the previous line left the reader.
+enum Context {
+    Section,
+    Paragraph,
+    Block(String),
+    Drawer,
+}
+
+/// A pull parser reading a document one line at a time, created by
+/// `DocumentParser::events`.
+///
+/// It only keeps the current line in memory, so it never looks ahead: blocks
+/// and drawers run until their closing line or the next headline, and
+/// `#+TODO:` lines only apply to the headlines after them. Lines that aren't
+/// headlines, keywords, planning lines, blocks or drawers are paragraph text.
+pub struct Events<'p, R> {
+    parser: &'p DocumentParser,
+    reader: R,
+    headline_matcher: &'static Regex,
+    todo_keywords: Vec<String>,
+    priorities: Priorities,
+    line: String,
+    /// The offset of the next line.
+    offset: usize,
+    context: Context,
+    /// How many headlines are open, and their levels.
+    levels: Vec<u32>,
+    pending: VecDeque<Event>,
+    done: bool,
+}
+
+impl DocumentParser {
+    /// Read a document from `reader` as a stream of events, without building
+    /// the tree.
+    pub fn events<R: BufRead>(&self, reader: R) -> Events<'_, R> {
+        Events {
+            parser: self,
+            reader,
+            headline_matcher: ::headline_regex(),
+            todo_keywords: self.todo_keywords.clone(),
+            priorities: self.priorities,
+            line: String::new(),
+            offset: 0,
+            context: Context::Section,
+            levels: Vec::new(),
+            pending: VecDeque::new(),
+            done: false,
+        }
+    }
+}
+
+impl<'p, R: BufRead> Events<'p, R> {
+    /// Queue the end of the current paragraph, block or drawer, if any.
+    fn close_context(&mut self) {
+        match self.context {
+            Context::Section => return,
+            Context::Paragraph => self.pending.push_back(Event::ParagraphEnd),
+            Context::Block(_) => self.pending.push_back(Event::BlockEnd),
+            Context::Drawer => self.pending.push_back(Event::DrawerEnd),
+        }
+        self.context = Context::Section;
+    }
+
+    /// Queue the events for `self.line`, which starts at `offset`.
+    fn read_line(&mut self, offset: usize) {
+        let line = self.line.trim_end_matches(['\n', '\r']);
+        if inlinetask::is_end_line(self.parser, line) {
+            self.close_context();
+            self.pending.push_back(Event::InlinetaskEnd);
+            return;
+        }
+        if let Some(captures) = self.headline_matcher.captures(line) {
+            let mut headline = self.parser.headline(&captures, &self.todo_keywords,
+                                                    self.priorities);
+            headline.span = Span::new(offset, offset + line.len());
+            let is_task = inlinetask::is_task_line(self.parser, line);
+            self.close_context();
+            if is_task {
+                self.pending.push_back(Event::InlinetaskStart(Box::new(headline)));
+                return;
+            }
+            while self.levels.last().is_some_and(|&level| level >= headline.level) {
+                self.levels.pop();
+                self.pending.push_back(Event::HeadlineEnd);
+            }
+            self.levels.push(headline.level);
+            self.pending.push_back(Event::HeadlineStart(Box::new(headline)));
+            return;
+        }
+        let ends_here = match self.context {
+            Context::Block(ref name) => block::is_end_line(line, name),
+            Context::Drawer => line.trim().eq_ignore_ascii_case(":END:"),
+            _ => false,
+        };
+        if ends_here {
+            self.close_context();
+            return;
+        }
+        if let Context::Block(_) | Context::Drawer = self.context {
+            self.pending.push_back(Event::Text(line.into()));
+            return;
+        }
+
+        let event = if line.trim().is_empty() {
+            None
+        } else if let Some((name, parameters)) = block::begin_line(line) {
+            let event = Event::BlockStart { name: name.into(), parameters: parameters.into() };
+            Some((event, Context::Block(name.into())))
+        } else if let Some(name) = drawer::drawer_name(line)
+            .filter(|name| !name.eq_ignore_ascii_case("END")) {
+            Some((Event::DrawerStart(name.into()), Context::Drawer))
+        } else if let Some((keyword, _)) = keyword::parse_keyword(&[line], &[offset], 0) {
+            for todo in keyword::find_todo_keywords(line) {
+                if !self.todo_keywords.contains(&todo) {
+                    self.todo_keywords.push(todo);
+                }
+            }
+            if keyword.key().eq_ignore_ascii_case("PRIORITIES") {
+                if let Some(priorities) = Priorities::parse(keyword.value()) {
+                    self.priorities = priorities;
+                }
+            }
+            Some((Event::Keyword(keyword), Context::Section))
+        } else if let Some(planning) = planning::parse_planning(line, offset) {
+            Some((Event::Planning(planning), Context::Section))
+        } else {
+            if let Context::Section = self.context {
+                self.pending.push_back(Event::ParagraphStart);
+                self.context = Context::Paragraph;
+            }
+            self.pending.push_back(Event::Text(line.into()));
+            return;
+        };
+        self.close_context();
+        if let Some((event, context)) = event {
+            self.pending.push_back(event);
+            self.context = context;
+        }
+    }
+}
+
+impl<'p, R: BufRead> Iterator for Events<'p, R> {
+    type Item = io::Result<Event>;
+
+    fn next(&mut self) -> Option<io::Result<Event>> {
+        while self.pending.is_empty() && !self.done {
+            self.line.clear();
+            match self.reader.read_line(&mut self.line) {
+                Ok(0) => {
+                    self.close_context();
+                    for _ in self.levels.drain(..) {
+                        self.pending.push_back(Event::HeadlineEnd);
+                    }
+                    self.done = true;
+                }
+                Ok(len) => {
+                    let offset = self.offset;
+                    self.offset += len;
+                    self.read_line(offset);
+                }
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+            }
+        }
+        self.pending.pop_front().map(Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_events() {
+        let text = "#+TODO: NEXT | DONE
+Intro text
+continues.
+* NEXT Write :work:
+SCHEDULED: <2024-05-01 Wed>
+:LOGBOOK:
+* Drawers end at headlines
+** Part
+#+BEGIN_SRC sh
+echo
+#+END_SRC
+
+* Last";
+        let events: Vec<_> = DocumentParser::new().events(text.as_bytes())
+            .collect::<io::Result<_>>()
+            .unwrap();
+        let summary: Vec<String> = events.iter().map(|event| match *event {
+            Event::HeadlineStart(ref headline) => {
+                format!("start {:?} {}", headline.keyword(), headline.title())
+            }
+            Event::Keyword(ref keyword) => format!("keyword {}", keyword.key()),
+            Event::Planning(ref planning) => format!("planning {}", planning.span().start),
+            Event::BlockStart { ref name, .. } => format!("block {}", name),
+            Event::DrawerStart(ref name) => format!("drawer {}", name),
+            Event::Text(ref text) => format!("text {}", text),
+            ref event => format!("{:?}", event),
+        }).collect();
+        assert_eq!(summary, [
+            "keyword TODO",
+            "ParagraphStart",
+            "text Intro text",
+            "text continues.",
+            "ParagraphEnd",
+            "start Some(\"NEXT\") Write",
+            "planning 62",
+            "drawer LOGBOOK",
+            "DrawerEnd",
+            "HeadlineEnd",
+            "start None Drawers end at headlines",
+            "start None Part",
+            "block SRC",
+            "text echo",
+            "BlockEnd",
+            "HeadlineEnd",
+            "HeadlineEnd",
+            "start None Last",
+            "HeadlineEnd",
+        ]);
+        match events[5] {
+            Event::HeadlineStart(ref headline) => {
+                assert_eq!(headline.span(), Span::new(42, 61));
+                assert_eq!(headline.tags(), ["work"]);
+            }
+            ref event => panic!("{:?}", event),
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use std::collections::HashSet;
+
+use DocumentParser;
+
+/// An identifier renamed by `deduplicate_ids`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rename {
+    /// The index of the text it was renamed in.
+    pub index: usize,
+    pub old: String,
+    pub new: String,
+}
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Footnote,
+    Target,
+    CustomId,
+}
+
+/// Rename footnote labels, `<<targets>>` and `CUSTOM_ID`s that several of
+/// `texts` define, when they are exported as one document: each collision
+/// gets a numbered name in the later text, like `note-2`, with the
+/// references to it in that text renamed too. The earliest text keeps its
+/// names.
+pub fn deduplicate_ids(texts: &mut [String]) -> Vec<Rename> {
+    let definitions: Vec<_> = texts.iter().map(|text| definitions(text)).collect();
+    let mut renames = Vec::new();
+    for (k, &kind) in [Kind::Footnote, Kind::Target, Kind::CustomId].iter().enumerate() {
+        let mut taken: HashSet<String> = definitions.iter()
+            .flat_map(|defined| defined[k].iter().cloned())
+            .collect();
+        if let Kind::Footnote = kind {
+            let references = texts.iter().flat_map(|text| ::footnote::find_references(text));
+            taken.extend(references.filter_map(|reference| reference.label().map(String::from)));
+        }
+        let mut seen = HashSet::new();
+        for (index, defined) in definitions.iter().enumerate() {
+            for old in &defined[k] {
+                if seen.insert(old.clone()) {
+                    continue;
+                }
+                let new = (2..).map(|n| format!("{}-{}", old, n))
+                    .find(|name| !taken.contains(name))
+                    .unwrap();
+                taken.insert(new.clone());
+                texts[index] = rename(&texts[index], kind, old, &new);
+                renames.push(Rename { index, old: old.clone(), new });
+            }
+        }
+    }
+    renames
+}
+
+/// The footnote labels, targets and custom ids `text` defines.
+fn definitions(text: &str) -> [Vec<String>; 3] {
+    let document = match DocumentParser::new().parse(text) {
+        Ok(document) => document,
+        Err(_) => return [Vec::new(), Vec::new(), Vec::new()],
+    };
+    let mut footnotes: Vec<String> = document.footnotes().keys().map(|&label| label.into())
+        .collect();
+    footnotes.sort();
+    let mut targets = Vec::new();
+    for (start, _) in text.match_indices("<<") {
+        if text[..start].ends_with('<') || text[start + 2..].starts_with('<') {
+            continue;
+        }
+        if let Some(end) = text[start + 2..].find(">>") {
+            let name = &text[start + 2..start + 2 + end];
+            if !name.is_empty() && !name.contains('\n') && !targets.iter().any(|t| t == name) {
+                targets.push(name.to_string());
+            }
+        }
+    }
+    let mut custom_ids: Vec<String> = Vec::new();
+    for id in document.all_headlines().filter_map(|headline| headline.property("CUSTOM_ID")) {
+        if !custom_ids.iter().any(|existing| existing == id) {
+            custom_ids.push(id.into());
+        }
+    }
+    [footnotes, targets, custom_ids]
+}
+
+/// `text` with the definition of `old` and the references to it renamed.
+fn rename(text: &str, kind: Kind, old: &str, new: &str) -> String {
+    match kind {
+        Kind::Footnote => text
+            .replace(&format!("[fn:{}]", old), &format!("[fn:{}]", new))
+            .replace(&format!("[fn:{}:", old), &format!("[fn:{}:", new)),
+        Kind::Target => text
+            .replace(&format!("<<{}>>", old), &format!("<<{}>>", new))
+            .replace(&format!("[[{}]", old), &format!("[[{}]", new)),
+        Kind::CustomId => {
+            let text = text.replace(&format!("[[#{}]", old), &format!("[[#{}]", new));
+            let lines: Vec<String> = text.split('\n')
+                .map(|line| {
+                    let trimmed = line.trim();
+                    let is_definition = trimmed.len() > 11
+                        && trimmed[..11].eq_ignore_ascii_case(":CUSTOM_ID:")
+                        && trimmed[11..].trim() == old;
+                    if is_definition {
+                        let indent = &line[..line.len() - line.trim_start().len()];
+                        format!("{}{} {}", indent, &trimmed[..11], new)
+                    } else {
+                        line.to_string()
+                    }
+                })
+                .collect();
+            lines.join("\n")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deduplicate_ids() {
+        let mut texts = vec![
+            "* Intro\nSee [fn:1] and <<here>>.\n\n[fn:1] First.\n".to_string(),
+            "* More
+:PROPERTIES:
+:CUSTOM_ID: more
+:END:
+Again [fn:1], [[here]] and [[#more][this]].
+<<here>>
+
+[fn:1] Second.
+".to_string(),
+            "* Last
+:PROPERTIES:
+:CUSTOM_ID: more
+:END:
+[fn:1-2] is taken, <<<radio>>> isn't a target.
+".to_string(),
+        ];
+        let renames = deduplicate_ids(&mut texts);
+        let renames: Vec<_> = renames.iter()
+            .map(|rename| (rename.index, &rename.old[..], &rename.new[..]))
+            .collect();
+        assert_eq!(renames, [(1, "1", "1-3"), (1, "here", "here-2"), (2, "more", "more-2")]);
+        assert_eq!(texts[1], "* More
+:PROPERTIES:
+:CUSTOM_ID: more
+:END:
+Again [fn:1-3], [[here-2]] and [[#more][this]].
+<<here-2>>
+
+[fn:1-3] Second.
+");
+        assert!(texts[2].contains(":CUSTOM_ID: more-2\n"));
+        assert!(texts[0].contains("[fn:1] First."));
+    }
+}
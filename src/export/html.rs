@@ -0,0 +1,1293 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::rc::Rc;
+
+use super::{ExportWarning, ExportWarningKind, is_broken, is_image};
+use agenda::Agenda;
+use diff::{HeadlineDiff, Run};
+use {Block, BlockKind, Checkbox, Content, Document, Element, GreaterElement, Headline, Link,
+     LinkIndex, LinkTarget, LinkType, ListItem, ListKind, Object, PlainList, Script, Scripts, Table,
+     Span, TableRow, TimestampStyle, link_radio_targets};
+
+/// Renders documents as HTML fragments, without the surrounding `<html>` and
+/// `<body>` tags, so they can be embedded in any page.
+///
+/// Each headline becomes an `<hN>` heading inside a `<div class="outline-N">`
+/// holding its section and children. Drawers, keywords, comment blocks and
+/// commented subtrees are not exported, archived subtrees only export their
+/// headline, and footnotes are collected at the end of the fragment in
+/// place of the footnote section. Text matching a `<<<radio target>>>`
+/// links to it.
+///
+/// Diagram blocks, `#+BEGIN_SRC mermaid` and the like, are written as
+/// `<pre class="mermaid">` holding their source for a script such as
+/// mermaid.js to draw in the browser, unless a `DiagramRenderer` is set.
+///
+/// With `self_contained` set the whole page is written instead, with
+/// everything it needs inside it, and with a `Template` the page it gives.
+#[derive(Clone)]
+pub struct HtmlExporter {
+    max_heading_level: u32,
+    commented: bool,
+    archived: bool,
+    timestamp_style: TimestampStyle,
+    time_elements: bool,
+    diagram_renderer: Option<Rc<dyn DiagramRenderer>>,
+    self_contained: bool,
+    resource_directory: PathBuf,
+    template: Option<Template>,
+}
+
+impl fmt::Debug for HtmlExporter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("HtmlExporter")
+            .field("max_heading_level", &self.max_heading_level)
+            .field("commented", &self.commented)
+            .field("archived", &self.archived)
+            .field("timestamp_style", &self.timestamp_style)
+            .field("time_elements", &self.time_elements)
+            .field("diagram_renderer", &self.diagram_renderer.is_some())
+            .field("self_contained", &self.self_contained)
+            .field("resource_directory", &self.resource_directory)
+            .field("template", &self.template)
+            .finish()
+    }
+}
+
+/// Turns the source of a diagram into HTML while exporting, for instance an
+/// `<img>` of an image it generated or an inline `<svg>`.
+///
+/// Closures taking the language, like `mermaid` or `plantuml`, and the
+/// source are renderers.
+pub trait DiagramRenderer {
+    /// The HTML for the diagram, or `None` to export it as if there were no
+    /// renderer.
+    fn render(&self, language: &str, source: &str) -> Option<String>;
+}
+
+impl<F: Fn(&str, &str) -> Option<String>> DiagramRenderer for F {
+    fn render(&self, language: &str, source: &str) -> Option<String> {
+        self(language, source)
+    }
+}
+
+impl Default for HtmlExporter {
+    fn default() -> Self {
+        HtmlExporter::new()
+    }
+}
+
+impl HtmlExporter {
+    pub fn new() -> Self {
+        HtmlExporter {
+            max_heading_level: 6,
+            commented: false,
+            archived: false,
+            timestamp_style: TimestampStyle::org(),
+            time_elements: false,
+            diagram_renderer: None,
+            self_contained: false,
+            resource_directory: PathBuf::new(),
+            template: None,
+        }
+    }
+
+    /// The deepest heading tag to use, between 1 and 6. Deeper headlines
+    /// use it as well.
+    pub fn max_heading_level(mut self, level: u32) -> Self {
+        self.max_heading_level = level.clamp(1, 6);
+        self
+    }
+
+    /// Whether to export commented subtrees too.
+    pub fn commented(mut self, export: bool) -> Self {
+        self.commented = export;
+        self
+    }
+
+    /// Whether to export the sections and children of archived headlines.
+    pub fn archived(mut self, export: bool) -> Self {
+        self.archived = export;
+        self
+    }
+
+    /// How to write timestamps, `TimestampStyle::org` by default.
+    pub fn timestamp_style(mut self, style: TimestampStyle) -> Self {
+        self.timestamp_style = style;
+        self
+    }
+
+    /// Whether to write timestamps as `<time>` elements with their start in
+    /// the `datetime` attribute, instead of as `<span>`s.
+    pub fn time_elements(mut self, time_elements: bool) -> Self {
+        self.time_elements = time_elements;
+        self
+    }
+
+    /// Render diagram blocks with `renderer` instead of leaving them to a
+    /// script.
+    pub fn diagram_renderer<R: DiagramRenderer + 'static>(mut self, renderer: R) -> Self {
+        self.diagram_renderer = Some(Rc::new(renderer));
+        self
+    }
+
+    /// Whether to write a whole page, with the stylesheet and MathJax
+    /// configuration inlined and local images embedded as `data:` URIs, so
+    /// it can be sent on its own as one file.
+    pub fn self_contained(mut self, self_contained: bool) -> Self {
+        self.self_contained = self_contained;
+        self
+    }
+
+    /// The directory relative image paths are read from when embedding
+    /// them, the current directory by default.
+    pub fn resource_directory<P: Into<PathBuf>>(mut self, directory: P) -> Self {
+        self.resource_directory = directory.into();
+        self
+    }
+
+    /// Export documents into `template`, with the fragment in its
+    /// `{{content}}` slot.
+    pub fn template(mut self, template: Template) -> Self {
+        self.template = Some(template);
+        self
+    }
+
+    pub fn export(&self, document: &Document) -> String {
+        self.export_with_warnings(document).0
+    }
+
+    /// Export `document` like `export`, along with the warnings about what
+    /// couldn't be exported as written, like links to headlines that aren't
+    /// there, in the order they were found.
+    pub fn export_with_warnings(&self, document: &Document) -> (String, Vec<ExportWarning>) {
+        let _span = trace_span!("export", format = "html");
+        let mut writer = self.writer(document);
+        if let Some(section) = document.first_section() {
+            writer.write_contents(section.contents());
+        }
+        for headline in document.headlines() {
+            writer.write_headline(headline);
+        }
+        writer.write_footnotes();
+        trace_event!(bytes = writer.out.len(), footnotes = writer.footnotes.len(), "exported");
+        if let Some(ref template) = self.template {
+            let keyword = |key| escape(document.keyword_value(key).unwrap_or("").trim());
+            let toc = writer.toc(document.headlines());
+            let page = template.fill(&[
+                ("header", template.header.clone()),
+                ("footer", template.footer.clone()),
+                ("content", writer.out),
+                ("title", keyword("TITLE")),
+                ("author", keyword("AUTHOR")),
+                ("date", keyword("DATE")),
+                ("filetags", escape(&document.file_tags().join(" "))),
+                ("toc", toc),
+            ]);
+            return (page, writer.warnings);
+        }
+        if !self.self_contained {
+            return (writer.out, writer.warnings);
+        }
+        let page = format!("<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>{}</title>
+<style>
+{}</style>
+<script>
+{}</script>
+</head>
+<body>
+{}</body>
+</html>
+", escape(document.keyword_value("TITLE").unwrap_or("").trim()),
+                stylesheet(Theme::Auto), MATHJAX_CONFIG, writer.out);
+        (page, writer.warnings)
+    }
+
+    /// Render a word diff from `diff::subtree_diff` as headings and
+    /// paragraphs, with insertions in `<ins>` and deletions in `<del>`.
+    /// Links are resolved in `document`, the new version.
+    pub fn export_diff(&self, document: &Document, diffs: &[HeadlineDiff]) -> String {
+        let mut writer = self.writer(document);
+        for diff in diffs {
+            let tag = diff.level.min(self.max_heading_level);
+            writer.out.push_str(&format!("<h{}>", tag));
+            writer.write_runs(&diff.title, false);
+            writer.out.push_str(&format!("</h{}>\n", tag));
+            if diff.body.iter().any(|run| !run.text().trim().is_empty()) {
+                writer.out.push_str("<p>\n");
+                writer.write_runs(&diff.body, true);
+                writer.out.push_str("\n</p>\n");
+            }
+        }
+        writer.out
+    }
+
+    fn writer<'a>(&'a self, document: &'a Document) -> Writer<'a> {
+        let mut anchors = HashMap::new();
+        collect_anchors(document.headlines(), "sec", &mut anchors);
+        let links = document.link_index();
+        Writer {
+            max_heading_level: self.max_heading_level,
+            commented: self.commented,
+            archived: self.archived,
+            timestamp_style: self.timestamp_style,
+            time_elements: self.time_elements,
+            diagram_renderer: self.diagram_renderer.as_deref(),
+            images: if self.self_contained { Some(&self.resource_directory) } else { None },
+            document,
+            anchors,
+            footnote_section: document.footnote_section(),
+            radio_targets: links.radio_targets().to_vec(),
+            links,
+            in_link: false,
+            scripts: document.scripts(),
+            footnotes: Vec::new(),
+            span: Span::default(),
+            warnings: Vec::new(),
+            out: String::new(),
+        }
+    }
+}
+
+/// A page for `HtmlExporter::template`, with `{{name}}` placeholders for:
+///
+/// - `content`: the exported document.
+/// - `header` and `footer`: the HTML given to `header` and `footer`.
+/// - `title`, `author` and `date`: the document's keywords.
+/// - `filetags`: the `#+FILETAGS:`, separated by spaces.
+/// - `toc`: nested `<ul>` lists linking to the exported headlines.
+///
+/// Other placeholders are left as written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    text: String,
+    header: String,
+    footer: String,
+}
+
+impl Template {
+    pub fn new<S: Into<String>>(text: S) -> Self {
+        Template { text: text.into(), header: String::new(), footer: String::new() }
+    }
+
+    pub fn header<S: Into<String>>(mut self, html: S) -> Self {
+        self.header = html.into();
+        self
+    }
+
+    pub fn footer<S: Into<String>>(mut self, html: S) -> Self {
+        self.footer = html.into();
+        self
+    }
+
+    /// The template with each placeholder named in `slots` replaced.
+    fn fill(&self, slots: &[(&str, String)]) -> String {
+        let mut out = String::with_capacity(self.text.len());
+        let mut rest = &self.text[..];
+        while let Some(start) = rest.find("{{") {
+            let end = match rest[start..].find("}}") {
+                Some(end) => start + end,
+                None => break,
+            };
+            let name = rest[start + 2..end].trim();
+            out.push_str(&rest[..start]);
+            match slots.iter().find(|slot| slot.0 == name) {
+                Some((_, value)) => out.push_str(value),
+                None => out.push_str(&rest[start..end + 2]),
+            }
+            rest = &rest[end + 2..];
+        }
+        out.push_str(rest);
+        out
+    }
+}
+
+/// Export `document` with the default options.
+pub fn to_html(document: &Document) -> String {
+    HtmlExporter::new().export(document)
+}
+
+impl<'a> Agenda<'a> {
+    /// The agenda as an HTML fragment: a `<div class="org-agenda">` with an
+    /// `<h2>` for each day and a list of its entries, with their keywords
+    /// and tags in the classes the exporter uses for headlines.
+    pub fn to_html(&self) -> String {
+        let mut out = String::from("<div class=\"org-agenda\">\n");
+        for (date, entries) in self.days() {
+            out.push_str(&format!("<h2 class=\"org-agenda-day\">{} {}</h2>\n", date,
+                                  ::timestamp::day_name(date)));
+            if entries.is_empty() {
+                continue;
+            }
+            out.push_str("<ul>\n");
+            for entry in entries {
+                out.push_str("<li>");
+                if let Some(time) = entry.time {
+                    out.push_str(&format!("<span class=\"org-agenda-time\">{}</span> ", time));
+                }
+                if let Some(label) = entry.label() {
+                    out.push_str(&format!("<span class=\"org-agenda-label\">{}</span> ",
+                                          escape(&label)));
+                }
+                let headline = entry.headline;
+                if let Some(keyword) = headline.keyword() {
+                    let class = if entry.done { "org-done" } else { "org-todo" };
+                    out.push_str(&format!("<span class=\"{}\">{}</span> ", class, escape(keyword)));
+                }
+                out.push_str(&escape(headline.title()));
+                for tag in headline.tags() {
+                    out.push_str(&format!(" <span class=\"org-tag\">{}</span>", escape(tag)));
+                }
+                out.push_str("</li>\n");
+            }
+            out.push_str("</ul>\n");
+        }
+        out.push_str("</div>\n");
+        out
+    }
+}
+
+/// Give every headline an anchor: its `CUSTOM_ID`, or its outline number like
+/// `sec-1-2`.
+fn collect_anchors(headlines: &[Headline], prefix: &str,
+                   anchors: &mut HashMap<*const Headline, String>) {
+    for (i, headline) in headlines.iter().enumerate() {
+        let number = format!("{}-{}", prefix, i + 1);
+        let anchor = headline.property("CUSTOM_ID").map_or_else(|| number.clone(), String::from);
+        anchors.insert(headline, anchor);
+        collect_anchors(headline.children(), &number, anchors);
+    }
+}
+
+/// The colours of a default stylesheet, see `stylesheet`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Theme {
+    Light,
+    Dark,
+    /// Light, or dark where the reader's system prefers it.
+    Auto,
+}
+
+/// A default stylesheet for exported HTML, styling the classes the exporter
+/// writes, like `org-todo`, `org-done`, `org-tag` and `org-src`. Self-contained
+/// pages use the `Auto` one.
+pub fn stylesheet(theme: Theme) -> String {
+    let colours = match theme {
+        Theme::Light => LIGHT.to_string(),
+        Theme::Dark => DARK.to_string(),
+        Theme::Auto => format!("{}@media (prefers-color-scheme: dark) {{\n{}}}\n", LIGHT, DARK),
+    };
+    format!("{}{}", LAYOUT, colours)
+}
+
+const LAYOUT: &str = "body { max-width: 50em; margin: 0 auto; padding: 1em; \
+                      font-family: sans-serif; line-height: 1.5; }
+pre { padding: 0.75em; overflow: auto; }
+code { font-family: monospace; }
+pre.example { border-left: 3px solid; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid; padding: 0.25em 0.5em; }
+img { max-width: 100%; }
+#footnotes { border-top: 1px solid; font-size: smaller; }
+.org-todo, .org-done { font-weight: bold; }
+.org-tag { border-radius: 0.25em; padding: 0 0.25em; font-size: smaller; }
+.org-agenda-time { font-variant-numeric: tabular-nums; }
+.org-agenda-label { font-style: italic; }
+";
+
+const LIGHT: &str = "body { color: #1f2328; background: #ffffff; }
+a { color: #0969da; }
+pre { background: #f6f8fa; }
+pre.example, th, td, #footnotes { border-color: #d0d7de; }
+.org-todo { color: #cf222e; }
+.org-done { color: #1a7f37; }
+.org-tag { color: #0969da; background: #ddf4ff; }
+";
+
+const DARK: &str = "body { color: #e6edf3; background: #0d1117; }
+a { color: #4493f8; }
+pre { background: #161b22; }
+pre.example, th, td, #footnotes { border-color: #30363d; }
+.org-todo { color: #ff7b72; }
+.org-done { color: #3fb950; }
+.org-tag { color: #4493f8; background: #121d2f; }
+";
+
+/// The MathJax configuration of self-contained pages, matching the math
+/// delimiters org exports.
+const MATHJAX_CONFIG: &str = "window.MathJax = {
+  tex: { inlineMath: [['\\\\(', '\\\\)'], ['$', '$']],
+         displayMath: [['\\\\[', '\\\\]'], ['$$', '$$']] }
+};
+";
+
+/// The contents of the image at `path` as a `data:` URI, or `None` if it
+/// can't be read.
+fn data_uri(path: &Path) -> Option<String> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    let mime = match &extension[..] {
+        "jpg" | "jpeg" => "image/jpeg".into(),
+        "svg" => "image/svg+xml".into(),
+        "png" | "gif" | "webp" => format!("image/{}", extension),
+        _ => return None,
+    };
+    let bytes = fs::read(path).ok()?;
+    Some(format!("data:{};base64,{}", mime, base64(&bytes)))
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const DIGITS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate()
+            .fold(0u32, |n, (i, &byte)| n | u32::from(byte) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(DIGITS[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+struct Writer<'a> {
+    max_heading_level: u32,
+    commented: bool,
+    archived: bool,
+    timestamp_style: TimestampStyle,
+    time_elements: bool,
+    diagram_renderer: Option<&'a dyn DiagramRenderer>,
+    /// The directory to read images from to embed them, if they are.
+    images: Option<&'a Path>,
+    document: &'a Document,
+    /// The anchor of each headline, by its address.
+    anchors: HashMap<*const Headline, String>,
+    footnote_section: Option<&'a Headline>,
+    links: LinkIndex<'a>,
+    radio_targets: Vec<String>,
+    /// Whether objects are being written inside a link, where text doesn't
+    /// link to radio targets.
+    in_link: bool,
+    scripts: Scripts,
+    /// The labels of referenced footnotes in order of first reference, with
+    /// the text of inline definitions.
+    footnotes: Vec<(String, Option<String>)>,
+    /// The span of the element or headline being written, for warnings.
+    span: Span,
+    warnings: Vec<ExportWarning>,
+    out: String,
+}
+
+impl<'a> Writer<'a> {
+    fn warn(&mut self, kind: ExportWarningKind, message: String) {
+        self.warnings.push(ExportWarning::new(kind, message, self.span));
+    }
+
+    fn anchor(&self, headline: &Headline) -> &str {
+        &self.anchors[&(headline as *const Headline)]
+    }
+
+    /// Lists linking to `headlines` and their children, leaving out the
+    /// ones `write_headline` does.
+    fn toc(&self, headlines: &[Headline]) -> String {
+        let shown: Vec<_> = headlines.iter()
+            .filter(|headline| {
+                !(headline.is_commented() && !self.commented
+                  || self.footnote_section.is_some_and(|section| ptr::eq(section, *headline)))
+            })
+            .collect();
+        if shown.is_empty() {
+            return String::new();
+        }
+        let mut out = String::from("<ul>\n");
+        for headline in shown {
+            out.push_str(&format!("<li><a href=\"#{}\">{}</a>", escape(self.anchor(headline)),
+                                  escape(headline.title())));
+            if !headline.is_archived() || self.archived {
+                let children = self.toc(headline.children());
+                if !children.is_empty() {
+                    out.push('\n');
+                    out.push_str(&children);
+                }
+            }
+            out.push_str("</li>\n");
+        }
+        out.push_str("</ul>\n");
+        out
+    }
+
+    fn write_headline(&mut self, headline: &'a Headline) {
+        if headline.is_commented() && !self.commented
+            || self.footnote_section.is_some_and(|section| ptr::eq(section, headline)) {
+            return;
+        }
+        self.span = headline.span();
+        let tag = headline.level().min(self.max_heading_level);
+        let anchor = self.anchor(headline).to_string();
+        self.out.push_str(&format!("<div class=\"outline-{}\">\n", headline.level()));
+        self.out.push_str(&format!("<h{} id=\"{}\">", tag, escape(&anchor)));
+        if let Some(keyword) = headline.keyword() {
+            let done = self.document.done_keywords().iter().any(|done| done == keyword);
+            let class = if done { "org-done" } else { "org-todo" };
+            self.out.push_str(&format!("<span class=\"{}\">{}</span> ", class, escape(keyword)));
+        }
+        self.write_objects(&headline.title_objects());
+        for tag in headline.tags() {
+            self.out.push_str(&format!(" <span class=\"org-tag\">{}</span>", escape(tag)));
+        }
+        self.out.push_str(&format!("</h{}>\n", tag));
+        if !headline.is_archived() || self.archived {
+            if let Some(section) = headline.section() {
+                self.write_contents(section.contents());
+            }
+            for child in headline.children() {
+                self.write_headline(child);
+            }
+        }
+        self.out.push_str("</div>\n");
+    }
+
+    fn write_contents(&mut self, contents: &[Content]) {
+        for content in contents {
+            let outer = self.span;
+            self.span = content.span().unwrap_or(outer);
+            let table = matches!(*content, Content::Greater(GreaterElement::Table(_)));
+            let attributes = content.affiliated()
+                .and_then(|affiliated| affiliated.attributes("html"));
+            if !table && attributes.is_some_and(|attributes| !attributes.is_empty()) {
+                self.warn(ExportWarningKind::UnsupportedAttribute,
+                          "#+ATTR_HTML is only exported on tables".into());
+            }
+            match *content {
+                Content::Greater(ref greater) => self.write_greater(greater),
+                Content::Element(ref element) => self.write_element(element),
+            }
+            self.span = outer;
+        }
+    }
+
+    /// Write the block as a diagram if it is one.
+    fn write_diagram(&mut self, block: &Block) -> bool {
+        let language = match block.diagram_language() {
+            Some(language) => language,
+            None => return false,
+        };
+        match self.diagram_renderer.and_then(|renderer| renderer.render(language, block.text())) {
+            Some(html) => {
+                self.out.push_str(&html);
+                if !html.ends_with('\n') {
+                    self.out.push('\n');
+                }
+            }
+            None => {
+                self.out.push_str(&format!("<pre class=\"{}\">", language));
+                self.out.push_str(&escape(block.text()));
+                self.out.push_str("</pre>\n");
+            }
+        }
+        true
+    }
+
+    fn write_greater(&mut self, greater: &GreaterElement) {
+        match *greater {
+            GreaterElement::Block(ref block) if self.write_diagram(block) => {}
+            GreaterElement::Block(ref block) => {
+                let (open, close) = match *block.kind() {
+                    BlockKind::Quote => ("<blockquote>\n".to_string(), "</blockquote>\n"),
+                    BlockKind::Special(ref name) => {
+                        (format!("<div class=\"{}\">\n", escape(name)), "</div>\n")
+                    }
+                    _ => ("<div class=\"center\">\n".to_string(), "</div>\n"),
+                };
+                self.out.push_str(&open);
+                self.write_contents(block.contents());
+                self.out.push_str(close);
+            }
+            GreaterElement::DynamicBlock(ref block) => self.write_contents(block.contents()),
+            GreaterElement::Inlinetask(ref task) => {
+                self.out.push_str("<div class=\"inlinetask\">\n<b>");
+                if let Some(keyword) = task.headline().keyword() {
+                    self.out.push_str(&escape(keyword));
+                    self.out.push(' ');
+                }
+                self.write_objects(&task.headline().title_objects());
+                self.out.push_str("</b><br>\n");
+                self.write_contents(task.contents());
+                self.out.push_str("</div>\n");
+            }
+            GreaterElement::PlainList(ref list) => self.write_list(list),
+            GreaterElement::Table(ref table) => self.write_table(table),
+            GreaterElement::Drawer(_) | GreaterElement::PropertyDrawer(_)
+                | GreaterElement::Footnote(_) => {}
+        }
+    }
+
+    fn write_element(&mut self, element: &Element) {
+        match *element {
+            Element::Block(ref block) if self.write_diagram(block) => {}
+            Element::Block(ref block) => match *block.kind() {
+                BlockKind::Src => {
+                    match block.language() {
+                        Some(language) => self.out.push_str(&format!(
+                            "<pre class=\"org-src org-src-{0}\"><code class=\"language-{0}\">",
+                            escape(language))),
+                        None => self.out.push_str("<pre class=\"org-src\"><code>"),
+                    }
+                    self.out.push_str(&escape(block.text()));
+                    self.out.push_str("</code></pre>\n");
+                }
+                BlockKind::Example => {
+                    self.out.push_str("<pre class=\"example\">");
+                    self.out.push_str(&escape(block.text()));
+                    self.out.push_str("</pre>\n");
+                }
+                BlockKind::Export if block.language()
+                    .is_some_and(|backend| backend.eq_ignore_ascii_case("html")) => {
+                    self.out.push_str(block.text());
+                    self.out.push('\n');
+                }
+                BlockKind::Verse => {
+                    self.out.push_str("<p class=\"verse\">\n");
+                    for (i, (indent, objects)) in block.verse_lines().iter().enumerate() {
+                        if i > 0 {
+                            self.out.push_str("<br>\n");
+                        }
+                        self.out.push_str(&"&#xa0;".repeat(*indent));
+                        self.write_objects(objects);
+                    }
+                    self.out.push_str("\n</p>\n");
+                }
+                _ => {}
+            },
+            Element::Paragraph(ref paragraph) => {
+                self.out.push_str("<p>\n");
+                self.write_objects(&paragraph.objects());
+                self.out.push_str("\n</p>\n");
+            }
+            // Written as text, like the paragraph it would be without
+            // lenient parsing.
+            Element::Raw(ref raw) => {
+                self.out.push_str("<p>\n");
+                self.write_text(raw.text().trim());
+                self.out.push_str("\n</p>\n");
+            }
+            Element::FixedWidth(ref fixed) => {
+                self.out.push_str("<pre class=\"example\">");
+                self.out.push_str(&escape(fixed.text()));
+                self.out.push_str("</pre>\n");
+            }
+            Element::HorizontalRule(_) => self.out.push_str("<hr>\n"),
+            Element::LatexEnvironment(ref latex) => {
+                // Left as written for MathJax to typeset.
+                self.out.push_str(&escape(latex.text()));
+                self.out.push('\n');
+            }
+            Element::BabelCall(_) | Element::Clock(_) | Element::Comment(_) | Element::Keyword(_)
+            | Element::Planning(_) => {}
+        }
+    }
+
+    fn write_list(&mut self, list: &PlainList) {
+        let (open, close) = match list.kind() {
+            ListKind::Unordered => ("<ul>\n", "</ul>\n"),
+            ListKind::Ordered => ("<ol>\n", "</ol>\n"),
+            ListKind::Descriptive => ("<dl>\n", "</dl>\n"),
+        };
+        self.out.push_str(open);
+        for item in list.items() {
+            if list.kind() == ListKind::Descriptive {
+                self.out.push_str("<dt>");
+                self.write_objects(&::parse_objects(item.tag().unwrap_or("")));
+                self.out.push_str("</dt><dd>");
+                self.write_item_body(item);
+                self.out.push_str("</dd>\n");
+            } else {
+                self.out.push_str("<li>");
+                self.write_item_body(item);
+                self.out.push_str("</li>\n");
+            }
+        }
+        self.out.push_str(close);
+    }
+
+    fn write_item_body(&mut self, item: &ListItem) {
+        match item.checkbox() {
+            Some(Checkbox::Checked) => self.out.push_str("<code>[X]</code> "),
+            Some(Checkbox::Partial) => self.out.push_str("<code>[-]</code> "),
+            Some(Checkbox::Unchecked) => self.out.push_str("<code>[&#xa0;]</code> "),
+            None => {}
+        }
+        // A lone paragraph goes directly into the item, like org does it.
+        match item.contents() {
+            [Content::Element(Element::Paragraph(ref paragraph))] => {
+                self.write_objects(&paragraph.objects());
+            }
+            contents => {
+                self.out.push('\n');
+                self.write_contents(contents);
+            }
+        }
+    }
+
+    fn write_table(&mut self, table: &Table) {
+        // Rows before the first rule are a header, if anything follows it.
+        let header_len = table.rows().iter()
+            .position(|row| *row == TableRow::Rule)
+            .filter(|&rule| {
+                rule > 0 && table.rows()[rule..].iter().any(|row| *row != TableRow::Rule)
+            })
+            .unwrap_or(0);
+        self.out.push_str("<table");
+        for (key, value) in table.affiliated().attributes("html").into_iter().flatten() {
+            if let Some(name) = key.strip_prefix(':').filter(|name| !name.is_empty()) {
+                self.out.push_str(&format!(" {}=\"{}\"", escape(name), escape(value)));
+            }
+        }
+        self.out.push_str(">\n");
+        if let Some(caption) = table.affiliated().caption_objects() {
+            self.out.push_str("<caption>");
+            self.write_objects(&caption);
+            self.out.push_str("</caption>\n");
+        }
+        for (i, row) in table.rows().iter().enumerate() {
+            let cells = match *row {
+                TableRow::Standard(ref cells) => cells,
+                TableRow::Rule => continue,
+            };
+            let tag = if i < header_len { "th" } else { "td" };
+            self.out.push_str("<tr>");
+            for cell in cells {
+                self.out.push_str(&format!("<{}>", tag));
+                self.write_objects(&::parse_objects(cell));
+                self.out.push_str(&format!("</{}>", tag));
+            }
+            self.out.push_str("</tr>\n");
+        }
+        self.out.push_str("</table>\n");
+    }
+
+    fn write_objects(&mut self, objects: &[Object]) {
+        for object in objects {
+            match *object {
+                Object::Text(ref text) => self.write_text(text),
+                Object::Raw(ref raw) => self.write_text(raw.text()),
+                Object::Bold(ref inner) => self.write_wrapped("<b>", inner, "</b>"),
+                Object::Italic(ref inner) => self.write_wrapped("<i>", inner, "</i>"),
+                Object::Underline(ref inner) => {
+                    self.write_wrapped("<span class=\"underline\">", inner, "</span>");
+                }
+                Object::StrikeThrough(ref inner) => self.write_wrapped("<del>", inner, "</del>"),
+                Object::Verbatim(ref text) | Object::Code(ref text) => {
+                    self.out.push_str(&format!("<code>{}</code>", escape(text)));
+                }
+                Object::Link(ref link) => self.write_link(link),
+                Object::Target(ref name) => {
+                    self.out.push_str(&format!("<a id=\"{}\"></a>", escape(name)));
+                }
+                Object::RadioTarget(ref text) => {
+                    self.out.push_str(&format!("<a id=\"{0}\">{0}</a>", escape(text)));
+                }
+                Object::Entity(ref entity) => self.out.push_str(&escape(entity.utf8())),
+                Object::Subscript(ref script) => self.write_script("_", "sub", script),
+                Object::Superscript(ref script) => self.write_script("^", "sup", script),
+                // Left for MathJax or KaTeX to typeset in the browser.
+                Object::LatexFragment(ref latex) => self.out.push_str(&escape(latex)),
+                Object::FootnoteReference(ref reference) => {
+                    let label = match reference.label() {
+                        Some(label) => label.to_string(),
+                        None => format!("anon.{}", self.footnotes.len() + 1),
+                    };
+                    if !self.footnotes.iter().any(|(l, _)| *l == label) {
+                        let definition = reference.definition().map(String::from);
+                        self.footnotes.push((label.clone(), definition));
+                    }
+                    let label = escape(&label);
+                    self.out.push_str(&format!(
+                        "<sup><a id=\"fnr.{0}\" href=\"#fn.{0}\">{0}</a></sup>", label));
+                }
+                Object::StatisticsCookie(cookie) => {
+                    self.out.push_str(&format!("<code>{}</code>", cookie));
+                }
+                Object::Timestamp(ref timestamp) => {
+                    let text = escape(&timestamp.format(self.timestamp_style));
+                    if self.time_elements {
+                        self.out.push_str(&format!(
+                            "<time class=\"timestamp\" datetime=\"{}\">{}</time>",
+                            timestamp.iso_start(), text));
+                    } else {
+                        self.out.push_str(&format!("<span class=\"timestamp\">{}</span>", text));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Write the runs of a word diff, starting a new paragraph at blank
+    /// lines outside insertions and deletions if `paragraphs` is set.
+    fn write_runs(&mut self, runs: &[Run], paragraphs: bool) {
+        for run in runs {
+            let (open, close) = match *run {
+                Run::Unchanged(_) => ("", ""),
+                Run::Inserted(_) => ("<ins>", "</ins>"),
+                Run::Deleted(_) => ("<del>", "</del>"),
+            };
+            self.out.push_str(open);
+            let text = run.text();
+            let text = if paragraphs && ptr::eq(run, &runs[0]) { text.trim_start() } else { text };
+            let text = if paragraphs && ptr::eq(run, runs.last().unwrap()) {
+                text.trim_end()
+            } else {
+                text
+            };
+            if paragraphs && open.is_empty() {
+                for (i, paragraph) in text.split("\n\n").enumerate() {
+                    if i > 0 {
+                        self.out.push_str("\n</p>\n<p>\n");
+                    }
+                    self.write_objects(&::parse_objects(paragraph));
+                }
+            } else {
+                self.write_objects(&::parse_objects(text));
+            }
+            self.out.push_str(close);
+        }
+    }
+
+    /// Write `text`, linking the parts that match radio targets.
+    fn write_text(&mut self, text: &str) {
+        if self.in_link || self.radio_targets.is_empty() {
+            self.out.push_str(&escape(text));
+            return;
+        }
+        for object in link_radio_targets(vec![Object::Text(text.into())], &self.radio_targets) {
+            match object {
+                Object::Text(ref text) => self.out.push_str(&escape(text)),
+                ref link => self.write_objects(::std::slice::from_ref(link)),
+            }
+        }
+    }
+
+    /// Write a subscript or superscript, or write it as it was written if
+    /// the document's options don't allow it.
+    fn write_script(&mut self, marker: &str, tag: &str, script: &Script) {
+        if self.scripts.allows(script) {
+            self.out.push_str(&format!("<{}>", tag));
+            self.write_objects(script.contents());
+            self.out.push_str(&format!("</{}>", tag));
+        } else {
+            self.out.push_str(marker);
+            self.out.push_str(if script.is_braced() { "{" } else { "" });
+            self.write_objects(script.contents());
+            self.out.push_str(if script.is_braced() { "}" } else { "" });
+        }
+    }
+
+    fn write_wrapped(&mut self, open: &str, inner: &[Object], close: &str) {
+        self.out.push_str(open);
+        self.write_objects(inner);
+        self.out.push_str(close);
+    }
+
+    fn link_href(&self, link: &Link) -> String {
+        let path = link.path();
+        match link.link_type() {
+            LinkType::File => match path.strip_suffix(".org") {
+                Some(stem) => format!("{}.html", stem),
+                None => path.into(),
+            },
+            LinkType::CustomId => format!("#{}", path),
+            LinkType::Coderef => format!("#coderef-{}", path),
+            LinkType::Id | LinkType::Headline | LinkType::Fuzzy | LinkType::Radio => {
+                match self.links.target(link) {
+                    Some(LinkTarget::Headline(headline)) => format!("#{}", self.anchor(headline)),
+                    _ => format!("#{}", path),
+                }
+            }
+            LinkType::Http | LinkType::Other(_) => link.destination().into(),
+        }
+    }
+
+    fn write_link(&mut self, link: &Link) {
+        if is_broken(&self.links, link) {
+            self.warn(ExportWarningKind::BrokenLink,
+                      format!("nothing in the document matches the link {}", link.destination()));
+        }
+        let href = escape(&self.link_href(link));
+        match link.description() {
+            None if link.link_type() == LinkType::File && is_image(link.path()) => {
+                let embedded = self.images
+                    .and_then(|directory| data_uri(&directory.join(link.path())));
+                if self.images.is_some() && embedded.is_none() {
+                    self.warn(ExportWarningKind::MissingImage,
+                              format!("could not read the image {}", link.path()));
+                }
+                let src = embedded.as_ref().unwrap_or(&href);
+                self.out.push_str(&format!("<img src=\"{}\" alt=\"{}\">", src, href));
+            }
+            Some(description) => {
+                self.out.push_str(&format!("<a href=\"{}\">", href));
+                let in_link = ::std::mem::replace(&mut self.in_link, true);
+                self.write_objects(description);
+                self.in_link = in_link;
+                self.out.push_str("</a>");
+            }
+            None => {
+                self.out.push_str(&format!("<a href=\"{}\">{}</a>", href, escape(link.path())));
+            }
+        }
+    }
+
+    fn write_footnotes(&mut self) {
+        if self.footnotes.is_empty() {
+            return;
+        }
+        let definitions = self.document.footnotes();
+        self.out.push_str("<div id=\"footnotes\">\n");
+        // Definitions may reference further footnotes, which get appended.
+        let mut i = 0;
+        while i < self.footnotes.len() {
+            let (label, inline) = self.footnotes[i].clone();
+            let label_html = escape(&label);
+            self.out.push_str(&format!(
+                "<div class=\"footdef\"><sup><a id=\"fn.{0}\" href=\"#fnr.{0}\">{0}</a></sup>\n",
+                label_html));
+            match (inline, definitions.get(&label[..])) {
+                (Some(text), _) => {
+                    self.out.push_str("<p>\n");
+                    self.write_objects(&::parse_objects(&text));
+                    self.out.push_str("\n</p>\n");
+                }
+                (None, Some(definition)) => self.write_contents(definition.contents()),
+                (None, None) => {}
+            }
+            self.out.push_str("</div>\n");
+            i += 1;
+        }
+        self.out.push_str("</div>\n");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use DocumentParser;
+
+    #[test]
+    fn test_export_html() {
+        let doc = DocumentParser::new().todo_keywords(vec!["TODO", "DONE"]).parse("#+TITLE: x
+Intro with *bold* & [[#later][a link]].
+* TODO First
+:PROPERTIES:
+:CUSTOM_ID: later
+:END:
+- [X] one
+- two
+  1. nested
+| a | b |
+|---+---|
+| <1> | 2 |
+** Second[fn:1]
+#+BEGIN_SRC rust
+fn main() {}
+#+END_SRC
+See [[*First]] and [[file:other.org][other]] or [[./img.png]].
+* Notes
+[fn:1] The note.").unwrap();
+        let html = HtmlExporter::new().max_heading_level(1).export(&doc);
+        assert_eq!(html, "<p>
+Intro with <b>bold</b> &amp; <a href=\"#later\">a link</a>.
+</p>
+<div class=\"outline-1\">
+<h1 id=\"later\"><span class=\"org-todo\">TODO</span> First</h1>
+<ul>
+<li><code>[X]</code> one</li>
+<li>
+<p>
+two
+</p>
+<ol>
+<li>nested</li>
+</ol>
+</li>
+</ul>
+<table>
+<tr><th>a</th><th>b</th></tr>
+<tr><td>&lt;1&gt;</td><td>2</td></tr>
+</table>
+<div class=\"outline-2\">
+<h1 id=\"sec-1-1\">Second<sup><a id=\"fnr.1\" href=\"#fn.1\">1</a></sup></h1>
+<pre class=\"org-src org-src-rust\"><code class=\"language-rust\">fn main() {}</code></pre>
+<p>
+See <a href=\"#later\">First</a> and <a href=\"other.html\">other</a> or <img src=\"./img.png\" alt=\"./img.png\">.
+</p>
+</div>
+</div>
+<div class=\"outline-1\">
+<h1 id=\"sec-2\">Notes</h1>
+</div>
+<div id=\"footnotes\">
+<div class=\"footdef\"><sup><a id=\"fn.1\" href=\"#fnr.1\">1</a></sup>
+<p>
+The note.
+</p>
+</div>
+</div>
+");
+
+        let doc = DocumentParser::new().parse("#+CAPTION[Prices]: Prices in /May/
+#+ATTR_HTML: :class wide \"striped\" :border 1
+#+ATTR_LATEX: :align l|r
+| Apple | 3 |").unwrap();
+        assert_eq!(HtmlExporter::new().export(&doc), "<table border=\"1\" \
+class=\"wide &quot;striped&quot;\">
+<caption>Prices in <i>May</i></caption>
+<tr><td>Apple</td><td>3</td></tr>
+</table>
+");
+
+        let doc = DocumentParser::new().parse("#+BEGIN_QUOTE
+- quoted
+#+END_QUOTE
+#+BEGIN_VERSE
+ Great *clouds*
+   overhead
+#+END_VERSE").unwrap();
+        assert_eq!(HtmlExporter::new().export(&doc), "<blockquote>
+<ul>
+<li>quoted</li>
+</ul>
+</blockquote>
+<p class=\"verse\">
+Great <b>clouds</b><br>
+&#xa0;&#xa0;overhead
+</p>
+");
+    }
+
+    #[test]
+    fn test_export_commented_and_archived() {
+        let doc = DocumentParser::new().parse("* COMMENT Draft
+* Old :ARCHIVE:
+Archived text.").unwrap();
+        assert_eq!(to_html(&doc), "<div class=\"outline-1\">
+<h1 id=\"sec-2\">Old <span class=\"org-tag\">ARCHIVE</span></h1>
+</div>
+");
+        let html = HtmlExporter::new().commented(true).archived(true).export(&doc);
+        assert!(html.contains("Draft</h1>"));
+        assert!(html.contains("Archived text."));
+    }
+
+    #[test]
+    fn test_export_diff() {
+        let old = DocumentParser::new().parse("* Plan\nRun *far* now.\n\nRest.").unwrap();
+        let new = DocumentParser::new().parse("* Plan\nRun *far* daily now.\n\nRest.").unwrap();
+        let diffs = ::diff::subtree_diff(&old.headlines()[0], &new.headlines()[0]);
+        assert_eq!(HtmlExporter::new().export_diff(&new, &diffs), "<h1>Plan</h1>
+<p>
+Run <b>far</b> <ins>daily </ins>now.
+</p>
+<p>
+Rest.
+</p>
+");
+    }
+
+    #[test]
+    fn test_export_radio_targets() {
+        let doc = DocumentParser::new().parse("* Terms
+A <<<Widget>>> is a part.
+* Use
+Add a *widget*, see [[Terms][widget terms]] and [[Widget]].").unwrap();
+        let html = to_html(&doc);
+        assert!(html.contains("A <a id=\"Widget\">Widget</a> is a part."));
+        assert!(html.contains("Add a <b><a href=\"#Widget\">widget</a></b>, \
+                               see <a href=\"#sec-1\">widget terms</a> and \
+                               <a href=\"#Widget\">Widget</a>."));
+    }
+
+    #[test]
+    #[ignore]
+    fn test_export_large_document() {
+        // A benchmark, run with `cargo test -- --ignored`. Links are looked up
+        // in an index made once per export, so this takes well under a second;
+        // searching the document for each link made it take minutes.
+        let mut text = String::new();
+        for i in 0..4000 {
+            text.push_str(&format!("* Note {}\nSee [[Note {}]] and [[target {}]].\n<<target {}>>\n",
+                                   i, (i + 1) % 4000, i, i));
+        }
+        let doc = DocumentParser::new().parse(&text).unwrap();
+        let start = ::std::time::Instant::now();
+        let html = to_html(&doc);
+        assert!(start.elapsed() < ::std::time::Duration::from_secs(60));
+        assert!(html.contains("See <a href=\"#sec-2\">Note 1</a> and \
+                               <a href=\"#target 0\">target 0</a>."));
+        assert!(html.contains("See <a href=\"#sec-1\">Note 0</a>"));
+    }
+
+    #[test]
+    fn test_export_scripts() {
+        let doc = DocumentParser::new().parse("H_{2}O, x^2 and $e^x$").unwrap();
+        assert!(to_html(&doc).contains("H<sub>2</sub>O, x<sup>2</sup> and $e^x$"));
+        let doc = DocumentParser::new().parse("#+OPTIONS: ^:{}\nH_{2}O and x^2").unwrap();
+        assert!(to_html(&doc).contains("H<sub>2</sub>O and x^2"));
+    }
+
+    #[test]
+    fn test_export_timestamps() {
+        let doc = DocumentParser::new().parse("Due <2024-05-01 Wed 14:30>.").unwrap();
+        assert!(to_html(&doc).contains("<span class=\"timestamp\">&lt;2024-05-01 Wed 14:30&gt;"));
+        let html = HtmlExporter::new()
+            .timestamp_style(TimestampStyle::org().day_names(false).twelve_hour(true))
+            .time_elements(true)
+            .export(&doc);
+        assert!(html.contains("<time class=\"timestamp\" datetime=\"2024-05-01T14:30\">\
+                               &lt;2024-05-01 2:30pm&gt;</time>"));
+    }
+
+    #[test]
+    fn test_export_diagrams() {
+        let doc = DocumentParser::new().parse("#+BEGIN_SRC mermaid
+graph TD; A-->B
+#+END_SRC
+#+BEGIN_PLANTUML
+Alice -> Bob
+#+END_PLANTUML").unwrap();
+        assert_eq!(to_html(&doc), "<pre class=\"mermaid\">graph TD; A--&gt;B</pre>
+<pre class=\"plantuml\">Alice -&gt; Bob</pre>
+");
+        let exporter = HtmlExporter::new().diagram_renderer(|language: &str, source: &str| {
+            match language {
+                "plantuml" => Some(format!("<img alt=\"{}\">", source.len())),
+                _ => None,
+            }
+        });
+        assert_eq!(exporter.export(&doc), "<pre class=\"mermaid\">graph TD; A--&gt;B</pre>
+<img alt=\"12\">
+");
+    }
+
+    #[test]
+    fn test_export_self_contained() {
+        assert_eq!((base64(b"Man"), base64(b"Ma"), base64(b"M")),
+                   ("TWFu".into(), "TWE=".into(), "TQ==".into()));
+        let name = format!("org-self-contained-{}", ::std::process::id());
+        let directory = ::std::env::temp_dir().join(name);
+        fs::create_dir_all(directory.join("img")).unwrap();
+        fs::write(directory.join("img/dot.png"), b"Man").unwrap();
+        let doc = ::DocumentParser::new()
+            .parse("#+TITLE: Notes & more\n[[./img/dot.png]] [[./img/gone.png]] \\(x^2\\)\n")
+            .unwrap();
+        let page = HtmlExporter::new().self_contained(true).resource_directory(&directory)
+            .export(&doc);
+        assert!(page.starts_with("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">
+<title>Notes &amp; more</title>\n<style>\n"));
+        assert!(page.contains("window.MathJax"));
+        assert!(page.contains("<img src=\"data:image/png;base64,TWFu\" alt=\"./img/dot.png\">"));
+        assert!(page.contains("<img src=\"./img/gone.png\" alt=\"./img/gone.png\">"));
+        assert!(page.ends_with("</body>\n</html>\n"));
+        assert!(!to_html(&doc).contains("<html>"));
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_export_template() {
+        let doc = ::DocumentParser::new().parse("#+TITLE: Trip <notes>
+#+FILETAGS: :travel:2024:
+* Plan
+** Bags
+* COMMENT Hidden
+").unwrap();
+        let template = Template::new("<h1>{{ title }}</h1> {{filetags}} {{unknown}}
+{{header}}{{toc}}{{content}}{{footer}}")
+            .header("<nav>Home</nav>\n")
+            .footer("<footer>Bye</footer>\n");
+        let page = HtmlExporter::new().template(template).export(&doc);
+        assert_eq!(page, "<h1>Trip &lt;notes&gt;</h1> travel 2024 {{unknown}}
+<nav>Home</nav>
+<ul>
+<li><a href=\"#sec-1\">Plan</a>
+<ul>
+<li><a href=\"#sec-1-1\">Bags</a></li>
+</ul>
+</li>
+</ul>
+<div class=\"outline-1\">
+<h1 id=\"sec-1\">Plan</h1>
+<div class=\"outline-2\">
+<h2 id=\"sec-1-1\">Bags</h2>
+</div>
+</div>
+<footer>Bye</footer>
+");
+    }
+
+    #[test]
+    fn test_export_classes() {
+        let parser = DocumentParser::new().todo_keywords(vec!["TODO", "DONE"]);
+        let doc = parser.parse("* DONE Ship :work:urgent:
+#+BEGIN_SRC
+plain
+#+END_SRC
+").unwrap();
+        assert_eq!(to_html(&doc), "<div class=\"outline-1\">
+<h1 id=\"sec-1\"><span class=\"org-done\">DONE</span> Ship \
+<span class=\"org-tag\">work</span> <span class=\"org-tag\">urgent</span></h1>
+<pre class=\"org-src\"><code>plain</code></pre>
+</div>
+");
+        let auto = stylesheet(Theme::Auto);
+        assert!(auto.starts_with(LAYOUT));
+        assert!(auto.contains(".org-done { color: #1a7f37; }"));
+        assert!(auto.contains("@media (prefers-color-scheme: dark) {\n"));
+        assert!(!stylesheet(Theme::Dark).contains("#1a7f37"));
+    }
+
+    #[test]
+    fn test_export_warnings() {
+        let text = "* Plan
+See [[*Nowhere]] and [[*Plan]].
+#+ATTR_HTML: :class wide
+Wide text.
+[[./missing.png]]
+";
+        let doc = DocumentParser::new().parse(text).unwrap();
+        let (html, warnings) = HtmlExporter::new().self_contained(true).export_with_warnings(&doc);
+        assert!(html.contains("<a href=\"#Nowhere\">"));
+        let found: Vec<_> = warnings.iter()
+            .map(|warning| (warning.kind(), &text[warning.span().start..warning.span().end]))
+            .collect();
+        assert_eq!(found, [
+            (ExportWarningKind::BrokenLink, "See [[*Nowhere]] and [[*Plan]]."),
+            (ExportWarningKind::UnsupportedAttribute, "Wide text.\n[[./missing.png]]"),
+            (ExportWarningKind::MissingImage, "Wide text.\n[[./missing.png]]"),
+        ]);
+        assert_eq!(warnings[0].to_string(), "nothing in the document matches the link *Nowhere \
+                                             at byte 7");
+        assert!(HtmlExporter::new().export_with_warnings(&doc).1.len() == 2);
+    }
+}
@@ -0,0 +1,479 @@
+use std::collections::HashMap;
+use std::ptr;
+
+use super::{ExportWarning, ExportWarningKind, is_broken, is_image, slug};
+use object::plain_text;
+use {BlockKind, Checkbox, Content, Document, Element, GreaterElement, Headline, Link, LinkIndex,
+     LinkType, ListItem, ListKind, Object, Script, Scripts, Span, Table, TimestampStyle};
+
+/// Render `document` as CommonMark, using the GitHub extensions for tables,
+/// strike-through and footnotes.
+///
+/// Headlines deeper than six levels become `######` headings. Links to
+/// headlines point at the anchors GitHub derives from heading text, or at an
+/// explicit anchor for headlines with a `CUSTOM_ID`. Links to `.org` files
+/// are rewritten to the `.md` files they export to. Drawers, keywords,
+/// comment blocks, commented subtrees and the footnote section are not
+/// exported, and archived subtrees only export their headline.
+pub fn to_markdown(document: &Document) -> String {
+    to_markdown_with(document, TimestampStyle::org())
+}
+
+/// Render `document` like `to_markdown`, with timestamps written in
+/// `timestamps`.
+pub fn to_markdown_with(document: &Document, timestamps: TimestampStyle) -> String {
+    to_markdown_with_warnings(document, timestamps).0
+}
+
+/// Render `document` like `to_markdown_with`, along with the warnings about
+/// what couldn't be exported as written, like links to headlines that
+/// aren't there, in the order they were found.
+pub fn to_markdown_with_warnings(document: &Document, timestamps: TimestampStyle)
+                                 -> (String, Vec<ExportWarning>) {
+    let _span = trace_span!("export", format = "markdown");
+    let mut writer = Writer {
+        timestamps,
+        scripts: document.scripts(),
+        anchors: HashMap::new(),
+        footnote_section: document.footnote_section(),
+        links: document.link_index(),
+        footnotes: Vec::new(),
+        span: Span::default(),
+        warnings: Vec::new(),
+    };
+    for headline in document.all_headlines() {
+        let anchor = match headline.property("CUSTOM_ID") {
+            Some(id) => id.to_string(),
+            None => slug(&plain_text(&headline.title_objects())),
+        };
+        writer.anchors.insert(headline, anchor);
+    }
+    let mut blocks = Vec::new();
+    if let Some(section) = document.first_section() {
+        blocks.extend(writer.contents(section.contents()));
+    }
+    for headline in document.headlines() {
+        writer.headline(headline, &mut blocks);
+    }
+    // Definitions may reference further footnotes, which get appended.
+    let definitions = document.footnotes();
+    let mut i = 0;
+    while i < writer.footnotes.len() {
+        let (label, inline) = writer.footnotes[i].clone();
+        let text = match (inline, definitions.get(&label[..])) {
+            (Some(text), _) => writer.objects(&::parse_objects(&text)),
+            (None, Some(definition)) => writer.contents(definition.contents()).join("\n\n"),
+            (None, None) => String::new(),
+        };
+        blocks.push(format!("[^{}]: {}", label, indent(&text, "    ")));
+        i += 1;
+    }
+    let mut out = blocks.join("\n\n");
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    trace_event!(bytes = out.len(), footnotes = writer.footnotes.len(), "exported");
+    (out, writer.warnings)
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if "\\`*_[]<>|".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Indent every line of `text` but the first with `prefix`.
+fn indent(text: &str, prefix: &str) -> String {
+    text.lines().enumerate()
+        .map(|(i, line)| {
+            if i == 0 || line.is_empty() { line.to_string() } else { format!("{}{}", prefix, line) }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// A fenced code block, with a fence longer than any backtick run inside.
+fn fenced(text: &str, language: &str) -> String {
+    let mut longest = 0;
+    let mut run = 0;
+    for c in text.chars() {
+        run = if c == '`' { run + 1 } else { 0 };
+        longest = longest.max(run);
+    }
+    let fence = "`".repeat(longest.max(2) + 1);
+    format!("{}{}\n{}\n{}", fence, language, text, fence)
+}
+
+struct Writer<'a> {
+    timestamps: TimestampStyle,
+    scripts: Scripts,
+    /// The anchor of each headline, by its address.
+    anchors: HashMap<*const Headline, String>,
+    footnote_section: Option<&'a Headline>,
+    links: LinkIndex<'a>,
+    /// The labels of referenced footnotes in order of first reference, with
+    /// the text of inline definitions.
+    footnotes: Vec<(String, Option<String>)>,
+    /// The span of the element or headline being written, for warnings.
+    span: Span,
+    warnings: Vec<ExportWarning>,
+}
+
+impl<'a> Writer<'a> {
+    fn anchor(&self, headline: &Headline) -> &str {
+        &self.anchors[&(headline as *const Headline)]
+    }
+
+    fn headline(&mut self, headline: &'a Headline, blocks: &mut Vec<String>) {
+        if headline.is_commented()
+            || self.footnote_section.is_some_and(|section| ptr::eq(section, headline)) {
+            return;
+        }
+        self.span = headline.span();
+        let mut line = "#".repeat(headline.level().min(6) as usize);
+        line.push(' ');
+        if let Some(keyword) = headline.keyword() {
+            line.push_str(keyword);
+            line.push(' ');
+        }
+        line.push_str(&self.objects(&headline.title_objects()));
+        if let Some(id) = headline.property("CUSTOM_ID") {
+            line.push_str(&format!(" <a id=\"{}\"></a>", id));
+        }
+        blocks.push(line);
+        if headline.is_archived() {
+            return;
+        }
+        if let Some(section) = headline.section() {
+            blocks.extend(self.contents(section.contents()));
+        }
+        for child in headline.children() {
+            self.headline(child, blocks);
+        }
+    }
+
+    /// Render each element as a block of lines.
+    fn contents(&mut self, contents: &[Content]) -> Vec<String> {
+        let mut blocks = Vec::new();
+        for content in contents {
+            let outer = self.span;
+            self.span = content.span().unwrap_or(outer);
+            match *content {
+                Content::Greater(ref greater) => blocks.extend(self.greater(greater)),
+                Content::Element(ref element) => blocks.extend(self.element(element)),
+            }
+            self.span = outer;
+        }
+        blocks
+    }
+
+    fn greater(&mut self, greater: &GreaterElement) -> Vec<String> {
+        match *greater {
+            GreaterElement::Block(ref block) if block.diagram_language().is_some() => {
+                vec![fenced(block.text(), block.diagram_language().unwrap())]
+            }
+            GreaterElement::Block(ref block) => {
+                let inner = self.contents(block.contents());
+                if *block.kind() == BlockKind::Quote {
+                    let quoted = inner.join("\n\n").lines()
+                        .map(|line| format!("> {}", line).trim_end().to_string())
+                        .collect::<Vec<_>>()
+                        .join("\n");
+                    vec![quoted]
+                } else {
+                    inner
+                }
+            }
+            GreaterElement::DynamicBlock(ref block) => self.contents(block.contents()),
+            GreaterElement::Inlinetask(ref task) => {
+                let headline = task.headline();
+                let keyword = headline.keyword().map_or(String::new(), |kwd| format!("{} ", kwd));
+                let title = format!("**{}{}**", keyword, self.objects(&headline.title_objects()));
+                let mut blocks = vec![title];
+                blocks.extend(self.contents(task.contents()));
+                blocks
+            }
+            GreaterElement::PlainList(ref list) => {
+                let mut number = 0;
+                let items: Vec<_> = list.items().iter()
+                    .map(|item| {
+                        let marker = match list.kind() {
+                            ListKind::Ordered => {
+                                number = item.counter().unwrap_or(number + 1);
+                                format!("{}. ", number)
+                            }
+                            _ => "- ".into(),
+                        };
+                        let body = self.item(item, list.kind() == ListKind::Descriptive);
+                        format!("{}{}", marker, indent(&body, &" ".repeat(marker.len())))
+                    })
+                    .collect();
+                vec![items.join("\n")]
+            }
+            GreaterElement::Table(ref table) => vec![self.table(table)],
+            GreaterElement::Drawer(_) | GreaterElement::PropertyDrawer(_)
+                | GreaterElement::Footnote(_) => Vec::new(),
+        }
+    }
+
+    fn element(&mut self, element: &Element) -> Option<String> {
+        match *element {
+            Element::Block(ref block) => match *block.kind() {
+                BlockKind::Src => Some(fenced(block.text(), block.language().unwrap_or(""))),
+                BlockKind::Example => Some(fenced(block.text(), "")),
+                BlockKind::Export => match block.language() {
+                    Some(backend) if backend.eq_ignore_ascii_case("md")
+                        || backend.eq_ignore_ascii_case("markdown") => Some(block.text().into()),
+                    _ => None,
+                },
+                BlockKind::Verse => {
+                    let lines: Vec<_> = block.verse_lines().iter()
+                        .map(|(indent, objects)| {
+                            format!("{}{}", "&nbsp;".repeat(*indent), self.objects(objects))
+                        })
+                        .collect();
+                    Some(lines.join("\\\n"))
+                }
+                _ => None,
+            },
+            Element::Paragraph(ref paragraph) => {
+                let lines: Vec<_> = self.objects(&paragraph.objects()).lines()
+                    .map(|line| line.trim_start().to_string())
+                    .collect();
+                Some(lines.join("\n"))
+            }
+            Element::Raw(ref raw) => Some(escape(raw.text().trim())),
+            Element::FixedWidth(ref fixed) => Some(fenced(fixed.text(), "")),
+            Element::HorizontalRule(_) => Some("---".into()),
+            Element::LatexEnvironment(ref latex) => Some(format!("$$\n{}\n$$", latex.text())),
+            Element::BabelCall(_) | Element::Clock(_) | Element::Comment(_) | Element::Keyword(_)
+            | Element::Planning(_) => None,
+        }
+    }
+
+    fn item(&mut self, item: &ListItem, descriptive: bool) -> String {
+        let mut body = String::new();
+        match item.checkbox() {
+            Some(Checkbox::Checked) => body.push_str("[x] "),
+            Some(Checkbox::Partial) => body.push_str("[-] "),
+            Some(Checkbox::Unchecked) => body.push_str("[ ] "),
+            None => {}
+        }
+        if descriptive {
+            let tag = self.objects(&::parse_objects(item.tag().unwrap_or("")));
+            body.push_str(&format!("**{}**: ", tag));
+        }
+        body.push_str(&self.contents(item.contents()).join("\n"));
+        body
+    }
+
+    fn table(&mut self, table: &Table) -> String {
+        let rows: Vec<Vec<String>> = table.data_rows()
+            .map(|cells| cells.iter().map(|cell| self.objects(&::parse_objects(cell))).collect())
+            .collect();
+        let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+        let line = |cells: &[String]| {
+            let mut line = String::from("|");
+            for i in 0..columns {
+                line.push_str(&format!(" {} |", cells.get(i).map_or("", |s| &s[..])));
+            }
+            line
+        };
+        let mut lines = Vec::new();
+        // GitHub tables always have a header, so the first row becomes one.
+        if let Some(header) = rows.first() {
+            lines.push(line(header));
+            lines.push(line(&vec!["---".to_string(); columns]));
+        }
+        lines.extend(rows.iter().skip(1).map(|row| line(row)));
+        lines.join("\n")
+    }
+
+    fn objects(&mut self, objects: &[Object]) -> String {
+        let mut out = String::new();
+        for object in objects {
+            match *object {
+                Object::Text(ref text) => out.push_str(&escape(text)),
+                Object::Raw(ref raw) => out.push_str(&escape(raw.text())),
+                Object::Bold(ref inner) => out.push_str(&format!("**{}**", self.objects(inner))),
+                Object::Italic(ref inner) => out.push_str(&format!("*{}*", self.objects(inner))),
+                Object::Underline(ref inner) => {
+                    out.push_str(&format!("<u>{}</u>", self.objects(inner)));
+                }
+                Object::StrikeThrough(ref inner) => {
+                    out.push_str(&format!("~~{}~~", self.objects(inner)));
+                }
+                Object::Verbatim(ref text) | Object::Code(ref text) => {
+                    let fence = if text.contains('`') { "``" } else { "`" };
+                    out.push_str(&format!("{0}{1}{0}", fence, text));
+                }
+                Object::Link(ref link) => out.push_str(&self.link(link)),
+                Object::Target(ref name) => out.push_str(&format!("<a id=\"{}\"></a>", name)),
+                Object::RadioTarget(ref text) => {
+                    out.push_str(&format!("<a id=\"{}\"></a>{}", text, escape(text)));
+                }
+                Object::Entity(ref entity) => out.push_str(entity.utf8()),
+                Object::Subscript(ref script) => out.push_str(&self.script("_", "sub", script)),
+                Object::Superscript(ref script) => out.push_str(&self.script("^", "sup", script)),
+                Object::LatexFragment(ref latex) => out.push_str(latex),
+                Object::FootnoteReference(ref reference) => {
+                    let label = match reference.label() {
+                        Some(label) => label.to_string(),
+                        None => format!("anon.{}", self.footnotes.len() + 1),
+                    };
+                    if !self.footnotes.iter().any(|(l, _)| *l == label) {
+                        let definition = reference.definition().map(String::from);
+                        self.footnotes.push((label.clone(), definition));
+                    }
+                    out.push_str(&format!("[^{}]", label));
+                }
+                Object::StatisticsCookie(cookie) => out.push_str(&escape(&cookie.to_string())),
+                Object::Timestamp(ref timestamp) => {
+                    out.push_str(&escape(&timestamp.format(self.timestamps)))
+                }
+            }
+        }
+        out
+    }
+
+    /// A subscript or superscript as HTML, or as written if the document's
+    /// options don't allow it.
+    fn script(&mut self, marker: &str, tag: &str, script: &Script) -> String {
+        let contents = self.objects(script.contents());
+        if self.scripts.allows(script) {
+            format!("<{0}>{1}</{0}>", tag, contents)
+        } else if script.is_braced() {
+            format!("{}{{{}}}", escape(marker), contents)
+        } else {
+            format!("{}{}", escape(marker), contents)
+        }
+    }
+
+    fn link_target(&self, link: &Link) -> String {
+        let path = link.path();
+        match link.link_type() {
+            LinkType::File => match path.strip_suffix(".org") {
+                Some(stem) => format!("{}.md", stem),
+                None => path.into(),
+            },
+            LinkType::CustomId => format!("#{}", path),
+            LinkType::Coderef => format!("#coderef-{}", path),
+            LinkType::Id | LinkType::Headline | LinkType::Fuzzy | LinkType::Radio => {
+                match self.links.headline(link) {
+                    Some(headline) => format!("#{}", self.anchor(headline)),
+                    None => format!("#{}", path),
+                }
+            }
+            LinkType::Http | LinkType::Other(_) => link.destination().into(),
+        }
+    }
+
+    fn link(&mut self, link: &Link) -> String {
+        if is_broken(&self.links, link) {
+            let message = format!("nothing in the document matches the link {}",
+                                  link.destination());
+            self.warnings.push(ExportWarning::new(ExportWarningKind::BrokenLink, message,
+                                                  self.span));
+        }
+        let target = self.link_target(link).replace(' ', "%20");
+        match link.description() {
+            None if link.link_type() == LinkType::File && is_image(link.path()) => {
+                format!("![{}]({})", escape(link.path()), target)
+            }
+            None if link.link_type() == LinkType::Http => format!("<{}>", target),
+            Some(description) => format!("[{}]({})", self.objects(description), target),
+            None => format!("[{}]({})", escape(link.path()), target),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use DocumentParser;
+
+    #[test]
+    fn test_export_markdown() {
+        let doc = DocumentParser::new().todo_keywords(vec!["TODO"]).parse("#+TITLE: x
+Intro with *bold*, /it/ and =a_b= [[https://x.org][site]].
+* TODO First Steps
+- [X] one
+- two
+  3. [@3] three
+  4. four
+| a | b |
+|---+---|
+| c |
+** Deeper[fn:1]
+:PROPERTIES:
+:CUSTOM_ID: deep
+:END:
+#+BEGIN_SRC rust
+fn main() {}
+#+END_SRC
+#+BEGIN_QUOTE
+Quoted
+#+END_QUOTE
+See [[*First Steps]], [[#deep]], [[file:b.org]], [[./i.png]] and https://y.org.
+[fn:1] The note.").unwrap();
+        assert_eq!(to_markdown(&doc), "Intro with **bold**, *it* and `a_b` [site](https://x.org).
+
+# TODO First Steps
+
+- [x] one
+- two
+  3. three
+  4. four
+
+| a | b |
+| --- | --- |
+| c |  |
+
+## Deeper[^1] <a id=\"deep\"></a>
+
+```rust
+fn main() {}
+```
+
+> Quoted
+
+See [First Steps](#first-steps), [deep](#deep), [b.org](b.md), ![./i.png](./i.png) and <https://y.org>.
+
+[^1]: The note.
+");
+    }
+
+    #[test]
+    fn test_export_timestamps() {
+        let doc = DocumentParser::new().parse("Due <2024-05-01 Wed 14:30>.").unwrap();
+        assert_eq!(to_markdown(&doc), "Due \\<2024-05-01 Wed 14:30\\>.\n");
+        assert_eq!(to_markdown_with(&doc, TimestampStyle::iso()), "Due 2024-05-01T14:30.\n");
+    }
+
+    #[test]
+    fn test_export_verse() {
+        let doc = DocumentParser::new().parse("#+BEGIN_VERSE
+ Great *clouds*
+   overhead
+#+END_VERSE").unwrap();
+        assert_eq!(to_markdown(&doc), "Great **clouds**\\\n&nbsp;&nbsp;overhead\n");
+    }
+
+    #[test]
+    fn test_export_warnings() {
+        let text = "* Plan [[#gone]]\n- See [[id:1234]].\n";
+        let doc = DocumentParser::new().parse(text).unwrap();
+        let (markdown, warnings) = to_markdown_with_warnings(&doc, TimestampStyle::org());
+        assert_eq!(markdown, to_markdown(&doc));
+        let found: Vec<_> = warnings.iter()
+            .map(|warning| (warning.kind(), &text[warning.span().start..warning.span().end]))
+            .collect();
+        assert_eq!(found, [(ExportWarningKind::BrokenLink, text),
+                           (ExportWarningKind::BrokenLink, "See [[id:1234]].")]);
+    }
+}
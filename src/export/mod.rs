@@ -0,0 +1,83 @@
+//! Rendering documents in other formats.
+
+use std::fmt;
+
+use Span;
+
+mod dedup;
+pub mod html;
+pub mod markdown;
+pub mod publish;
+
+pub use self::dedup::{Rename, deduplicate_ids};
+
+/// What an `ExportWarning` is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportWarningKind {
+    /// An internal link to nothing in the document, exported pointing at
+    /// an anchor that isn't there.
+    BrokenLink,
+    /// Attributes of an element the backend doesn't export them for.
+    UnsupportedAttribute,
+    /// An image that couldn't be read to embed it, linked to instead.
+    MissingImage,
+}
+
+/// Something an exporter couldn't export as written and worked around, so
+/// that publishing can stop on it instead of shipping the result.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportWarning {
+    kind: ExportWarningKind,
+    message: String,
+    span: Span,
+}
+
+impl ExportWarning {
+    fn new<S: Into<String>>(kind: ExportWarningKind, message: S, span: Span) -> ExportWarning {
+        ExportWarning { kind, message: message.into(), span }
+    }
+
+    pub fn kind(&self) -> ExportWarningKind {
+        self.kind
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// The element or headline the warning is about.
+    pub fn span(&self) -> Span {
+        self.span
+    }
+}
+
+impl fmt::Display for ExportWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at byte {}", self.message, self.span.start)
+    }
+}
+
+/// Whether `link` should point at something in the document, and nothing
+/// there matches it.
+fn is_broken(links: &::LinkIndex, link: &::Link) -> bool {
+    match link.link_type() {
+        ::LinkType::Id | ::LinkType::CustomId | ::LinkType::Headline | ::LinkType::Fuzzy
+        | ::LinkType::Radio => links.target(link).is_none(),
+        _ => false,
+    }
+}
+
+/// Whether a link without a description to `path` should show the image.
+fn is_image(path: &str) -> bool {
+    let path = path.to_ascii_lowercase();
+    [".png", ".jpg", ".jpeg", ".gif", ".svg", ".webp"].iter().any(|ext| path.ends_with(ext))
+}
+
+/// The anchor GitHub gives a heading with the text `title`, also the name of
+/// a published subtree's file.
+fn slug(title: &str) -> String {
+    title.trim().to_lowercase().chars()
+        .filter(|&c| c.is_alphanumeric() || c == ' ' || c == '-' || c == '_')
+        .map(|c| if c == ' ' { '-' } else { c })
+        .collect()
+}
@@ -0,0 +1,327 @@
+//! Exporting a document whose subtrees go to different backends. A subtree
+//! with an `EXPORT_BACKEND` property, like `:EXPORT_BACKEND: beamer`, is
+//! exported on its own with that backend, to its `EXPORT_FILE_NAME` or a
+//! file named after its title, and left out of the rest of the document,
+//! which goes to the default backend.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+
+use super::html::HtmlExporter;
+use super::markdown;
+use super::slug;
+use {Document, Headline};
+
+/// Something that can render a document, like `HtmlExporter` or a function
+/// filling in a template.
+pub trait Backend {
+    fn export(&self, document: &Document) -> String;
+
+    /// The settings the output depends on besides the document, for the
+    /// `CacheKey`s of `Publisher::publish_cached`.
+    fn options(&self) -> String {
+        String::new()
+    }
+}
+
+impl Backend for HtmlExporter {
+    fn export(&self, document: &Document) -> String {
+        HtmlExporter::export(self, document)
+    }
+
+    fn options(&self) -> String {
+        format!("{:?}", self)
+    }
+}
+
+impl<F: Fn(&Document) -> String> Backend for F {
+    fn export(&self, document: &Document) -> String {
+        self(document)
+    }
+}
+
+/// A file made by `Publisher::publish`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Output {
+    pub path: String,
+    /// The name of the backend that made it.
+    pub backend: String,
+    pub contents: String,
+}
+
+/// What an exported file is stored under in an `ExportCache`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    /// The `Document::content_hash` of the part of the document exported.
+    pub hash: u64,
+    /// The name of the backend, in lower case.
+    pub backend: String,
+    /// The backend's `Backend::options`.
+    pub options: String,
+}
+
+/// Storage for the files made by `Publisher::publish_cached`, kept between
+/// runs by the caller, in memory with a `HashMap` or elsewhere.
+pub trait ExportCache {
+    fn get(&self, key: &CacheKey) -> Option<String>;
+    fn insert(&mut self, key: CacheKey, contents: String);
+}
+
+impl ExportCache for HashMap<CacheKey, String> {
+    fn get(&self, key: &CacheKey) -> Option<String> {
+        HashMap::get(self, key).cloned()
+    }
+
+    fn insert(&mut self, key: CacheKey, contents: String) {
+        HashMap::insert(self, key, contents);
+    }
+}
+
+/// Why a document couldn't be published.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublishError {
+    backend: String,
+    headline: String,
+}
+
+impl PublishError {
+    /// The backend named in the `EXPORT_BACKEND` property.
+    pub fn backend(&self) -> &str {
+        &self.backend
+    }
+
+    /// The title of the headline with the property.
+    pub fn headline(&self) -> &str {
+        &self.headline
+    }
+}
+
+impl fmt::Display for PublishError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "no backend named {} for headline {}", self.backend, self.headline)
+    }
+}
+
+impl Error for PublishError {}
+
+/// Backends by name, each with the extension of the files it makes.
+pub struct Publisher {
+    backends: Vec<(String, String, Box<dyn Backend>)>,
+    default: String,
+}
+
+impl Default for Publisher {
+    fn default() -> Self {
+        Publisher::new()
+    }
+}
+
+impl Publisher {
+    /// The `html` backend, with the default `HtmlExporter`, and the `md` or
+    /// `markdown` backend, with `to_markdown`. The default backend is `html`.
+    pub fn new() -> Self {
+        Publisher { backends: Vec::new(), default: "html".into() }
+            .backend("html", "html", HtmlExporter::new())
+            .backend("md", "md", markdown::to_markdown)
+            .backend("markdown", "md", markdown::to_markdown)
+    }
+
+    /// Add the backend `name`, matched ignoring case, making files ending in
+    /// `.extension`, replacing any backend of the same name.
+    pub fn backend<B: Backend + 'static>(mut self, name: &str, extension: &str, backend: B)
+                                         -> Self {
+        self.backends.retain(|(existing, _, _)| !existing.eq_ignore_ascii_case(name));
+        self.backends.push((name.into(), extension.into(), Box::new(backend)));
+        self
+    }
+
+    /// The backend for the parts of documents without an `EXPORT_BACKEND`.
+    pub fn default_backend(mut self, name: &str) -> Self {
+        self.default = name.into();
+        self
+    }
+
+    /// Export `document`, read from `file_name`: each subtree with an
+    /// `EXPORT_BACKEND` to its own file next to it, and the rest, if there is
+    /// any, to `file_name` with the default backend's extension. Subtrees
+    /// inside a dispatched one can name another backend in turn.
+    pub fn publish(&self, document: &Document, file_name: &str)
+                   -> Result<Vec<Output>, PublishError> {
+        self.publish_with(document, file_name, None)
+    }
+
+    /// Like `publish`, but taking each file from `cache` if the part of the
+    /// document it was exported from is unchanged, and storing the ones it
+    /// exports, so publishing again only exports the changed subtrees.
+    pub fn publish_cached(&self, document: &Document, file_name: &str,
+                          cache: &mut dyn ExportCache) -> Result<Vec<Output>, PublishError> {
+        self.publish_with(document, file_name, Some(cache))
+    }
+
+    fn publish_with(&self, document: &Document, file_name: &str,
+                    mut cache: Option<&mut dyn ExportCache>)
+                    -> Result<Vec<Output>, PublishError> {
+        let mut outputs = Vec::new();
+        let mut rest = document.clone();
+        rest.headlines = without_dispatched(&document.headlines);
+        if rest.first_section.is_some() || !rest.headlines.is_empty() {
+            let untitled = PublishError { backend: self.default.clone(), headline: String::new() };
+            let (extension, backend) = self.find(&self.default).ok_or(untitled)?;
+            let path = Path::new(file_name).with_extension(extension);
+            outputs.push(Output {
+                path: path.to_string_lossy().into(),
+                backend: self.default.clone(),
+                contents: render(&self.default, backend, &rest, &mut cache),
+            });
+        }
+
+        let directory = Path::new(file_name).parent().unwrap_or_else(|| Path::new(""));
+        for headline in document.all_headlines() {
+            let name = match headline.property("EXPORT_BACKEND") {
+                Some(name) => name.trim(),
+                None => continue,
+            };
+            let (extension, backend) = self.find(name).ok_or_else(|| PublishError {
+                backend: name.into(),
+                headline: headline.title().into(),
+            })?;
+            let mut subtree = document.clone();
+            subtree.first_section = None;
+            let mut headline = headline.clone();
+            headline.headlines = without_dispatched(&headline.headlines);
+            let file = match headline.property("EXPORT_FILE_NAME") {
+                Some(file) => Path::new(file.trim()).to_path_buf(),
+                None => Path::new(&slug(headline.title())).to_path_buf(),
+            };
+            let file = match file.extension() {
+                Some(_) => file,
+                None => file.with_extension(extension),
+            };
+            subtree.headlines = vec![headline];
+            outputs.push(Output {
+                path: directory.join(file).to_string_lossy().into(),
+                backend: name.into(),
+                contents: render(name, backend, &subtree, &mut cache),
+            });
+        }
+        Ok(outputs)
+    }
+
+    fn find(&self, name: &str) -> Option<(&str, &dyn Backend)> {
+        self.backends.iter()
+            .find(|(existing, _, _)| existing.eq_ignore_ascii_case(name))
+            .map(|(_, extension, backend)| (&extension[..], &**backend))
+    }
+}
+
+/// `document` exported with `backend`, named `name`, from `cache` if it is
+/// there.
+fn render(name: &str, backend: &dyn Backend, document: &Document,
+          cache: &mut Option<&mut dyn ExportCache>) -> String {
+    let cache = match *cache {
+        Some(ref mut cache) => cache,
+        None => return backend.export(document),
+    };
+    let key = CacheKey {
+        hash: document.content_hash(),
+        backend: name.to_lowercase(),
+        options: backend.options(),
+    };
+    if let Some(contents) = cache.get(&key) {
+        return contents;
+    }
+    let contents = backend.export(document);
+    cache.insert(key, contents.clone());
+    contents
+}
+
+/// Copies of `headlines` and their descendants, leaving out the subtrees
+/// with an `EXPORT_BACKEND`.
+fn without_dispatched(headlines: &[Headline]) -> Vec<Headline> {
+    headlines.iter()
+        .filter(|headline| headline.property("EXPORT_BACKEND").is_none())
+        .map(|headline| {
+            let mut headline = headline.clone();
+            headline.headlines = without_dispatched(&headline.headlines);
+            headline
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use DocumentParser;
+
+    #[test]
+    fn test_publish() {
+        let doc = DocumentParser::new().parse("Intro.
+* Notes
+Kept in the page.
+* Talk
+:PROPERTIES:
+:EXPORT_BACKEND: beamer
+:EXPORT_FILE_NAME: slides/talk
+:END:
+** Slide
+*** Readme
+:PROPERTIES:
+:EXPORT_BACKEND: md
+:END:
+Read me.
+").unwrap();
+        let beamer = |document: &Document| -> String {
+            let titles: Vec<_> = document.all_headlines().map(|h| h.title()).collect();
+            format!("frames: {}", titles.join(", "))
+        };
+        let publisher = Publisher::new().backend("Beamer", "tex", beamer);
+        let outputs = publisher.publish(&doc, "site/index.org").unwrap();
+        let files: Vec<_> = outputs.iter()
+            .map(|output| (&output.path[..], &output.backend[..]))
+            .collect();
+        assert_eq!(files, [("site/index.html", "html"), ("site/slides/talk.tex", "beamer"),
+                           ("site/readme.md", "md")]);
+        assert!(outputs[0].contents.contains("Kept in the page."));
+        assert!(!outputs[0].contents.contains("Talk"));
+        assert_eq!(outputs[1].contents, "frames: Talk, Slide");
+        assert_eq!(outputs[2].contents, "### Readme\n\nRead me.\n");
+
+        let error = Publisher::new().publish(&doc, "index.org").unwrap_err();
+        assert_eq!((error.backend(), error.headline()), ("beamer", "Talk"));
+    }
+
+    #[test]
+    fn test_publish_cached() {
+        let text = "Intro.
+* Talk
+:PROPERTIES:
+:EXPORT_BACKEND: beamer
+:END:
+** Slide
+* Notes
+Kept in the page.
+";
+        let exports = ::std::rc::Rc::new(::std::cell::Cell::new(0));
+        let counter = exports.clone();
+        let beamer = move |document: &Document| -> String {
+            counter.set(counter.get() + 1);
+            format!("frames: {}", document.headlines()[0].title())
+        };
+        let publisher = Publisher::new().backend("beamer", "tex", beamer);
+        let mut cache = HashMap::new();
+        let parser = DocumentParser::new();
+        let first = publisher.publish_cached(&parser.parse(text).unwrap(), "index.org",
+                                             &mut cache).unwrap();
+        assert_eq!((exports.get(), cache.len()), (1, 2));
+        let changed = parser.parse(&text.replace("Kept", "Still kept")).unwrap();
+        let second = publisher.publish_cached(&changed, "index.org", &mut cache).unwrap();
+        assert_eq!((exports.get(), cache.len()), (1, 3));
+        assert_eq!(second[1], first[1]);
+        assert!(second[0].contents.contains("Still kept"));
+        let retitled = parser.parse(&text.replace("Talk", "Keynote")).unwrap();
+        publisher.publish_cached(&retitled, "index.org", &mut cache).unwrap();
+        assert_eq!(exports.get(), 2);
+    }
+}
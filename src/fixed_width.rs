@@ -0,0 +1,87 @@
+use keyword::{Affiliate, Affiliated};
+use span::{self, Shift, Span};
+
+/// Consecutive lines each starting with a colon followed by a space or
+/// nothing, shown verbatim:
+///
+/// ```ignore
+/// : $ cargo build
+/// :    Compiling org v0.1.0
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct FixedWidth {
+    text: String,
+    affiliated: Affiliated,
+    span: Span,
+}
+
+impl FixedWidth {
+    /// The lines without their `: ` markers.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn affiliated(&self) -> &Affiliated {
+        &self.affiliated
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+}
+
+impl Affiliate for FixedWidth {
+    fn affiliated_mut(&mut self) -> &mut Affiliated {
+        &mut self.affiliated
+    }
+}
+
+impl Shift for FixedWidth {
+    fn shift(&mut self, delta: isize) {
+        self.affiliated.shift(delta);
+        self.span.shift(delta);
+    }
+}
+
+/// The text of `line` after its colon, if it is a fixed-width line.
+fn fixed_width_line(line: &str) -> Option<&str> {
+    let rest = line.trim_start().strip_prefix(':')?;
+    if rest.is_empty() {
+        Some(rest)
+    } else {
+        rest.strip_prefix(' ')
+    }
+}
+
+/// Try to parse a fixed-width area starting at `lines[start]`. Returns it and
+/// the index of the first line after it.
+pub fn parse_fixed_width(lines: &[&str], offsets: &[usize], start: usize)
+                         -> Option<(FixedWidth, usize)> {
+    fixed_width_line(lines[start])?;
+    let end = (start..lines.len())
+        .find(|&i| fixed_width_line(lines[i]).is_none())
+        .unwrap_or(lines.len());
+    let text: Vec<_> = lines[start..end].iter().filter_map(|line| fixed_width_line(line)).collect();
+    Some((FixedWidth {
+        text: text.join("\n"),
+        affiliated: Affiliated::default(),
+        span: span::lines_span(lines, offsets, start, end),
+    }, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_fixed_width() {
+        let lines = [": $ cargo build", ":    Compiling", ":END:", ":"];
+        let offsets = span::line_offsets(&lines.join("\n"), 0);
+        let (fixed, next) = parse_fixed_width(&lines, &offsets, 0).unwrap();
+        assert_eq!(next, 2);
+        assert_eq!(fixed.text(), "$ cargo build\n   Compiling");
+        assert!(parse_fixed_width(&lines, &offsets, 2).is_none());
+        assert_eq!(parse_fixed_width(&lines, &offsets, 3).unwrap().0.text(), "");
+    }
+}
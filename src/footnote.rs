@@ -0,0 +1,213 @@
+use span::{self, Shift, Span};
+use {Content, DocumentParser};
+
+/// A footnote definition, starting with its label in the first column:
+///
+/// ```ignore
+/// [fn:label] The footnote text, which can go
+/// on for several lines.
+/// ```
+///
+/// It ends at the next footnote definition, the next headline, or two
+/// consecutive blank lines.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct FootnoteDefinition {
+    label: String,
+    text: String,
+    contents: Vec<Content>,
+    span: Span,
+}
+
+impl FootnoteDefinition {
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// The raw text of the definition, after its label.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn contents(&self) -> &[Content] {
+        &self.contents
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+}
+
+impl Shift for FootnoteDefinition {
+    fn shift(&mut self, delta: isize) {
+        self.span.shift(delta);
+        self.contents.shift(delta);
+    }
+}
+
+/// A footnote reference in running text: `[fn:label]`, an inline definition
+/// `[fn:label:text]`, or an anonymous one `[fn::text]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct FootnoteReference {
+    label: Option<String>,
+    definition: Option<String>,
+}
+
+impl FootnoteReference {
+    /// The label, which only anonymous inline footnotes lack.
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_ref().map(|s| &s[..])
+    }
+
+    /// The text of an inline definition.
+    pub fn definition(&self) -> Option<&str> {
+        self.definition.as_ref().map(|s| &s[..])
+    }
+}
+
+fn is_label_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-'
+}
+
+/// The label and remaining text of a line starting a footnote definition.
+fn definition_line(line: &str) -> Option<(&str, &str)> {
+    let rest = line.strip_prefix("[fn:")?;
+    let close = rest.find(']')?;
+    let label = &rest[..close];
+    if label.is_empty() || !label.chars().all(is_label_char) {
+        return None;
+    }
+    Some((label, rest[close + 1..].trim()))
+}
+
+/// Try to parse a footnote definition starting at `lines[start]`. Returns the
+/// definition and the index of the first line after it.
+pub fn parse_definition(parser: &DocumentParser, lines: &[&str], offsets: &[usize], start: usize)
+                        -> Option<(FootnoteDefinition, usize)> {
+    let (label, first) = definition_line(lines[start])?;
+    let mut body = vec![first];
+    let mut i = start + 1;
+    while i < lines.len() && definition_line(lines[i]).is_none() {
+        let blank = lines[i].trim().is_empty();
+        if blank && lines.get(i + 1).is_some_and(|next| next.trim().is_empty()) {
+            break;
+        }
+        body.push(lines[i]);
+        i += 1;
+    }
+    while body.len() > 1 && body.last().is_some_and(|line| line.trim().is_empty()) {
+        body.pop();
+        i -= 1;
+    }
+    let first_offset = first.as_ptr() as usize - lines[start].as_ptr() as usize;
+    let mut body_offsets = vec![offsets[start] + first_offset];
+    body_offsets.extend_from_slice(&offsets[start + 1..i]);
+    Some((FootnoteDefinition {
+        label: label.into(),
+        text: body.join("\n"),
+        contents: ::parse_contents(parser, &body, &body_offsets),
+        span: span::lines_span(lines, offsets, start, i),
+    }, i))
+}
+
+/// Parse a footnote reference at the very start of `text`, returning it along
+/// with the number of bytes it spans.
+pub fn parse_reference(text: &str) -> Option<(FootnoteReference, usize)> {
+    let rest = text.strip_prefix("[fn:")?;
+    let label_len = rest.find(|c: char| !is_label_char(c))?;
+    let label = &rest[..label_len];
+    let label = if label.is_empty() { None } else { Some(label.to_string()) };
+    match rest[label_len..].chars().next()? {
+        ']' if label.is_some() => {
+            let reference = FootnoteReference { label, definition: None };
+            Some((reference, 4 + label_len + 1))
+        }
+        ':' => {
+            let definition_start = label_len + 1;
+            // The inline definition may itself contain brackets, e.g. links.
+            let mut depth = 0;
+            for (i, c) in rest[definition_start..].char_indices() {
+                match c {
+                    '[' => depth += 1,
+                    ']' if depth > 0 => depth -= 1,
+                    ']' => {
+                        let definition = rest[definition_start..definition_start + i].trim();
+                        let reference = FootnoteReference {
+                            label,
+                            definition: Some(definition.into()),
+                        };
+                        return Some((reference, 4 + definition_start + i + 1));
+                    }
+                    _ => {}
+                }
+            }
+            None
+        }
+        _ => None,
+    }
+}
+
+/// Find every footnote reference in `text`, skipping the labels that start
+/// footnote definitions.
+pub fn find_references(text: &str) -> Vec<FootnoteReference> {
+    let mut references = Vec::new();
+    let mut offset = 0;
+    while let Some(found) = text[offset..].find("[fn:") {
+        let start = offset + found;
+        let at_line_start = start == 0 || text[..start].ends_with('\n');
+        match parse_reference(&text[start..]) {
+            Some((reference, len)) if !(at_line_start && reference.definition.is_none()) => {
+                references.push(reference);
+                offset = start + len;
+            }
+            _ => offset = start + 1,
+        }
+    }
+    references
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_definition() {
+        let parser = DocumentParser::new();
+        let lines: Vec<_> = "[fn:1] First line
+continued
+
+still here
+
+
+[fn:note] Second
+[fn:bad label] no".lines().collect();
+        let offsets = span::line_offsets(&lines.join("\n"), 0);
+        let (first, next) = parse_definition(&parser, &lines, &offsets, 0).unwrap();
+        assert_eq!(first.label(), "1");
+        assert_eq!(first.text(), "First line\ncontinued\n\nstill here");
+        assert_eq!(next, 4);
+        assert_eq!(first.span(), Span::new(0, 39));
+        let (second, next) = parse_definition(&parser, &lines, &offsets, 6).unwrap();
+        assert_eq!(second.label(), "note");
+        assert_eq!(second.text(), "Second\n[fn:bad label] no");
+        assert_eq!(next, 8);
+        assert!(parse_definition(&parser, &lines, &offsets, 7).is_none());
+    }
+
+    #[test]
+    fn test_find_references() {
+        let references = find_references("[fn:1] a definition with a ref[fn:2],
+an inline one[fn:3:with [[https://x.org][a link]]] and [fn:: anonymous].");
+        assert_eq!(references, [
+            FootnoteReference { label: Some("2".into()), definition: None },
+            FootnoteReference {
+                label: Some("3".into()),
+                definition: Some("with [[https://x.org][a link]]".into()),
+            },
+            FootnoteReference { label: None, definition: Some("anonymous".into()) },
+        ]);
+        assert!(parse_reference("[fn:]").is_none());
+        assert!(parse_reference("[fn:1:unclosed").is_none());
+    }
+}
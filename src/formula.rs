@@ -0,0 +1,1557 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use table::TableRow;
+use timestamp;
+use {Content, Date, Document, DocumentParser, GreaterElement, Headline, Now, Section, SystemNow,
+     Time, Timestamp, parse_duration};
+
+/// Why a `#+TBLFM:` formula couldn't be evaluated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormulaError {
+    formula: String,
+    message: String,
+}
+
+impl FormulaError {
+    fn new<S: Into<String>>(formula: &str, message: S) -> FormulaError {
+        FormulaError { formula: formula.into(), message: message.into() }
+    }
+
+    /// The formula as written, like `$3=$1*$2`.
+    pub fn formula(&self) -> &str {
+        &self.formula
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for FormulaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} in formula {}", self.message, self.formula)
+    }
+}
+
+impl Error for FormulaError {}
+
+/// The data rows of a table, as indices into its rows, and where its
+/// horizontal rules are.
+struct Grid<'a> {
+    rows: &'a mut Vec<TableRow>,
+    data: Vec<usize>,
+    /// For each horizontal rule, how many data rows come before it.
+    rules: Vec<usize>,
+    columns: usize,
+    /// What `now()` and `today()` give.
+    now: &'a dyn Now,
+}
+
+impl<'a> Grid<'a> {
+    fn new(rows: &'a mut Vec<TableRow>, now: &'a dyn Now) -> Grid<'a> {
+        let mut data = Vec::new();
+        let mut rules = Vec::new();
+        let mut columns = 0;
+        for (i, row) in rows.iter().enumerate() {
+            match *row {
+                TableRow::Standard(ref cells) => {
+                    data.push(i);
+                    columns = columns.max(cells.len());
+                }
+                TableRow::Rule => rules.push(data.len()),
+            }
+        }
+        Grid { rows, data, rules, columns, now }
+    }
+
+    /// The first data row below the header, if the table has one.
+    fn body_start(&self) -> usize {
+        self.rules.first().cloned()
+            .filter(|&rule| rule > 0 && rule < self.data.len())
+            .unwrap_or(0)
+    }
+
+    fn get(&self, row: usize, col: usize) -> &str {
+        match self.rows[self.data[row]] {
+            TableRow::Standard(ref cells) => cells.get(col).map_or("", |cell| &cell[..]),
+            TableRow::Rule => "",
+        }
+    }
+
+    fn set(&mut self, row: usize, col: usize, value: String) {
+        if let TableRow::Standard(ref mut cells) = self.rows[self.data[row]] {
+            if cells.len() <= col {
+                cells.resize(col + 1, String::new());
+            }
+            cells[col] = value;
+        }
+    }
+}
+
+/// A field a formula sets, and what to set it to.
+struct Assignment<'f> {
+    row: usize,
+    col: usize,
+    expression: &'f str,
+    format: Option<&'f str>,
+    formula: &'f str,
+}
+
+/// Evaluate `formulas` on `rows`: column formulas like `$3=$1*$2` set every
+/// field of the column below the header, and field formulas like
+/// `@2$1=vsum(@3..@5)` one field, in place of any column formula there.
+/// Each field is computed after the fields it reads, and formulas that read
+/// their own results, through other fields or not, are an error. A field
+/// read by its own formula only gives the value it had before.
+///
+/// Besides calc-like expressions, the right-hand side can be one of the
+/// simple Lisp formulas tables often use, like `'(concat $1 " " $2)` or
+/// `'(if (> $2 10) "many" "few")`, run without access to anything but the
+/// table.
+///
+/// Fields and literals like `<2024-05-01 Wed>` holding timestamps are dates,
+/// and strings like `"2d"` or `"1:30"` durations: dates differ by a number
+/// of days, and move by days or by durations, so that
+/// `$3=$2-$1` counts the days between two dates and `$4=$1+"2d 4h"` gives
+/// a timestamp. `now()` and `today()` are the current time and date, and
+/// `<`, `>`, `<=`, `>=`, `=` and `!=` compare two values, giving 1 or 0.
+pub fn evaluate(rows: &mut Vec<TableRow>, formulas: &[String]) -> Result<(), FormulaError> {
+    evaluate_at(rows, formulas, &SystemNow::utc())
+}
+
+/// `evaluate`, with `now()` and `today()` taken from `now`.
+pub fn evaluate_at(rows: &mut Vec<TableRow>, formulas: &[String], now: &dyn Now)
+                   -> Result<(), FormulaError> {
+    let mut grid = Grid::new(rows, now);
+    let mut assignments = Vec::new();
+    let mut fields = Vec::new();
+    for formula in formulas {
+        let equals = formula.find('=')
+            .ok_or_else(|| FormulaError::new(formula, "missing ="))?;
+        let target = formula[..equals].trim();
+        let (expression, format) = match formula[equals + 1..].find(';') {
+            Some(semi) => {
+                (&formula[equals + 1..equals + 1 + semi], Some(&formula[equals + 2 + semi..]))
+            }
+            None => (&formula[equals + 1..], None),
+        };
+        let mut parser = Parser::new(&grid, target, 0, 0, formula);
+        let (row, col) = parser.reference()?;
+        if !parser.at_end() {
+            return Err(FormulaError::new(formula, "bad target"));
+        }
+        let col = col.ok_or_else(|| FormulaError::new(formula, "target without a column"))?;
+        match row {
+            None => {
+                for row in grid.body_start()..grid.data.len() {
+                    assignments.push(Assignment { row, col, expression, format, formula });
+                }
+            }
+            Some(row) => fields.push(Assignment { row, col, expression, format, formula }),
+        }
+    }
+    for field in fields {
+        assignments.retain(|assignment| (assignment.row, assignment.col) != (field.row, field.col));
+        assignments.push(field);
+    }
+    for i in order(&grid, &assignments)? {
+        let Assignment { row, col, expression, format, formula } = assignments[i];
+        let value = compute(&grid, expression, format, row, col, formula)?;
+        grid.set(row, col, value);
+    }
+    Ok(())
+}
+
+/// The order to compute `assignments` in, each after the ones setting the
+/// fields it reads, or an error naming a formula in a cycle.
+fn order(grid: &Grid, assignments: &[Assignment]) -> Result<Vec<usize>, FormulaError> {
+    let setters: HashMap<(usize, usize), usize> = assignments.iter().enumerate()
+        .map(|(i, assignment)| ((assignment.row, assignment.col), i))
+        .collect();
+    let mut reads = Vec::with_capacity(assignments.len());
+    for (i, assignment) in assignments.iter().enumerate() {
+        let Assignment { row, col, expression, formula, .. } = *assignment;
+        let fields = match lisp(expression) {
+            Some(text) => {
+                let mut fields = Vec::new();
+                for reference in Sexp::parse(text, formula)?.references() {
+                    let mut parser = Parser::new(grid, reference, row, col, formula);
+                    fields.extend(parser.range_fields()?);
+                    if !parser.at_end() {
+                        return parser.error(format!("bad reference {}", reference));
+                    }
+                }
+                fields
+            }
+            None => {
+                let mut parser = Parser::new(grid, expression, row, col, formula);
+                parser.reads = Some(Vec::new());
+                parser.comparison()?;
+                if !parser.at_end() {
+                    return parser.error("unexpected text");
+                }
+                parser.reads.unwrap_or_default()
+            }
+        };
+        reads.push(fields.iter()
+            .filter_map(|field| setters.get(field).cloned())
+            .filter(|&setter| setter != i)
+            .collect::<Vec<_>>());
+    }
+
+    // A depth-first search without recursion, since a running total down a
+    // long table is a chain as long as the table.
+    const UNSEEN: u8 = 0;
+    const ON_STACK: u8 = 1;
+    const DONE: u8 = 2;
+    let mut state = vec![UNSEEN; assignments.len()];
+    let mut order = Vec::with_capacity(assignments.len());
+    for start in 0..assignments.len() {
+        if state[start] != UNSEEN {
+            continue;
+        }
+        state[start] = ON_STACK;
+        let mut stack = vec![(start, 0)];
+        while let Some(top) = stack.len().checked_sub(1) {
+            let (node, next) = stack[top];
+            match reads[node].get(next) {
+                Some(&setter) => {
+                    stack[top].1 += 1;
+                    match state[setter] {
+                        UNSEEN => {
+                            state[setter] = ON_STACK;
+                            stack.push((setter, 0));
+                        }
+                        ON_STACK => {
+                            let field = &assignments[setter];
+                            return Err(FormulaError::new(
+                                assignments[node].formula,
+                                format!("circular reference through @{}${}", field.row + 1,
+                                        field.col + 1)));
+                        }
+                        _ => {}
+                    }
+                }
+                None => {
+                    state[node] = DONE;
+                    order.push(node);
+                    stack.pop();
+                }
+            }
+        }
+    }
+    Ok(order)
+}
+
+/// The part of a formula after its `;`, like `%.2f` or `NE`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Mode {
+    /// A `%.Nf`, `%.Ne` or `%d` to write the result with: the conversion
+    /// and the digits after the point.
+    printf: Option<(char, usize)>,
+    /// `N`: read fields that aren't numbers as 0.
+    numbers: bool,
+    /// `E`: keep empty fields in ranges, as 0.
+    empty: bool,
+    /// `T`: read `H:MM` and `H:MM:SS` fields as seconds, and write the
+    /// result as `HH:MM:SS`.
+    duration: bool,
+}
+
+impl Mode {
+    fn parse(text: &str, formula: &str) -> Result<Mode, FormulaError> {
+        let unknown = || FormulaError::new(formula, format!("unknown format {}", text.trim()));
+        let mut mode = Mode::default();
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '%' => {
+                    let mut digits = String::new();
+                    if chars.next_if_eq(&'.').is_some() {
+                        while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                            digits.push(digit);
+                        }
+                    }
+                    let conversion = chars.next().filter(|c| "fed".contains(*c))
+                        .ok_or_else(unknown)?;
+                    let decimals = match &digits[..] {
+                        "" if conversion == 'd' => 0,
+                        "" => 6,
+                        _ if conversion == 'd' => return Err(unknown()),
+                        digits => digits.parse().map_err(|_| unknown())?,
+                    };
+                    mode.printf = Some((conversion, decimals));
+                }
+                'N' => mode.numbers = true,
+                'E' => mode.empty = true,
+                'T' => mode.duration = true,
+                _ if c.is_whitespace() => {}
+                _ => return Err(unknown()),
+            }
+        }
+        Ok(mode)
+    }
+
+    /// `value` written as the mode asks.
+    fn write(&self, value: f64) -> String {
+        if self.duration {
+            let seconds = value.round() as i64;
+            let sign = if seconds < 0 { "-" } else { "" };
+            let seconds = seconds.abs();
+            return format!("{}{:02}:{:02}:{:02}", sign, seconds / 3600, seconds / 60 % 60,
+                           seconds % 60);
+        }
+        match self.printf {
+            Some(('e', decimals)) => format!("{:.*e}", decimals, value),
+            Some((_, decimals)) => format!("{:.*}", decimals, value),
+            None => format_number(value),
+        }
+    }
+}
+
+/// The seconds in a duration like `1:30` or `1:30:15`.
+fn duration_seconds(text: &str) -> Option<f64> {
+    let parts: Vec<&str> = text.split(':').collect();
+    if parts.len() < 2 || parts.len() > 3
+        || parts.iter().any(|part| part.is_empty() || !part.bytes().all(|b| b.is_ascii_digit())) {
+        return None;
+    }
+    let seconds = parts.iter().zip(&[3600.0, 60.0, 1.0])
+        .map(|(part, unit)| part.parse::<f64>().unwrap_or(0.0) * unit)
+        .sum();
+    Some(seconds)
+}
+
+fn compute(grid: &Grid, expression: &str, format: Option<&str>, row: usize, col: usize,
+           formula: &str) -> Result<String, FormulaError> {
+    let mode = Mode::parse(format.unwrap_or(""), formula)?;
+    if let Some(text) = lisp(expression) {
+        let lisp = Lisp { grid, row, col, formula, mode };
+        return Ok(match lisp.eval(&Sexp::parse(text, formula)?)? {
+            Value::Nil => String::new(),
+            Value::T => "t".into(),
+            Value::Number(number) if number.is_finite() => mode.write(number),
+            Value::Number(_) => return Err(FormulaError::new(formula, "division by zero")),
+            Value::Text(text) => text,
+        });
+    }
+    let mut parser = Parser::new(grid, expression, row, col, formula);
+    parser.mode = mode;
+    let value = parser.comparison()?;
+    if !parser.at_end() {
+        return Err(FormulaError::new(formula, "unexpected text"));
+    }
+    if !value.is_finite() {
+        return Err(FormulaError::new(formula, "division by zero"));
+    }
+    Ok(value.write(&mode))
+}
+
+/// `value` without trailing zeros, rounded to ten decimals.
+fn format_number(value: f64) -> String {
+    let rounded = (value * 1e10).round() / 1e10;
+    if rounded == 0.0 { "0".into() } else { rounded.to_string() }
+}
+
+const EPOCH: Date = Date { year: 1970, month: 1, day: 1 };
+
+/// A value of a calc-like expression.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Calc {
+    Number(f64),
+    /// A point in time, as minutes since 1970-01-01 00:00, along with
+    /// whether it has a time of day and is an active timestamp, to write it
+    /// back the way it was.
+    Date { minutes: f64, time: bool, active: bool },
+    /// A length of time in minutes.
+    Duration(f64),
+}
+
+impl Calc {
+    fn timestamp(timestamp: &Timestamp) -> Calc {
+        let time = timestamp.time();
+        let minutes = timestamp.date().days_since(EPOCH) as f64 * 1440.0
+            + time.map_or(0.0, |time| time.hour as f64 * 60.0 + time.minute as f64);
+        Calc::Date { minutes, time: time.is_some(), active: timestamp.is_active() }
+    }
+
+    /// The number, timestamp or duration in a property value like `3`,
+    /// `<2024-05-01 Wed>` or `1:30`.
+    fn parse(text: &str) -> Option<Calc> {
+        let text = text.trim();
+        if let Ok(number) = text.parse() {
+            return Some(Calc::Number(number));
+        }
+        match Timestamp::parse(text) {
+            Some(timestamp) => Some(Calc::timestamp(&timestamp)),
+            None => parse_duration(text).map(|minutes| Calc::Duration(minutes as f64)),
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match *self {
+            Calc::Number(_) => "number",
+            Calc::Date { .. } => "date",
+            Calc::Duration(_) => "duration",
+        }
+    }
+
+    fn is_finite(&self) -> bool {
+        match *self {
+            Calc::Number(number) | Calc::Duration(number) => number.is_finite(),
+            Calc::Date { minutes, .. } => minutes.is_finite(),
+        }
+    }
+
+    /// This date moved by `minutes`, gaining a time of day if that moves it
+    /// by part of a day.
+    fn shift(self, by: f64) -> Calc {
+        match self {
+            Calc::Date { minutes, time, active } => {
+                Calc::Date { minutes: minutes + by, time: time || by % 1440.0 != 0.0, active }
+            }
+            other => other,
+        }
+    }
+
+    /// The value written as a field: numbers as the mode asks, dates as
+    /// timestamps and durations as `H:MM`.
+    fn write(&self, mode: &Mode) -> String {
+        match *self {
+            Calc::Number(number) => mode.write(number),
+            Calc::Date { minutes, time, active } => {
+                let minutes = minutes.round() as i64;
+                let date = EPOCH.add_days(minutes.div_euclid(1440));
+                let minute = minutes.rem_euclid(1440);
+                let time = if time {
+                    Some(Time { hour: (minute / 60) as u8, minute: (minute % 60) as u8 })
+                } else {
+                    None
+                };
+                Timestamp::new(active, date, time).to_string()
+            }
+            Calc::Duration(minutes) => {
+                let minutes = minutes.round() as i64;
+                let sign = if minutes < 0 { "-" } else { "" };
+                let minutes = minutes.abs();
+                format!("{}{}:{:02}", sign, minutes / 60, minutes % 60)
+            }
+        }
+    }
+}
+
+/// The values of the names in a headline query.
+type Names<'a> = dyn Fn(&str) -> Option<Calc> + 'a;
+
+/// A recursive descent parser and evaluator for the right-hand side of a
+/// formula, relative to the field at `row` and `col`.
+struct Parser<'a, 'g: 'a> {
+    grid: &'a Grid<'g>,
+    chars: Vec<char>,
+    pos: usize,
+    row: usize,
+    col: usize,
+    formula: &'a str,
+    /// When set, the fields the expression reads are collected here instead
+    /// of being read.
+    reads: Option<Vec<(usize, usize)>>,
+    mode: Mode,
+    /// What names that aren't functions stand for, outside of tables.
+    names: Option<&'a Names<'a>>,
+    /// Whether a name had no value, so that the result means nothing.
+    missing: bool,
+}
+
+impl<'a, 'g> Parser<'a, 'g> {
+    fn new(grid: &'a Grid<'g>, text: &str, row: usize, col: usize, formula: &'a str)
+           -> Parser<'a, 'g> {
+        Parser { grid, chars: text.chars().collect(), pos: 0, row, col, formula, reads: None,
+                 mode: Mode::default(), names: None, missing: false }
+    }
+
+    fn error<T, S: Into<String>>(&self, message: S) -> Result<T, FormulaError> {
+        Err(FormulaError::new(self.formula, message))
+    }
+
+    fn skip_space(&mut self) {
+        while self.chars.get(self.pos).is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_space();
+        self.chars.get(self.pos).cloned()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn at_end(&mut self) -> bool {
+        self.peek().is_none()
+    }
+
+    /// Whether values don't matter, since the expression is only being
+    /// looked at or its result is thrown away.
+    fn lenient(&self) -> bool {
+        self.reads.is_some() || self.missing
+    }
+
+    /// An expression, or two compared with `<`, `>`, `<=`, `>=`, `=` or
+    /// `!=`, which gives 1 if the comparison holds and 0 if not.
+    fn comparison(&mut self) -> Result<Calc, FormulaError> {
+        let left = self.expression()?;
+        let first = match self.peek() {
+            Some(c) if "<>=!".contains(c) => c,
+            _ => return Ok(left),
+        };
+        self.pos += 1;
+        let second = match self.chars.get(self.pos) {
+            Some(&c) if c == '=' || (first == '<' && c == '>') => {
+                self.pos += 1;
+                Some(c)
+            }
+            _ => None,
+        };
+        let right = self.expression()?;
+        let ordering = match (left, right) {
+            (Calc::Number(a), Calc::Number(b))
+            | (Calc::Duration(a), Calc::Duration(b))
+            | (Calc::Date { minutes: a, .. }, Calc::Date { minutes: b, .. }) => a.partial_cmp(&b),
+            _ if self.lenient() => None,
+            _ => {
+                return self.error(format!("can't compare a {} with a {}", left.kind(),
+                                          right.kind()));
+            }
+        };
+        let holds = match (first, second) {
+            ('<', None) => ordering == Some(Ordering::Less),
+            ('>', None) => ordering == Some(Ordering::Greater),
+            ('<', Some('=')) => ordering.is_some_and(|ordering| ordering != Ordering::Greater),
+            ('>', Some('=')) => ordering.is_some_and(|ordering| ordering != Ordering::Less),
+            ('=', _) => ordering == Some(Ordering::Equal),
+            ('!', Some('=')) | ('<', Some('>')) => ordering != Some(Ordering::Equal),
+            _ => return self.error("unexpected !"),
+        };
+        Ok(Calc::Number(if holds { 1.0 } else { 0.0 }))
+    }
+
+    fn expression(&mut self) -> Result<Calc, FormulaError> {
+        let mut value = self.term()?;
+        loop {
+            let op = match self.peek() {
+                Some(c) if c == '+' || c == '-' => c,
+                _ => return Ok(value),
+            };
+            self.pos += 1;
+            let right = self.term()?;
+            value = self.apply(op, value, right)?;
+        }
+    }
+
+    fn term(&mut self) -> Result<Calc, FormulaError> {
+        let mut value = self.factor()?;
+        loop {
+            let op = match self.peek() {
+                Some(c) if c == '*' || c == '/' => c,
+                _ => return Ok(value),
+            };
+            self.pos += 1;
+            let right = self.factor()?;
+            value = self.apply(op, value, right)?;
+        }
+    }
+
+    /// `left op right`: dates minus dates are the days between them, dates
+    /// move by numbers of days and by durations, and durations add up and
+    /// scale by numbers.
+    fn apply(&self, op: char, left: Calc, right: Calc) -> Result<Calc, FormulaError> {
+        let arithmetic = |a: f64, b: f64| match op {
+            '+' => a + b,
+            '-' => a - b,
+            '*' => a * b,
+            _ => a / b,
+        };
+        let sign = if op == '-' { -1.0 } else { 1.0 };
+        Ok(match (op, left, right) {
+            (_, Calc::Number(a), Calc::Number(b)) => Calc::Number(arithmetic(a, b)),
+            ('+', Calc::Duration(a), Calc::Duration(b))
+            | ('-', Calc::Duration(a), Calc::Duration(b))
+            | ('*', Calc::Duration(a), Calc::Number(b))
+            | ('/', Calc::Duration(a), Calc::Number(b)) => Calc::Duration(arithmetic(a, b)),
+            ('*', Calc::Number(a), Calc::Duration(b)) => Calc::Duration(a * b),
+            ('/', Calc::Duration(a), Calc::Duration(b)) => Calc::Number(a / b),
+            ('-', Calc::Date { minutes: a, .. }, Calc::Date { minutes: b, .. }) => {
+                Calc::Number((a - b) / 1440.0)
+            }
+            ('+', date @ Calc::Date { .. }, Calc::Number(days))
+            | ('-', date @ Calc::Date { .. }, Calc::Number(days))
+            | ('+', Calc::Number(days), date @ Calc::Date { .. }) => {
+                date.shift(sign * days * 1440.0)
+            }
+            ('+', date @ Calc::Date { .. }, Calc::Duration(minutes))
+            | ('-', date @ Calc::Date { .. }, Calc::Duration(minutes))
+            | ('+', Calc::Duration(minutes), date @ Calc::Date { .. }) => {
+                date.shift(sign * minutes)
+            }
+            _ if self.lenient() => Calc::Number(0.0),
+            _ => {
+                return self.error(format!("can't use {} on a {} and a {}", op, left.kind(),
+                                          right.kind()));
+            }
+        })
+    }
+
+    fn factor(&mut self) -> Result<Calc, FormulaError> {
+        match self.peek() {
+            Some('-') => {
+                self.pos += 1;
+                match self.factor()? {
+                    Calc::Number(number) => Ok(Calc::Number(-number)),
+                    Calc::Duration(minutes) => Ok(Calc::Duration(-minutes)),
+                    _ if self.lenient() => Ok(Calc::Number(0.0)),
+                    Calc::Date { .. } => self.error("can't negate a date"),
+                }
+            }
+            Some('(') => {
+                self.pos += 1;
+                let value = self.comparison()?;
+                if !self.eat(')') {
+                    return self.error("missing )");
+                }
+                Ok(value)
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => self.number().map(Calc::Number),
+            Some('<') | Some('[') => {
+                let rest: String = self.chars[self.pos..].iter().collect();
+                match timestamp::parse_prefix(&rest) {
+                    Some((timestamp, len)) => {
+                        self.pos += rest[..len].chars().count();
+                        Ok(Calc::timestamp(&timestamp))
+                    }
+                    None => self.error("bad timestamp"),
+                }
+            }
+            Some('"') => {
+                self.pos += 1;
+                let start = self.pos;
+                while self.chars.get(self.pos).is_some_and(|&c| c != '"') {
+                    self.pos += 1;
+                }
+                let text: String = self.chars[start..self.pos].iter().collect();
+                if !self.eat('"') {
+                    return self.error("missing \"");
+                }
+                match Timestamp::parse(&text) {
+                    Some(timestamp) => Ok(Calc::timestamp(&timestamp)),
+                    None => match parse_duration(&text) {
+                        Some(minutes) => Ok(Calc::Duration(minutes as f64)),
+                        None => self.error(format!("\"{}\" is not a date or a duration", text)),
+                    },
+                }
+            }
+            Some('@') | Some('$') => {
+                let (row, col) = self.reference()?;
+                if self.peek() == Some('.') {
+                    return self.error("range outside of a function");
+                }
+                let row = row.unwrap_or(self.row);
+                let col = col.unwrap_or(self.col);
+                self.field(row, col)
+            }
+            Some(c) if c.is_ascii_alphabetic() => self.function(),
+            Some(c) => self.error(format!("unexpected {}", c)),
+            None => self.error("unexpected end"),
+        }
+    }
+
+    fn number(&mut self) -> Result<f64, FormulaError> {
+        let start = self.pos;
+        while self.chars.get(self.pos).is_some_and(|&c| c.is_ascii_digit() || c == '.') {
+            self.pos += 1;
+        }
+        let text: String = self.chars[start..self.pos].iter().collect();
+        match text.parse() {
+            Ok(number) => Ok(number),
+            Err(_) => self.error(format!("bad number {}", text)),
+        }
+    }
+
+    /// The number or timestamp in the field at `row` and `col`, where empty
+    /// fields are 0, read as the mode asks.
+    fn field(&mut self, row: usize, col: usize) -> Result<Calc, FormulaError> {
+        if row >= self.grid.data.len() || col >= self.grid.columns {
+            return self.error("reference outside the table");
+        }
+        if let Some(ref mut reads) = self.reads {
+            reads.push((row, col));
+            return Ok(Calc::Number(0.0));
+        }
+        let text = self.grid.get(row, col);
+        if text.is_empty() {
+            return Ok(Calc::Number(0.0));
+        }
+        match text.parse() {
+            Ok(number) => Ok(Calc::Number(number)),
+            Err(_) if self.mode.duration && duration_seconds(text).is_some() => {
+                Ok(Calc::Number(duration_seconds(text).unwrap_or(0.0)))
+            }
+            Err(_) if self.mode.numbers => Ok(Calc::Number(0.0)),
+            Err(_) => match Timestamp::parse(text) {
+                Some(timestamp) => Ok(Calc::timestamp(&timestamp)),
+                None => self.error(format!("{} is not a number", text)),
+            },
+        }
+    }
+
+    /// The number in the field at `row` and `col`, for functions of ranges.
+    fn number_field(&mut self, row: usize, col: usize) -> Result<f64, FormulaError> {
+        match self.field(row, col)? {
+            Calc::Number(number) => Ok(number),
+            _ => self.error(format!("{} is not a number", self.grid.get(row, col))),
+        }
+    }
+
+    fn function(&mut self) -> Result<Calc, FormulaError> {
+        let start = self.pos;
+        while self.chars.get(self.pos).is_some_and(|&c| c.is_ascii_alphanumeric() || c == '_') {
+            self.pos += 1;
+        }
+        let name: String = self.chars[start..self.pos].iter().collect();
+        if !self.eat('(') {
+            return match self.names.map(|names| names(&name)) {
+                Some(Some(value)) => Ok(value),
+                Some(None) => {
+                    self.missing = true;
+                    Ok(Calc::Number(0.0))
+                }
+                None => self.error(format!("unknown name {}", name)),
+            };
+        }
+        if name == "now" || name == "today" {
+            if !self.eat(')') {
+                return self.error(format!("{} takes no arguments", name));
+            }
+            let (date, time) = self.grid.now.now();
+            let time = if name == "now" { Some(time) } else { None };
+            return Ok(Calc::timestamp(&Timestamp::new(true, date, time)));
+        }
+        let values = self.range()?;
+        if !self.eat(')') {
+            return self.error("missing )");
+        }
+        let sum: f64 = values.iter().sum();
+        Ok(Calc::Number(match &name[..] {
+            "vsum" => sum,
+            "vmean" | "vmin" | "vmax" if values.is_empty() => 0.0,
+            "vmean" => sum / values.len() as f64,
+            "vmin" => values.iter().cloned().fold(f64::INFINITY, f64::min),
+            "vmax" => values.iter().cloned().fold(f64::NEG_INFINITY, f64::max),
+            "vcount" => values.len() as f64,
+            _ => return self.error(format!("unknown function {}", name)),
+        }))
+    }
+
+    /// The numbers in the non-empty fields of a range like `@2$1..@4$3` or
+    /// `$2..$3`, or of a single reference.
+    fn range(&mut self) -> Result<Vec<f64>, FormulaError> {
+        let mut values = Vec::new();
+        for (row, col) in self.range_fields()? {
+            if row < self.grid.data.len() && self.grid.get(row, col).is_empty()
+                && self.reads.is_none() && !self.mode.empty {
+                continue;
+            }
+            values.push(self.number_field(row, col)?);
+        }
+        Ok(values)
+    }
+
+    /// The fields of a range or a single reference, row by row.
+    fn range_fields(&mut self) -> Result<Vec<(usize, usize)>, FormulaError> {
+        let (row1, col1) = self.reference()?;
+        let (row2, col2) = if self.eat('.') {
+            if !self.eat('.') {
+                return self.error("expected ..");
+            }
+            let end = self.pos;
+            let (row2, col2) = self.reference()?;
+            // A rule at the end of a range ends it above the rule.
+            let row2 = if self.chars[end..self.pos].contains(&'I') {
+                row2.map(|row| row.saturating_sub(1))
+            } else {
+                row2
+            };
+            (row2, col2)
+        } else {
+            (row1, col1)
+        };
+        let rows = (row1.unwrap_or(self.row), row2.or(row1).unwrap_or(self.row));
+        let cols = (col1.unwrap_or(self.col), col2.or(col1).unwrap_or(self.col));
+        let mut fields = Vec::new();
+        for row in rows.0.min(rows.1)..=rows.0.max(rows.1) {
+            for col in cols.0.min(cols.1)..=cols.0.max(cols.1) {
+                fields.push((row, col));
+            }
+        }
+        Ok(fields)
+    }
+
+    /// A reference like `@2$3`, `$1` or `@-1`, as a data row and a column
+    /// counted from zero. Parts left out are `None`.
+    fn reference(&mut self) -> Result<(Option<usize>, Option<usize>), FormulaError> {
+        let mut row = None;
+        let mut col = None;
+        if self.eat('@') {
+            row = Some(self.position(self.row, self.grid.data.len(), true)?);
+        }
+        if self.eat('$') {
+            col = Some(self.position(self.col, self.grid.columns, false)?);
+        }
+        if row.is_none() && col.is_none() {
+            return self.error("expected a reference");
+        }
+        Ok((row, col))
+    }
+
+    /// The part of a reference after `@` or `$`, relative to `current` and
+    /// counting up to `count`.
+    fn position(&mut self, current: usize, count: usize, is_row: bool)
+                -> Result<usize, FormulaError> {
+        let sign = match self.chars.get(self.pos) {
+            Some(&c) if c == '+' || c == '-' => {
+                self.pos += 1;
+                Some(c)
+            }
+            Some('<') => {
+                self.pos += 1;
+                return Ok(0);
+            }
+            Some('>') => {
+                self.pos += 1;
+                return Ok(count.saturating_sub(1));
+            }
+            Some('I') if is_row => {
+                let start = self.pos;
+                while self.chars.get(self.pos) == Some(&'I') {
+                    self.pos += 1;
+                }
+                let rule = self.pos - start;
+                return match self.grid.rules.get(rule - 1) {
+                    Some(&rows_above) => Ok(rows_above),
+                    None => self.error("no such horizontal rule"),
+                };
+            }
+            _ => None,
+        };
+        let start = self.pos;
+        while self.chars.get(self.pos).is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        let digits: String = self.chars[start..self.pos].iter().collect();
+        let n: usize = match digits.parse() {
+            Ok(n) => n,
+            Err(_) => return self.error("expected a number in a reference"),
+        };
+        let position = match sign {
+            Some('+') => current.checked_add(n),
+            Some(_) => current.checked_sub(n),
+            None => n.checked_sub(1),
+        };
+        match position.filter(|&position| position < count) {
+            Some(position) => Ok(position),
+            None => self.error("reference outside the table"),
+        }
+    }
+}
+
+/// The `(...)` of a Lisp formula like `'(concat $1 " " $2)`.
+fn lisp(expression: &str) -> Option<&str> {
+    expression.trim().strip_prefix('\'').filter(|text| text.starts_with('('))
+}
+
+/// An expression of a Lisp formula.
+#[derive(Debug, Clone, PartialEq)]
+enum Sexp {
+    List(Vec<Sexp>),
+    Number(f64),
+    Text(String),
+    Symbol(String),
+    /// A field reference or range, like `$1` or `@2$1..@4$1`.
+    Reference(String),
+}
+
+impl Sexp {
+    fn parse(text: &str, formula: &str) -> Result<Sexp, FormulaError> {
+        let chars: Vec<char> = text.chars().collect();
+        let mut pos = 0;
+        let sexp = Sexp::read(&chars, &mut pos, formula)?;
+        if chars[pos..].iter().any(|c| !c.is_whitespace()) {
+            return Err(FormulaError::new(formula, "unexpected text after the expression"));
+        }
+        Ok(sexp)
+    }
+
+    fn read(chars: &[char], pos: &mut usize, formula: &str) -> Result<Sexp, FormulaError> {
+        let error = |message: &str| Err(FormulaError::new(formula, message));
+        while chars.get(*pos).is_some_and(|c| c.is_whitespace()) {
+            *pos += 1;
+        }
+        match chars.get(*pos) {
+            None => error("unexpected end"),
+            Some(')') => error("unexpected )"),
+            Some('\'') => {
+                *pos += 1;
+                Sexp::read(chars, pos, formula)
+            }
+            Some('(') => {
+                *pos += 1;
+                let mut items = Vec::new();
+                loop {
+                    while chars.get(*pos).is_some_and(|c| c.is_whitespace()) {
+                        *pos += 1;
+                    }
+                    match chars.get(*pos) {
+                        Some(')') => {
+                            *pos += 1;
+                            return Ok(Sexp::List(items));
+                        }
+                        None => return error("missing )"),
+                        _ => items.push(Sexp::read(chars, pos, formula)?),
+                    }
+                }
+            }
+            Some('"') => {
+                *pos += 1;
+                let mut text = String::new();
+                loop {
+                    match chars.get(*pos) {
+                        Some('"') => break,
+                        Some('\\') => {
+                            *pos += 1;
+                            match chars.get(*pos) {
+                                Some('n') => text.push('\n'),
+                                Some(&c) => text.push(c),
+                                None => return error("unterminated string"),
+                            }
+                        }
+                        Some(&c) => text.push(c),
+                        None => return error("unterminated string"),
+                    }
+                    *pos += 1;
+                }
+                *pos += 1;
+                Ok(Sexp::Text(text))
+            }
+            Some(_) => {
+                let start = *pos;
+                while chars.get(*pos).is_some_and(|&c| !c.is_whitespace() && c != '(' && c != ')') {
+                    *pos += 1;
+                }
+                let atom: String = chars[start..*pos].iter().collect();
+                Ok(if atom.starts_with(['@', '$']) {
+                    Sexp::Reference(atom)
+                } else if let Ok(number) = atom.parse() {
+                    Sexp::Number(number)
+                } else {
+                    Sexp::Symbol(atom)
+                })
+            }
+        }
+    }
+
+    /// Every reference in the expression, in order.
+    fn references(&self) -> Vec<&str> {
+        match *self {
+            Sexp::Reference(ref reference) => vec![reference],
+            Sexp::List(ref items) => items.iter().flat_map(Sexp::references).collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// A value of a Lisp formula.
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Nil,
+    T,
+    Number(f64),
+    Text(String),
+}
+
+impl Value {
+    fn is_true(&self) -> bool {
+        *self != Value::Nil
+    }
+
+    fn text(&self) -> String {
+        match *self {
+            Value::Nil => String::new(),
+            Value::T => "t".into(),
+            Value::Number(number) => format_number(number),
+            Value::Text(ref text) => text.clone(),
+        }
+    }
+}
+
+/// A small, side-effect free evaluator for the common Lisp formulas: the
+/// arithmetic and comparison functions, `concat`, `format`, `if`, `and`,
+/// `or` and a few string functions. Field references give the field's text,
+/// or its number with the `N` mode, and ranges give every field as another
+/// argument. Arithmetic reads text as numbers, always in floating point.
+struct Lisp<'a, 'g: 'a> {
+    grid: &'a Grid<'g>,
+    row: usize,
+    col: usize,
+    formula: &'a str,
+    mode: Mode,
+}
+
+impl<'a, 'g> Lisp<'a, 'g> {
+    fn error<T, S: Into<String>>(&self, message: S) -> Result<T, FormulaError> {
+        Err(FormulaError::new(self.formula, message))
+    }
+
+    fn eval(&self, sexp: &Sexp) -> Result<Value, FormulaError> {
+        let mut values = self.eval_spliced(sexp)?;
+        match values.len() {
+            1 => Ok(values.remove(0)),
+            _ => self.error("a range outside of a function call"),
+        }
+    }
+
+    /// The value of `sexp`, or the values of each field of a range.
+    fn eval_spliced(&self, sexp: &Sexp) -> Result<Vec<Value>, FormulaError> {
+        match *sexp {
+            Sexp::Number(number) => Ok(vec![Value::Number(number)]),
+            Sexp::Text(ref text) => Ok(vec![Value::Text(text.clone())]),
+            Sexp::Symbol(ref symbol) => match &symbol[..] {
+                "nil" => Ok(vec![Value::Nil]),
+                "t" => Ok(vec![Value::T]),
+                _ => self.error(format!("unknown variable {}", symbol)),
+            },
+            Sexp::Reference(ref reference) => self.reference(reference),
+            Sexp::List(ref items) => self.call(items).map(|value| vec![value]),
+        }
+    }
+
+    fn reference(&self, reference: &str) -> Result<Vec<Value>, FormulaError> {
+        let mut parser = Parser::new(self.grid, reference, self.row, self.col, self.formula);
+        parser.mode = self.mode;
+        let fields = parser.range_fields()?;
+        let single = fields.len() == 1;
+        let mut values = Vec::new();
+        for (row, col) in fields {
+            if row >= self.grid.data.len() || col >= self.grid.columns {
+                return self.error("reference outside the table");
+            }
+            let text = self.grid.get(row, col);
+            if text.is_empty() && !single && !self.mode.empty {
+                continue;
+            }
+            values.push(if self.mode.numbers {
+                Value::Number(parser.number_field(row, col)?)
+            } else {
+                Value::Text(text.into())
+            });
+        }
+        Ok(values)
+    }
+
+    fn number(&self, value: &Value) -> Result<f64, FormulaError> {
+        match *value {
+            Value::Number(number) => Ok(number),
+            Value::Text(ref text) if text.trim().is_empty() => Ok(0.0),
+            Value::Text(ref text) => match text.trim().parse() {
+                Ok(number) => Ok(number),
+                Err(_) => self.error(format!("{} is not a number", text)),
+            },
+            _ => self.error(format!("{} is not a number", value.text())),
+        }
+    }
+
+    fn call(&self, items: &[Sexp]) -> Result<Value, FormulaError> {
+        let name = match items.first() {
+            Some(Sexp::Symbol(name)) => &name[..],
+            Some(_) => return self.error("a call to something that isn't a function"),
+            None => return Ok(Value::Nil),
+        };
+        let forms = &items[1..];
+        let truth = |value: bool| if value { Value::T } else { Value::Nil };
+        // The special forms don't evaluate all of their arguments.
+        match name {
+            "if" => {
+                if forms.len() < 2 {
+                    return self.error("if needs a condition and a result");
+                }
+                return if self.eval(&forms[0])?.is_true() {
+                    self.eval(&forms[1])
+                } else {
+                    self.progn(&forms[2..])
+                };
+            }
+            "when" | "unless" => {
+                let condition = match forms.first() {
+                    Some(condition) => self.eval(condition)?.is_true(),
+                    None => return self.error(format!("{} needs a condition", name)),
+                };
+                return if condition == (name == "when") {
+                    self.progn(&forms[1..])
+                } else {
+                    Ok(Value::Nil)
+                };
+            }
+            "and" => {
+                let mut value = Value::T;
+                for form in forms {
+                    value = self.eval(form)?;
+                    if !value.is_true() {
+                        break;
+                    }
+                }
+                return Ok(value);
+            }
+            "or" => {
+                for form in forms {
+                    let value = self.eval(form)?;
+                    if value.is_true() {
+                        return Ok(value);
+                    }
+                }
+                return Ok(Value::Nil);
+            }
+            _ => {}
+        }
+
+        let mut args = Vec::new();
+        for form in forms {
+            args.extend(self.eval_spliced(form)?);
+        }
+        let numbers = || args.iter().map(|arg| self.number(arg)).collect::<Result<Vec<_>, _>>();
+        let one = |what: &str| -> Result<&Value, FormulaError> {
+            match args.len() {
+                1 => Ok(&args[0]),
+                _ => self.error(format!("{} takes one {}", name, what)),
+            }
+        };
+        Ok(match name {
+            "+" => Value::Number(numbers()?.iter().sum()),
+            "*" => Value::Number(numbers()?.iter().product()),
+            "-" => {
+                let numbers = numbers()?;
+                Value::Number(match numbers.split_first() {
+                    None => 0.0,
+                    Some((&first, [])) => -first,
+                    Some((&first, rest)) => rest.iter().fold(first, |value, n| value - n),
+                })
+            }
+            "/" => match numbers()?.split_first() {
+                Some((&first, rest)) if !rest.is_empty() => {
+                    Value::Number(rest.iter().fold(first, |value, n| value / n))
+                }
+                _ => return self.error("/ takes two numbers or more"),
+            },
+            "%" | "mod" => match numbers()?[..] {
+                [a, b] => Value::Number(if name == "%" { a % b } else { a - b * (a / b).floor() }),
+                _ => return self.error(format!("{} takes two numbers", name)),
+            },
+            "max" | "min" => {
+                let numbers = numbers()?;
+                if numbers.is_empty() {
+                    return self.error(format!("{} takes a number or more", name));
+                }
+                let pick = if name == "max" { f64::max } else { f64::min };
+                Value::Number(numbers[1..].iter().fold(numbers[0], |a, &b| pick(a, b)))
+            }
+            "abs" => Value::Number(self.number(one("number")?)?.abs()),
+            "=" | "<" | ">" | "<=" | ">=" | "/=" => {
+                let numbers = numbers()?;
+                if numbers.len() < 2 {
+                    return self.error(format!("{} takes two numbers or more", name));
+                }
+                truth(numbers.windows(2).all(|pair| match name {
+                    "=" => pair[0] == pair[1],
+                    "<" => pair[0] < pair[1],
+                    ">" => pair[0] > pair[1],
+                    "<=" => pair[0] <= pair[1],
+                    ">=" => pair[0] >= pair[1],
+                    _ => pair[0] != pair[1],
+                }))
+            }
+            "string=" | "equal" => match args[..] {
+                [ref a, ref b] if name == "equal" => truth(a == b),
+                [ref a, ref b] => truth(a.text() == b.text()),
+                _ => return self.error(format!("{} takes two arguments", name)),
+            },
+            "not" | "null" => truth(!one("argument")?.is_true()),
+            "concat" => Value::Text(args.iter().map(Value::text).collect()),
+            "upcase" => Value::Text(one("string")?.text().to_uppercase()),
+            "downcase" => Value::Text(one("string")?.text().to_lowercase()),
+            "length" => Value::Number(one("string")?.text().chars().count() as f64),
+            "string-to-number" => {
+                Value::Number(one("string")?.text().trim().parse().unwrap_or(0.0))
+            }
+            "number-to-string" => Value::Text(format_number(self.number(one("number")?)?)),
+            "substring" => {
+                let text: Vec<char> = match args.first() {
+                    Some(text) => text.text().chars().collect(),
+                    None => return self.error("substring needs a string"),
+                };
+                let len = text.len() as f64;
+                let index = |i: usize, default: f64| -> Result<usize, FormulaError> {
+                    let n = match args.get(i) {
+                        Some(&Value::Nil) | None => default,
+                        Some(arg) => self.number(arg)?,
+                    };
+                    let n = if n < 0.0 { len + n } else { n };
+                    if n < 0.0 || n > len {
+                        return self.error("substring index outside the string");
+                    }
+                    Ok(n as usize)
+                };
+                let (from, to) = (index(1, 0.0)?, index(2, len)?);
+                Value::Text(text[from..to.max(from)].iter().collect())
+            }
+            "format" => Value::Text(self.format(&args)?),
+            _ => return self.error(format!("unknown function {}", name)),
+        })
+    }
+
+    /// The value of the last of `forms`, or nil without any.
+    fn progn(&self, forms: &[Sexp]) -> Result<Value, FormulaError> {
+        let mut value = Value::Nil;
+        for form in forms {
+            value = self.eval(form)?;
+        }
+        Ok(value)
+    }
+
+    /// `(format "..." args)`, with `%s`, `%d`, `%.Nf` and `%%`.
+    fn format(&self, args: &[Value]) -> Result<String, FormulaError> {
+        let template = match args.first() {
+            Some(Value::Text(template)) => template,
+            _ => return self.error("format needs a format string"),
+        };
+        let mut args = args[1..].iter();
+        let mut next = || args.next().ok_or_else(|| {
+            FormulaError::new(self.formula, "not enough arguments for format")
+        });
+        let mut out = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                out.push(c);
+                continue;
+            }
+            let mut decimals = String::new();
+            if chars.next_if_eq(&'.').is_some() {
+                while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                    decimals.push(digit);
+                }
+            }
+            match chars.next() {
+                Some('%') => out.push('%'),
+                Some('s') => out.push_str(&next()?.text()),
+                Some('d') => out.push_str(&format!("{:.0}", self.number(next()?)?.trunc())),
+                Some('f') => {
+                    let decimals = decimals.parse().unwrap_or(6);
+                    out.push_str(&format!("{:.*}", decimals, self.number(next()?)?));
+                }
+                _ => return self.error("unknown format in format string"),
+            }
+        }
+        Ok(out)
+    }
+}
+
+impl Document {
+    /// Evaluate the `#+TBLFM:` formulas of every table in the document,
+    /// writing the results into the tables and realigning them. Returns the
+    /// formulas that couldn't be evaluated; the tables they belong to are
+    /// left as they were.
+    pub fn recalculate_tables(&mut self) -> Vec<FormulaError> {
+        self.recalculate_tables_at(&SystemNow::utc())
+    }
+
+    /// `recalculate_tables`, with `now()` and `today()` taken from `now`.
+    pub fn recalculate_tables_at(&mut self, now: &dyn Now) -> Vec<FormulaError> {
+        let mut errors = Vec::new();
+        if let Some(ref mut section) = self.first_section {
+            recalculate_section(&self.parser, section, now, &mut errors);
+        }
+        ::visit_headlines_mut(&mut self.headlines, &mut |headline| {
+            if let Some(ref mut section) = headline.section {
+                recalculate_section(&headline.parser, section, now, &mut errors);
+            }
+        });
+        errors
+    }
+
+    /// The headlines for which `expression` holds, in document order. It is
+    /// written like a table formula, such as `DEADLINE - SCHEDULED > 3` or
+    /// `SCHEDULED <= now() + "2d"`, with names standing for the headline's
+    /// `SCHEDULED`, `DEADLINE` and `CLOSED` timestamps and otherwise for its
+    /// properties, read as numbers, timestamps or durations like `EFFORT`.
+    /// Headlines without a value for a name don't match.
+    pub fn find_by_expression(&self, expression: &str, now: &dyn Now)
+                              -> Result<Vec<&Headline>, FormulaError> {
+        let mut rows = Vec::new();
+        let grid = Grid::new(&mut rows, now);
+        let mut found = Vec::new();
+        for headline in self.all_headlines() {
+            let names = |name: &str| match name {
+                "SCHEDULED" => headline.scheduled().map(Calc::timestamp),
+                "DEADLINE" => headline.deadline().map(Calc::timestamp),
+                "CLOSED" => headline.closed().map(Calc::timestamp),
+                _ => headline.property(name).and_then(Calc::parse),
+            };
+            let mut parser = Parser::new(&grid, expression, 0, 0, expression);
+            parser.names = Some(&names);
+            let value = parser.comparison()?;
+            if !parser.at_end() {
+                return parser.error("unexpected text");
+            }
+            match value {
+                _ if parser.missing => {}
+                Calc::Number(number) => if number != 0.0 {
+                    found.push(headline);
+                },
+                _ => return parser.error(format!("a {} isn't true or false", value.kind())),
+            }
+        }
+        Ok(found)
+    }
+}
+
+/// Recalculate the tables in `section`, parsing it again with `parser` if
+/// any changed.
+fn recalculate_section(parser: &DocumentParser, section: &mut Section, now: &dyn Now,
+                       errors: &mut Vec<FormulaError>) {
+    fn collect(contents: &[Content], now: &dyn Now, edits: &mut Vec<(usize, usize, String)>,
+               errors: &mut Vec<FormulaError>) {
+        for content in contents {
+            if let Content::Greater(GreaterElement::Table(ref table)) = *content {
+                if table.formulas().is_empty() {
+                    continue;
+                }
+                let mut table = table.clone();
+                match table.recalculate_at(now) {
+                    Ok(()) => edits.push((table.span().start, table.rows().len(),
+                                          table.to_org_string())),
+                    Err(error) => errors.push(error),
+                }
+            }
+            let (children, items) = content.children();
+            collect(children, now, edits, errors);
+            for item in items {
+                collect(item.contents(), now, edits, errors);
+            }
+        }
+    }
+
+    let mut edits = Vec::new();
+    collect(&section.contents, now, &mut edits, errors);
+    if edits.is_empty() {
+        return;
+    }
+    let base = section.span.start;
+    let mut text = section.text.clone();
+    edits.sort_by_key(|&(start, _, _)| start);
+    for &(start, rows, ref table) in edits.iter().rev() {
+        let start = start - base;
+        let line = &text[start..];
+        let indent = line[..line.len() - line.trim_start_matches([' ', '\t']).len()].to_string();
+        let mut end = start;
+        for _ in 0..rows {
+            end = text[end..].find('\n').map_or(text.len(), |newline| end + newline + 1);
+        }
+        let mut replacement: String = table.lines()
+            .map(|line| format!("{}{}\n", indent, line))
+            .collect();
+        if !text[..end].ends_with('\n') {
+            replacement.pop();
+        }
+        text.replace_range(start..end, &replacement);
+    }
+    if text != section.text {
+        if let Some(updated) = Section::from_text(parser, &text, base) {
+            *section = updated;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(text: &str) -> Vec<TableRow> {
+        let lines: Vec<_> = text.lines().collect();
+        let offsets = ::span::line_offsets(text, 0);
+        let (table, _) = ::table::parse_table(&lines, &offsets, 0).unwrap();
+        table.rows().to_vec()
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let mut table = rows("| Item | Qty | Price | Total |
+|------+-----+-------+-------|
+| Tea  |   2 |   1.5 |       |
+| Cake |   3 |     2 |       |
+|------+-----+-------+-------|
+| Sum  |     |       |       |");
+        let formulas = ["$4=$2*$3".to_string(), "@>$4=vsum(@I..@II)".into(),
+                        "@>$2=vmean(@2..@-1);%.1f".into(), "@4$3=@-1 - (@3$3 - 1) / 4".into()];
+        evaluate(&mut table, &formulas).unwrap();
+        let cells: Vec<_> = table.iter()
+            .filter_map(|row| match *row {
+                TableRow::Standard(ref cells) => Some(cells[1..].join(" ")),
+                TableRow::Rule => None,
+            })
+            .collect();
+        assert_eq!(cells, ["Qty Price Total", "2 1.5 3", "3 2 6", "2.5 1.75 9"]);
+
+        let error = evaluate(&mut table, &["$2=$1+1".to_string()]).unwrap_err();
+        assert_eq!(error.to_string(), "Tea is not a number in formula $2=$1+1");
+        assert!(evaluate(&mut table, &["$2=@9$1".to_string()]).is_err());
+        assert!(evaluate(&mut table, &["$2=1/0".to_string()]).is_err());
+        assert!(evaluate(&mut table, &["$2=$3..$4".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_formats() {
+        let mut table = rows("| 1:30 | 0:45:30 | x |  |  |\n| 2 |  | 4 |  |  |");
+        let formulas = ["@1$4=$1+$2;T".to_string(), "@1$5=vmean($1..$3);N".into(),
+                        "@2$4=vmean($1..$3);E %.2f".into(), "@2$5=$1*1000;%.1e".into()];
+        evaluate(&mut table, &formulas).unwrap();
+        assert_eq!(table[0], TableRow::Standard(vec!["1:30".into(), "0:45:30".into(),
+                                                     "x".into(), "02:15:30".into(),
+                                                     "0".into()]));
+        assert_eq!(table[1], TableRow::Standard(vec!["2".into(), "".into(), "4".into(),
+                                                     "2.00".into(), "2.0e3".into()]));
+        let error = evaluate(&mut table, &["@1$4=$3".to_string()]).unwrap_err();
+        assert_eq!(error.message(), "x is not a number");
+        assert_eq!(Mode::parse("%d", "").unwrap().write(-2.6), "-3");
+        assert_eq!(Mode::parse("T", "").unwrap().write(-90.0), "-00:01:30");
+        assert!(Mode::parse("%.2x", "").is_err());
+        assert!(Mode::parse("Q", "").is_err());
+    }
+
+    #[test]
+    fn test_lisp() {
+        let mut table = rows("| Ada | Lovelace | 12 |  |  |  |
+| Alan | Turing | 3 |  |  |  |");
+        let formulas = ["$4='(concat $1 \" \" (upcase $2))".to_string(),
+                        "$5='(if (> $3 10) \"many\" (format \"%d left\" (- 10 $3)))".into(),
+                        "@1$6='(+ $3 @2$3 (length $1));%.1f".into(),
+                        "@2$6='(and (string= $1 \"Alan\") (substring $2 -3))".into()];
+        evaluate(&mut table, &formulas).unwrap();
+        assert_eq!(table[0], TableRow::Standard(vec!["Ada".into(), "Lovelace".into(), "12".into(),
+                                                     "Ada LOVELACE".into(), "many".into(),
+                                                     "18.0".into()]));
+        assert_eq!(table[1], TableRow::Standard(vec!["Alan".into(), "Turing".into(), "3".into(),
+                                                     "Alan TURING".into(), "7 left".into(),
+                                                     "ing".into()]));
+        evaluate(&mut table, &["@1$6='(apply '+ (list $1..$3));N".to_string()]).unwrap_err();
+        evaluate(&mut table, &["@1$6='(max $1..$3);N".to_string()]).unwrap();
+        assert_eq!(table[0], TableRow::Standard(vec!["Ada".into(), "Lovelace".into(), "12".into(),
+                                                     "Ada LOVELACE".into(), "many".into(),
+                                                     "12".into()]));
+        let error = evaluate(&mut table, &["@1$6='(* $1 2)".to_string()]).unwrap_err();
+        assert_eq!(error.message(), "Ada is not a number");
+        let error = evaluate(&mut table, &["@1$6='(shell-command \"ls\")".to_string()])
+            .unwrap_err();
+        assert_eq!(error.message(), "unknown function shell-command");
+        let formulas = ["@1$5='(concat @2$5)".to_string(), "@2$5='(concat @1$5)".into()];
+        assert!(evaluate(&mut table, &formulas).unwrap_err().message().starts_with("circular"));
+    }
+
+    #[test]
+    fn test_evaluation_order() {
+        let mut table = rows("| 1 |  |\n| 2 |  |\n| 3 |  |");
+        let formulas = ["@1$2=@2$2*10".to_string(), "$2=@+1$2+$1".into(), "@3$2=$1".into()];
+        evaluate(&mut table, &formulas).unwrap();
+        let totals: Vec<_> = table.iter()
+            .filter_map(|row| match *row {
+                TableRow::Standard(ref cells) => Some(&cells[1][..]),
+                TableRow::Rule => None,
+            })
+            .collect();
+        assert_eq!(totals, ["50", "5", "3"]);
+
+        let formulas = ["@1$2=@3$2+1".to_string(), "@2$2=@1$2".into(), "@3$2=vsum(@2..@2)".into()];
+        let error = evaluate(&mut table, &formulas).unwrap_err();
+        assert_eq!(error.to_string(), "circular reference through @1$2 in formula @2$2=@1$2");
+        evaluate(&mut table, &["$1=$1+1".to_string()]).unwrap();
+        assert_eq!(table[0], TableRow::Standard(vec!["2".into(), "50".into()]));
+    }
+
+    #[test]
+    fn test_dates() {
+        let now = (Date { year: 2024, month: 5, day: 10 }, Time { hour: 9, minute: 30 });
+        let mut table = rows("| <2024-05-01 Wed> | [2024-05-04 Sat 10:00] |  |  |  |  |  |");
+        let formulas = ["$3=$2-$1".to_string(), "$4=$1+3".into(), "$5=$2+\"1d 2h\"".into(),
+                        "$6=now()-\"0:30\"".into(),
+                        "$7=(today()-$1 > 7) + (\"2h\"/\"30min\")".into()];
+        evaluate_at(&mut table, &formulas, &now).unwrap();
+        assert_eq!(table[0], TableRow::Standard(vec![
+            "<2024-05-01 Wed>".into(), "[2024-05-04 Sat 10:00]".into(), "3.4166666667".into(),
+            "<2024-05-04 Sat>".into(), "[2024-05-05 Sun 12:00]".into(),
+            "<2024-05-10 Fri 09:00>".into(), "5".into()]));
+        let error = evaluate_at(&mut table, &["$3=$1+$2".to_string()], &now).unwrap_err();
+        assert_eq!(error.message(), "can't use + on a date and a date");
+        assert_eq!(Calc::Duration(-90.0).write(&Mode::default()), "-1:30");
+    }
+
+    #[test]
+    fn test_find_by_expression() {
+        let doc = DocumentParser::new().parse("* Soon
+DEADLINE: <2024-05-12 Sun> SCHEDULED: <2024-05-09 Thu>
+:PROPERTIES:
+:EFFORT: 2:30
+:END:
+* Later
+DEADLINE: <2024-06-01 Sat> SCHEDULED: <2024-05-31 Fri>
+* Unplanned").unwrap();
+        let now = (Date { year: 2024, month: 5, day: 10 }, Time { hour: 9, minute: 30 });
+        let titles = |expression: &str| -> Vec<&str> {
+            doc.find_by_expression(expression, &now).unwrap().iter()
+                .map(|headline| headline.title()).collect()
+        };
+        assert_eq!(titles("DEADLINE - SCHEDULED > 2"), ["Soon"]);
+        assert_eq!(titles("DEADLINE <= now() + \"1w\""), ["Soon"]);
+        assert_eq!(titles("SCHEDULED >= <2024-05-01>"), ["Soon", "Later"]);
+        assert_eq!(titles("EFFORT > \"2h\""), ["Soon"]);
+        assert!(doc.find_by_expression("DEADLINE", &now).is_err());
+        assert!(doc.find_by_expression("1 +", &now).is_err());
+    }
+
+    #[test]
+    fn test_recalculate_tables() {
+        let mut doc = DocumentParser::new().parse("* Budget
+  | a | b | sum |
+  |---+---+-----|
+  | 1 | 2 |     |
+  | 10 | 20 | x |
+  #+TBLFM: $3=$1+$2
+* Broken
+| 1 |
+#+TBLFM: $1=$9").unwrap();
+        let errors = doc.recalculate_tables();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(doc.headlines()[0].section().unwrap().text(), "  |  a |  b | sum |
+  |----+----+-----|
+  |  1 |  2 |   3 |
+  | 10 | 20 |  30 |
+  #+TBLFM: $3=$1+$2
+");
+        assert_eq!(doc.headlines()[1].section().unwrap().text(), "| 1 |\n#+TBLFM: $1=$9");
+    }
+}
@@ -0,0 +1,287 @@
+//! Project timelines for gantt charts. Every headline with a TODO keyword, a
+//! planning timestamp or an `EFFORT` is a task. It starts when scheduled and
+//! ends at its deadline or at the end of a scheduled date range.
+//!
+//! Dependencies follow org-depend: a `BLOCKER` property lists the `ID`s of
+//! the tasks that must be finished first, or `previous-sibling`, and a
+//! headline with an `ORDERED` property makes each of its children depend on
+//! the one before.
+
+use std::ptr;
+
+use object::plain_text;
+use {Date, Document, Headline};
+
+/// How many minutes of effort make a day of a chart.
+pub const WORKDAY_MINUTES: u32 = 8 * 60;
+
+#[derive(Debug, Clone)]
+pub struct Task<'a> {
+    /// The headline's `ID`, or `task1`, `task2` and so on in document order
+    /// for those without one. Characters charts don't allow in names become
+    /// `_`.
+    pub id: String,
+    pub headline: &'a Headline,
+    /// The top-level headline the task is filed under, which may be the
+    /// task's own.
+    pub group: &'a Headline,
+    pub start: Option<Date>,
+    pub end: Option<Date>,
+    /// The `EFFORT` property in minutes.
+    pub effort: Option<u32>,
+    /// The ids of the tasks that must be done before this one starts.
+    pub depends_on: Vec<String>,
+}
+
+impl<'a> Task<'a> {
+    /// The title without markup.
+    pub fn name(&self) -> String {
+        plain_text(&self.headline.title_objects())
+    }
+
+    /// How many days the task takes: from its start to its end inclusive if
+    /// it has both, or else its effort in workdays, rounded up.
+    pub fn days(&self) -> Option<u32> {
+        match (self.start, self.end) {
+            (Some(start), Some(end)) if end >= start => Some(end.days_since(start) as u32 + 1),
+            _ => self.effort.map(|effort| effort.div_ceil(WORKDAY_MINUTES).max(1)),
+        }
+    }
+}
+
+/// The tasks of the document, in order.
+pub fn timeline(document: &Document) -> Vec<Task<'_>> {
+    struct Builder<'a> {
+        tasks: Vec<Task<'a>>,
+        /// Each task's `ID` property as written, to resolve `BLOCKER`s.
+        written_ids: Vec<Option<&'a str>>,
+        blockers: Vec<Vec<&'a str>>,
+    }
+
+    impl<'a> Builder<'a> {
+        /// Add the tasks among `headlines` and their descendants, with each
+        /// depending on the sibling task before it if `ordered`.
+        fn add(&mut self, headlines: &'a [Headline], group: Option<&'a Headline>, ordered: bool) {
+            let mut previous: Option<usize> = None;
+            for headline in headlines {
+                let group = group.unwrap_or(headline);
+                if is_task(headline) {
+                    let index = self.tasks.len();
+                    let mut task = Task {
+                        id: headline.property("ID").map_or(format!("task{}", index + 1), sanitize),
+                        headline,
+                        group,
+                        start: headline.scheduled().map(|scheduled| scheduled.date()),
+                        end: headline.deadline().map(|deadline| deadline.date())
+                            .or_else(|| headline.scheduled().and_then(|s| s.end_date())),
+                        effort: headline.property("EFFORT").and_then(parse_effort),
+                        depends_on: Vec::new(),
+                    };
+                    let mut blockers = Vec::new();
+                    for blocker in headline.property("BLOCKER").unwrap_or("").split_whitespace() {
+                        if blocker == "previous-sibling" {
+                            if let Some(previous) = previous {
+                                task.depends_on.push(self.tasks[previous].id.clone());
+                            }
+                        } else {
+                            blockers.push(blocker);
+                        }
+                    }
+                    if let Some(previous) = previous.filter(|_| ordered) {
+                        let id = self.tasks[previous].id.clone();
+                        if !task.depends_on.contains(&id) {
+                            task.depends_on.push(id);
+                        }
+                    }
+                    self.tasks.push(task);
+                    self.written_ids.push(headline.property("ID"));
+                    self.blockers.push(blockers);
+                    previous = Some(index);
+                }
+                self.add(headline.children(), Some(group), headline.property("ORDERED").is_some());
+            }
+        }
+    }
+
+    let mut builder = Builder { tasks: Vec::new(), written_ids: Vec::new(), blockers: Vec::new() };
+    builder.add(document.headlines(), None, false);
+    let Builder { mut tasks, written_ids, blockers } = builder;
+    for (i, blockers) in blockers.iter().enumerate() {
+        for blocker in blockers {
+            if let Some(found) = written_ids.iter().position(|id| id == &Some(*blocker)) {
+                let id = tasks[found].id.clone();
+                if !tasks[i].depends_on.contains(&id) {
+                    tasks[i].depends_on.push(id);
+                }
+            }
+        }
+    }
+    tasks
+}
+
+fn is_task(headline: &Headline) -> bool {
+    headline.keyword().is_some() || headline.scheduled().is_some()
+        || headline.deadline().is_some() || headline.property("EFFORT").is_some()
+}
+
+fn sanitize(id: &str) -> String {
+    id.chars().map(|c| if c.is_alphanumeric() || c == '_' || c == '-' { c } else { '_' }).collect()
+}
+
+/// Parse an effort like `1:30`, `45min`, `2h`, `3d` or `1w` into minutes.
+/// Days and weeks count as workdays.
+fn parse_effort(effort: &str) -> Option<u32> {
+    let effort = effort.trim();
+    if let Some(colon) = effort.find(':') {
+        let hours: u32 = effort[..colon].parse().ok()?;
+        let minutes: u32 = effort[colon + 1..].parse().ok()?;
+        return Some(hours * 60 + minutes);
+    }
+    let digits = effort.find(|c: char| !c.is_ascii_digit()).unwrap_or(effort.len());
+    let value: u32 = effort[..digits].parse().ok()?;
+    let unit = match effort[digits..].trim() {
+        "min" | "m" => 1,
+        "h" => 60,
+        "d" => WORKDAY_MINUTES,
+        "w" => 5 * WORKDAY_MINUTES,
+        _ => return None,
+    };
+    Some(value * unit)
+}
+
+/// Text safe to use as a task name in a chart.
+fn chart_name(name: &str) -> String {
+    let name = name.replace([':', ';', '#', '[', ']'], " ");
+    name.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// The tasks as a Mermaid gantt chart, with a section for each top-level
+/// headline. Tasks with neither dates nor dependencies follow the one
+/// before.
+pub fn to_mermaid(tasks: &[Task], title: &str) -> String {
+    let mut out = String::from("gantt\n    dateFormat YYYY-MM-DD\n");
+    if !title.is_empty() {
+        out.push_str(&format!("    title {}\n", chart_name(title)));
+    }
+    let mut group: Option<&Headline> = None;
+    for task in tasks {
+        if group.is_none_or(|group| !ptr::eq(group, task.group)) {
+            let name = chart_name(&plain_text(&task.group.title_objects()));
+            out.push_str(&format!("    section {}\n", name));
+            group = Some(task.group);
+        }
+        let mut fields = vec![task.id.clone()];
+        if let Some(start) = task.start {
+            fields.push(start.to_string());
+        } else if !task.depends_on.is_empty() {
+            fields.push(format!("after {}", task.depends_on.join(" ")));
+        } else if let Some(end) = task.end {
+            fields.push(end.add_days(1 - task.days().unwrap_or(1) as i64).to_string());
+        }
+        match (task.end, task.days()) {
+            // Mermaid's end dates are exclusive.
+            (Some(end), _) => fields.push(end.add_days(1).to_string()),
+            (None, Some(days)) => fields.push(format!("{}d", days)),
+            (None, None) => fields.push("1d".into()),
+        }
+        out.push_str(&format!("    {} :{}\n", chart_name(&task.name()), fields.join(", ")));
+    }
+    out
+}
+
+/// The tasks as a PlantUML gantt chart.
+pub fn to_plantuml(tasks: &[Task]) -> String {
+    let mut out = String::from("@startgantt\n");
+    if let Some(start) = tasks.iter().filter_map(|task| task.start.or(task.end)).min() {
+        out.push_str(&format!("Project starts {}\n", start));
+    }
+    for task in tasks {
+        let name = format!("[{}] as [{}]", chart_name(&task.name()), task.id);
+        match (task.start, task.end, task.days()) {
+            (Some(start), Some(end), _) => {
+                out.push_str(&format!("{} starts {} and ends {}\n", name, start, end));
+            }
+            (Some(start), None, days) => {
+                out.push_str(&format!("{} starts {} and lasts {} days\n", name, start,
+                                      days.unwrap_or(1)));
+            }
+            (None, Some(end), days) => {
+                out.push_str(&format!("{} lasts {} days and ends {}\n", name, days.unwrap_or(1),
+                                      end));
+            }
+            (None, None, days) => out.push_str(&format!("{} lasts {} days\n", name,
+                                                         days.unwrap_or(1))),
+        }
+        for dependency in &task.depends_on {
+            out.push_str(&format!("[{}] starts at [{}]'s end\n", task.id, dependency));
+        }
+    }
+    out.push_str("@endgantt\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use DocumentParser;
+
+    #[test]
+    fn test_timeline() {
+        let doc = DocumentParser::new().todo_keywords(vec!["TODO", "DONE"]).parse("* Website
+:PROPERTIES:
+:ORDERED: t
+:END:
+** DONE Design
+SCHEDULED: <2024-05-06 Mon>--<2024-05-08 Wed>
+:PROPERTIES:
+:ID: design
+:END:
+** TODO Build: *backend*
+:PROPERTIES:
+:EFFORT: 12h
+:END:
+** TODO Launch
+DEADLINE: <2024-05-20 Mon>
+* Marketing
+** TODO Announce
+:PROPERTIES:
+:BLOCKER: design
+:EFFORT: 1:30
+:END:").unwrap();
+        let tasks = timeline(&doc);
+        let ids: Vec<_> = tasks.iter().map(|task| &task.id[..]).collect();
+        assert_eq!(ids, ["design", "task2", "task3", "task4"]);
+        assert_eq!(tasks[0].days(), Some(3));
+        assert_eq!(tasks[1].days(), Some(2));
+        assert_eq!(tasks[1].depends_on, ["design"]);
+        assert_eq!(tasks[2].depends_on, ["task2"]);
+        assert_eq!(tasks[3].depends_on, ["design"]);
+        assert_eq!(tasks[3].effort, Some(90));
+
+        assert_eq!(to_mermaid(&tasks, "Launch plan"), "gantt
+    dateFormat YYYY-MM-DD
+    title Launch plan
+    section Website
+    Design :design, 2024-05-06, 2024-05-09
+    Build backend :task2, after design, 2d
+    Launch :task3, after task2, 2024-05-21
+    section Marketing
+    Announce :task4, after design, 1d
+");
+        assert_eq!(to_plantuml(&tasks[..2]), "@startgantt
+Project starts 2024-05-06
+[Design] as [design] starts 2024-05-06 and ends 2024-05-08
+[Build backend] as [task2] lasts 2 days
+[task2] starts at [design]'s end
+@endgantt
+");
+    }
+
+    #[test]
+    fn test_parse_effort() {
+        assert_eq!(parse_effort("1:30"), Some(90));
+        assert_eq!(parse_effort("45min"), Some(45));
+        assert_eq!(parse_effort("2d"), Some(960));
+        assert_eq!(parse_effort("soon"), None);
+    }
+}
@@ -0,0 +1,261 @@
+//! Loading included files and setup files from URLs, with the `http`
+//! feature, for documents that share a `#+SETUPFILE:` kept on a server.
+
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+use include::normalize_url;
+use {Loader, decode};
+
+/// A `Loader` that fetches URLs starting with one of its allowed prefixes,
+/// keeping what it fetched so that each URL is only fetched once, and reads
+/// other paths with another loader. Includes in a fetched file resolve to
+/// URLs on its server, so they never reach the other loader:
+///
+/// ```ignore
+/// let loader = HttpLoader::new(|path: &str| fs::read_to_string(path))
+///     .allow("https://example.com/org/");
+/// ```
+///
+/// It fetches `http://` URLs itself. For `https://` URLs, give it a client
+/// that speaks TLS with `fetch`.
+pub struct HttpLoader<L> {
+    local: L,
+    allowed: Vec<String>,
+    fetch: Option<Box<dyn Loader>>,
+    cache: HashMap<String, String>,
+    timeout: Duration,
+}
+
+impl<L: Loader> HttpLoader<L> {
+    /// Read paths that aren't URLs with `local`. No URLs are allowed until
+    /// they are added with `allow`.
+    pub fn new(local: L) -> HttpLoader<L> {
+        HttpLoader {
+            local,
+            allowed: Vec::new(),
+            fetch: None,
+            cache: HashMap::new(),
+            timeout: Duration::from_secs(30),
+        }
+    }
+
+    /// Allow URLs starting with `prefix`, like `https://example.com/org/`.
+    /// A prefix without a path only allows URLs on that host, and not
+    /// `https://example.com.evil.org` for `https://example.com`.
+    pub fn allow<S: Into<String>>(mut self, prefix: S) -> Self {
+        self.allowed.push(prefix.into());
+        self
+    }
+
+    /// Fetch URLs with `fetch` rather than the built-in client, which only
+    /// speaks plain HTTP.
+    pub fn fetch<F: Loader + 'static>(mut self, fetch: F) -> Self {
+        self.fetch = Some(Box::new(fetch));
+        self
+    }
+
+    /// How long the built-in client waits for the server, 30 seconds by
+    /// default.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Forget what was fetched, so that the next loads fetch it again.
+    pub fn clear_cache(&mut self) {
+        self.cache.clear();
+    }
+
+    /// Whether `url`, with its dot segments already resolved by
+    /// `normalize_url`, starts with one of the allowed prefixes.
+    fn is_allowed(&self, url: &str) -> bool {
+        self.allowed.iter().any(|prefix| {
+            url.strip_prefix(&prefix[..]).is_some_and(|rest| {
+                prefix.ends_with('/') || rest.is_empty() || rest.starts_with(['/', '?', '#'])
+            })
+        })
+    }
+}
+
+impl<L: Loader> Loader for HttpLoader<L> {
+    fn load(&mut self, path: &str) -> io::Result<String> {
+        if !path.contains("://") {
+            return self.local.load(path);
+        }
+        let url = normalize_url(path);
+        if let Some(text) = self.cache.get(&url) {
+            return Ok(text.clone());
+        }
+        if !self.is_allowed(&url) {
+            let message = format!("{} isn't an allowed URL", url);
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, message));
+        }
+        let text = match self.fetch {
+            Some(ref mut fetch) => fetch.load(&url)?,
+            None => get(&url, self.timeout)?,
+        };
+        self.cache.insert(url, text.clone());
+        Ok(text)
+    }
+}
+
+fn error<S: Into<String>>(kind: io::ErrorKind, message: S) -> io::Error {
+    io::Error::new(kind, message.into())
+}
+
+/// Fetch `url` with an HTTP/1.1 `GET`.
+fn get(url: &str, timeout: Duration) -> io::Result<String> {
+    let rest = url.strip_prefix("http://").ok_or_else(|| {
+        error(io::ErrorKind::Unsupported, format!("can't fetch {} without a client", url))
+    })?;
+    let (host, path) = match rest.find(['/', '?', '#']) {
+        Some(slash) => (&rest[..slash], &rest[slash..]),
+        None => (rest, "/"),
+    };
+    let path = path.split('#').next().filter(|path| !path.is_empty()).unwrap_or("/");
+    let address = if host.contains(':') { host.to_string() } else { format!("{}:80", host) };
+    let mut stream = TcpStream::connect(address)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+    let request = format!("GET {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: org-rs\r\n\
+                           Connection: close\r\n\r\n", path, host);
+    stream.write_all(request.as_bytes())?;
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response)?;
+
+    let invalid = || error(io::ErrorKind::InvalidData, format!("bad response from {}", url));
+    let head_end = response.windows(4).position(|w| w == b"\r\n\r\n").ok_or_else(invalid)?;
+    let head = String::from_utf8_lossy(&response[..head_end]);
+    let mut lines = head.split("\r\n");
+    let status = lines.next().ok_or_else(invalid)?;
+    let code = status.split(' ').nth(1).ok_or_else(invalid)?;
+    let mut chunked = false;
+    let mut location = None;
+    for line in lines {
+        let (name, value) = match line.find(':') {
+            Some(colon) => (line[..colon].trim(), line[colon + 1..].trim()),
+            None => continue,
+        };
+        if name.eq_ignore_ascii_case("transfer-encoding") {
+            chunked = value.eq_ignore_ascii_case("chunked");
+        } else if name.eq_ignore_ascii_case("location") {
+            location = Some(value);
+        }
+    }
+    if code != "200" {
+        let message = match location {
+            Some(location) => format!("{} moved to {}", url, location),
+            None => format!("{} answered {}", url, &status[status.find(' ').unwrap() + 1..]),
+        };
+        return Err(error(io::ErrorKind::Other, message));
+    }
+
+    let body = &response[head_end + 4..];
+    let body = if chunked { unchunk(body).ok_or_else(invalid)? } else { body.to_vec() };
+    Ok(decode(&body).text)
+}
+
+/// The body of a response sent with `Transfer-Encoding: chunked`.
+fn unchunk(mut body: &[u8]) -> Option<Vec<u8>> {
+    let mut unchunked = Vec::new();
+    loop {
+        let line_end = body.windows(2).position(|w| w == b"\r\n")?;
+        let size = String::from_utf8_lossy(&body[..line_end]);
+        let size = usize::from_str_radix(size.split(';').next()?.trim(), 16).ok()?;
+        if size == 0 {
+            return Some(unchunked);
+        }
+        // The size comes from the server, so adding it may overflow.
+        let end = (line_end + 2).checked_add(size)?;
+        unchunked.extend_from_slice(body.get(line_end + 2..end)?);
+        body = body.get(end.checked_add(2)?..)?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+    use std::thread;
+
+    use super::*;
+    use DocumentParser;
+
+    #[test]
+    fn test_unchunk() {
+        assert_eq!(unchunk(b"4\r\nWiki\r\n6;x=y\r\npedia!\r\n0\r\n\r\n").unwrap(), b"Wikipedia!");
+        assert!(unchunk(b"ffffffffffffffff\r\nWiki\r\n0\r\n\r\n").is_none());
+        assert!(unchunk(b"fffffffffffffffd\r\nWiki\r\n0\r\n\r\n").is_none());
+        assert!(unchunk(b"9\r\nWiki\r\n0\r\n\r\n").is_none());
+    }
+
+    #[test]
+    fn test_http_loader() {
+        // Answers one request, so a second fetch of the same URL would fail.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let host = listener.local_addr().unwrap().to_string();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buffer = [0; 1024];
+            while !request.ends_with(b"\r\n\r\n") {
+                let len = stream.read(&mut buffer).unwrap();
+                request.extend_from_slice(&buffer[..len]);
+            }
+            assert!(request.starts_with(b"GET /org/setup.org HTTP/1.1\r\n"));
+            stream.write_all(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+                               9\r\n#+TODO: T\r\na\r\nODO | DONE\r\n0\r\n\r\n").unwrap();
+        });
+
+        let local = |path: &str| match path {
+            "setup.org" => Ok("#+TITLE: Local\n".to_string()),
+            _ => Err(io::Error::new(io::ErrorKind::NotFound, "not found")),
+        };
+        let mut loader = HttpLoader::new(local).allow(format!("http://{}/org/", host));
+        let url = format!("http://{}/org/setup.org", host);
+        assert_eq!(loader.load(&url).unwrap(), "#+TODO: TODO | DONE");
+        server.join().unwrap();
+        assert_eq!(loader.load(&url).unwrap(), "#+TODO: TODO | DONE");
+        assert_eq!(loader.load("setup.org").unwrap(), "#+TITLE: Local\n");
+        let denied = loader.load(&format!("http://{}/private.org", host)).unwrap_err();
+        assert_eq!(denied.kind(), io::ErrorKind::PermissionDenied);
+        assert!(!HttpLoader::new(local).allow("http://example.com")
+                .is_allowed("http://example.com.evil.org/setup.org"));
+
+        let text = format!("#+SETUPFILE: {}\n* TODO Task\n", url);
+        let doc = DocumentParser::new().parse_with_includes("notes.org", &text, loader).unwrap();
+        assert_eq!(doc.headlines()[0].keyword(), Some("TODO"));
+    }
+
+    #[test]
+    fn test_remote_includes() {
+        let local = |path: &str| Ok(format!("local {}", path));
+        let fetch = |url: &str| match url {
+            "http://example.com/org/notes.org" => {
+                Ok("#+INCLUDE: \"/etc/hostname\"\n#+INCLUDE: \"../private.org\"\n\
+                    #+INCLUDE: \"./shared.org\"\n".to_string())
+            }
+            "http://example.com/org/shared.org" => Ok("Shared.".to_string()),
+            _ => Ok(format!("remote {}", url)),
+        };
+        let mut loader = HttpLoader::new(local).fetch(fetch).allow("http://example.com/org/");
+        let denied = loader.load("http://example.com/org/../private.org").unwrap_err();
+        assert_eq!(denied.kind(), io::ErrorKind::PermissionDenied);
+        assert!(loader.load("http://example.com/org/%2e%2e/private.org").is_err());
+
+        let text = "#+INCLUDE: \"http://example.com/org/notes.org\"\n";
+        let doc = DocumentParser::new().parse_with_includes("notes.org", text, loader).unwrap();
+        let text = doc.to_org_string();
+        assert!(!text.contains("local") && !text.contains("remote"));
+        assert!(text.ends_with("Shared.\n"));
+        let warnings: Vec<_> = doc.warnings().iter().map(|warning| warning.message()).collect();
+        assert_eq!(warnings, [
+            "couldn't include http://example.com/etc/hostname: \
+             http://example.com/etc/hostname isn't an allowed URL",
+            "couldn't include http://example.com/private.org: \
+             http://example.com/private.org isn't an allowed URL",
+        ]);
+    }
+}
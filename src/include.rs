@@ -0,0 +1,548 @@
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use span::{self, Span};
+use {Diagnostic, Document, DocumentParser, ParseError};
+
+/// Where a line of a document parsed with `DocumentParser::parse_with_includes`
+/// came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Provenance {
+    /// The line in the expanded text, without its newline.
+    pub span: Span,
+    /// The file it was read from.
+    pub file: String,
+    /// Where the line starts in that file.
+    pub offset: usize,
+}
+
+/// Reads the files included with `#+INCLUDE:` and `#+SETUPFILE:` for
+/// `DocumentParser::parse_with_includes`.
+///
+/// Closures taking the path, like `std::fs::read_to_string`, are loaders.
+pub trait Loader {
+    /// The text of the file at `path`, relative to the including file, or
+    /// the URL it names.
+    fn load(&mut self, path: &str) -> io::Result<String>;
+}
+
+impl<F: FnMut(&str) -> io::Result<String>> Loader for F {
+    fn load(&mut self, path: &str) -> io::Result<String> {
+        self(path)
+    }
+}
+
+/// How the included text is spliced in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Kind {
+    Org,
+    /// `src lang`, `example` or `export backend`, wrapped in the block of
+    /// that name with its parameters.
+    Block(String, String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Include {
+    file: String,
+    kind: Kind,
+    /// The first and last line to include, counted from one.
+    lines: (Option<usize>, Option<usize>),
+    minlevel: Option<usize>,
+}
+
+/// The value of a `prefix` line, like `#+include:`, matched
+/// case-insensitively.
+fn keyword_value<'a>(line: &'a str, prefix: &str) -> Option<&'a str> {
+    let line = line.trim();
+    if line.len() >= prefix.len()
+        && line.is_char_boundary(prefix.len())
+        && line[..prefix.len()].eq_ignore_ascii_case(prefix) {
+        Some(line[prefix.len()..].trim())
+    } else {
+        None
+    }
+}
+
+/// Split `value` into words, where double quotes group words together.
+fn words(value: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut chars = value.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' {
+            chars.next();
+            words.push(chars.by_ref().take_while(|&c| c != '"').collect());
+        } else {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek().filter(|c| !c.is_whitespace()) {
+                word.push(c);
+                chars.next();
+            }
+            words.push(word);
+        }
+    }
+    words
+}
+
+fn parse_include(value: &str) -> Option<Include> {
+    let words = words(value);
+    let file = words.first().filter(|file| !file.is_empty())?.clone();
+    let mut include = Include { file, kind: Kind::Org, lines: (None, None), minlevel: None };
+    let mut rest = words[1..].iter();
+    while let Some(word) = rest.next() {
+        if word.eq_ignore_ascii_case(":lines") {
+            let range = rest.next()?;
+            let dash = range.find('-')?;
+            let number = |digits: &str| digits.trim().parse().ok().filter(|&n| n > 0);
+            include.lines = (number(&range[..dash]), number(&range[dash + 1..]));
+        } else if word.eq_ignore_ascii_case(":minlevel") {
+            include.minlevel = Some(rest.next()?.parse().ok().filter(|&n| n > 0)?);
+        } else if word.starts_with(':') {
+            rest.next();
+        } else if include.kind == Kind::Org {
+            let name = word.to_ascii_uppercase();
+            let parameters = match &name[..] {
+                "SRC" | "EXPORT" => rest.clone().take_while(|word| !word.starts_with(':'))
+                    .cloned().collect::<Vec<_>>().join(" "),
+                "EXAMPLE" => String::new(),
+                _ => return None,
+            };
+            if !parameters.is_empty() {
+                rest.nth(parameters.split(' ').count() - 1);
+            }
+            include.kind = Kind::Block(name, parameters);
+        }
+    }
+    Some(include)
+}
+
+/// How deep includes and setup files may nest, so that files including each
+/// other under names that don't repeat, like `a/../a/../a.org`, still stop.
+const MAX_DEPTH: usize = 32;
+
+/// The path of `file` included from `from`: relative paths are relative to
+/// the directory of `from`, and URLs are kept as they are. Paths and URLs
+/// are normalized, so that every spelling of a file is the same. Every path
+/// included from a URL is resolved against it, absolute ones included, so
+/// that a fetched file can only name other URLs and never local files.
+fn resolve(from: &str, file: &str) -> String {
+    if file.contains("://") {
+        return normalize_url(file);
+    }
+    let host = match from.find("://") {
+        Some(scheme) => scheme + 3,
+        None => {
+            let parent = Path::new(from).parent().unwrap_or_else(|| Path::new(""));
+            return normalize_path(&parent.join(file));
+        }
+    };
+    let end = from.find(['?', '#']).unwrap_or(from.len());
+    let path = host + from[host..end].find('/').unwrap_or(end - host);
+    let base = if file.starts_with('/') {
+        &from[..path]
+    } else {
+        match from[path..end].rfind('/') {
+            Some(slash) => &from[..path + slash + 1],
+            None => return normalize_url(&format!("{}/{}", &from[..path], file)),
+        }
+    };
+    normalize_url(&format!("{}{}", base, file))
+}
+
+/// `path` with its `.` components dropped and each `..` folded into the
+/// component before it, without looking at the file system, like
+/// `book/./notes/../main.org` to `book/main.org`. A `..` at the top of a
+/// relative path is kept, and at the top of an absolute one dropped.
+fn normalize_path(path: &Path) -> String {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normalized.pop();
+                }
+                Some(Component::RootDir) | Some(Component::Prefix(_)) => {}
+                _ => normalized.push(".."),
+            },
+            _ => normalized.push(component),
+        }
+    }
+    normalized.to_string_lossy().into_owned()
+}
+
+/// `url` with the `.` and `..` segments of its path resolved, written
+/// plainly or percent-encoded, like `http://example.com/a/b/../c` to
+/// `http://example.com/a/c`. A `..` at the top of the path is dropped.
+pub fn normalize_url(url: &str) -> String {
+    let host = url.find("://").map_or(0, |scheme| scheme + 3);
+    let end = host + url[host..].find(['?', '#']).unwrap_or(url.len() - host);
+    let path = match url[host..end].find('/') {
+        Some(slash) => host + slash,
+        None => return url.into(),
+    };
+    let is_dots = |segment: &str, dots: &str| {
+        segment.to_ascii_lowercase().replace("%2e", ".") == dots
+    };
+    let mut segments: Vec<&str> = Vec::new();
+    let mut trailing_slash = false;
+    for segment in url[path + 1..end].split('/') {
+        trailing_slash = is_dots(segment, ".") || is_dots(segment, "..");
+        if is_dots(segment, "..") {
+            segments.pop();
+        } else if !trailing_slash {
+            segments.push(segment);
+        }
+    }
+    if trailing_slash {
+        segments.push("");
+    }
+    format!("{}/{}{}", &url[..path], segments.join("/"), &url[end..])
+}
+
+/// `line` as a headline `shift` levels deeper, keeping at least one star.
+fn shift_headline(line: &str, shift: isize) -> String {
+    let stars = line.len() - line.trim_start_matches('*').len();
+    let is_headline = stars > 0 && line[stars..].chars().next().is_none_or(|c| c == ' ');
+    if shift == 0 || !is_headline {
+        return line.into();
+    }
+    let level = (stars as isize + shift).max(1) as usize;
+    format!("{}{}", "*".repeat(level), &line[stars..])
+}
+
+/// The lines of `text` with their offsets, without newlines.
+fn lines_with_offsets(text: &str) -> Vec<(&str, usize)> {
+    text.lines().zip(span::line_offsets(text, 0)).collect()
+}
+
+struct Expander<L> {
+    loader: L,
+    text: String,
+    provenance: Vec<Provenance>,
+    failures: Vec<Diagnostic>,
+    /// The files being expanded, to stop an include of a file in itself.
+    stack: Vec<String>,
+}
+
+impl<L: Loader> Expander<L> {
+    fn push_line(&mut self, line: &str, file: &str, offset: usize) {
+        let start = self.text.len();
+        self.text.push_str(line);
+        self.text.push('\n');
+        self.provenance.push(Provenance {
+            span: Span::new(start, start + line.len()),
+            file: file.into(),
+            offset,
+        });
+    }
+
+    /// Add `text`, read from `file` starting at `base`, with its headlines
+    /// `shift` levels deeper.
+    fn expand(&mut self, file: &str, text: &str, base: usize, shift: isize) {
+        for (line, offset) in lines_with_offsets(text) {
+            let offset = base + offset;
+            if let Some(setup) = keyword_value(line, "#+setupfile:") {
+                self.push_line(line, file, offset);
+                self.setup_file(file, line, setup.trim_matches('"'));
+                continue;
+            }
+            let include = keyword_value(line, "#+include:").and_then(parse_include);
+            match include {
+                Some(include) => self.include(file, line, offset, &include, shift),
+                None => self.push_line(&shift_headline(line, shift), file, offset),
+            }
+        }
+    }
+
+    /// Read `file` included from `from`, unless that would include a file in
+    /// itself or nest deeper than `MAX_DEPTH`, or add a warning for the
+    /// `#+INCLUDE:` or `#+SETUPFILE:` line at `start` in the text.
+    fn load(&mut self, from: &str, file: &str, start: usize, line: &str)
+            -> Option<(String, String)> {
+        let path = resolve(from, file);
+        let loaded = if self.stack.contains(&path) {
+            Err(format!("{} includes itself", path))
+        } else if self.stack.len() > MAX_DEPTH {
+            Err(format!("couldn't include {}: includes nest more than {} deep", path, MAX_DEPTH))
+        } else {
+            self.loader.load(&path).map_err(|error| format!("couldn't include {}: {}", path, error))
+        };
+        match loaded {
+            Ok(text) => Some((path, text)),
+            Err(message) => {
+                self.failures.push(Diagnostic::new(message, Span::new(start, start + line.len())));
+                None
+            }
+        }
+    }
+
+    /// Add the settings of the `#+SETUPFILE:` `line` of `from`, which is
+    /// already in the text: the keyword lines of `file`, like `#+TODO:` and
+    /// `#+OPTIONS:`, and those of the setup files it names.
+    fn setup_file(&mut self, from: &str, line: &str, file: &str) {
+        let start = self.text.len() - line.len() - 1;
+        let (path, text) = match self.load(from, file, start, line) {
+            Some(loaded) => loaded,
+            None => return,
+        };
+        self.stack.push(path.clone());
+        for (setting, setting_offset) in lines_with_offsets(&text) {
+            let trimmed = setting.trim_start().to_ascii_uppercase();
+            if !trimmed.starts_with("#+") || !trimmed.contains(':')
+                || trimmed.starts_with("#+BEGIN") || trimmed.starts_with("#+END") {
+                continue;
+            }
+            self.push_line(setting, &path, setting_offset);
+            if let Some(nested) = keyword_value(setting, "#+setupfile:") {
+                self.setup_file(&path, setting, nested.trim_matches('"'));
+            }
+        }
+        self.stack.pop();
+    }
+
+    /// Add what the `#+INCLUDE:` `line` of `file` includes, or the line
+    /// itself and a warning if it can't be read.
+    fn include(&mut self, from: &str, line: &str, offset: usize, include: &Include,
+               shift: isize) {
+        let start = self.text.len();
+        let (path, text) = match self.load(from, &include.file, start, line) {
+            Some(loaded) => loaded,
+            None => {
+                self.push_line(line, from, offset);
+                return;
+            }
+        };
+
+        let lines = lines_with_offsets(&text);
+        let first = include.lines.0.map_or(0, |first| first - 1).min(lines.len());
+        let last = include.lines.1.map_or(lines.len(), |last| last.min(lines.len())).max(first);
+        let start = lines.get(first).map_or(text.len(), |&(_, offset)| offset);
+        let end = if last == lines.len() {
+            text.len()
+        } else {
+            lines[last].1
+        };
+        let selected = &text[start..end];
+
+        match include.kind {
+            Kind::Org => {
+                let min_level = lines[first..last].iter()
+                    .map(|&(line, _)| line.len() - line.trim_start_matches('*').len())
+                    .filter(|&stars| stars > 0)
+                    .min();
+                let own_shift = match (include.minlevel, min_level) {
+                    (Some(minlevel), Some(level)) => minlevel as isize - level as isize,
+                    _ => 0,
+                };
+                self.stack.push(path.clone());
+                self.expand(&path, selected, start, shift + own_shift);
+                self.stack.pop();
+            }
+            Kind::Block(ref name, ref parameters) => {
+                let begin = match &parameters[..] {
+                    "" => format!("#+BEGIN_{}", name),
+                    _ => format!("#+BEGIN_{} {}", name, parameters),
+                };
+                self.push_line(&begin, from, offset);
+                for (line, line_offset) in lines_with_offsets(selected) {
+                    let trimmed = line.trim_start();
+                    let escaped = if trimmed.starts_with('*') || trimmed.starts_with("#+") {
+                        format!(",{}", line)
+                    } else {
+                        line.into()
+                    };
+                    self.push_line(&escaped, &path, start + line_offset);
+                }
+                self.push_line(&format!("#+END_{}", name), from, offset);
+            }
+        }
+    }
+}
+
+impl DocumentParser {
+    /// Parse `text`, the contents of the file `name`, with each
+    /// `#+INCLUDE: "file.org"` line replaced by the text of the file, and
+    /// each `#+SETUPFILE: "setup.org"` line followed by the keyword lines of
+    /// that file. `loader` reads a file, given its path relative to the
+    /// including file, like `std::fs::read_to_string` does.
+    ///
+    /// Includes can be nested up to 32 deep, and take the options:
+    ///
+    /// - `:lines "5-10"` to include lines 5 to 10, or `"-10"` or `"5-"`.
+    /// - `:minlevel 2` to shift the included headlines so that the
+    ///   topmost are at level 2.
+    /// - `src lang`, `example` or `export backend` before the options to
+    ///   wrap the text in a block of that kind.
+    ///
+    /// URLs are included like files, by a loader that fetches them like
+    /// `HttpLoader`, with the `http` feature. Includes that can't be read
+    /// stay in the text and are listed in `Document::warnings`.
+    /// `Document::origin` maps offsets in the document back to the files
+    /// they came from.
+    pub fn parse_with_includes<L>(&self, name: &str, text: &str, loader: L)
+                                  -> Result<Document, ParseError>
+        where L: Loader {
+        let mut expander = Expander {
+            loader,
+            text: String::new(),
+            provenance: Vec::new(),
+            failures: Vec::new(),
+            stack: vec![resolve("", name)],
+        };
+        expander.expand(name, text, 0, 0);
+        if !text.ends_with('\n') {
+            expander.text.pop();
+        }
+        let mut document = self.parse(&expander.text)?;
+        document.provenance = expander.provenance;
+        if !expander.failures.is_empty() {
+            document.warnings.extend(expander.failures);
+            document.warnings.sort_by_key(|warning| warning.span().start);
+        }
+        Ok(document)
+    }
+}
+
+impl Document {
+    /// Where each line came from, if the document was parsed with
+    /// `DocumentParser::parse_with_includes`. Edits to the document don't
+    /// update it.
+    pub fn provenance(&self) -> &[Provenance] {
+        &self.provenance
+    }
+
+    /// The file and offset in it that `offset` in the document came from,
+    /// or `None` if the document wasn't parsed with
+    /// `DocumentParser::parse_with_includes`.
+    pub fn origin(&self, offset: usize) -> Option<(&str, usize)> {
+        let index = match self.provenance.binary_search_by_key(&offset, |p| p.span.start) {
+            Ok(index) => index,
+            Err(0) => return None,
+            Err(index) => index - 1,
+        };
+        let line = &self.provenance[index];
+        let column = (offset - line.span.start).min(line.span.end - line.span.start);
+        Some((&line.file, line.offset + column))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_include() {
+        let include = parse_include("\"my notes.org\" :minlevel 2 :lines \"3-\"").unwrap();
+        assert_eq!(include, Include {
+            file: "my notes.org".into(),
+            kind: Kind::Org,
+            lines: (Some(3), None),
+            minlevel: Some(2),
+        });
+        let include = parse_include("init.el src emacs-lisp :lines \"-4\"").unwrap();
+        assert_eq!(include.kind, Kind::Block("SRC".into(), "emacs-lisp".into()));
+        assert_eq!(include.lines, (None, Some(4)));
+        assert!(parse_include("").is_none());
+    }
+
+    #[test]
+    fn test_resolve() {
+        assert_eq!(resolve("book/main.org", "../notes.org"), "notes.org");
+        assert_eq!(resolve("book/main.org", "./parts/../a.org"), "book/a.org");
+        assert_eq!(resolve("main.org", "../../notes.org"), "../../notes.org");
+        assert_eq!(resolve("/main.org", "../notes.org"), "/notes.org");
+        assert_eq!(resolve("notes.org", "/etc/hostname"), "/etc/hostname");
+        let from = "https://example.com/org/notes.org?v=2";
+        assert_eq!(resolve(from, "setup.org"), "https://example.com/org/setup.org");
+        assert_eq!(resolve(from, "/etc/hostname"), "https://example.com/etc/hostname");
+        assert_eq!(resolve(from, "../../private.org"), "https://example.com/private.org");
+        assert_eq!(resolve("https://example.com", "a.org"), "https://example.com/a.org");
+        assert_eq!(resolve(from, "http://other.org/./a/%2E%2e/b/"), "http://other.org/b/");
+        assert_eq!(normalize_url("https://example.com/a/b/..?x=/../#y"),
+                   "https://example.com/a/?x=/../#y");
+    }
+
+    #[test]
+    fn test_parse_with_includes() {
+        let load = |path: &str| match path {
+            "book/chapter.org" => Ok("* Chapter\n#+BEGIN_FOO\n** Part\n#+INCLUDE: \"chapter.org\"\n"
+                                     .to_string()),
+            "book/code.py" => Ok("# one\nprint(1)\n* two\nend\n".to_string()),
+            _ => Err(io::Error::new(io::ErrorKind::NotFound, "not found")),
+        };
+        let text = "#+TITLE: Book
+* Intro
+#+INCLUDE: \"chapter.org\" :minlevel 2
+#+INCLUDE: code.py src python :lines \"2-3\"
+#+include: missing.org";
+        let doc = DocumentParser::new().parse_with_includes("book/main.org", text, load).unwrap();
+        assert_eq!(doc.to_org_string(), "#+TITLE: Book
+* Intro
+** Chapter
+#+BEGIN_FOO
+*** Part
+#+INCLUDE: \"chapter.org\"
+#+BEGIN_SRC python
+print(1)
+,* two
+#+END_SRC
+#+include: missing.org");
+
+        let warnings: Vec<_> = doc.warnings().iter()
+            .map(|warning| (warning.message(), doc.origin(warning.span().start)))
+            .collect();
+        assert_eq!(warnings, [
+            ("#+BEGIN_FOO block is never closed", Some(("book/chapter.org", 10))),
+            ("book/chapter.org includes itself", Some(("book/chapter.org", 30))),
+            ("couldn't include book/missing.org: not found", Some(("book/main.org", 102))),
+        ]);
+        let python = doc.to_org_string().find(",* two").unwrap();
+        assert_eq!(doc.origin(python + 2), Some(("book/code.py", 17)));
+        assert_eq!(doc.origin(0), Some(("book/main.org", 0)));
+    }
+
+    #[test]
+    fn test_include_cycles() {
+        let mut loads = 0;
+        let load = |path: &str| {
+            loads += 1;
+            Ok(format!("* {}\n#+INCLUDE: \"../dir/a.org\"\n", path))
+        };
+        let text = "#+INCLUDE: \"./a.org\"\n#+INCLUDE: \"../dir/a.org\"\n";
+        let doc = DocumentParser::new().parse_with_includes("dir/a.org", text, load).unwrap();
+        assert_eq!(loads, 0);
+        let warnings: Vec<_> = doc.warnings().iter().map(|warning| warning.message()).collect();
+        assert_eq!(warnings, ["dir/a.org includes itself", "dir/a.org includes itself"]);
+
+        let load = |path: &str| Ok(format!("#+INCLUDE: \"{}.org\"\n", path));
+        let doc = DocumentParser::new().parse_with_includes("main.org", "#+INCLUDE: a", load)
+            .unwrap();
+        assert_eq!(doc.warnings().len(), 1);
+        assert!(doc.warnings()[0].message().ends_with("includes nest more than 32 deep"));
+    }
+
+    #[test]
+    fn test_setup_file() {
+        let load = |path: &str| match path {
+            "shared/setup.org" => Ok("Setup for notes.\n#+TODO: TODO | DONE\n\
+                                      #+SETUPFILE: more.org\n".to_string()),
+            "shared/more.org" => Ok("#+OPTIONS: ^:{}\n#+BEGIN_SRC\n".to_string()),
+            _ => Err(io::Error::new(io::ErrorKind::NotFound, "not found")),
+        };
+        let text = "#+SETUPFILE: \"shared/setup.org\"\n* TODO Task\n";
+        let doc = DocumentParser::new().parse_with_includes("notes.org", text, load).unwrap();
+        assert_eq!(doc.to_org_string(), "#+SETUPFILE: \"shared/setup.org\"
+#+TODO: TODO | DONE
+#+SETUPFILE: more.org
+#+OPTIONS: ^:{}
+* TODO Task
+");
+        assert_eq!(doc.headlines()[0].keyword(), Some("TODO"));
+        assert_eq!(doc.origin(doc.to_org_string().find("#+OPTIONS").unwrap()),
+                   Some(("shared/more.org", 0)));
+        assert!(doc.warnings().is_empty());
+    }
+}
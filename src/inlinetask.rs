@@ -0,0 +1,132 @@
+use span::{self, Shift, Span};
+use {Content, DocumentParser, Headline};
+
+/// An inline task, a headline with at least `inlinetask_min_level` stars
+/// that sits inside a section instead of starting a subtree, as written by
+/// org-inlinetask:
+///
+/// ```ignore
+/// *************** TODO Check the figures :review:
+/// SCHEDULED: <2024-05-09 Thu>
+/// The body runs until a line of the same stars followed by END.
+/// *************** END
+/// ```
+///
+/// Without an END line the task is the headline line alone.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Inlinetask {
+    headline: Box<Headline>,
+    has_end: bool,
+}
+
+impl Inlinetask {
+    /// The task as a headline: its keyword, priority, title and tags, and
+    /// the planning line, properties and section of its body. It never has
+    /// children.
+    pub fn headline(&self) -> &Headline {
+        &self.headline
+    }
+
+    /// The elements of the body.
+    pub fn contents(&self) -> &[Content] {
+        self.headline.section().map_or(&[], |section| section.contents())
+    }
+
+    /// Whether the task is closed by an END line.
+    pub fn has_end(&self) -> bool {
+        self.has_end
+    }
+
+    /// Where the task is in the text, from its headline line to its END
+    /// line.
+    pub fn span(&self) -> Span {
+        self.headline.span()
+    }
+}
+
+impl Shift for Inlinetask {
+    fn shift(&mut self, delta: isize) {
+        self.headline.shift(delta);
+    }
+}
+
+/// Whether `line` starts an inline task or ends one for `parser`.
+pub fn is_task_line(parser: &DocumentParser, line: &str) -> bool {
+    let min_level = match parser.inlinetask_min_level {
+        Some(level) => level as usize,
+        None => return false,
+    };
+    let stars = line.len() - line.trim_start_matches('*').len();
+    stars >= min_level.max(1)
+        && line[stars..].chars().next().is_none_or(|c| c == ' ' || c == '\t')
+}
+
+/// Whether `line` is the END line closing an inline task.
+pub fn is_end_line(parser: &DocumentParser, line: &str) -> bool {
+    is_task_line(parser, line) && line.trim_start_matches('*').trim() == "END"
+}
+
+/// Try to parse an inline task starting at `lines[start]`. Returns the task
+/// and the index of the first line after it.
+pub fn parse_inlinetask(parser: &DocumentParser, lines: &[&str], offsets: &[usize], start: usize)
+                        -> Option<(Inlinetask, usize)> {
+    let line = lines[start];
+    if !is_task_line(parser, line) || is_end_line(parser, line) {
+        return None;
+    }
+    let captures = ::headline_regex().captures(line)?;
+    let mut headline = parser.headline(&captures, &parser.todo_keywords, parser.priorities);
+    let end = (start + 1..lines.len())
+        .find(|&i| is_task_line(parser, lines[i]))
+        .filter(|&i| is_end_line(parser, lines[i]));
+    let next = match end {
+        Some(end) => {
+            if end > start + 1 {
+                let body = span::lines_text(lines, offsets, start + 1, end);
+                headline.set_body(parser, &body, offsets[start + 1]);
+            }
+            end + 1
+        }
+        None => start + 1,
+    };
+    headline.span = span::lines_span(lines, offsets, start, next);
+    Some((Inlinetask { headline: Box::new(headline), has_end: end.is_some() }, next))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_inlinetask() {
+        let parser = DocumentParser::new().todo_keywords(vec!["TODO", "DONE"])
+            .inlinetask_min_level(Some(4));
+        let lines: Vec<_> = "**** TODO Check the figures :review:
+SCHEDULED: <2024-05-09 Thu>
+Some notes.
+**** END
+***** DONE One line
+***** Another".lines().collect();
+        let offsets = span::line_offsets(&lines.join("\n"), 0);
+        let (task, next) = parse_inlinetask(&parser, &lines, &offsets, 0).unwrap();
+        assert_eq!(next, 4);
+        assert!(task.has_end());
+        let headline = task.headline();
+        assert_eq!(headline.keyword(), Some("TODO"));
+        assert_eq!(headline.title(), "Check the figures");
+        assert_eq!(headline.tags(), ["review"]);
+        assert_eq!(headline.scheduled().unwrap().date().day, 9);
+        assert_eq!(task.contents().len(), 2);
+        assert_eq!(task.span(), Span::new(0, 85));
+
+        let (task, next) = parse_inlinetask(&parser, &lines, &offsets, 4).unwrap();
+        assert_eq!(next, 5);
+        assert!(!task.has_end());
+        assert!(task.contents().is_empty());
+        assert!(parse_inlinetask(&parser, &lines, &offsets, 3).is_none());
+        assert!(parse_inlinetask(&parser, &["*** Shallow"], &[0], 0).is_none());
+        let parser = parser.inlinetask_min_level(None);
+        assert!(parse_inlinetask(&parser, &lines, &offsets, 0).is_none());
+    }
+}
@@ -0,0 +1,210 @@
+//! Invoices from tracked time: a line for each headline with time clocked
+//! in a range, billed at its `RATE` per hour. Headlines inherit the rate of
+//! their ancestors, or of a `#+PROPERTY: RATE` line.
+
+use clock;
+use export::html::HtmlExporter;
+use object::plain_text;
+use table;
+use {Date, Document, DocumentParser, Headline};
+
+/// A line of an invoice: the time clocked on one headline itself, without
+/// its children, which have lines of their own.
+#[derive(Debug, Clone, Copy)]
+pub struct LineItem<'a> {
+    pub headline: &'a Headline,
+    pub minutes: u32,
+    /// The `RATE` per hour it is billed at, if it has one.
+    pub rate: Option<f64>,
+}
+
+impl<'a> LineItem<'a> {
+    pub fn amount(&self) -> Option<f64> {
+        self.rate.map(|rate| rate * self.minutes as f64 / 60.0)
+    }
+
+    /// The title without markup.
+    pub fn name(&self) -> String {
+        plain_text(&self.headline.title_objects())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Invoice<'a> {
+    items: Vec<LineItem<'a>>,
+}
+
+impl<'a> Invoice<'a> {
+    /// The invoice for the time clocked in `document`, only counting time
+    /// from `range.0` to `range.1` inclusive if a range is given, like
+    /// `Document::clock_report`.
+    pub fn new(document: &'a Document, range: Option<(Date, Date)>) -> Invoice<'a> {
+        fn add<'a>(headlines: &'a [Headline], inherited: Option<f64>,
+                   range: Option<(Date, Date)>, items: &mut Vec<LineItem<'a>>) {
+            for headline in headlines {
+                let rate = headline.property("RATE").and_then(parse_rate).or(inherited);
+                let minutes = clock::own_minutes(headline, range);
+                if minutes > 0 {
+                    items.push(LineItem { headline, minutes, rate });
+                }
+                add(headline.children(), rate, range, items);
+            }
+        }
+
+        let rate = document.file_properties().into_iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case("RATE"))
+            .and_then(|(_, value)| parse_rate(&value));
+        let mut items = Vec::new();
+        add(document.headlines(), rate, range, &mut items);
+        Invoice { items }
+    }
+
+    /// The lines, in document order.
+    pub fn items(&self) -> &[LineItem<'a>] {
+        &self.items
+    }
+
+    pub fn minutes(&self) -> u32 {
+        self.items.iter().map(|item| item.minutes).sum()
+    }
+
+    /// The sum of the amounts of the lines with a rate.
+    pub fn total(&self) -> f64 {
+        self.items.iter().filter_map(LineItem::amount).sum()
+    }
+
+    /// The cells of the table, the header first and the total last.
+    fn rows(&self) -> Vec<Vec<String>> {
+        let mut rows = vec![vec!["Task".to_string(), "Time".into(), "Rate".into(),
+                                 "Amount".into()]];
+        for item in &self.items {
+            rows.push(vec![item.name(), duration(item.minutes),
+                           item.rate.map_or_else(String::new, money),
+                           item.amount().map_or_else(String::new, money)]);
+        }
+        rows.push(vec!["Total".into(), duration(self.minutes()), String::new(),
+                       money(self.total())]);
+        rows
+    }
+
+    /// The invoice as an aligned org table, with rules under the header and
+    /// above the total.
+    pub fn to_org(&self) -> String {
+        let rows = self.rows();
+        let last = rows.len() - 1;
+        let mut lines = Vec::new();
+        for (i, cells) in rows.iter().enumerate() {
+            if i == last {
+                lines.push("|-".to_string());
+            }
+            let cells: Vec<String> = cells.iter().map(|cell| cell.replace('|', "\\vert{}")).collect();
+            lines.push(format!("| {} |", cells.join(" | ")));
+            if i == 0 {
+                lines.push("|-".to_string());
+            }
+        }
+        let lines: Vec<&str> = lines.iter().map(|line| &line[..]).collect();
+        let offsets = vec![0; lines.len()];
+        match table::parse_table(&lines, &offsets, 0) {
+            Some((table, _)) => table.to_org_string(),
+            None => String::new(),
+        }
+    }
+
+    /// The invoice as HTML, the table of `to_org` exported with the default
+    /// options.
+    pub fn to_html(&self) -> String {
+        match DocumentParser::new().parse(&self.to_org()) {
+            Ok(document) => HtmlExporter::new().export(&document),
+            Err(_) => String::new(),
+        }
+    }
+
+    /// The invoice as a LaTeX `tabular`, to put in a document of its own.
+    pub fn to_latex(&self) -> String {
+        let rows = self.rows();
+        let last = rows.len() - 1;
+        let mut out = String::from("\\begin{tabular}{lrrr}\n\\hline\n");
+        for (i, cells) in rows.iter().enumerate() {
+            if i == last {
+                out.push_str("\\hline\n");
+            }
+            let cells: Vec<String> = cells.iter().map(|cell| latex_escape(cell)).collect();
+            out.push_str(&format!("{} \\\\\n", cells.join(" & ")));
+            if i == 0 {
+                out.push_str("\\hline\n");
+            }
+        }
+        out.push_str("\\hline\n\\end{tabular}\n");
+        out
+    }
+}
+
+fn parse_rate(value: &str) -> Option<f64> {
+    value.trim().parse().ok()
+}
+
+fn duration(minutes: u32) -> String {
+    format!("{}:{:02}", minutes / 60, minutes % 60)
+}
+
+fn money(amount: f64) -> String {
+    format!("{:.2}", amount)
+}
+
+fn latex_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' | '%' | '$' | '#' | '_' | '{' | '}' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '~' => escaped.push_str("\\textasciitilde{}"),
+            '^' => escaped.push_str("\\textasciicircum{}"),
+            '\\' => escaped.push_str("\\textbackslash{}"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invoice() {
+        let doc = DocumentParser::new().parse("#+PROPERTY: RATE 100
+* Client work
+  :PROPERTIES:
+  :RATE: 120
+  :END:
+** Design & review
+   :LOGBOOK:
+   CLOCK: [2024-05-07 Tue 09:00]--[2024-05-07 Tue 10:30] =>  1:30
+   CLOCK: [2024-04-30 Tue 09:00]--[2024-04-30 Tue 10:00] =>  1:00
+   :END:
+* Support
+  :LOGBOOK:
+  CLOCK: [2024-05-08 Wed 14:00]--[2024-05-08 Wed 14:45] =>  0:45
+  :END:").unwrap();
+        let may = (Date { year: 2024, month: 5, day: 1 }, Date { year: 2024, month: 5, day: 31 });
+        let invoice = Invoice::new(&doc, Some(may));
+        assert_eq!(invoice.items().len(), 2);
+        assert_eq!(invoice.items()[0].rate, Some(120.0));
+        assert_eq!(invoice.minutes(), 135);
+        assert_eq!(invoice.total(), 255.0);
+        assert_eq!(invoice.to_org(), "| Task            | Time |   Rate | Amount |
+|-----------------+------+--------+--------|
+| Design & review | 1:30 | 120.00 | 180.00 |
+| Support         | 0:45 | 100.00 |  75.00 |
+|-----------------+------+--------+--------|
+| Total           | 2:15 |        | 255.00 |
+");
+        assert!(invoice.to_html().contains("<tr><td>Design &amp; review</td><td>1:30</td>\
+                                            <td>120.00</td><td>180.00</td></tr>"));
+        assert!(invoice.to_latex().contains("Design \\& review & 1:30 & 120.00 & 180.00 \\\\\n"));
+        assert_eq!(Invoice::new(&doc, None).minutes(), 195);
+    }
+}
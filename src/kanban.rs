@@ -0,0 +1,186 @@
+//! Kanban boards built from TODO keywords: each keyword is a column, holding
+//! the headlines in that state as cards. Moving a card changes the keyword of
+//! its headline.
+
+use {Document, Headline, Symbol};
+
+/// A board made by `Document::kanban`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Board {
+    pub columns: Vec<Column>,
+}
+
+impl Board {
+    pub fn column(&self, keyword: &str) -> Option<&Column> {
+        self.columns.iter().find(|column| column.keyword == keyword)
+    }
+}
+
+/// The cards whose headlines have `keyword`, in document order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Column {
+    pub keyword: String,
+    pub cards: Vec<Card>,
+}
+
+/// A headline on the board. Cards hold a copy of what a board shows rather
+/// than a reference, so that the document can still be changed through
+/// `Document::move_card`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Card {
+    /// The index of the headline among its siblings, from the top level down.
+    path: Vec<usize>,
+    keyword: String,
+    title: String,
+    priority: Option<char>,
+    tags: Vec<Symbol>,
+    effort: Option<String>,
+    assignee: Option<String>,
+}
+
+impl Card {
+    fn new(path: Vec<usize>, keyword: &str, headline: &Headline) -> Card {
+        Card {
+            path,
+            keyword: keyword.into(),
+            title: headline.title().into(),
+            priority: headline.priority(),
+            tags: headline.tags().to_vec(),
+            effort: headline.property("EFFORT").map(String::from),
+            assignee: headline.property("ASSIGNEE").map(String::from),
+        }
+    }
+
+    /// The index of the card's headline among its siblings, from the top
+    /// level down.
+    pub fn path(&self) -> &[usize] {
+        &self.path
+    }
+
+    pub fn keyword(&self) -> &str {
+        &self.keyword
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn priority(&self) -> Option<char> {
+        self.priority
+    }
+
+    pub fn tags(&self) -> &[Symbol] {
+        &self.tags
+    }
+
+    /// The `EFFORT` property, like `1:30`.
+    pub fn effort(&self) -> Option<&str> {
+        self.effort.as_ref().map(|effort| &effort[..])
+    }
+
+    /// The `ASSIGNEE` property.
+    pub fn assignee(&self) -> Option<&str> {
+        self.assignee.as_ref().map(|assignee| &assignee[..])
+    }
+}
+
+impl Document {
+    /// The TODO keywords in effect for the document, or `TODO` and `DONE` if
+    /// it has none.
+    pub fn kanban_columns(&self) -> Vec<String> {
+        if self.todo_keywords().is_empty() {
+            vec!["TODO".into(), "DONE".into()]
+        } else {
+            self.todo_keywords().to_vec()
+        }
+    }
+
+    /// A board with a column for each of `columns`, or for each of the
+    /// `kanban_columns` if `None`. Headlines whose keyword isn't a column are
+    /// left off.
+    pub fn kanban(&self, columns: Option<&[&str]>) -> Board {
+        fn add_cards(headlines: &[Headline], path: &mut Vec<usize>, columns: &mut [Column]) {
+            for (i, headline) in headlines.iter().enumerate() {
+                path.push(i);
+                if let Some(keyword) = headline.keyword() {
+                    if let Some(column) = columns.iter_mut().find(|c| c.keyword == keyword) {
+                        column.cards.push(Card::new(path.clone(), keyword, headline));
+                    }
+                }
+                add_cards(headline.children(), path, columns);
+                path.pop();
+            }
+        }
+
+        let keywords = match columns {
+            Some(columns) => columns.iter().map(|&column| column.into()).collect(),
+            None => self.kanban_columns(),
+        };
+        let mut columns: Vec<_> = keywords.into_iter()
+            .map(|keyword| Column { keyword, cards: Vec::new() })
+            .collect();
+        add_cards(&self.headlines, &mut Vec::new(), &mut columns);
+        Board { columns }
+    }
+
+    /// Move `card` to `column` by changing the keyword of its headline.
+    /// Returns false, changing nothing, if the document no longer has the
+    /// card's headline where it was found with the same title and keyword.
+    pub fn move_card(&mut self, card: &Card, column: &str) -> bool {
+        let (&first, rest) = match card.path.split_first() {
+            Some(split) => split,
+            None => return false,
+        };
+        let mut headline = match self.headlines.get_mut(first) {
+            Some(headline) => headline,
+            None => return false,
+        };
+        for &i in rest {
+            headline = match headline.headlines.get_mut(i) {
+                Some(child) => child,
+                None => return false,
+            };
+        }
+        if headline.title() != card.title || headline.keyword() != Some(&card.keyword[..]) {
+            return false;
+        }
+        headline.set_keyword(Some(column));
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use DocumentParser;
+
+    #[test]
+    fn test_kanban() {
+        let mut doc = DocumentParser::new().parse("#+TODO: TODO DOING | DONE
+* DOING [#A] Write docs :docs:
+:PROPERTIES:
+:Effort: 2:00
+:ASSIGNEE: sam
+:END:
+* Release
+** TODO Tag the release
+** DONE Fix the build
+* Notes").unwrap();
+        let board = doc.kanban(None);
+        let columns: Vec<_> = board.columns.iter()
+            .map(|column| (&column.keyword[..], column.cards.len()))
+            .collect();
+        assert_eq!(columns, [("TODO", 1), ("DOING", 1), ("DONE", 1)]);
+        let card = &board.column("DOING").unwrap().cards[0];
+        assert_eq!(card.title(), "Write docs");
+        assert_eq!(card.priority(), Some('A'));
+        assert_eq!(card.effort(), Some("2:00"));
+        assert_eq!(card.assignee(), Some("sam"));
+
+        let todo = board.column("TODO").unwrap().cards[0].clone();
+        assert_eq!(todo.path(), [1, 0]);
+        assert!(doc.move_card(&todo, "DOING"));
+        assert_eq!(doc.headlines()[1].children()[0].keyword(), Some("DOING"));
+        assert!(!doc.move_card(&todo, "DONE"));
+        assert_eq!(doc.kanban(Some(&["DOING"])).columns[0].cards.len(), 2);
+    }
+}
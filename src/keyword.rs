@@ -0,0 +1,339 @@
+use std::collections::BTreeMap;
+
+use object::Object;
+use span::{self, Shift, Span};
+
+/// A keyword line, such as the in-buffer settings at the top of a file:
+///
+/// ```ignore
+/// #+TITLE: Notes
+/// #+TODO: TODO NEXT | DONE CANCELLED
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Keyword {
+    key: String,
+    optional: Option<String>,
+    value: String,
+    span: Span,
+}
+
+impl Keyword {
+    /// The key as written, like `TITLE` or `title`.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// The `[...]` part of the keywords that take a second value, like the
+    /// short caption of `#+CAPTION[short]: long`.
+    pub fn optional(&self) -> Option<&str> {
+        self.optional.as_ref().map(|s| &s[..])
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+}
+
+impl Shift for Keyword {
+    fn shift(&mut self, delta: isize) {
+        self.span.shift(delta);
+    }
+}
+
+/// The affiliated keywords written directly above an element, which belong
+/// to it rather than standing on their own:
+///
+/// ```ignore
+/// #+NAME: prices
+/// #+CAPTION: Prices in May
+/// #+ATTR_HTML: :class wide
+/// | Apple | 3 |
+/// ```
+///
+/// The element's span doesn't include them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Affiliated {
+    keywords: Vec<Keyword>,
+    /// The `:key value` pairs of the `#+ATTR_BACKEND:` lines by the backend
+    /// in upper case.
+    attributes: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl Affiliated {
+    /// The keywords in order.
+    pub fn keywords(&self) -> &[Keyword] {
+        &self.keywords
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keywords.is_empty()
+    }
+
+    /// The value of the first keyword for `key`, matched case-insensitively.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.keywords.iter()
+            .find(|keyword| keyword.key.eq_ignore_ascii_case(key))
+            .map(|keyword| &keyword.value[..])
+    }
+
+    /// The `#+NAME:`, which links and table formulas refer to the element by.
+    pub fn name(&self) -> Option<&str> {
+        self.get("NAME")
+    }
+
+    pub fn caption(&self) -> Option<&str> {
+        self.get("CAPTION")
+    }
+
+    /// The caption parsed into objects, since it can have markup and links.
+    pub fn caption_objects(&self) -> Option<Vec<Object>> {
+        self.caption().map(::parse_objects)
+    }
+
+    /// The short caption of `#+CAPTION[short]: long`, for lists of figures
+    /// and tables.
+    pub fn short_caption(&self) -> Option<&str> {
+        self.keywords.iter()
+            .find(|keyword| keyword.key.eq_ignore_ascii_case("CAPTION"))
+            .and_then(Keyword::optional)
+    }
+
+    pub fn short_caption_objects(&self) -> Option<Vec<Object>> {
+        self.short_caption().map(::parse_objects)
+    }
+
+    /// The attributes of every `#+ATTR_BACKEND:` line for `backend`, like
+    /// `html` or `latex`, by their keys with the colon, like `:width`. A key
+    /// given again on a later line takes its value from there.
+    pub fn attributes(&self, backend: &str) -> Option<&BTreeMap<String, String>> {
+        self.attributes.get(&backend.to_ascii_uppercase())
+    }
+
+    /// The value of the attribute `key` for `backend`, like `attribute("html",
+    /// ":width")`.
+    pub fn attribute(&self, backend: &str, key: &str) -> Option<&str> {
+        self.attributes(backend)?.get(key).map(|value| &value[..])
+    }
+
+    /// The backends with `#+ATTR_BACKEND:` lines, in upper case.
+    pub fn attribute_backends(&self) -> impl Iterator<Item = &str> {
+        self.attributes.keys().map(|backend| &backend[..])
+    }
+}
+
+impl Shift for Affiliated {
+    fn shift(&mut self, delta: isize) {
+        self.keywords.shift(delta);
+    }
+}
+
+/// Elements that can have affiliated keywords.
+pub trait Affiliate {
+    fn affiliated_mut(&mut self) -> &mut Affiliated;
+}
+
+/// Whether `key` is one of org's affiliated keywords.
+fn is_affiliated_key(key: &str) -> bool {
+    let key = key.to_ascii_uppercase();
+    ["CAPTION", "HEADER", "NAME", "PLOT", "RESULTS"].contains(&&key[..])
+        || (key.starts_with("ATTR_") && key.len() > 5)
+}
+
+/// Try to parse the affiliated keywords starting at `lines[start]`. Returns
+/// them and the index of the line after the last one.
+pub fn parse_affiliated(lines: &[&str], offsets: &[usize], start: usize)
+                        -> Option<(Affiliated, usize)> {
+    let mut keywords = Vec::new();
+    let mut i = start;
+    while i < lines.len() {
+        match parse_keyword(lines, offsets, i) {
+            Some((keyword, next)) if is_affiliated_key(&keyword.key) => {
+                keywords.push(keyword);
+                i = next;
+            }
+            _ => break,
+        }
+    }
+    if keywords.is_empty() {
+        return None;
+    }
+    let mut attributes = BTreeMap::new();
+    for keyword in &keywords {
+        let key = keyword.key.to_ascii_uppercase();
+        if let Some(backend) = key.strip_prefix("ATTR_") {
+            attributes.entry(backend.to_string())
+                .or_insert_with(BTreeMap::new)
+                .extend(::block::parse_plist(&keyword.value));
+        }
+    }
+    Some((Affiliated { keywords, attributes }, i))
+}
+
+/// The key and value of a `#+KEY: value` line.
+fn keyword_line(line: &str) -> Option<(&str, &str)> {
+    let rest = line.trim().strip_prefix("#+")?;
+    let colon = rest.find(':')?;
+    let key = &rest[..colon];
+    if key.is_empty() || key.contains(char::is_whitespace) {
+        return None;
+    }
+    Some((key, rest[colon + 1..].trim()))
+}
+
+/// Try to parse a keyword at `lines[start]`. Returns the keyword and the index
+/// of the line after it.
+pub fn parse_keyword(lines: &[&str], offsets: &[usize], start: usize) -> Option<(Keyword, usize)> {
+    let (mut key, value) = keyword_line(lines[start])?;
+    let mut optional = None;
+    if let Some(open) = key.find('[').filter(|_| key.ends_with(']')) {
+        if ["CAPTION", "RESULTS"].iter().any(|dual| key[..open].eq_ignore_ascii_case(dual)) {
+            optional = Some(key[open + 1..key.len() - 1].into());
+            key = &key[..open];
+        }
+    }
+    Some((Keyword {
+        key: key.into(),
+        optional,
+        value: value.into(),
+        span: span::lines_span(lines, offsets, start, start + 1),
+    }, start + 1))
+}
+
+/// The value of the last `#+KEY:` line for `key` anywhere in `text`, with
+/// the key matched case-insensitively.
+pub fn find_keyword<'a>(text: &'a str, key: &str) -> Option<&'a str> {
+    text.lines()
+        .rev()
+        .filter_map(keyword_line)
+        .find(|&(k, _)| k.eq_ignore_ascii_case(key))
+        .map(|(_, value)| value)
+}
+
+/// Split a TODO keyword sequence like `TODO NEXT | DONE` into the keywords
+/// for unfinished and for finished states. Without a `|` the last keyword is
+/// the finished state. Fast access keys like the `(t)` in `TODO(t)` are
+/// dropped.
+pub fn split_sequence<S: AsRef<str>>(words: &[S]) -> (Vec<String>, Vec<String>) {
+    let words: Vec<_> = words.iter()
+        .map(|word| {
+            let word = word.as_ref();
+            word.find('(').map_or(word, |paren| &word[..paren])
+        })
+        .filter(|word| !word.is_empty())
+        .collect();
+    let bar = words.iter().position(|&word| word == "|")
+        .unwrap_or(words.len().saturating_sub(1));
+    let done_start = if words.get(bar) == Some(&"|") { bar + 1 } else { bar };
+    let todo = words[..bar].iter().map(|&word| word.into()).collect();
+    let done = words[done_start..].iter()
+        .filter(|&&word| word != "|")
+        .map(|&word| word.into())
+        .collect();
+    (todo, done)
+}
+
+/// The TODO keyword sequences declared by `#+TODO:`, `#+SEQ_TODO:` and
+/// `#+TYP_TODO:` lines anywhere in `text`, in order, split by
+/// `split_sequence`.
+pub fn find_todo_sequences(text: &str) -> Vec<(Vec<String>, Vec<String>)> {
+    text.lines()
+        .filter_map(keyword_line)
+        .filter(|&(key, _)| {
+            let key = key.to_ascii_uppercase();
+            key == "TODO" || key == "SEQ_TODO" || key == "TYP_TODO"
+        })
+        .map(|(_, value)| split_sequence(&value.split_whitespace().collect::<Vec<_>>()))
+        .collect()
+}
+
+/// The TODO keywords declared anywhere in `text`, finished or not, in order.
+pub fn find_todo_keywords(text: &str) -> Vec<String> {
+    let mut keywords: Vec<String> = Vec::new();
+    for (todo, done) in find_todo_sequences(text) {
+        for keyword in todo.into_iter().chain(done) {
+            if !keywords.contains(&keyword) {
+                keywords.push(keyword);
+            }
+        }
+    }
+    keywords
+}
+
+/// The keywords for finished states declared anywhere in `text`, in order.
+pub fn find_done_keywords(text: &str) -> Vec<String> {
+    let mut keywords: Vec<String> = Vec::new();
+    for (_, done) in find_todo_sequences(text) {
+        for keyword in done {
+            if !keywords.contains(&keyword) {
+                keywords.push(keyword);
+            }
+        }
+    }
+    keywords
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_keyword() {
+        let lines = ["#+TITLE: My notes", "#+options:", "#+ not: one", "#+BEGIN_SRC"];
+        let offsets = span::line_offsets(&lines.join("\n"), 0);
+        let (title, next) = parse_keyword(&lines, &offsets, 0).unwrap();
+        assert_eq!((title.key(), title.value(), next), ("TITLE", "My notes", 1));
+        let (options, _) = parse_keyword(&lines, &offsets, 1).unwrap();
+        assert_eq!((options.key(), options.value()), ("options", ""));
+        assert_eq!(options.span(), Span::new(18, 28));
+        assert!(parse_keyword(&lines, &offsets, 2).is_none());
+        assert!(parse_keyword(&lines, &offsets, 3).is_none());
+
+        assert_eq!(find_todo_keywords("#+TODO: TODO(t) NEXT | DONE(d!)
+#+seq_todo: WAIT TODO
+#+TITLE: x"), ["TODO", "NEXT", "DONE", "WAIT"]);
+        assert_eq!(find_done_keywords("#+TODO: TODO | DONE CANCELLED\n#+TODO: WAIT"),
+                   ["DONE", "CANCELLED", "WAIT"]);
+        assert_eq!(split_sequence(&["TODO", "NEXT", "DONE"]),
+                   (vec!["TODO".into(), "NEXT".into()], vec!["DONE".to_string()]));
+        assert_eq!(find_keyword("#+priorities: 1 5 3\n#+TITLE: x", "PRIORITIES"), Some("1 5 3"));
+    }
+
+    #[test]
+    fn test_parse_affiliated() {
+        let lines = ["#+NAME: prices", "#+attr_html: :class wide :border 1",
+                     "#+ATTR_LATEX: :environment longtable :align l|r", "#+ATTR_HTML: :border 2",
+                     "#+TITLE: x"];
+        let offsets = span::line_offsets(&lines.join("\n"), 0);
+        let (affiliated, next) = parse_affiliated(&lines, &offsets, 0).unwrap();
+        assert_eq!(next, 4);
+        assert_eq!(affiliated.name(), Some("prices"));
+        assert_eq!(affiliated.caption(), None);
+        let html: Vec<_> = affiliated.attributes("HTML").unwrap().iter()
+            .map(|(key, value)| (&key[..], &value[..]))
+            .collect();
+        assert_eq!(html, [(":border", "2"), (":class", "wide")]);
+        assert_eq!(affiliated.attribute("latex", ":align"), Some("l|r"));
+        assert_eq!(affiliated.attribute_backends().collect::<Vec<_>>(), ["HTML", "LATEX"]);
+        assert!(affiliated.attributes("odt").is_none());
+        assert!(parse_affiliated(&lines, &offsets, 4).is_none());
+
+        let lines = ["#+CAPTION[Prices]: Prices in /May/", "#+RESULTS[a1b2]:", "#+TODO[x]: y"];
+        let offsets = span::line_offsets(&lines.join("\n"), 0);
+        let (affiliated, next) = parse_affiliated(&lines, &offsets, 0).unwrap();
+        assert_eq!(next, 2);
+        assert_eq!(affiliated.caption(), Some("Prices in /May/"));
+        assert_eq!(affiliated.short_caption(), Some("Prices"));
+        assert_eq!(affiliated.caption_objects().unwrap()[1],
+                   Object::Italic(vec![Object::Text("May".into())]));
+        assert_eq!(affiliated.keywords()[1].key(), "RESULTS");
+        assert_eq!(affiliated.keywords()[1].optional(), Some("a1b2"));
+        let (todo, _) = parse_keyword(&lines, &offsets, 2).unwrap();
+        assert_eq!((todo.key(), todo.optional()), ("TODO[x]", None));
+    }
+}
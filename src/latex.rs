@@ -0,0 +1,87 @@
+use keyword::{Affiliate, Affiliated};
+use span::{self, Shift, Span};
+
+/// A LaTeX environment standing on its own lines:
+///
+/// ```ignore
+/// \begin{equation}
+/// e^{i\pi} + 1 = 0
+/// \end{equation}
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct LatexEnvironment {
+    name: String,
+    text: String,
+    affiliated: Affiliated,
+    span: Span,
+}
+
+impl LatexEnvironment {
+    /// The name of the environment, like `equation` or `align*`.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The environment as written, from `\begin` to `\end`.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn affiliated(&self) -> &Affiliated {
+        &self.affiliated
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+}
+
+impl Affiliate for LatexEnvironment {
+    fn affiliated_mut(&mut self) -> &mut Affiliated {
+        &mut self.affiliated
+    }
+}
+
+impl Shift for LatexEnvironment {
+    fn shift(&mut self, delta: isize) {
+        self.affiliated.shift(delta);
+        self.span.shift(delta);
+    }
+}
+
+/// Try to parse a LaTeX environment starting at `lines[start]`. Returns it and
+/// the index of the first line after its `\end` line.
+pub fn parse_latex_environment(lines: &[&str], offsets: &[usize], start: usize)
+                               -> Option<(LatexEnvironment, usize)> {
+    let rest = lines[start].trim().strip_prefix("\\begin{")?;
+    let close = rest.find('}')?;
+    let name = &rest[..close];
+    if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '*') {
+        return None;
+    }
+    let end_line = format!("\\end{{{}}}", name);
+    let end = (start..lines.len()).find(|&i| lines[i].trim().starts_with(&end_line))?;
+    Some((LatexEnvironment {
+        name: name.into(),
+        text: lines[start..end + 1].join("\n"),
+        affiliated: Affiliated::default(),
+        span: span::lines_span(lines, offsets, start, end + 1),
+    }, end + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_latex_environment() {
+        let lines = ["\\begin{align*}", "a &= b", "\\end{align*}", "\\begin{never}"];
+        let offsets = span::line_offsets(&lines.join("\n"), 0);
+        let (latex, next) = parse_latex_environment(&lines, &offsets, 0).unwrap();
+        assert_eq!(next, 3);
+        assert_eq!(latex.name(), "align*");
+        assert_eq!(latex.text(), "\\begin{align*}\na &= b\n\\end{align*}");
+        assert!(parse_latex_environment(&lines, &offsets, 3).is_none());
+    }
+}
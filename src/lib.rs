@@ -1,10 +1,450 @@
 extern crate regex;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "tracing")]
+extern crate tracing;
+
+use std::collections::HashMap;
+use std::mem;
+use std::sync::{Arc, OnceLock};
+
+#[macro_use]
+mod trace;
+
+pub mod agenda;
+pub mod annotations;
+pub mod bookmarks;
+pub mod columns;
+pub mod contacts;
+pub mod daily;
+pub mod diff;
+pub mod drill;
+pub mod export;
+pub mod gantt;
+pub mod invoice;
+pub mod kanban;
+pub mod lint;
+pub mod planner;
+pub mod store;
+pub mod tangle;
+
+mod babel;
+mod block;
+mod capabilities;
+mod clock;
+mod comment;
+mod complexity;
+mod drawer;
+mod encoding;
+mod error;
+mod events;
+mod fixed_width;
+mod footnote;
+mod formula;
+#[cfg(feature = "http")]
+mod http;
+mod include;
+mod inlinetask;
+mod keyword;
+mod latex;
+mod list;
+mod now;
+mod object;
+mod outline;
+mod paragraph;
+mod planning;
+mod property;
+mod raw;
+mod refile;
+mod rule;
+mod span;
+mod statistics;
+mod symbol;
+mod table;
+mod tags;
+mod target;
+mod timestamp;
+mod todo;
+mod visit;
+mod writer;
+
+pub use babel::{BabelCall, HeaderArgs, Noweb, Tangle};
+pub use block::{Block, BlockKind, DynamicBlock};
+pub use capabilities::{Capabilities, capabilities};
+pub use clock::{Clock, ClockEntry, Session};
+pub use comment::{Comment, CommentEntry};
+pub use complexity::ComplexityReport;
+pub use drawer::{Drawer, PropertyDrawer};
+pub use encoding::{Decoded, Encoding, decode};
+pub use error::{Diagnostic, ParseError};
+pub use events::{Event, Events};
+pub use fixed_width::FixedWidth;
+pub use footnote::{FootnoteDefinition, FootnoteReference};
+pub use formula::FormulaError;
+#[cfg(feature = "http")]
+pub use http::HttpLoader;
+pub use include::{Loader, Provenance};
+pub use inlinetask::Inlinetask;
+pub use keyword::{Affiliated, Keyword};
+pub use latex::LatexEnvironment;
+pub use list::{BulletStyle, Checkbox, ListItem, ListKind, PlainList};
+pub use now::{Now, SystemNow};
+pub use object::{Entity, Link, LinkType, Object, Script, Scripts, link_radio_targets,
+                 parse_objects};
+pub use outline::Cursor;
+pub use paragraph::Paragraph;
+pub use planning::Planning;
+pub use property::{PropertyError, PropertySchema, PropertyType, parse_duration};
+pub use raw::Raw;
+pub use rule::HorizontalRule;
+pub use span::{Span, TextEdit, line_column};
+pub use statistics::StatisticsCookie;
+pub use symbol::Symbol;
+
+use keyword::Affiliate;
+use span::Shift;
+pub use table::{Table, TableRow};
+pub use tags::TagQuery;
+pub use target::{LinkIndex, LinkTarget};
+pub use timestamp::{Date, Delay, Repeater, RepeaterKind, Time, TimeUnit, Timestamp,
+                    TimestampStyle};
+pub use todo::{TodoConfig, TodoError, TodoLogging};
+pub use visit::{Descendants, Visitor};
+pub use writer::{TimestampFormat, WriterConfig};
+
+/// A parsed org document.
+///
+/// With the `serde` feature enabled, the document and every node in it
+/// implement `Serialize` and `Deserialize`. Structs serialize as maps of their
+/// fields and enums use serde's default externally tagged shape, so a bold
+/// object becomes `{"Bold": [{"Text": "word"}]}`.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub struct Document {
     /// Text before the first headline in the document also belongs to a
     /// section.
     first_section: Option<Section>,
     headlines: Vec<Headline>,
+    warnings: Vec<Diagnostic>,
+    todo_keywords: Vec<String>,
+    done_keywords: Vec<String>,
+    priorities: Priorities,
+    footnote_section: String,
+    encoding: Encoding,
+    provenance: Vec<Provenance>,
+    /// The parser the document was read with, along with the TODO keywords
+    /// and priorities declared in it, to read sections again after an edit.
+    #[cfg_attr(feature = "serde", serde(skip, default = "default_parser"))]
+    parser: Arc<DocumentParser>,
+}
+
+impl Document {
+    /// The section holding any text before the first headline.
+    pub fn first_section(&self) -> Option<&Section> {
+        self.first_section.as_ref()
+    }
+
+    /// The malformed constructs found while parsing, like blocks and drawers
+    /// that are never closed, in the order they appear. The parser reads them
+    /// as plain text. `DocumentParser::parse_outline` doesn't look for any.
+    pub fn warnings(&self) -> &[Diagnostic] {
+        &self.warnings
+    }
+
+    /// The TODO keywords in effect for the document: those given to the
+    /// parser and those declared in its `#+TODO:` lines, finished or not.
+    pub fn todo_keywords(&self) -> &[String] {
+        &self.todo_keywords
+    }
+
+    /// The TODO keywords that mark a headline as finished, or `DONE` if none
+    /// are set.
+    pub fn done_keywords(&self) -> &[String] {
+        &self.done_keywords
+    }
+
+    /// The range of priorities, from `#+PRIORITIES:` or the parser.
+    pub fn priorities(&self) -> Priorities {
+        self.priorities
+    }
+
+    /// The headline holding the footnote definitions, like org's
+    /// `org-footnote-section`. Exporters leave it out.
+    pub fn footnote_section(&self) -> Option<&Headline> {
+        self.all_headlines().find(|headline| headline.title == self.footnote_section)
+    }
+
+    /// The top-level headlines of the document.
+    pub fn headlines(&self) -> &[Headline] {
+        &self.headlines
+    }
+
+    /// Iterate over every headline in the document, depth-first, in the order
+    /// they appear in the text.
+    pub fn all_headlines(&self) -> Headlines<'_> {
+        Headlines::new(&self.headlines)
+    }
+
+    /// All footnote definitions in the document, by label.
+    pub fn footnotes(&self) -> HashMap<&str, &FootnoteDefinition> {
+        let sections = self.first_section.iter()
+            .chain(self.all_headlines().filter_map(|h| h.section.as_ref()));
+        let mut footnotes = HashMap::new();
+        for section in sections {
+            for content in &section.contents {
+                if let Content::Greater(GreaterElement::Footnote(ref definition)) = *content {
+                    footnotes.insert(definition.label(), definition);
+                }
+            }
+        }
+        footnotes
+    }
+
+    /// The keywords of the document, like `#+TITLE:` and `#+OPTIONS:`, by
+    /// key in upper case. The values of a repeated keyword are joined with
+    /// spaces.
+    pub fn keywords(&self) -> HashMap<String, String> {
+        let sections = self.first_section.iter()
+            .chain(self.all_headlines().filter_map(|h| h.section.as_ref()));
+        let mut keywords: HashMap<String, String> = HashMap::new();
+        for section in sections {
+            for content in &section.contents {
+                if let Content::Element(Element::Keyword(ref keyword)) = *content {
+                    let value = keywords.entry(keyword.key().to_ascii_uppercase()).or_default();
+                    if !value.is_empty() && !keyword.value().is_empty() {
+                        value.push(' ');
+                    }
+                    value.push_str(keyword.value());
+                }
+            }
+        }
+        keywords
+    }
+
+    /// Every keyword line of the document, grouped by key in upper case, the
+    /// keys in the order they first appear and the lines of each in order,
+    /// with their spans.
+    pub fn keyword_table(&self) -> Vec<(String, Vec<&Keyword>)> {
+        let sections = self.first_section.iter()
+            .chain(self.all_headlines().filter_map(|h| h.section.as_ref()));
+        let mut table: Vec<(String, Vec<&Keyword>)> = Vec::new();
+        for section in sections {
+            for content in &section.contents {
+                if let Content::Element(Element::Keyword(ref keyword)) = *content {
+                    let key = keyword.key().to_ascii_uppercase();
+                    match table.iter_mut().find(|(k, _)| *k == key) {
+                        Some((_, lines)) => lines.push(keyword),
+                        None => table.push((key, vec![keyword])),
+                    }
+                }
+            }
+        }
+        table
+    }
+
+    /// The value of the last `#+KEY:` line for `key`, matched ignoring case,
+    /// which is the one org goes by for most settings.
+    pub fn keyword_value(&self, key: &str) -> Option<&str> {
+        let key = key.to_ascii_uppercase();
+        let (_, lines) = self.keyword_table().into_iter().find(|(k, _)| *k == key)?;
+        lines.last().map(|keyword| keyword.value())
+    }
+
+    /// Which subscripts and superscripts to export, from the `^` item of
+    /// `#+OPTIONS:`.
+    pub fn scripts(&self) -> Scripts {
+        let options = self.keywords().remove("OPTIONS").unwrap_or_default();
+        match options.split_whitespace().filter_map(|item| item.strip_prefix("^:")).next_back() {
+            Some("{}") => Scripts::Braced,
+            Some("nil") => Scripts::None,
+            _ => Scripts::All,
+        }
+    }
+
+    /// Every footnote reference in the document, in order, including those in
+    /// headline titles.
+    pub fn footnote_references(&self) -> Vec<FootnoteReference> {
+        let mut references = Vec::new();
+        if let Some(ref section) = self.first_section {
+            references.extend(footnote::find_references(&section.text));
+        }
+        for headline in self.all_headlines() {
+            references.extend(footnote::find_references(&headline.title));
+            if let Some(ref section) = headline.section {
+                references.extend(footnote::find_references(&section.text));
+            }
+        }
+        references
+    }
+
+    /// Every link in the document, in order, including those in headline
+    /// titles.
+    pub fn links(&self) -> Vec<Link> {
+        let mut links = Vec::new();
+        if let Some(ref section) = self.first_section {
+            links.extend(object::find_links(&section.text));
+        }
+        for headline in self.all_headlines() {
+            links.extend(object::find_links(&headline.title));
+            if let Some(ref section) = headline.section {
+                links.extend(object::find_links(&section.text));
+            }
+        }
+        links
+    }
+
+    /// Find the headline an internal link points to: `id:` and `#custom-id`
+    /// links match the `ID` and `CUSTOM_ID` properties, `*Title` and fuzzy
+    /// links match headline titles. Other links point outside the document,
+    /// so they resolve to `None`.
+    pub fn resolve_link(&self, link: &Link) -> Option<&Headline> {
+        let path = link.path();
+        let mut headlines = self.all_headlines();
+        match link.link_type() {
+            LinkType::Id => headlines.find(|h| h.property("ID") == Some(path)),
+            LinkType::CustomId => headlines.find(|h| h.property("CUSTOM_ID") == Some(path)),
+            LinkType::Headline | LinkType::Fuzzy => headlines.find(|h| h.title == path),
+            _ => None,
+        }
+    }
+
+    /// The innermost headline, element or list item that was parsed from the
+    /// text around the byte `offset`. See `object_at_offset` for the objects
+    /// in it.
+    pub fn node_at_offset(&self, offset: usize) -> Option<Node<'_>> {
+        let mut section = self.first_section.as_ref();
+        let mut headline = None;
+        let mut headlines = &self.headlines[..];
+        while let Some(h) = headlines.iter().find(|h| h.span.contains(offset)) {
+            section = h.section.as_ref();
+            headline = Some(h);
+            headlines = &h.headlines;
+        }
+        section.filter(|section| section.span.contains(offset))
+            .and_then(|section| content_at_offset(&section.contents, offset))
+            .or(headline.map(Node::Headline))
+    }
+
+    /// The innermost inline object in a headline title or a paragraph that
+    /// was parsed from the text around the byte `offset`, and its span.
+    /// Objects aren't kept in the tree but parsed from the text of their
+    /// element when asked for, so they aren't among the nodes of
+    /// `node_at_offset`.
+    pub fn object_at_offset(&self, offset: usize) -> Option<(Object, Span)> {
+        match self.node_at_offset(offset)? {
+            Node::Content(&Content::Element(Element::Paragraph(ref paragraph))) => {
+                paragraph.object_at(offset)
+            }
+            Node::Headline(headline) => {
+                let start = headline.span.start + headline.title_offset;
+                let offset = offset.checked_sub(start)?;
+                let (object, span) = object::object_at(&headline.title, offset)?;
+                Some((object, Span::new(start + span.start, start + span.end)))
+            }
+            _ => None,
+        }
+    }
+
+    /// Prefix every headline title with its outline number, like
+    /// `org-num-mode` displays it (`1 `, `1.1 `, `1.2 `, `2 `...).
+    ///
+    /// Existing numbers are replaced, so this can be re-run after editing the
+    /// outline. A headline that skips levels gets zeros for the missing
+    /// components, e.g. `*` followed by `***` numbers the latter `1.0.1`.
+    /// Commented subtrees, the footnote section and subtrees with an
+    /// `UNNUMBERED` property other than `nil` are skipped and left as they
+    /// are, and don't count towards the numbers of the headlines after them.
+    pub fn number_headlines(&mut self) {
+        fn visit(headlines: &mut [Headline], footnote_section: &str, counters: &mut Vec<u32>) {
+            for headline in headlines {
+                if headline.commented || headline.title == footnote_section
+                    || headline.property("UNNUMBERED").is_some_and(|value| value != "nil") {
+                    continue;
+                }
+                let level = headline.level as usize;
+                counters.resize(level, 0);
+                counters[level - 1] += 1;
+                let number = counters.iter()
+                    .map(|n| n.to_string())
+                    .collect::<Vec<_>>()
+                    .join(".");
+                let title = strip_number_prefix(&headline.title).to_string();
+                headline.title = format!("{} {}", number, title);
+                visit(&mut headline.headlines, footnote_section, counters);
+            }
+        }
+
+        visit(&mut self.headlines, &self.footnote_section, &mut Vec::new());
+    }
+
+    /// Remove outline numbers previously inserted by `number_headlines`.
+    ///
+    /// Numbers are recognized purely by their shape, so a title that
+    /// genuinely starts with something like `2024 ` will lose it too.
+    pub fn strip_numbering(&mut self) {
+        visit_headlines_mut(&mut self.headlines, &mut |headline| {
+            headline.title = strip_number_prefix(&headline.title).to_string();
+        });
+    }
+
+    pub fn headlines_mut(&mut self) -> &mut [Headline] {
+        &mut self.headlines
+    }
+
+    /// Insert a top-level headline at `index`, changing its level to 1 and
+    /// shifting its subtree along with it.
+    pub fn insert_headline(&mut self, index: usize, mut headline: Headline) {
+        headline.set_level(1);
+        self.headlines.insert(index, headline);
+    }
+
+    /// Remove the top-level headline at `index` along with its subtree.
+    /// Moving a headline is a remove followed by an insert.
+    pub fn remove_headline(&mut self, index: usize) -> Headline {
+        self.headlines.remove(index)
+    }
+
+    /// Write the document back out as org text.
+    ///
+    /// Headline lines are rebuilt from their parts, so tags are separated
+    /// from the title by a single space and bodies holding only whitespace
+    /// are dropped. Everything else is reproduced as it was parsed. See
+    /// `to_org_string_with` for other formatting.
+    pub fn to_org_string(&self) -> String {
+        self.to_org_string_with(&WriterConfig::new())
+    }
+}
+
+impl Shift for Document {
+    fn shift(&mut self, delta: isize) {
+        self.first_section.shift(delta);
+        self.headlines.shift(delta);
+        self.warnings.shift(delta);
+    }
+}
+
+/// Call `f` on every headline in the tree, depth-first in document order.
+fn visit_headlines_mut<F: FnMut(&mut Headline)>(headlines: &mut [Headline], f: &mut F) {
+    for headline in headlines {
+        f(headline);
+        visit_headlines_mut(&mut headline.headlines, f);
+    }
+}
+
+/// Returns the title without a leading `1.2.3 ` style outline number.
+fn strip_number_prefix(title: &str) -> &str {
+    let mut expect_digit = true;
+    for (i, c) in title.char_indices() {
+        match c {
+            _ if c.is_ascii_digit() => expect_digit = false,
+            '.' if !expect_digit => expect_digit = true,
+            ' ' if !expect_digit || i > 0 && title[..i].ends_with('.') => {
+                return &title[i + 1..];
+            }
+            _ => return title,
+        }
+    }
+    title
 }
 
 /// A headline contains directly one section (optionally), followed by any
@@ -17,135 +457,1230 @@ pub struct Document {
 /// ```
 ///
 /// - STARS is a string starting at column 0, containing at least one asterisk
-/// (and up to org-inlinetask-min-level if org-inlinetask library is loaded) and
-/// ended by a space character. The number of asterisks is used to define the
-/// level of the headline. It's the sole compulsory part of a headline.
+///   (and up to org-inlinetask-min-level if org-inlinetask library is loaded)
+///   and ended by a space character. The number of asterisks is used to define
+///   the level of the headline. It's the sole compulsory part of a headline.
 ///
 /// - KEYWORD is a TODO keyword, which has to belong to the list defined in
-/// org-todo-keywords-1. Case is significant.
+///   org-todo-keywords-1. Case is significant.
 ///
 /// - PRIORITY is a priority cookie, i.e. a single letter preceded by a hash
-/// sign # and enclosed within square brackets.
+///   sign # and enclosed within square brackets.
 ///
 /// - TITLE can be made of any character but a new line. Though, it will match
-/// after every other part have been matched.
+///   after every other part have been matched. A title starting with the word
+///   `COMMENT` makes the headline commented, and the word is not part of the
+///   title.
 ///
 /// - TAGS is made of words containing any alpha-numeric character, underscore,
-/// at sign, hash sign or percent sign, and separated with colons.
+///   at sign, hash sign or percent sign, and separated with colons.
 #[derive(Debug, Clone)]
-struct Headline {
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Headline {
     level: u32,
-    keyword: Option<String>,
+    keyword: Option<Symbol>,
     priority: Option<char>,
+    commented: bool,
     title: String,
-    tags: Vec<String>,
+    /// Where the title starts in the headline line.
+    title_offset: usize,
+    tags: Vec<Symbol>,
+    planning: Planning,
+    properties: PropertyDrawer,
     section: Option<Section>,
     headlines: Vec<Headline>,
+    span: Span,
+    /// The parser the headline was read with, see `Document::parser`.
+    #[cfg_attr(feature = "serde", serde(skip, default = "default_parser"))]
+    parser: Arc<DocumentParser>,
+}
+
+impl Headline {
+    /// A new headline with neither a body nor children.
+    pub fn new<S: Into<String>>(level: u32, title: S) -> Headline {
+        Headline {
+            level: level.max(1),
+            keyword: None,
+            priority: None,
+            commented: false,
+            title: title.into(),
+            title_offset: 0,
+            tags: Vec::new(),
+            planning: Planning::default(),
+            properties: PropertyDrawer::default(),
+            section: None,
+            headlines: Vec::new(),
+            span: Span::default(),
+            parser: default_parser(),
+        }
+    }
+
+    /// The number of stars in front of the headline.
+    pub fn level(&self) -> u32 {
+        self.level
+    }
+
+    pub fn keyword(&self) -> Option<&str> {
+        self.keyword.as_ref().map(|s| s.as_str())
+    }
+
+    pub fn priority(&self) -> Option<char> {
+        self.priority
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// Whether the title starts with `COMMENT`. Exporters leave out commented
+    /// headlines along with their children.
+    pub fn is_commented(&self) -> bool {
+        self.commented
+    }
+
+    /// Whether the headline has the `ARCHIVE` tag. Exporters only show the
+    /// headline of an archived subtree by default.
+    pub fn is_archived(&self) -> bool {
+        self.tags.iter().any(|tag| tag == "ARCHIVE")
+    }
+
+    /// The title parsed into inline objects such as emphasis and links.
+    pub fn title_objects(&self) -> Vec<Object> {
+        parse_objects(&self.title)
+    }
+
+    pub fn tags(&self) -> &[Symbol] {
+        &self.tags
+    }
+
+    /// The SCHEDULED, DEADLINE and CLOSED timestamps from the planning line.
+    pub fn planning(&self) -> &Planning {
+        &self.planning
+    }
+
+    pub fn scheduled(&self) -> Option<&Timestamp> {
+        self.planning.scheduled()
+    }
+
+    pub fn deadline(&self) -> Option<&Timestamp> {
+        self.planning.deadline()
+    }
+
+    pub fn closed(&self) -> Option<&Timestamp> {
+        self.planning.closed()
+    }
+
+    /// The headline's property drawer, empty if it has none.
+    pub fn properties(&self) -> &PropertyDrawer {
+        &self.properties
+    }
+
+    /// Look up a property of this headline, ignoring the case of `key`.
+    pub fn property(&self, key: &str) -> Option<&str> {
+        self.properties.get(key)
+    }
+
+    /// The section directly below the headline, before any child headline.
+    pub fn section(&self) -> Option<&Section> {
+        self.section.as_ref()
+    }
+
+    /// Where the headline was parsed from, from its stars to the end of its
+    /// last descendant. Headlines built in code have an empty span.
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    /// Every timestamp in the title and in the section directly below the
+    /// headline, including its planning line, in order.
+    pub fn timestamps(&self) -> Vec<Timestamp> {
+        let mut timestamps = timestamp::find_timestamps(&self.title);
+        if let Some(ref section) = self.section {
+            timestamps.extend(section.timestamps());
+        }
+        timestamps
+    }
+
+    /// The direct children of this headline.
+    pub fn children(&self) -> &[Headline] {
+        &self.headlines
+    }
+
+    /// Iterate over all headlines below this one, depth-first, not including
+    /// this headline itself.
+    pub fn descendants(&self) -> Headlines<'_> {
+        Headlines::new(&self.headlines)
+    }
+
+    pub fn set_title<S: Into<String>>(&mut self, title: S) {
+        self.title = title.into();
+    }
+
+    pub fn set_keyword(&mut self, keyword: Option<&str>) {
+        self.keyword = keyword.map(Symbol::new);
+    }
+
+    pub fn set_priority(&mut self, priority: Option<char>) {
+        self.priority = priority;
+    }
+
+    pub fn set_commented(&mut self, commented: bool) {
+        self.commented = commented;
+    }
+
+    /// Add a tag, unless the headline already has it.
+    pub fn add_tag(&mut self, tag: &str) {
+        if !self.tags.iter().any(|t| t == tag) {
+            self.tags.push(Symbol::new(tag));
+        }
+    }
+
+    /// Remove a tag, returning whether the headline had it.
+    pub fn remove_tag(&mut self, tag: &str) -> bool {
+        let before = self.tags.len();
+        self.tags.retain(|t| t != tag);
+        self.tags.len() != before
+    }
+
+    /// Set a property, creating the property drawer if needed.
+    pub fn set_property(&mut self, key: &str, value: &str) {
+        self.properties.insert(key, value);
+        self.write_properties();
+    }
+
+    /// Remove a property, and the property drawer along with the last one.
+    pub fn remove_property(&mut self, key: &str) -> Option<String> {
+        let value = self.properties.remove(key)?;
+        self.write_properties();
+        Some(value)
+    }
+
+    /// Replace the property drawer in the section text, so the writer picks
+    /// up changed properties. The rest of the text is kept as it is, line
+    /// endings included, and the new drawer lines end like its other lines.
+    fn write_properties(&mut self) {
+        let (mut text, offset) = match self.section.take() {
+            Some(section) => (section.text, section.span.start),
+            None => (String::new(), self.body_start()),
+        };
+        let lines: Vec<&str> = text.lines().collect();
+        let offsets = span::line_offsets(&text, 0);
+        let start = match lines.first() {
+            Some(line) if planning::parse_planning(line, offset).is_some() => 1,
+            _ => 0,
+        };
+        let end = drawer::parse_property_drawer(&lines, &offsets, start)
+            .map_or(start, |(_, end)| end);
+        let newline = if text.contains("\r\n") { "\r\n" } else { "\n" };
+        let mut drawer = String::new();
+        if !self.properties.is_empty() {
+            drawer.push_str(":PROPERTIES:");
+            drawer.push_str(newline);
+            for (key, value) in self.properties.iter() {
+                drawer.push(':');
+                drawer.push_str(key);
+                drawer.push(':');
+                if !value.is_empty() {
+                    drawer.push(' ');
+                    drawer.push_str(value);
+                }
+                drawer.push_str(newline);
+            }
+            drawer.push_str(":END:");
+            drawer.push_str(newline);
+        }
+        let ends_with_newline = text.is_empty() || text.ends_with('\n');
+        let at = |line: usize| offsets.get(line).cloned().unwrap_or(text.len());
+        let (from, to) = (at(start), at(end));
+        if from == text.len() && !ends_with_newline && !drawer.is_empty() {
+            drawer.insert_str(0, newline);
+        }
+        text.replace_range(from..to, &drawer);
+        if !ends_with_newline && text.ends_with('\n') {
+            let len = text.len() - if text.ends_with("\r\n") { 2 } else { 1 };
+            text.truncate(len);
+        }
+        self.section = Section::from_text(&self.parser, &text, offset);
+    }
+
+    /// Where the section below the headline line starts, or would start if
+    /// there is none: before the first child.
+    fn body_start(&self) -> usize {
+        self.headlines.first().map_or(self.span.end, |child| child.span.start)
+    }
+
+    /// Change the level of the headline, shifting its whole subtree by the
+    /// same amount. Levels never go below 1.
+    pub fn set_level(&mut self, level: u32) {
+        let level = level.max(1);
+        let (from, to) = (self.level as i64, level as i64);
+        visit_headlines_mut(std::slice::from_mut(self), &mut |headline| {
+            headline.level = (headline.level as i64 - from + to).max(1) as u32;
+        });
+    }
+
+    /// Move the subtree one level up. Returns false, without changing
+    /// anything, for a headline that is already at level 1.
+    pub fn promote(&mut self) -> bool {
+        if self.level == 1 {
+            return false;
+        }
+        let level = self.level - 1;
+        self.set_level(level);
+        true
+    }
+
+    /// Move the subtree one level down.
+    pub fn demote(&mut self) {
+        let level = self.level + 1;
+        self.set_level(level);
+    }
+
+    pub fn children_mut(&mut self) -> &mut [Headline] {
+        &mut self.headlines
+    }
+
+    /// Insert a child headline at `index`, changing its level to one below
+    /// this headline and shifting its subtree along with it.
+    pub fn insert_child(&mut self, index: usize, mut child: Headline) {
+        child.set_level(self.level + 1);
+        self.headlines.insert(index, child);
+    }
+
+    /// Remove the child at `index` along with its subtree.
+    pub fn remove_child(&mut self, index: usize) -> Headline {
+        self.headlines.remove(index)
+    }
+
+    /// Write the headline and its subtree back out as org text, see
+    /// `Document::to_org_string`.
+    pub fn to_org_string(&self) -> String {
+        self.to_org_string_with(&WriterConfig::new())
+    }
+
+    /// Attach the text between this headline and the next one, which starts
+    /// at `offset` in the document.
+    fn set_body(&mut self, parser: &DocumentParser, body: &str, offset: usize) {
+        let lines: Vec<&str> = body.lines().collect();
+        let offsets = span::line_offsets(body, offset);
+        let mut start = 0;
+        if let Some(planning) = lines.first().and_then(|line| planning::parse_planning(line, offset)) {
+            self.planning = planning;
+            start = 1;
+        }
+        if let Some((properties, _)) = drawer::parse_property_drawer(&lines, &offsets, start) {
+            self.properties = properties;
+        }
+        self.section = Section::from_text(parser, body, offset);
+    }
+}
+
+impl Shift for Headline {
+    fn shift(&mut self, delta: isize) {
+        self.planning.shift(delta);
+        self.properties.shift(delta);
+        self.section.shift(delta);
+        self.headlines.shift(delta);
+        self.span.shift(delta);
+    }
+}
+
+/// Depth-first iterator over a tree of headlines, see
+/// `Document::all_headlines`.
+#[derive(Debug, Clone)]
+pub struct Headlines<'a> {
+    stack: Vec<std::slice::Iter<'a, Headline>>,
+    without_commented: bool,
+    without_archived: bool,
+}
+
+impl<'a> Headlines<'a> {
+    fn new(headlines: &'a [Headline]) -> Self {
+        Headlines {
+            stack: vec![headlines.iter()],
+            without_commented: false,
+            without_archived: false,
+        }
+    }
+
+    /// Leave out commented headlines and everything under them.
+    pub fn without_commented(mut self) -> Self {
+        self.without_commented = true;
+        self
+    }
+
+    /// Leave out archived headlines and everything under them.
+    pub fn without_archived(mut self) -> Self {
+        self.without_archived = true;
+        self
+    }
+}
+
+impl<'a> Iterator for Headlines<'a> {
+    type Item = &'a Headline;
+
+    fn next(&mut self) -> Option<&'a Headline> {
+        while let Some(top) = self.stack.last_mut().map(|iter| iter.next()) {
+            match top {
+                Some(headline) if self.without_commented && headline.commented => {}
+                Some(headline) if self.without_archived && headline.is_archived() => {}
+                Some(headline) => {
+                    self.stack.push(headline.headlines.iter());
+                    return Some(headline);
+                }
+                None => {
+                    self.stack.pop();
+                }
+            }
+        }
+        None
+    }
 }
 
 /// A section contains directly any greater element or element. Only a headline
 /// can contain a section.
 #[derive(Debug, Clone)]
-struct Section {
-    contents: Vec<GreaterElement>,
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Section {
+    text: String,
+    contents: Vec<Content>,
+    span: Span,
 }
 
-// @Todo: Implement greater elements
-#[allow(unused)]
+impl Section {
+    /// A section for the raw `text` between two headlines, or `None` if there
+    /// is nothing but whitespace there.
+    /// The text starts at `offset` in the document.
+    fn from_text(parser: &DocumentParser, text: &str, offset: usize) -> Option<Section> {
+        if text.trim().is_empty() {
+            return None;
+        }
+        let lines: Vec<&str> = text.lines().collect();
+        Some(Section {
+            text: text.into(),
+            contents: parse_contents(parser, &lines, &span::line_offsets(text, offset)),
+            span: Span::new(offset, offset + text.len()),
+        })
+    }
+
+    /// The raw text of the section, exactly as it appears in the document.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Every timestamp in the section's text, in order.
+    pub fn timestamps(&self) -> Vec<Timestamp> {
+        timestamp::find_timestamps(&self.text)
+    }
+
+    /// The elements recognized in the section, in order.
+    pub fn contents(&self) -> &[Content] {
+        &self.contents
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+}
+
+impl Shift for Section {
+    fn shift(&mut self, delta: isize) {
+        self.contents.shift(delta);
+        self.span.shift(delta);
+    }
+}
+
+/// Parse the elements found in the body of a section or greater element,
+/// given as its lines along with the offset of each line in the document.
+/// Lines that don't start any other element are grouped into paragraphs.
+/// Affiliated keywords like `#+NAME:` are attached to the element right
+/// below them, or kept as plain keywords if it can't take them.
+fn parse_contents(parser: &DocumentParser, lines: &[&str], offsets: &[usize]) -> Vec<Content> {
+    let mut contents = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        if let Some((content, next)) = parse_affiliated_element(parser, lines, offsets, i) {
+            contents.push(content);
+            i = next;
+        } else if let Some((content, next)) = parse_element(parser, lines, offsets, i) {
+            contents.push(content);
+            i = next;
+        } else if lines[i].trim().is_empty() {
+            i += 1;
+        } else {
+            let (paragraph, next) = paragraph::parse_paragraph(parser, lines, offsets, i);
+            contents.push(Content::Element(Element::Paragraph(paragraph)));
+            i = next;
+        }
+    }
+    contents
+}
+
+/// Try to parse affiliated keywords starting at `lines[i]` together with the
+/// element they belong to. Returns the element and the index of the first
+/// line after it.
+fn parse_affiliated_element(parser: &DocumentParser, lines: &[&str], offsets: &[usize],
+                            i: usize) -> Option<(Content, usize)> {
+    let (affiliated, start) = keyword::parse_affiliated(lines, offsets, i)?;
+    if start == lines.len() || lines[start].trim().is_empty() {
+        return None;
+    }
+    let (mut content, next) = parse_element(parser, lines, offsets, start).unwrap_or_else(|| {
+        let (paragraph, next) = paragraph::parse_paragraph(parser, lines, offsets, start);
+        (Content::Element(Element::Paragraph(paragraph)), next)
+    });
+    *content.affiliated_mut()? = affiliated;
+    Some((content, next))
+}
+
+/// Try to parse an element other than a paragraph starting at `lines[i]`.
+/// Returns it and the index of the first line after it.
+fn parse_element(parser: &DocumentParser, lines: &[&str], offsets: &[usize], i: usize)
+                 -> Option<(Content, usize)> {
+    if let Some((definition, next)) = footnote::parse_definition(parser, lines, offsets, i) {
+        Some((Content::Greater(GreaterElement::Footnote(definition)), next))
+    } else if let Some((task, next)) = inlinetask::parse_inlinetask(parser, lines, offsets, i) {
+        Some((Content::Greater(GreaterElement::Inlinetask(task)), next))
+    } else if let Some(rule) = rule::parse_rule(lines[i], offsets[i]) {
+        Some((Content::Element(Element::HorizontalRule(rule)), i + 1))
+    } else if let Some((list, next)) = list::parse_list(parser, lines, offsets, i) {
+        Some((Content::Greater(GreaterElement::PlainList(list)), next))
+    } else if let Some((table, next)) = table::parse_table(lines, offsets, i) {
+        Some((Content::Greater(GreaterElement::Table(table)), next))
+    } else if let Some((drawer, next)) = drawer::parse_property_drawer(lines, offsets, i) {
+        Some((Content::Greater(GreaterElement::PropertyDrawer(drawer)), next))
+    } else if let Some((drawer, next)) = drawer::parse_drawer(parser, lines, offsets, i)
+        .filter(|(drawer, _)| parser.allows_drawer(drawer.name())) {
+        Some((Content::Greater(GreaterElement::Drawer(drawer)), next))
+    } else if let Some((block, next)) = block::parse_dynamic_block(parser, lines, offsets, i) {
+        Some((Content::Greater(GreaterElement::DynamicBlock(block)), next))
+    } else if let Some((block, next)) = block::parse_block(parser, lines, offsets, i) {
+        Some((if block.kind().is_greater() {
+            Content::Greater(GreaterElement::Block(block))
+        } else {
+            Content::Element(Element::Block(block))
+        }, next))
+    } else if let Some((latex, next)) = latex::parse_latex_environment(lines, offsets, i) {
+        Some((Content::Element(Element::LatexEnvironment(latex)), next))
+    } else if let Some((call, next)) = babel::parse_babel_call(lines, offsets, i) {
+        Some((Content::Element(Element::BabelCall(call)), next))
+    } else if let Some((keyword, next)) = keyword::parse_keyword(lines, offsets, i) {
+        Some((Content::Element(Element::Keyword(keyword)), next))
+    } else if let Some(clock) = clock::parse_clock(lines[i], offsets[i]) {
+        Some((Content::Element(Element::Clock(clock)), i + 1))
+    } else if let Some((comment, next)) = comment::parse_comment(lines, offsets, i) {
+        Some((Content::Element(Element::Comment(comment)), next))
+    } else if let Some((fixed, next)) = fixed_width::parse_fixed_width(lines, offsets, i) {
+        Some((Content::Element(Element::FixedWidth(fixed)), next))
+    } else if let Some(planning) = planning::parse_planning(lines[i], offsets[i]) {
+        Some((Content::Element(Element::Planning(planning)), i + 1))
+    } else {
+        raw::parse_raw_line(parser, lines[i], offsets[i])
+            .map(|raw| (Content::Element(Element::Raw(raw)), i + 1))
+    }
+}
+
+/// Anything a section or greater element can directly contain.
 #[derive(Debug, Clone)]
-enum GreaterElement {
-    Block,
-    Drawer,
-    DynamicBlock,
-    Footnote,
-    Inlinetask,
-    PlainList,
-    PropertyDrawer,
-    Table,
-}
-
-#[allow(unused)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum Content {
+    Greater(GreaterElement),
+    Element(Element),
+}
+
+impl Content {
+    /// Where the element is in the text, if it was parsed from one.
+    pub fn span(&self) -> Option<Span> {
+        match *self {
+            Content::Greater(ref greater) => match *greater {
+                GreaterElement::Block(ref block) => Some(block.span()),
+                GreaterElement::Drawer(ref drawer) => Some(drawer.span()),
+                GreaterElement::DynamicBlock(ref block) => Some(block.span()),
+                GreaterElement::Footnote(ref definition) => Some(definition.span()),
+                GreaterElement::Inlinetask(ref task) => Some(task.span()),
+                GreaterElement::PlainList(ref list) => Some(list.span()),
+                GreaterElement::PropertyDrawer(ref drawer) => Some(drawer.span()),
+                GreaterElement::Table(ref table) => Some(table.span()),
+            },
+            Content::Element(ref element) => match *element {
+                Element::BabelCall(ref call) => Some(call.span()),
+                Element::Block(ref block) => Some(block.span()),
+                Element::Clock(ref clock) => Some(clock.span()),
+                Element::Comment(ref comment) => Some(comment.span()),
+                Element::FixedWidth(ref fixed) => Some(fixed.span()),
+                Element::HorizontalRule(ref rule) => Some(rule.span()),
+                Element::Keyword(ref keyword) => Some(keyword.span()),
+                Element::LatexEnvironment(ref latex) => Some(latex.span()),
+                Element::Paragraph(ref paragraph) => Some(paragraph.span()),
+                Element::Planning(ref planning) => Some(planning.span()),
+                Element::Raw(ref raw) => Some(raw.span()),
+            },
+        }
+    }
+
+    /// The affiliated keywords of the element, if it is one that can have
+    /// them.
+    pub fn affiliated(&self) -> Option<&Affiliated> {
+        match *self {
+            Content::Greater(ref greater) => match *greater {
+                GreaterElement::Block(ref block) => Some(block.affiliated()),
+                GreaterElement::Drawer(ref drawer) => Some(drawer.affiliated()),
+                GreaterElement::DynamicBlock(ref block) => Some(block.affiliated()),
+                GreaterElement::PlainList(ref list) => Some(list.affiliated()),
+                GreaterElement::Table(ref table) => Some(table.affiliated()),
+                _ => None,
+            },
+            Content::Element(ref element) => match *element {
+                Element::BabelCall(ref call) => Some(call.affiliated()),
+                Element::Block(ref block) => Some(block.affiliated()),
+                Element::FixedWidth(ref fixed) => Some(fixed.affiliated()),
+                Element::HorizontalRule(ref rule) => Some(rule.affiliated()),
+                Element::LatexEnvironment(ref latex) => Some(latex.affiliated()),
+                Element::Paragraph(ref paragraph) => Some(paragraph.affiliated()),
+                _ => None,
+            },
+        }
+    }
+
+    fn affiliated_mut(&mut self) -> Option<&mut Affiliated> {
+        match *self {
+            Content::Greater(ref mut greater) => match *greater {
+                GreaterElement::Block(ref mut block) => Some(block.affiliated_mut()),
+                GreaterElement::Drawer(ref mut drawer) => Some(drawer.affiliated_mut()),
+                GreaterElement::DynamicBlock(ref mut block) => Some(block.affiliated_mut()),
+                GreaterElement::PlainList(ref mut list) => Some(list.affiliated_mut()),
+                GreaterElement::Table(ref mut table) => Some(table.affiliated_mut()),
+                _ => None,
+            },
+            Content::Element(ref mut element) => match *element {
+                Element::BabelCall(ref mut call) => Some(call.affiliated_mut()),
+                Element::Block(ref mut block) => Some(block.affiliated_mut()),
+                Element::FixedWidth(ref mut fixed) => Some(fixed.affiliated_mut()),
+                Element::HorizontalRule(ref mut rule) => Some(rule.affiliated_mut()),
+                Element::LatexEnvironment(ref mut latex) => Some(latex.affiliated_mut()),
+                Element::Paragraph(ref mut paragraph) => Some(paragraph.affiliated_mut()),
+                _ => None,
+            },
+        }
+    }
+
+    /// The elements directly inside, or the items of a plain list.
+    fn children(&self) -> (&[Content], &[ListItem]) {
+        match *self {
+            Content::Greater(GreaterElement::Block(ref block)) => (block.contents(), &[]),
+            Content::Greater(GreaterElement::Drawer(ref drawer)) => (drawer.contents(), &[]),
+            Content::Greater(GreaterElement::DynamicBlock(ref block)) => (block.contents(), &[]),
+            Content::Greater(GreaterElement::Footnote(ref definition)) => {
+                (definition.contents(), &[])
+            }
+            Content::Greater(GreaterElement::Inlinetask(ref task)) => (task.contents(), &[]),
+            Content::Greater(GreaterElement::PlainList(ref list)) => (&[], list.items()),
+            _ => (&[], &[]),
+        }
+    }
+}
+
+impl Shift for Content {
+    fn shift(&mut self, delta: isize) {
+        match *self {
+            Content::Greater(ref mut greater) => match *greater {
+                GreaterElement::Block(ref mut block) => block.shift(delta),
+                GreaterElement::Drawer(ref mut drawer) => drawer.shift(delta),
+                GreaterElement::DynamicBlock(ref mut block) => block.shift(delta),
+                GreaterElement::Footnote(ref mut definition) => definition.shift(delta),
+                GreaterElement::Inlinetask(ref mut task) => task.shift(delta),
+                GreaterElement::PlainList(ref mut list) => list.shift(delta),
+                GreaterElement::PropertyDrawer(ref mut drawer) => drawer.shift(delta),
+                GreaterElement::Table(ref mut table) => table.shift(delta),
+            },
+            Content::Element(ref mut element) => match *element {
+                Element::BabelCall(ref mut call) => call.shift(delta),
+                Element::Block(ref mut block) => block.shift(delta),
+                Element::Clock(ref mut clock) => clock.shift(delta),
+                Element::Comment(ref mut comment) => comment.shift(delta),
+                Element::FixedWidth(ref mut fixed) => fixed.shift(delta),
+                Element::HorizontalRule(ref mut rule) => rule.shift(delta),
+                Element::Keyword(ref mut keyword) => keyword.shift(delta),
+                Element::LatexEnvironment(ref mut latex) => latex.shift(delta),
+                Element::Paragraph(ref mut paragraph) => paragraph.shift(delta),
+                Element::Planning(ref mut planning) => planning.shift(delta),
+                Element::Raw(ref mut raw) => raw.shift(delta),
+            },
+        }
+    }
+}
+
+/// A node of the tree, as found by `Document::node_at_offset`.
+#[derive(Debug, Clone, Copy)]
+pub enum Node<'a> {
+    Headline(&'a Headline),
+    Content(&'a Content),
+    ListItem(&'a ListItem),
+}
+
+/// The innermost node among `contents` whose span contains `offset`.
+fn content_at_offset(contents: &[Content], offset: usize) -> Option<Node<'_>> {
+    let content = contents.iter().find(|c| c.span().is_some_and(|span| span.contains(offset)))?;
+    let (children, items) = content.children();
+    if let Some(item) = items.iter().find(|item| item.span().contains(offset)) {
+        return Some(content_at_offset(item.contents(), offset).unwrap_or(Node::ListItem(item)));
+    }
+    Some(content_at_offset(children, offset).unwrap_or(Node::Content(content)))
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum GreaterElement {
+    /// Center, quote and special blocks.
+    Block(Block),
+    Drawer(Drawer),
+    DynamicBlock(DynamicBlock),
+    Footnote(FootnoteDefinition),
+    Inlinetask(Inlinetask),
+    PlainList(PlainList),
+    PropertyDrawer(PropertyDrawer),
+    Table(Table),
+}
+
 #[derive(Debug, Clone)]
-enum Element {
-    BabelCall,
-    Block,
-    Planning,
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum Element {
+    /// `#+CALL: name(arguments)`.
+    BabelCall(BabelCall),
+    /// Comment, example, export, src and verse blocks.
+    Block(Block),
+    Clock(Clock),
+    Comment(Comment),
+    FixedWidth(FixedWidth),
+    HorizontalRule(HorizontalRule),
+    Keyword(Keyword),
+    LatexEnvironment(LatexEnvironment),
+    Paragraph(Paragraph),
+    Planning(Planning),
+    /// A line that can't be parsed, kept as written, see
+    /// `DocumentParser::lenient`.
+    Raw(Raw),
+}
+
+/// The range of priority cookies, from the highest to the lowest, and the
+/// priority of headlines without a cookie, as set by `#+PRIORITIES: A C B`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Priorities {
+    pub highest: char,
+    pub lowest: char,
+    pub default: char,
+}
+
+impl Default for Priorities {
+    fn default() -> Self {
+        Priorities { highest: 'A', lowest: 'C', default: 'B' }
+    }
+}
+
+impl Priorities {
+    /// Parse the value of a `#+PRIORITIES:` line.
+    fn parse(value: &str) -> Option<Priorities> {
+        let mut chars = value.split_whitespace().map(|word| {
+            let mut chars = word.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Some(c),
+                _ => None,
+            }
+        });
+        let (highest, lowest, default) = (chars.next()??, chars.next()??, chars.next()??);
+        Some(Priorities { highest, lowest, default })
+    }
+
+    /// Whether `priority` is one of the range.
+    pub fn contains(&self, priority: char) -> bool {
+        let (low, high) = if self.highest <= self.lowest {
+            (self.highest, self.lowest)
+        } else {
+            (self.lowest, self.highest)
+        };
+        low <= priority && priority <= high
+    }
 }
 
-struct DocumentParser {
+#[derive(Debug, Clone)]
+pub struct DocumentParser {
     todo_keywords: Vec<String>,
+    done_keywords: Vec<String>,
+    case_insensitive_keywords: bool,
+    priorities: Priorities,
+    drawers: Option<Vec<String>>,
+    opaque_drawers: Vec<String>,
+    footnote_section: String,
+    inlinetask_min_level: Option<u32>,
+    lenient: bool,
+}
+
+impl Default for DocumentParser {
+    fn default() -> Self {
+        DocumentParser::new()
+    }
 }
 
 impl DocumentParser {
     pub fn new() -> Self {
         DocumentParser {
             todo_keywords: Vec::new(),
+            done_keywords: Vec::new(),
+            case_insensitive_keywords: false,
+            priorities: Priorities::default(),
+            drawers: None,
+            opaque_drawers: Vec::new(),
+            footnote_section: "Footnotes".into(),
+            inlinetask_min_level: Some(15),
+            lenient: false,
         }
     }
 
+    /// Set the TODO keywords, replacing any given before, as one sequence
+    /// written like org's `org-todo-keywords`: a `"|"` separates the
+    /// keywords for unfinished states from those for finished ones, and
+    /// without one the last keyword is the finished state. Keywords declared
+    /// in the file with `#+TODO:` lines are added to these.
     pub fn todo_keywords<S: Into<String>>(mut self, keywords: Vec<S>) -> Self {
-        self.todo_keywords = keywords.into_iter().map(|s| s.into()).collect();
+        self.todo_keywords.clear();
+        self.done_keywords.clear();
+        self.todo_sequence(keywords)
+    }
+
+    /// Add another sequence of TODO keywords, written like those given to
+    /// `todo_keywords`.
+    pub fn todo_sequence<S: Into<String>>(mut self, keywords: Vec<S>) -> Self {
+        let words: Vec<String> = keywords.into_iter().map(|s| s.into()).collect();
+        let (todo, done) = keyword::split_sequence(&words);
+        for keyword in todo.iter().chain(&done) {
+            if !self.todo_keywords.contains(keyword) {
+                self.todo_keywords.push(keyword.clone());
+            }
+        }
+        for keyword in done {
+            if !self.done_keywords.contains(&keyword) {
+                self.done_keywords.push(keyword);
+            }
+        }
         self
     }
 
-    pub fn parse(&self, text: &str) -> Result<Document, ()> {
-        let headline_matcher =
-            regex::Regex::new(r"(?mx)
-^(\*+)\s                     # STARS
-(?:(\S+)\s                   # KEYWORD
-   \[\#(.)\]\s)?             # PRIORITY
-(.*?)\s*                     # TITLE
-(:(?:[a-zA-Z0-9_@\#%]+:)+)?  # TAGS
-$");
-        // println!("{:?}", headline_matcher);
-        let headline_matcher = headline_matcher.unwrap();
-        let mut headlines = Vec::new();
-        for headline in headline_matcher.captures_iter(text) {
-            let stars = &headline[1];
-            let priority = headline.get(3)
-                .map(|x| text[x.start()..x.end()].chars().next().unwrap());
-            let mut title: String = headline.get(4)
-                .map(|x| text[x.start()..x.end()].trim().into())
-                .unwrap_or_default();
-            let keyword = match headline.get(2).map(|x| &text[x.start()..x.end()]) {
-                None => {
-                    let mut keyword_out = None;
-                    for keyword in &self.todo_keywords {
-                        if title.starts_with(keyword) {
-                            keyword_out = Some(keyword.clone());
-                            break;
-                        }
-                    }
-                    if let Some(ref kwd) = keyword_out {
-                        title = title[kwd.len()..].trim().into();
-                    }
-                    keyword_out
+    /// Match TODO keywords regardless of case, also accepting a trailing
+    /// colon (`todo:`), as found in files imported from other tools. Matched
+    /// keywords are canonicalized to the spelling given in `todo_keywords`.
+    pub fn case_insensitive_keywords(mut self, enabled: bool) -> Self {
+        self.case_insensitive_keywords = enabled;
+        self
+    }
+
+    /// The range of priorities, `A` to `C` with `B` as the default unless
+    /// set. A `#+PRIORITIES:` line in the file takes precedence. Cookies
+    /// outside the range like `[#D]` are left in the title.
+    pub fn priorities(mut self, highest: char, lowest: char, default: char) -> Self {
+        self.priorities = Priorities { highest, lowest, default };
+        self
+    }
+
+    /// Only recognize drawers with these names, besides `PROPERTIES` and
+    /// `LOGBOOK`, as org did before 8.0 with `#+DRAWERS:`. Other drawer-like
+    /// lines are read as text. By default drawers may have any name.
+    pub fn drawers<S: Into<String>>(mut self, names: Vec<S>) -> Self {
+        self.drawers = Some(names.into_iter().map(|s| s.into()).collect());
+        self
+    }
+
+    /// Keep the contents of drawers with these names as text, for drawers
+    /// that hold machine data rather than org: `Drawer::text` has it as
+    /// written and `Drawer::contents` is empty, so nothing reads or edits
+    /// it as lists, tables or timestamps. The names are recognized even if
+    /// `drawers` doesn't list them.
+    pub fn opaque_drawers<S: Into<String>>(mut self, names: Vec<S>) -> Self {
+        self.opaque_drawers = names.into_iter().map(|s| s.into()).collect();
+        self
+    }
+
+    /// The title of the headline holding footnote definitions, `Footnotes`
+    /// by default. See `Document::footnote_section`.
+    pub fn footnote_section<S: Into<String>>(mut self, title: S) -> Self {
+        self.footnote_section = title.into();
+        self
+    }
+
+    /// How many stars make a headline an inline task, like
+    /// `org-inlinetask-min-level`: 15 by default, or `None` to read every
+    /// headline as the start of a subtree.
+    pub fn inlinetask_min_level(mut self, level: Option<u32>) -> Self {
+        self.inlinetask_min_level = level;
+        self
+    }
+
+    /// Keep the lines that can't be parsed, like a `#+BEGIN_SRC` nothing
+    /// closes or a stray `:END:`, as `Element::Raw` rather than reading them
+    /// into paragraphs, and timestamps that can't be parsed as `Object::Raw`,
+    /// each with the diagnostic also listed in `Document::warnings`. Either
+    /// way no text is lost: the document still writes back out as it was.
+    pub fn lenient(mut self, enabled: bool) -> Self {
+        self.lenient = enabled;
+        self
+    }
+
+    fn allows_drawer(&self, name: &str) -> bool {
+        name.eq_ignore_ascii_case("LOGBOOK") || self.is_opaque_drawer(name) || self.drawers.as_ref()
+            .is_none_or(|names| names.iter().any(|allowed| allowed.eq_ignore_ascii_case(name)))
+    }
+
+    fn is_opaque_drawer(&self, name: &str) -> bool {
+        self.opaque_drawers.iter().any(|opaque| opaque.eq_ignore_ascii_case(name))
+    }
+
+    /// Look up the TODO keyword in `keywords` that `word` spells, if any.
+    fn canonical_keyword<'a>(&self, keywords: &'a [String], word: &str) -> Option<&'a String> {
+        if self.case_insensitive_keywords {
+            let word = word.trim_end_matches(':').to_lowercase();
+            keywords.iter().find(|kwd| kwd.to_lowercase() == word)
+        } else {
+            keywords.iter().find(|kwd| *kwd == word)
+        }
+    }
+
+    /// Parse `text` into a document. Malformed constructs don't make this
+    /// fail; they are read as plain text and listed in `Document::warnings`.
+    pub fn parse(&self, text: &str) -> Result<Document, ParseError> {
+        self.parse_headlines(text, true)
+    }
+
+    /// Parse only the headline skeleton of `text`: levels, keywords,
+    /// priorities, titles and tags. Section contents are never looked at,
+    /// which makes this the cheap option for tools that just need the outline.
+    pub fn parse_outline(&self, text: &str) -> Result<Document, ParseError> {
+        self.parse_headlines(text, false)
+    }
+
+    /// Update `document`, parsed from `old_text`, for `edit`. Only the
+    /// top-level headlines around the edit are parsed again; the others are
+    /// reused as they are, so editors can call this on every change. The
+    /// result is the same as parsing the edited text from scratch.
+    pub fn reparse_range(&self, document: Document, old_text: &str, edit: &TextEdit)
+                         -> Result<Document, ParseError> {
+        let _span = trace_span!("reparse_range", start = edit.range.start, end = edit.range.end);
+        let text = edit.apply(old_text);
+        let todo_sequences = keyword::find_todo_sequences(&text);
+        if todo_sequences != keyword::find_todo_sequences(old_text)
+            || keyword::find_keyword(&text, "PRIORITIES")
+                != keyword::find_keyword(old_text, "PRIORITIES") {
+            return self.parse(&text);
+        }
+        let mut document = document;
+        let mut first_section = document.first_section.take();
+        let mut headlines = mem::take(&mut document.headlines);
+        let warnings = mem::take(&mut document.warnings);
+        let Span { start, end } = edit.range;
+        let delta = edit.delta();
+        // The edit may demote the headlines it touches below the one before
+        // them, so that one is parsed again as well. It can't change itself.
+        let first = match headlines.iter().position(|h| h.span.end >= start) {
+            Some(touched) if touched > 0 => touched - 1,
+            _ => 0,
+        };
+        let with_first_section = first == 0 && headlines.first().is_none_or(|h| h.span.end >= start);
+        let after = headlines.iter().rposition(|h| h.span.start <= end).map_or(0, |last| last + 1);
+        let region_start = if with_first_section { 0 } else { headlines[first].span.start };
+        let region_end = headlines.get(after)
+            .map_or(text.len(), |h| (h.span.start as isize + delta) as usize);
+
+        let mut parser = self.clone();
+        for (todo, done) in todo_sequences {
+            parser.todo_keywords.extend(todo.into_iter().chain(done));
+        }
+        parser.priorities = document.priorities;
+        let mut region = parser.parse(&text[region_start..region_end])?;
+        region.shift(region_start as isize);
+        // A deeper headline after the region would become a child of its last one.
+        if let (Some(last), Some(next)) = (region.headlines.last(), headlines.get(after)) {
+            if last.level < next.level {
+                return self.parse(&text);
+            }
+        }
+
+        trace_event!(bytes = region_end - region_start,
+                     reused = headlines.len() - after.saturating_sub(first), "reparsed region");
+        let old_region_end = (region_end as isize - delta) as usize;
+        let mut tail = headlines.split_off(after);
+        tail.shift(delta);
+        headlines.truncate(if with_first_section { 0 } else { first });
+        headlines.extend(region.headlines);
+        headlines.extend(tail);
+        if with_first_section {
+            first_section = region.first_section;
+        }
+        let (mut before, rest): (Vec<_>, Vec<_>) = warnings.into_iter()
+            .partition(|warning| warning.span().start < region_start);
+        before.extend(region.warnings);
+        for mut warning in rest.into_iter().filter(|w| w.span().start >= old_region_end) {
+            warning.shift(delta);
+            before.push(warning);
+        }
+        document.first_section = first_section;
+        document.headlines = headlines;
+        document.warnings = before;
+        Ok(document)
+    }
+
+    /// Build the headline matched by `headline_regex` at `captures`, where
+    /// `todo_keywords` are all the keywords in effect and `priorities` the
+    /// range of priorities.
+    fn headline(&self, captures: &regex::Captures, todo_keywords: &[String],
+                priorities: Priorities) -> Headline {
+        let line = captures.get(0).unwrap();
+        let mut priority = captures.get(3).map(|x| x.as_str().chars().next().unwrap());
+        let mut title: String = captures.get(4)
+            .map(|x| x.as_str().trim().into())
+            .unwrap_or_default();
+        let mut title_offset = None;
+        // The word before a priority cookie is only a keyword if it is one of
+        // `todo_keywords`. Otherwise the word and the cookie are both title.
+        let mut word = captures.get(2);
+        if let Some(kwd) = word.filter(|kwd| {
+            self.canonical_keyword(todo_keywords, kwd.as_str()).is_none()
+        }) {
+            let start = kwd.start() - line.start();
+            let end = captures.get(4).map_or(start, |matched| matched.end() - line.start());
+            title = line.as_str()[start..end].trim_end().into();
+            title_offset = Some(start);
+            priority = None;
+            word = None;
+        }
+        if let Some(cookie) = priority.filter(|&cookie| !priorities.contains(cookie)) {
+            title = format!("[#{}] {}", cookie, title);
+            priority = None;
+            title_offset = captures.get(3).map(|cookie| cookie.start() - 2 - line.start());
+        }
+        let keyword = match word.map(|x| x.as_str()) {
+            None => {
+                let first_word = title.split_whitespace().next().unwrap_or("");
+                let keyword_out = self.canonical_keyword(todo_keywords, first_word)
+                    .map(Symbol::from);
+                if keyword_out.is_some() {
+                    title = title[first_word.len()..].trim().into();
                 }
-                Some(kwd) => Some(kwd.into()),
-            };
-            let tags: Vec<_> = headline.get(5)
-                .map(|x| &text[x.start()..x.end()])
-                .map(|x| x[1..x.len()-1].split(':').map(String::from).collect())
-                .unwrap_or_default();
-            headlines.push(Headline {
-                level: stars.len() as u32,
-                priority: priority,
-                keyword: keyword,
-                title: title,
-                tags: tags,
-                section: None,
-                headlines: Vec::new(),
+                keyword_out
+            }
+            Some(kwd) => self.canonical_keyword(todo_keywords, kwd).map(Symbol::from),
+        };
+        let commented = title == "COMMENT" || title.starts_with("COMMENT ");
+        if commented {
+            title = title["COMMENT".len()..].trim_start().into();
+        }
+        let tags: Vec<_> = captures.get(5)
+            .map(|x| x.as_str())
+            .map(|x| x[1..x.len()-1].split(':').map(Symbol::new).collect())
+            .unwrap_or_default();
+        // What is left of the title is the end of what the regex matched.
+        let title_offset = title_offset.unwrap_or_else(|| match captures.get(4) {
+            Some(matched) => matched.start() + matched.as_str().trim_end().len() - title.len()
+                - line.start(),
+            None => captures[1].len(),
+        });
+        Headline {
+            level: captures[1].len() as u32,
+            priority,
+            keyword,
+            commented,
+            title,
+            title_offset,
+            tags,
+            planning: Planning::default(),
+            properties: PropertyDrawer::default(),
+            section: None,
+            headlines: Vec::new(),
+            span: Span::new(line.start(), line.end()),
+            parser: default_parser(),
+        }
+    }
+
+    fn parse_headlines(&self, text: &str, with_sections: bool) -> Result<Document, ParseError> {
+        let _span = trace_span!("parse", bytes = text.len(), outline = !with_sections);
+        let headline_matcher = headline_regex();
+        // Keywords declared with `#+TODO:` lines in the file count as well.
+        let mut todo_keywords = self.todo_keywords.clone();
+        for keyword in keyword::find_todo_keywords(text) {
+            if !todo_keywords.contains(&keyword) {
+                todo_keywords.push(keyword);
+            }
+        }
+        let mut done_keywords = self.done_keywords.clone();
+        for keyword in keyword::find_done_keywords(text) {
+            if !done_keywords.contains(&keyword) {
+                done_keywords.push(keyword);
+            }
+        }
+        if done_keywords.is_empty() {
+            done_keywords.push("DONE".into());
+        }
+        let priorities = keyword::find_keyword(text, "PRIORITIES")
+            .and_then(Priorities::parse)
+            .unwrap_or(self.priorities);
+        // Sections and the inline tasks in them use the keywords and
+        // priorities of the file, and so do edits that read them again.
+        let parser = Arc::new(DocumentParser {
+            todo_keywords: todo_keywords.clone(),
+            done_keywords: done_keywords.clone(),
+            priorities,
+            ..self.clone()
+        });
+        let mut first_section = None;
+        let mut headlines: Vec<Headline> = Vec::new();
+        // Where the body of the previous headline (or the document) begins.
+        let mut section_start = 0;
+        for captures in headline_matcher.captures_iter(text) {
+            let line = captures.get(0).unwrap();
+            if inlinetask::is_task_line(self, line.as_str()) {
+                continue;
+            }
+            if let Some(previous) = headlines.last_mut() {
+                previous.span.end = line.start();
+            }
+            if with_sections {
+                let body = &text[section_start..line.start()];
+                match headlines.last_mut() {
+                    Some(previous) => previous.set_body(&parser, body, section_start),
+                    None => first_section = Section::from_text(&parser, body, section_start),
+                }
+            }
+            section_start = next_line_start(text, line.end());
+
+            let mut headline = self.headline(&captures, &todo_keywords, priorities);
+            headline.span.end = text.len();
+            headline.parser = parser.clone();
+            headlines.push(headline);
+        }
+        if with_sections {
+            let body = &text[section_start..];
+            match headlines.last_mut() {
+                Some(previous) => previous.set_body(&parser, body, section_start),
+                None => first_section = Section::from_text(&parser, body, section_start),
+            }
+        }
+
+        let mut document = Document {
+            first_section,
+            headlines: nest_headlines(headlines),
+            warnings: Vec::new(),
+            todo_keywords,
+            done_keywords,
+            priorities,
+            footnote_section: self.footnote_section.clone(),
+            encoding: Encoding::Utf8,
+            provenance: Vec::new(),
+            parser,
+        };
+        if with_sections {
+            let _span = trace_span!("find_warnings");
+            document.warnings = find_warnings(&document.parser, &document, text);
+        }
+        trace_event!(headlines = document.all_headlines().count(),
+                     nodes = document.descendants().count(),
+                     warnings = document.warnings.len(),
+                     "parsed document");
+        Ok(document)
+    }
+}
+
+/// The parser of the documents and headlines that weren't parsed, or whose
+/// parser was lost in serializing them, shared by all of them.
+fn default_parser() -> Arc<DocumentParser> {
+    static DEFAULT: OnceLock<Arc<DocumentParser>> = OnceLock::new();
+    DEFAULT.get_or_init(|| Arc::new(DocumentParser::new())).clone()
+}
+
+/// The offset just past the line ending at `line_end`, or the end of `text`.
+fn next_line_start(text: &str, line_end: usize) -> usize {
+    match text[line_end..].find('\n') {
+        Some(newline) => line_end + newline + 1,
+        None => text.len(),
+    }
+}
+
+/// Matches headline lines, capturing the stars, keyword, priority, title and
+/// tags. It is compiled once and shared by every parser.
+fn headline_regex() -> &'static regex::Regex {
+    static HEADLINE: OnceLock<regex::Regex> = OnceLock::new();
+    HEADLINE.get_or_init(|| regex::Regex::new(r"(?mx)
+^(\*+)                                        # STARS
+(?:[\x20\t]+
+   (?:(\S+)[\x20\t]+                          # KEYWORD
+      \[\#(.)\][\x20\t]+)?                    # PRIORITY
+   (.*?))??                                   # TITLE
+(?:[\x20\t]+(:(?:[a-zA-Z0-9_@\#%]+:)+))?      # TAGS
+[\x20\t]*\r?$").unwrap())
+}
+
+/// Report the delimiter lines that ended up in paragraphs because the element
+/// they belong to couldn't be parsed, like a `#+BEGIN_SRC` with no `#+END_SRC`.
+fn find_warnings(parser: &DocumentParser, document: &Document, text: &str) -> Vec<Diagnostic> {
+    let lines: Vec<&str> = text.lines().collect();
+    let offsets = span::line_offsets(text, 0);
+    let mut warnings = Vec::new();
+    for node in document.descendants() {
+        let span = match node {
+            Node::Content(&Content::Element(Element::Paragraph(ref paragraph))) => {
+                warnings.extend(paragraph.raw_timestamps().iter()
+                                .map(|raw| raw.diagnostic().clone()));
+                paragraph.span()
+            }
+            Node::Content(&Content::Element(Element::Raw(ref raw))) => {
+                warnings.push(raw.diagnostic().clone());
+                continue;
+            }
+            _ => continue,
+        };
+        let first = offsets.partition_point(|&offset| offset <= span.start) - 1;
+        for i in (first..lines.len()).take_while(|&i| offsets[i] < span.end) {
+            let start = span.start.max(offsets[i]);
+            let line = &text[start..offsets[i] + lines[i].len()];
+            // Drawer lines are only text when drawers are restricted.
+            let message = block::stray_delimiter(line).or_else(|| {
+                drawer::stray_delimiter(line).filter(|_| parser.drawers.is_none())
             });
+            if let Some(message) = message {
+                let start = start + line.len() - line.trim_start().len();
+                let end = offsets[i] + lines[i].trim_end().len();
+                warnings.push(Diagnostic::new(message, Span::new(start, end)));
+            }
         }
+    }
+    warnings
+}
 
-        // @Todo: Reorganize the sections hierarchically
-        // @Todo: Start parsing the sections
+/// Arrange a flat list of headlines, in document order, into a tree. Each
+/// headline becomes a child of the closest preceding headline with fewer
+/// stars, so skipped levels (`*` followed by `***`) still nest directly.
+fn nest_headlines(flat: Vec<Headline>) -> Vec<Headline> {
+    fn close_innermost(open: &mut Vec<Headline>, roots: &mut Vec<Headline>) {
+        let headline = open.pop().unwrap();
+        match open.last_mut() {
+            Some(parent) => {
+                parent.span.end = parent.span.end.max(headline.span.end);
+                parent.headlines.push(headline);
+            }
+            None => roots.push(headline),
+        }
+    }
 
-        Ok(Document {
-            first_section: None,
-            headlines: headlines,
-        })
+    let mut roots = Vec::new();
+    // The chain of headlines that can still receive children, outermost first.
+    let mut open: Vec<Headline> = Vec::new();
+    for headline in flat {
+        while open.last().is_some_and(|last| last.level >= headline.level) {
+            close_innermost(&mut open, &mut roots);
+        }
+        open.push(headline);
     }
+    while !open.is_empty() {
+        close_innermost(&mut open, &mut roots);
+    }
+    roots
 }
 
 #[cfg(test)]
@@ -154,23 +1689,44 @@ mod tests {
 
     #[test]
     fn test_document_parser() {
-        println!();
-        println!("{:#?}", DocumentParser::new().parse("* Hello!
+        let doc = DocumentParser::new().parse("* Hello!
 ** This is a second heading
 
-Let's write a "));
+Let's write a ").unwrap();
+        let titles: Vec<_> = doc.all_headlines().map(|h| h.title()).collect();
+        assert_eq!(titles, ["Hello!", "This is a second heading"]);
+        let second = &doc.headlines()[0].children()[0];
+        assert_eq!(second.section().unwrap().text(), "\nLet's write a ");
+
+        let outline = DocumentParser::new().parse_outline("Intro\n* A\nBody").unwrap();
+        assert!(outline.first_section().is_none());
+        assert!(outline.headlines()[0].section().is_none());
 
-        println!("{:#?}", DocumentParser::new()
+        let doc = DocumentParser::new()
             .todo_keywords(vec!["TODO", "DONE"]).parse("*
 
 ** DONE
 
 *** Some e-mail
 
-**** TODO [#A] COMMENT Title :tag:a2%:"));
+**** TODO [#A] COMMENT Title :tag:a2%:").unwrap();
+        let headlines: Vec<_> = doc.all_headlines().collect();
+        assert_eq!(headlines.len(), 4);
+        assert!(doc.first_section().is_none());
+        assert_eq!(headlines[0].title(), "");
+        assert_eq!(headlines[1].keyword(), Some("DONE"));
+        assert_eq!(headlines[1].title(), "");
+        assert_eq!(headlines[2].level(), 3);
+        assert_eq!(headlines[2].keyword(), None);
+        let last = headlines[3];
+        assert_eq!(last.level(), 4);
+        assert_eq!(last.keyword(), Some("TODO"));
+        assert_eq!(last.priority(), Some('A'));
+        assert!(last.is_commented());
+        assert_eq!(last.title(), "Title");
+        assert_eq!(last.tags(), ["tag", "a2%"]);
 
-
-        println!("{:#?}", DocumentParser::new().parse("An introduction.
+        let doc = DocumentParser::new().parse("An introduction.
 
 * A Headline
 
@@ -180,8 +1736,664 @@ Let's write a "));
 
 ** Sub-Topic 2
 
-*** Additional entry"));
+*** Additional entry").unwrap();
+        let levels: Vec<_> = doc.all_headlines().map(|h| h.level()).collect();
+        assert_eq!(levels, [1, 2, 2, 3]);
+        assert_eq!(doc.headlines().len(), 1);
+        assert_eq!(doc.first_section().unwrap().text(), "An introduction.\n\n");
+        assert_eq!(doc.headlines()[0].section().unwrap().text(), "\n  Some text.\n\n");
+        let children = doc.headlines()[0].children();
+        assert!(children[0].section().is_none());
+        assert_eq!(children.len(), 2);
+        assert_eq!(children[1].children()[0].title(), "Additional entry");
+    }
+
+    #[test]
+    fn test_headline_timestamps() {
+        let doc = DocumentParser::new().parse("* Meeting <2024-03-04 Mon 10:00>
+SCHEDULED: <2024-03-01 Fri>
+Notes from [2024-03-04 Mon].
+** Child <2024-04-01 Mon>").unwrap();
+        let dates: Vec<_> = doc.headlines()[0].timestamps().iter()
+            .map(|ts| ts.date().day)
+            .collect();
+        assert_eq!(dates, [4, 1, 4]);
+        assert_eq!(doc.headlines()[0].scheduled().unwrap().date().day, 1);
+        assert!(doc.headlines()[0].children()[0].scheduled().is_none());
+    }
 
-        assert!(false);
+    #[test]
+    fn test_title_objects() {
+        let doc = DocumentParser::new()
+            .parse("* TODO Read *this* [[https://x.org][page]] :work:").unwrap();
+        let objects = doc.headlines()[0].title_objects();
+        assert_eq!(objects.len(), 4);
+        assert_eq!(objects[1], Object::Bold(vec![Object::Text("this".into())]));
+        assert!(matches!(objects[3], Object::Link(_)));
+    }
+
+    #[test]
+    fn test_links() {
+        let doc = DocumentParser::new().parse("See [[#b]] and [[https://x.org]].
+* A
+:PROPERTIES:
+:ID: 1
+:END:
+* B *with [[id:1]]*
+:PROPERTIES:
+:CUSTOM_ID: b
+:END:
+Back to [[*A]], [[A]] or [[missing]].").unwrap();
+        let titles: Vec<_> = doc.links().iter()
+            .map(|link| doc.resolve_link(link).map(|h| h.title()))
+            .collect();
+        assert_eq!(titles, [
+            Some("B *with [[id:1]]*"), None, Some("A"), Some("A"), Some("A"), None,
+        ]);
+    }
+
+    #[test]
+    fn test_keywords() {
+        let doc = DocumentParser::new().todo_keywords(vec!["TODO"]).parse("#+TITLE: Plans
+#+todo: NEXT | DONE
+#+OPTIONS: toc:nil
+* NEXT Write
+* TODO Read
+** DONE Sleep
+#+options: num:nil").unwrap();
+        let keywords = doc.keywords();
+        assert_eq!(keywords["TITLE"], "Plans");
+        assert_eq!(keywords["TODO"], "NEXT | DONE");
+        assert_eq!(keywords["OPTIONS"], "toc:nil num:nil");
+        let found: Vec<_> = doc.all_headlines().map(|h| h.keyword()).collect();
+        assert_eq!(found, [Some("NEXT"), Some("TODO"), Some("DONE")]);
+
+        let table: Vec<_> = doc.keyword_table().iter()
+            .map(|(key, lines)| (key.clone(), lines.iter().map(|k| k.span()).collect::<Vec<_>>()))
+            .collect();
+        assert_eq!(table, [("TITLE".to_string(), vec![Span::new(0, 14)]),
+                           ("TODO".to_string(), vec![Span::new(15, 34)]),
+                           ("OPTIONS".to_string(), vec![Span::new(35, 53), Span::new(93, 111)])]);
+        assert_eq!(doc.keyword_value("options"), Some("num:nil"));
+        assert_eq!(doc.keyword_value("AUTHOR"), None);
+    }
+
+    #[test]
+    fn test_to_org_string() {
+        let text = "#+TITLE: Round trip
+Intro.
+* TODO [#B] Plan :work:
+SCHEDULED: <2024-03-01 Fri>
+:PROPERTIES:
+:ID: 1
+:END:
+- item
+  continued
+** Child
+#+BEGIN_SRC sh
+echo hi
+#+END_SRC
+*** Grandchild
+* DONE
+Last";
+        let parser = DocumentParser::new().todo_keywords(vec!["TODO", "DONE"]);
+        let doc = parser.parse(text).unwrap();
+        assert_eq!(doc.to_org_string(), text);
+        assert_eq!(doc.headlines()[1].to_org_string(), "* DONE\nLast");
+
+        let doc = parser.parse("* A   :tag:\n\n\n* B").unwrap();
+        assert_eq!(doc.to_org_string(), "* A :tag:\n* B\n");
+
+        for &(text, title, tags) in &[("* Title :tag: \n", "Title", &["tag"][..]),
+                                      ("* Title:tag:\n", "Title:tag:", &[][..]),
+                                      ("* :tag:\n", "", &["tag"][..])] {
+            let doc = parser.parse(text).unwrap();
+            assert_eq!(doc.headlines()[0].title(), title);
+            assert_eq!(doc.headlines()[0].tags(), tags);
+            let written = doc.to_org_string();
+            let reparsed = parser.parse(&written).unwrap();
+            assert_eq!(reparsed.headlines()[0].title(), title);
+            assert_eq!(reparsed.headlines()[0].tags(), tags);
+            assert_eq!(reparsed.to_org_string(), written);
+        }
+    }
+
+    #[test]
+    fn test_editing() {
+        let parser = DocumentParser::new().todo_keywords(vec!["TODO", "DONE"]);
+        let mut doc = parser.parse("* TODO A :x:
+SCHEDULED: <2024-03-01 Fri>
+:PROPERTIES:
+:ID: 1
+:END:
+Body
+** B
+*** C
+* D
+").unwrap();
+        {
+            let a = &mut doc.headlines_mut()[0];
+            a.set_keyword(Some("DONE"));
+            a.set_priority(Some('A'));
+            a.add_tag("y");
+            assert!(a.remove_tag("x"));
+            assert!(!a.remove_tag("x"));
+            a.set_property("effort", "1:00");
+            assert_eq!(a.remove_property("id"), Some("1".into()));
+            let b = a.remove_child(0);
+            a.insert_child(0, Headline::new(5, "New"));
+            a.children_mut()[0].add_tag("new");
+            doc.insert_headline(1, b);
+        }
+        assert!(!doc.headlines_mut()[1].promote());
+        doc.headlines_mut()[2].demote();
+        doc.headlines_mut()[2].remove_property("missing");
+        assert_eq!(doc.to_org_string(), "* DONE [#A] A :y:
+SCHEDULED: <2024-03-01 Fri>
+:PROPERTIES:
+:effort: 1:00
+:END:
+Body
+** New :new:
+* B
+** C
+** D
+");
+        let mut b = doc.remove_headline(1);
+        b.remove_child(0);
+        b.set_property("ID", "2");
+        assert_eq!(b.to_org_string(), "* B\n:PROPERTIES:\n:ID: 2\n:END:\n");
+        assert_eq!(b.property("id"), Some("2"));
+
+        let text = "* A\r\nSCHEDULED: <2024-03-01 Fri>\r\n:PROPERTIES:\r\n:ID: 1\r\n:END:\r\n\
+                    Body\r\n* B\r\n** C\r\n* D";
+        let mut doc = parser.parse(text).unwrap();
+        doc.headlines_mut()[0].set_property("EFFORT", "0:30");
+        doc.headlines_mut()[1].set_property("ID", "2");
+        doc.headlines_mut()[2].set_property("ID", "3");
+        let sections: Vec<_> = doc.headlines().iter()
+            .map(|headline| headline.section().unwrap().text())
+            .collect();
+        assert_eq!(sections, [
+            "SCHEDULED: <2024-03-01 Fri>\r\n:PROPERTIES:\r\n:ID: 1\r\n:EFFORT: 0:30\r\n:END:\r\n\
+             Body\r\n",
+            ":PROPERTIES:\n:ID: 2\n:END:\n",
+            ":PROPERTIES:\n:ID: 3\n:END:\n",
+        ]);
+        assert_eq!(doc.headlines()[1].section().unwrap().span().start, text.find("** C").unwrap());
+        doc.headlines_mut()[0].remove_property("ID");
+        doc.headlines_mut()[0].remove_property("EFFORT");
+        assert_eq!(doc.headlines()[0].section().unwrap().text(),
+                   "SCHEDULED: <2024-03-01 Fri>\r\nBody\r\n");
+    }
+
+    #[test]
+    fn test_headline_properties() {
+        let doc = DocumentParser::new().parse("* A
+DEADLINE: <2024-03-01 Fri>
+:PROPERTIES:
+:ID: 1234
+:END:
+* B
+:PROPERTIES:
+:CUSTOM_ID: b
+:END:
+* C
+
+:PROPERTIES:
+:ID: too-late
+:END:").unwrap();
+        let headlines = doc.headlines();
+        assert_eq!(headlines[0].property("id"), Some("1234"));
+        assert_eq!(headlines[1].property("CUSTOM_ID"), Some("b"));
+        assert!(headlines[2].properties().is_empty());
+    }
+
+    #[test]
+    fn test_parser_options() {
+        let parser = DocumentParser::new()
+            .todo_keywords(vec!["TODO", "NEXT", "|", "DONE"])
+            .todo_sequence(vec!["REPORT", "BUG", "FIXED"])
+            .priorities('A', 'E', 'C')
+            .drawers(vec!["NOTES"])
+            .footnote_section("Notes");
+        let doc = parser.parse("* NEXT [#E] Release
+:NOTES:
+Ship it.
+:END:
+:DETAILS:
+:END:
+* BUG [#F] Crash
+* Notes").unwrap();
+        assert_eq!(doc.todo_keywords(), ["TODO", "NEXT", "DONE", "REPORT", "BUG", "FIXED"]);
+        assert_eq!(doc.done_keywords(), ["DONE", "FIXED"]);
+        let headlines = doc.headlines();
+        assert_eq!((headlines[0].keyword(), headlines[0].priority()), (Some("NEXT"), Some('E')));
+        assert_eq!((headlines[1].priority(), headlines[1].title()), (None, "[#F] Crash"));
+        match headlines[0].section().unwrap().contents() {
+            [Content::Greater(GreaterElement::Drawer(drawer)),
+             Content::Element(Element::Paragraph(_))] => assert_eq!(drawer.name(), "NOTES"),
+            other => panic!("expected a drawer and a paragraph, got {:?}", other),
+        }
+        assert!(doc.warnings().is_empty());
+        assert_eq!(doc.footnote_section().unwrap().title(), "Notes");
+
+        let parser = DocumentParser::new().todo_keywords(vec!["TODO", "DONE"]);
+        let doc = parser.parse("#+PRIORITIES: 1 9 5
+* TODO [#3] Call
+* DONE [#A] Later").unwrap();
+        assert_eq!(doc.priorities(), Priorities { highest: '1', lowest: '9', default: '5' });
+        assert_eq!(doc.headlines()[0].priority(), Some('3'));
+        assert_eq!(doc.headlines()[1].title(), "[#A] Later");
+        assert_eq!(doc.done_keywords(), ["DONE"]);
+
+        // Edits read the sections again with the same options.
+        let parser = DocumentParser::new().lenient(true).drawers(vec!["NOTES"]);
+        let mut doc = parser.parse("* Data
+| 2 | 0 |
+#+TBLFM: $2=$1*3
+#+BEGIN_SRC
+:DETAILS:
+:END:
+").unwrap();
+        doc.recalculate_tables();
+        doc.headlines_mut()[0].set_property("ID", "1");
+        let written = doc.to_org_string();
+        assert!(written.contains("| 2 | 6 |"));
+        let contents = doc.headlines()[0].section().unwrap().contents();
+        assert!(matches!(contents[2], Content::Element(Element::Raw(_))));
+        let reparsed = parser.parse(&written).unwrap();
+        assert_eq!(format!("{:?}", contents),
+                   format!("{:?}", reparsed.headlines()[0].section().unwrap().contents()));
+    }
+
+    #[test]
+    fn test_footnotes() {
+        let doc = DocumentParser::new().parse("Intro[fn:a].
+* Title[fn:b]
+Body[fn:c:inline].
+* Footnotes
+[fn:a] About the intro.
+
+[fn:b] About the title.").unwrap();
+        let footnotes = doc.footnotes();
+        assert_eq!(footnotes.len(), 2);
+        assert_eq!(footnotes["b"].text(), "About the title.");
+        let labels: Vec<_> = doc.footnote_references().iter()
+            .map(|r| r.label().unwrap().to_string())
+            .collect();
+        assert_eq!(labels, ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_headline_nesting() {
+        let doc = DocumentParser::new().parse("* A
+*** B
+** C
+* D
+** E
+*** F
+** G").unwrap();
+        fn shape(headlines: &[Headline]) -> String {
+            headlines.iter()
+                .map(|h| if h.children().is_empty() {
+                    h.title().to_string()
+                } else {
+                    format!("{}({})", h.title(), shape(h.children()))
+                })
+                .collect::<Vec<_>>()
+                .join(" ")
+        }
+        assert_eq!(shape(doc.headlines()), "A(B C) D(E(F) G)");
+    }
+
+    #[test]
+    fn test_commented_and_archived() {
+        let mut doc = DocumentParser::new().todo_keywords(vec!["TODO"]).parse("* TODO COMMENT Draft
+** Inside the draft
+* Old :ARCHIVE:
+** Inside the archive
+* COMMENTARY
+* Kept").unwrap();
+        let draft = &doc.headlines()[0];
+        assert!(draft.is_commented());
+        assert_eq!(draft.title(), "Draft");
+        assert!(doc.headlines()[1].is_archived());
+        assert!(!doc.headlines()[2].is_commented());
+        let titles: Vec<_> = doc.all_headlines().without_commented().without_archived()
+            .map(|headline| headline.title())
+            .collect();
+        assert_eq!(titles, ["COMMENTARY", "Kept"]);
+        assert_eq!(doc.all_headlines().without_archived().count(), 4);
+
+        doc.headlines_mut()[3].set_commented(true);
+        assert_eq!(doc.headlines()[3].to_org_string(), "* COMMENT Kept\n");
+        assert_eq!(doc.headlines()[0].to_org_string(),
+                   "* TODO COMMENT Draft\n** Inside the draft\n");
+    }
+
+    #[test]
+    fn test_inlinetasks() {
+        let text = "* Chapter
+Text before.
+*************** TODO Check the figures
+Against the report.
+*************** END
+Text after.
+*************** Quick note
+** Section";
+        let doc = DocumentParser::new().todo_keywords(vec!["TODO"]).parse(text).unwrap();
+        assert_eq!(doc.all_headlines().count(), 2);
+        let chapter = &doc.headlines()[0];
+        assert_eq!(chapter.children()[0].title(), "Section");
+        let tasks: Vec<_> = chapter.section().unwrap().contents().iter()
+            .filter_map(|content| match *content {
+                Content::Greater(GreaterElement::Inlinetask(ref task)) => Some(task),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(tasks.len(), 2);
+        assert_eq!(tasks[0].headline().keyword(), Some("TODO"));
+        assert_eq!(tasks[0].contents().len(), 1);
+        assert_eq!(tasks[1].headline().title(), "Quick note");
+        assert!(!tasks[1].has_end());
+
+        let events: Vec<_> = DocumentParser::new().events(text.as_bytes())
+            .filter_map(|event| match event.unwrap() {
+                Event::InlinetaskStart(_) => Some("start"),
+                Event::InlinetaskEnd => Some("end"),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(events, ["start", "end", "start"]);
+
+        let doc = DocumentParser::new().inlinetask_min_level(None).parse(text).unwrap();
+        assert_eq!(doc.all_headlines().count(), 5);
+
+        let text = "*************** task\r\n\r\nbody\r\n*************** END\r\n";
+        let doc = DocumentParser::new().parse(text).unwrap();
+        let task = match doc.first_section().unwrap().contents()[0] {
+            Content::Greater(GreaterElement::Inlinetask(ref task)) => task,
+            ref other => panic!("{:?}", other),
+        };
+        assert_eq!(task.headline().section().unwrap().text(), "\r\nbody");
+        assert_eq!(task.contents()[0].span().unwrap().slice(text), "body");
+        assert_eq!(doc.to_org_string(), text);
+    }
+
+    #[test]
+    fn test_lesser_elements() {
+        let doc = DocumentParser::new().parse("Some text
+-----
+#+NAME: prices
+#+CAPTION: Fruit prices
+| apple | 1 |
+# A comment
+: fixed width
+#+NAME: loose
+
+\\begin{equation}
+x = 1
+\\end{equation}").unwrap();
+        let contents = doc.first_section().unwrap().contents();
+        let kinds: Vec<_> = contents.iter()
+            .map(|content| match *content {
+                Content::Greater(GreaterElement::Table(_)) => "table",
+                Content::Element(Element::Comment(_)) => "comment",
+                Content::Element(Element::FixedWidth(_)) => "fixed_width",
+                Content::Element(Element::HorizontalRule(_)) => "rule",
+                Content::Element(Element::Keyword(_)) => "keyword",
+                Content::Element(Element::LatexEnvironment(_)) => "latex",
+                Content::Element(Element::Paragraph(_)) => "paragraph",
+                _ => "other",
+            })
+            .collect();
+        assert_eq!(kinds, ["paragraph", "rule", "table", "comment", "fixed_width", "keyword",
+                           "latex"]);
+        let affiliated = contents[2].affiliated().unwrap();
+        assert_eq!(affiliated.name(), Some("prices"));
+        assert_eq!(affiliated.caption(), Some("Fruit prices"));
+        let text = doc.first_section().unwrap().text();
+        assert_eq!(contents[2].span().unwrap().start, text.find('|').unwrap());
+        assert!(contents[0].affiliated().unwrap().is_empty());
+        assert!(contents[3].affiliated().is_none());
+    }
+
+    #[test]
+    fn test_number_headlines() {
+        let mut doc = DocumentParser::new().parse("* A
+** B
+** C
+* D
+*** E").unwrap();
+        doc.number_headlines();
+        // Numbering again must replace, not stack, the prefixes.
+        doc.number_headlines();
+        let titles: Vec<_> = doc.all_headlines().map(|h| h.title()).collect();
+        assert_eq!(titles, ["1 A", "1.1 B", "1.2 C", "2 D", "2.0.1 E"]);
+
+        doc.strip_numbering();
+        let titles: Vec<_> = doc.all_headlines().map(|h| h.title()).collect();
+        assert_eq!(titles, ["A", "B", "C", "D", "E"]);
+
+        let mut doc = DocumentParser::new().parse("* A
+* COMMENT Draft
+** Inside the draft
+* Appendix
+:PROPERTIES:
+:UNNUMBERED: t
+:END:
+** Inside the appendix
+* B
+:PROPERTIES:
+:UNNUMBERED: nil
+:END:
+** C
+* Footnotes").unwrap();
+        doc.number_headlines();
+        let titles: Vec<_> = doc.all_headlines().map(|h| h.title()).collect();
+        assert_eq!(titles, ["1 A", "Draft", "Inside the draft", "Appendix",
+                            "Inside the appendix", "2 B", "2.1 C", "Footnotes"]);
+        assert!(doc.footnote_section().is_some());
+    }
+
+    #[test]
+    fn test_case_insensitive_keywords() {
+        let text = "* todo: Call mom
+* Done [#B] Taxes
+* TODOS are not keywords";
+        let parser = DocumentParser::new().todo_keywords(vec!["TODO", "DONE"]);
+        let doc = parser.parse(text).unwrap();
+        assert_eq!(doc.headlines[0].keyword, None);
+        assert_eq!(doc.headlines[1].keyword, None);
+        assert_eq!(doc.headlines[1].priority, None);
+        assert_eq!(doc.headlines[1].title, "Done [#B] Taxes");
+
+        let doc = parser.clone().case_insensitive_keywords(true).parse(text).unwrap();
+        let keywords: Vec<_> = doc.headlines.iter()
+            .map(|h| h.keyword.as_ref().map(|k| &k[..]))
+            .collect();
+        assert_eq!(keywords, [Some("TODO"), Some("DONE"), None]);
+        assert_eq!(doc.headlines[0].title, "Call mom");
+        assert_eq!(doc.headlines[2].title, "TODOS are not keywords");
+
+        let doc = parser.parse("* Meeting [#A] with Bob\n* TODO [#A]  Call").unwrap();
+        let headline = &doc.headlines[0];
+        assert_eq!((headline.keyword(), headline.priority()), (None, None));
+        assert_eq!(headline.title(), "Meeting [#A] with Bob");
+        assert_eq!(headline.title_offset, 2);
+        assert_eq!(doc.headlines[1].keyword(), Some("TODO"));
+        assert_eq!(doc.headlines[1].priority(), Some('A'));
+        assert_eq!(doc.to_org_string(), "* Meeting [#A] with Bob\n* TODO [#A] Call\n");
+    }
+
+    #[test]
+    fn test_spans() {
+        let text = "Intro
+* A
+Some /text/.
+- one
+- two
+** B
+#+TITLE: x
+* C";
+        let doc = DocumentParser::new().parse(text).unwrap();
+        let a = &doc.headlines()[0];
+        assert_eq!(a.span().slice(text), &text[6..text.find("* C").unwrap()]);
+        assert_eq!(a.section().unwrap().span(), Span::new(10, 35));
+        assert_eq!(a.children()[0].span(), Span::new(35, 51));
+        assert_eq!(doc.headlines()[1].span(), Span::new(51, 54));
+
+        match doc.node_at_offset(text.find("- two").unwrap()) {
+            Some(Node::ListItem(item)) => assert_eq!(item.span().slice(text), "- two"),
+            node => panic!("{:?}", node),
+        }
+        match doc.node_at_offset(text.find("text").unwrap()) {
+            Some(Node::Content(content)) => {
+                assert_eq!(content.span().unwrap().slice(text), "Some /text/.");
+            }
+            node => panic!("{:?}", node),
+        }
+        match doc.node_at_offset(text.find("#+TITLE").unwrap()) {
+            Some(Node::Content(&Content::Element(Element::Keyword(ref keyword)))) => {
+                assert_eq!(keyword.key(), "TITLE");
+            }
+            node => panic!("{:?}", node),
+        }
+        match doc.node_at_offset(text.find("* A").unwrap()) {
+            Some(Node::Headline(headline)) => assert_eq!(headline.title(), "A"),
+            node => panic!("{:?}", node),
+        }
+        assert!(matches!(doc.node_at_offset(0), Some(Node::Content(_))));
+        assert_eq!(line_column(text, a.children()[0].span().start), (6, 1));
+    }
+
+    #[test]
+    fn test_object_spans() {
+        let text = "* TODO Read [[https://orgmode.org][the *manual*]] :docs:
+- item with ~code~
+  and *bold
+  across* lines
+* TODO [#Z] Plan
+";
+        let doc = DocumentParser::new().todo_keywords(vec!["TODO"]).parse(text).unwrap();
+        let at = |needle: &str| {
+            let (object, span) = doc.object_at_offset(text.find(needle).unwrap()).unwrap();
+            (object, span.slice(text))
+        };
+        assert_eq!(at("manual"), (Object::Text("manual".into()), "manual"));
+        assert!(matches!(at("*manual"), (Object::Bold(_), "*manual*")));
+        let link = "[[https://orgmode.org][the *manual*]]";
+        assert!(matches!(at("[[https"), (Object::Link(_), slice) if slice == link));
+        assert!(doc.object_at_offset(text.find(":docs:").unwrap()).is_none());
+        assert_eq!(at("~code"), (Object::Code("code".into()), "~code~"));
+        assert_eq!(at("across"), (Object::Text("bold\nacross".into()), "bold\n  across"));
+        assert!(matches!(at("*bold"), (Object::Bold(_), "*bold\n  across*")));
+        assert!(doc.object_at_offset(text.find("  and").unwrap()).is_none());
+        assert_eq!(at("Plan"), (Object::Text("[#Z] Plan".into()), "[#Z] Plan"));
+
+        let text = "- due\n  <2024-02-30 Fri>";
+        let doc = DocumentParser::new().lenient(true).parse(text).unwrap();
+        assert_eq!(doc.warnings()[0].span().slice(text), "<2024-02-30 Fri>");
+        let (raw, span) = doc.object_at_offset(text.find("Fri").unwrap()).unwrap();
+        assert!(matches!(raw, Object::Raw(_)));
+        assert_eq!(span.slice(text), "<2024-02-30 Fri>");
+    }
+
+    #[test]
+    fn test_warnings() {
+        let text = "* A
+:LOGBOOK:
+- item
+  #+begin_src sh
+  ls
+* B
+#+BEGIN_QUOTE
+:END:
+#+END_QUOTE
+:END:";
+        let doc = DocumentParser::new().parse(text).unwrap();
+        let warnings: Vec<_> = doc.warnings().iter()
+            .map(|w| (w.message(), w.span().slice(text)))
+            .collect();
+        assert_eq!(warnings, [
+            (":LOGBOOK: drawer is never closed", ":LOGBOOK:"),
+            ("#+BEGIN_src block is never closed", "#+begin_src sh"),
+            (":END: has no drawer to close", ":END:"),
+            (":END: has no drawer to close", ":END:"),
+        ]);
+        assert_eq!(doc.headlines()[0].children().len(), 0);
+        assert!(DocumentParser::new().parse_outline(text).unwrap().warnings().is_empty());
+    }
+
+    #[test]
+    fn test_lenient() {
+        let text = "* A
+#+BEGIN_SRC sh
+ls
+:END:
+Due <2024-02-30 Fri> or [2024-03-01 Fri].
+";
+        let strict = DocumentParser::new().parse(text).unwrap();
+        let doc = DocumentParser::new().lenient(true).parse(text).unwrap();
+        let contents = doc.headlines()[0].section().unwrap().contents();
+        let kinds: Vec<_> = contents.iter().map(|content| match *content {
+            Content::Element(Element::Raw(ref raw)) => raw.text(),
+            Content::Element(Element::Paragraph(ref paragraph)) => paragraph.text(),
+            _ => panic!("unexpected {:?}", content),
+        }).collect();
+        assert_eq!(kinds, ["#+BEGIN_SRC sh", "ls", ":END:",
+                           "Due <2024-02-30 Fri> or [2024-03-01 Fri]."]);
+        let objects = match contents[3] {
+            Content::Element(Element::Paragraph(ref paragraph)) => paragraph.objects(),
+            _ => unreachable!(),
+        };
+        match objects[1] {
+            Object::Raw(ref raw) => assert_eq!(raw.span().slice(text), "<2024-02-30 Fri>"),
+            ref other => panic!("expected raw text, got {:?}", other),
+        }
+        assert!(matches!(objects[3], Object::Timestamp(_)));
+
+        let messages: Vec<_> = doc.warnings().iter().map(|w| w.message()).collect();
+        assert_eq!(messages, ["#+BEGIN_SRC block is never closed", ":END: has no drawer to close",
+                              "timestamp can't be parsed"]);
+        assert_eq!(&doc.warnings()[..2], strict.warnings());
+        assert_eq!(doc.to_org_string(), text);
+
+        let doc = DocumentParser::new().lenient(true).parse("[/]=é[#Z] ").unwrap();
+        assert!(doc.warnings().is_empty());
+    }
+
+    #[test]
+    fn test_reparse_range() {
+        let text = "#+TODO: NEXT | DONE
+Intro
+* A
+text
+** A.1
+- item
+* B
+:END:
+* C
+  more";
+        let parser = DocumentParser::new();
+        let at = |needle: &str| text.find(needle).unwrap();
+        let edits = [
+            TextEdit::new(Span::new(at("- item") + 2, at("- item") + 6), "changed"),
+            TextEdit::new(Span::new(at("* B"), at("* B") + 1), "**"),
+            TextEdit::new(Span::new(at("* C"), at("* C") + 2), ""),
+            TextEdit::new(Span::new(at("Intro"), at("Intro")), "* New\n"),
+            TextEdit::new(Span::new(text.len(), text.len()), "\n#+BEGIN_SRC"),
+            TextEdit::new(Span::new(at("text"), at("* C")), ""),
+            TextEdit::new(Span::new(at("NEXT |"), at("NEXT |") + 4), "WAIT"),
+            TextEdit::new(Span::new(at("* A"), at("* A") + 1), "***"),
+            TextEdit::new(Span::new(at("more"), at("more") + 4), "\n** D"),
+        ];
+        for edit in &edits {
+            let old = parser.parse(text).unwrap();
+            let reparsed = parser.reparse_range(old, text, edit).unwrap();
+            let parsed = parser.parse(&edit.apply(text)).unwrap();
+            assert_eq!(format!("{:?}", reparsed), format!("{:?}", parsed), "{:?}", edit);
+        }
     }
 }
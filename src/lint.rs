@@ -0,0 +1,524 @@
+//! Checks for documents that parse but are likely wrong, like links to
+//! headlines that don't exist, for validating org files before they are
+//! committed, and repairs for the mechanical ones.
+
+use std::cmp::Reverse;
+use std::collections::HashSet;
+
+use span::{self, Span};
+use {Diagnostic, Document, LinkType, Priorities, PropertySchema, block, drawer, list};
+
+/// A problem found by `lint`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lint {
+    /// The check that found it:
+    ///
+    /// - `parse`: a malformed construct from `Document::warnings`.
+    /// - `broken-link`: a `*Title` or `#custom-id` link to no headline.
+    /// - `duplicate-id`: an `ID` or `CUSTOM_ID` used by another headline.
+    /// - `undefined-footnote`: a reference to a footnote with no definition.
+    /// - `disallowed-value`: a property value that a `KEY_ALL` property
+    ///   doesn't allow, see `Document::disallowed_properties`.
+    /// - `property-type`: a property value of the wrong type for the schema
+    ///   given to `lint_properties`.
+    pub rule: &'static str,
+    pub diagnostic: Diagnostic,
+}
+
+impl Lint {
+    fn new<S: Into<String>>(rule: &'static str, message: S, span: Span) -> Lint {
+        Lint { rule, diagnostic: Diagnostic::new(message, span) }
+    }
+
+    /// Whether the document is malformed, rather than only suspicious.
+    pub fn is_error(&self) -> bool {
+        self.rule == "parse"
+    }
+}
+
+/// Check `document`, parsed from `text`, returning the problems found in
+/// order of where they are.
+pub fn lint(document: &Document, text: &str) -> Vec<Lint> {
+    let mut lints: Vec<Lint> = document.warnings().iter()
+        .map(|warning| Lint { rule: "parse", diagnostic: warning.clone() })
+        .collect();
+
+    // Links and references don't know where they are, so find them in order
+    // in the text.
+    let mut from = 0;
+    for link in document.links() {
+        let written = format!("[[{}]", link.destination());
+        let start = match text[from..].find(&written) {
+            Some(start) => from + start,
+            None => continue,
+        };
+        from = start + written.len();
+        let internal = matches!(link.link_type(), LinkType::Headline | LinkType::CustomId);
+        if internal && document.resolve_link(&link).is_none() {
+            let end = text[start..].find("]]").map_or(from, |end| start + end + 2);
+            let message = format!("link to missing headline {}", link.destination());
+            lints.push(Lint::new("broken-link", message, Span::new(start, end)));
+        }
+    }
+
+    let definitions = document.footnotes();
+    let mut from = 0;
+    for reference in document.footnote_references() {
+        let label = match reference.label() {
+            Some(label) => label,
+            None => continue,
+        };
+        let written = format!("[fn:{}", label);
+        let start = match text[from..].find(&written) {
+            Some(start) => from + start,
+            None => continue,
+        };
+        from = start + written.len();
+        if reference.definition().is_none() && !definitions.contains_key(label) {
+            let message = format!("footnote {} is never defined", label);
+            lints.push(Lint::new("undefined-footnote", message, Span::new(start, from + 1)));
+        }
+    }
+
+    let mut seen = HashSet::new();
+    for headline in document.all_headlines() {
+        for &key in &["ID", "CUSTOM_ID"] {
+            let id = match headline.property(key) {
+                Some(id) => id,
+                None => continue,
+            };
+            if !seen.insert((key, id)) {
+                let span = headline.properties().span();
+                lints.push(Lint::new("duplicate-id", format!("duplicate {} {}", key, id), span));
+            }
+        }
+    }
+    for (headline, error) in document.disallowed_properties() {
+        let span = headline.properties().span();
+        lints.push(Lint::new("disallowed-value", error.to_string(), span));
+    }
+
+    lints.sort_by_key(|lint| lint.diagnostic.span().start);
+    lints
+}
+
+/// Check the properties of every headline of `document` against `schema`,
+/// returning the values of the wrong type in order.
+pub fn lint_properties(document: &Document, schema: &PropertySchema) -> Vec<Lint> {
+    let mut lints = Vec::new();
+    for headline in document.all_headlines() {
+        for (key, value) in headline.properties().iter() {
+            if let Err(error) = schema.check(key, value) {
+                let span = headline.properties().span();
+                lints.push(Lint::new("property-type", error.to_string(), span));
+            }
+        }
+    }
+    lints
+}
+
+/// A change made by `Document::repair`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Repair {
+    /// What was repaired:
+    ///
+    /// - `unclosed-block`: an `#+END_` line was added to a block.
+    /// - `unclosed-drawer`: an `:END:` line was added to a `PROPERTIES` or
+    ///   `LOGBOOK` drawer.
+    /// - `list-indentation`: an item was indented like the item before it
+    ///   in the same list.
+    /// - `priority-cookie`: a priority cookie was rewritten to be read as
+    ///   one.
+    pub rule: &'static str,
+    /// What was done, at the line it was done to in the text of the
+    /// document before the repairs.
+    pub diagnostic: Diagnostic,
+}
+
+/// Blocks holding text rather than elements, so that lines in them aren't
+/// list items or drawers.
+const LITERAL_BLOCKS: &[&str] = &["COMMENT", "EXAMPLE", "EXPORT", "SRC", "VERSE"];
+
+fn is_headline(line: &str) -> bool {
+    let stars = line.len() - line.trim_start_matches('*').len();
+    stars > 0 && line[stars..].chars().next().is_none_or(char::is_whitespace)
+}
+
+fn is_literal_block(name: &str) -> bool {
+    LITERAL_BLOCKS.iter().any(|literal| literal.eq_ignore_ascii_case(name))
+}
+
+fn indent_of(line: &str) -> &str {
+    &line[..line.len() - line.trim_start().len()]
+}
+
+/// The character of a priority cookie like `[#A]` or `[ #a ]` at the start
+/// of `text`, whether it was written as org reads it, and the text after it.
+fn priority_cookie(text: &str) -> Option<(char, bool, &str)> {
+    let close = text.strip_prefix('[')?.find(']')? + 1;
+    let inside = &text[1..close];
+    let mut chars = inside.trim().strip_prefix('#')?.trim().chars();
+    let cookie = chars.next().filter(|_| chars.next().is_none())?;
+    let rest = &text[close + 1..];
+    let exact = inside.len() == 1 + cookie.len_utf8()
+        && rest.chars().next().is_none_or(char::is_whitespace);
+    Some((cookie, exact, rest))
+}
+
+/// The headline `line` with its priority cookie written so that org reads
+/// it: after the TODO keyword, without spaces inside, in the case of
+/// `priorities` and followed by a space. `None` if it needs no change.
+fn fix_priority(line: &str, keywords: &[String], priorities: Priorities) -> Option<String> {
+    if !is_headline(line) {
+        return None;
+    }
+    let stars = &line[..line.len() - line.trim_start_matches('*').len()];
+    let rest = line[stars.len()..].trim_start();
+    let is_keyword = |word: &str| keywords.iter().any(|keyword| keyword == word);
+    let mut keyword = rest.split_whitespace().next().filter(|&word| is_keyword(word));
+    let after = rest[keyword.map_or(0, str::len)..].trim_start();
+    let (cookie, exact, title) = priority_cookie(after)?;
+    let mut title = title.trim_start();
+    let moved = keyword.is_none() && title.split_whitespace().next().is_some_and(is_keyword);
+    if moved {
+        let word = title.split_whitespace().next().unwrap();
+        keyword = Some(word);
+        title = title[word.len()..].trim_start();
+    }
+    let upper = cookie.to_ascii_uppercase();
+    let fixed_cookie = if priorities.contains(cookie) { cookie } else { upper };
+    if !priorities.contains(fixed_cookie) || (exact && !moved && fixed_cookie == cookie) {
+        return None;
+    }
+    let mut fixed = format!("{} ", stars);
+    if let Some(keyword) = keyword {
+        fixed.push_str(keyword);
+        fixed.push(' ');
+    }
+    fixed.push_str(&format!("[#{}]", fixed_cookie));
+    if !title.is_empty() {
+        fixed.push(' ');
+        fixed.push_str(title);
+    }
+    Some(fixed)
+}
+
+/// Repairs to the lines of a document, given as its lines and where each one
+/// starts in its text.
+struct Repairer<'a> {
+    lines: Vec<String>,
+    offsets: Vec<usize>,
+    original: Vec<&'a str>,
+    repairs: Vec<Repair>,
+}
+
+impl<'a> Repairer<'a> {
+    fn report<S: Into<String>>(&mut self, rule: &'static str, message: S, line: usize) {
+        let start = self.offsets[line];
+        let span = Span::new(start, start + self.original[line].len());
+        self.repairs.push(Repair { rule, diagnostic: Diagnostic::new(message, span) });
+    }
+
+    fn priorities(&mut self, keywords: &[String], priorities: Priorities) {
+        for i in 0..self.lines.len() {
+            if let Some(fixed) = fix_priority(&self.lines[i], keywords, priorities) {
+                let message = format!("priority cookie rewritten as {}", fixed);
+                self.lines[i] = fixed;
+                self.report("priority-cookie", message, i);
+            }
+        }
+    }
+
+    /// Indent each list item like the item before it in the same list, and
+    /// the lines belonging to it by as much.
+    fn lists(&mut self) {
+        // The items that lines can still belong to, outermost first, as
+        // their indentation as written and as repaired.
+        let mut open: Vec<(usize, usize)> = Vec::new();
+        let mut literal: Option<String> = None;
+        let mut blank_run = 0;
+        for i in 0..self.lines.len() {
+            let line = self.lines[i].clone();
+            let indent = indent_of(&line).len();
+            let shift = |open: &[(usize, usize)]| {
+                open.last().map_or(0, |&(written, repaired)| repaired as isize - written as isize)
+            };
+            if let Some(ref name) = literal {
+                if block::is_end_line(&line, name) {
+                    literal = None;
+                }
+            } else if is_headline(&line) || blank_run >= 2 && !line.trim().is_empty() {
+                open.clear();
+            } else if list::is_item_line(&line) {
+                let mut sibling = None;
+                while open.last().is_some_and(|&(written, _)| written > indent) {
+                    sibling = open.pop();
+                }
+                let repaired = match open.last() {
+                    Some(&(written, repaired)) if written == indent => {
+                        open.pop();
+                        repaired
+                    }
+                    _ => match sibling {
+                        Some((_, repaired)) => repaired,
+                        None => (indent as isize + shift(&open)).max(0) as usize,
+                    },
+                };
+                if sibling.is_some() && repaired != indent {
+                    self.report("list-indentation",
+                                format!("item indented by {} like the one before it", repaired),
+                                i);
+                }
+                open.push((indent, repaired));
+            } else if !line.trim().is_empty() {
+                while open.last().is_some_and(|&(written, _)| written >= indent) {
+                    open.pop();
+                }
+                if let Some((name, _)) = block::begin_line(&line)
+                    .filter(|&(name, _)| is_literal_block(name)) {
+                    literal = Some(name.into());
+                }
+            }
+            blank_run = if line.trim().is_empty() { blank_run + 1 } else { 0 };
+            let shift = shift(&open);
+            if shift != 0 && !line.trim().is_empty() {
+                let repaired = (indent as isize + shift).max(0) as usize;
+                self.lines[i] = format!("{}{}", " ".repeat(repaired), line.trim_start());
+            }
+        }
+    }
+
+    /// Add `#+END_` lines to blocks and `:END:` lines to drawers that aren't
+    /// closed: blocks before the next headline, property drawers after
+    /// their last property and logbook drawers before the next blank line.
+    fn delimiters(&mut self) {
+        let mut insertions: Vec<(usize, String)> = Vec::new();
+        let lines = self.lines.clone();
+        let mut i = 0;
+        while i < lines.len() {
+            let line = &lines[i];
+            let boundary = (i + 1..lines.len()).find(|&j| is_headline(&lines[j]))
+                .unwrap_or(lines.len());
+            let last_filled = |from: usize, to: usize| {
+                (from..to).rev().find(|&j| !lines[j].trim().is_empty()).unwrap_or(from)
+            };
+            if let Some((name, _)) = block::begin_line(line) {
+                let end = (i + 1..boundary).find(|&j| block::is_end_line(&lines[j], name));
+                let end = end.unwrap_or_else(|| {
+                    let last = last_filled(i, boundary);
+                    insertions.push((last + 1, format!("{}#+END_{}", indent_of(line), name)));
+                    self.report("unclosed-block", format!("#+END_{} added", name), i);
+                    last
+                });
+                i = if is_literal_block(name) { end + 1 } else { i + 1 };
+                continue;
+            }
+            let name = match drawer::drawer_name(line) {
+                Some(name) if ["PROPERTIES", "LOGBOOK"].iter()
+                    .any(|drawer| drawer.eq_ignore_ascii_case(name)) => name,
+                _ => {
+                    i += 1;
+                    continue;
+                }
+            };
+            let next_drawer = (i + 1..boundary)
+                .find(|&j| drawer::drawer_name(&lines[j]).is_some())
+                .unwrap_or(boundary);
+            if next_drawer < boundary && lines[next_drawer].trim().eq_ignore_ascii_case(":END:") {
+                i = next_drawer + 1;
+                continue;
+            }
+            let last = if name.eq_ignore_ascii_case("PROPERTIES") {
+                i + lines[i + 1..next_drawer].iter()
+                    .take_while(|line| {
+                        let line = line.trim();
+                        line.starts_with(':') && line[1..].contains(':')
+                    })
+                    .count()
+            } else {
+                i + lines[i + 1..next_drawer].iter()
+                    .take_while(|line| !line.trim().is_empty())
+                    .count()
+            };
+            insertions.push((last + 1, format!("{}:END:", indent_of(line))));
+            self.report("unclosed-drawer", format!(":END: added to :{}: drawer", name), i);
+            i = last + 1;
+        }
+        // From the end, so that the lines are still where they were. Blocks
+        // found later are inside those found earlier, so at the same line
+        // their ends are inserted last to go first.
+        insertions.sort_by_key(|&(at, _)| Reverse(at));
+        for (at, end) in insertions {
+            self.lines.insert(at, end);
+        }
+    }
+}
+
+impl Document {
+    /// Fix the mechanical problems `lint` finds, returning what was done in
+    /// order of where it was done:
+    ///
+    /// - Blocks that aren't closed are closed after their last line before
+    ///   the next headline.
+    /// - `PROPERTIES` drawers that aren't closed are closed after their last
+    ///   property, and `LOGBOOK` drawers before the next blank line.
+    /// - List items indented differently from the item before them in the
+    ///   same list are indented like it, along with their contents.
+    /// - Priority cookies like `[#a]`, `[# A]` or those before the TODO
+    ///   keyword are written like `TODO [#A]`.
+    ///
+    /// The document is parsed again from its repaired text, with its TODO
+    /// keywords and priorities.
+    pub fn repair(&mut self) -> Vec<Repair> {
+        let text = self.to_org_string();
+        let mut repairer = Repairer {
+            lines: text.lines().map(String::from).collect(),
+            offsets: span::line_offsets(&text, 0),
+            original: text.lines().collect(),
+            repairs: Vec::new(),
+        };
+        repairer.priorities(&self.todo_keywords, self.priorities);
+        repairer.lists();
+        repairer.delimiters();
+        let mut repairs = repairer.repairs;
+        if repairs.is_empty() {
+            return repairs;
+        }
+        repairs.sort_by_key(|repair| repair.diagnostic.span().start);
+
+        let mut repaired = repairer.lines.join("\n");
+        if text.ends_with('\n') {
+            repaired.push('\n');
+        }
+        if let Ok(mut document) = self.parser.parse(&repaired) {
+            document.encoding = self.encoding;
+            *self = document;
+        }
+        repairs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {DocumentParser, PropertyType};
+
+    #[test]
+    fn test_lint() {
+        let text = "* One
+:PROPERTIES:
+:CUSTOM_ID: one
+:END:
+See [[*Two]], [[#one]], [[*Three][3]] and [fn:1] [fn:2] [fn:3:inline].
+#+BEGIN_SRC
+* Two
+:PROPERTIES:
+:CUSTOM_ID: one
+:END:
+
+[fn:1] Defined.
+";
+        let doc = DocumentParser::new().parse(text).unwrap();
+        let lints: Vec<_> = lint(&doc, text).iter()
+            .map(|lint| {
+                let span = lint.diagnostic.span();
+                (lint.rule, &text[span.start..span.end])
+            })
+            .collect();
+        assert_eq!(lints[0], ("broken-link", "[[*Three][3]]"));
+        assert_eq!(lints[1], ("undefined-footnote", "[fn:2]"));
+        assert_eq!(lints[2], ("parse", "#+BEGIN_SRC"));
+        assert_eq!(lints[3].0, "duplicate-id");
+        assert_eq!(lints.len(), 4);
+        assert!(lint(&doc, text)[2].is_error());
+    }
+
+    #[test]
+    fn test_lint_properties() {
+        let doc = DocumentParser::new().parse("* One
+:PROPERTIES:
+:Effort: 1:30
+:Billable: maybe
+:END:
+* Two
+:PROPERTIES:
+:EFFORT: a while
+:END:
+").unwrap();
+        let schema = PropertySchema::new()
+            .property("Effort", PropertyType::Duration)
+            .property("Billable", PropertyType::Bool);
+        let lints: Vec<_> = lint_properties(&doc, &schema).iter()
+            .map(|lint| lint.diagnostic.message().to_string())
+            .collect();
+        assert_eq!(lints, ["property Billable is \"maybe\", not a boolean",
+                           "property EFFORT is \"a while\", not a duration"]);
+
+        let text = "* One\n:PROPERTIES:\n:Owner_ALL: alice bob\n:Owner: carol\n:END:\n";
+        let doc = DocumentParser::new().parse(text).unwrap();
+        let lints = lint(&doc, text);
+        assert_eq!(lints[0].rule, "disallowed-value");
+        assert_eq!(lints.len(), 1);
+    }
+
+    #[test]
+    fn test_repair() {
+        let text = "#+TODO: TODO | DONE
+* TODO [#a] Fix
+:PROPERTIES:
+:ID: fix
+Notes:
+- one
+    - nested
+  - nested two
+    more
+- two
+* [# B] TODO Other
+#+BEGIN_QUOTE
+#+BEGIN_SRC rust
+fn main() {}
+
+* DONE Last
+";
+        let mut doc = DocumentParser::new().parse(text).unwrap();
+        assert!(!doc.warnings().is_empty());
+        let repairs: Vec<_> = doc.repair().iter()
+            .map(|repair| {
+                let span = repair.diagnostic.span();
+                (repair.rule, &text[span.start..span.end])
+            })
+            .collect();
+        assert_eq!(repairs, [
+            ("priority-cookie", "* TODO [#a] Fix"),
+            ("unclosed-drawer", ":PROPERTIES:"),
+            ("list-indentation", "  - nested two"),
+            ("priority-cookie", "* [# B] TODO Other"),
+            ("unclosed-block", "#+BEGIN_QUOTE"),
+            ("unclosed-block", "#+BEGIN_SRC rust"),
+        ]);
+        assert_eq!(doc.to_org_string(), "#+TODO: TODO | DONE
+* TODO [#A] Fix
+:PROPERTIES:
+:ID: fix
+:END:
+Notes:
+- one
+    - nested
+    - nested two
+      more
+- two
+* TODO [#B] Other
+#+BEGIN_QUOTE
+#+BEGIN_SRC rust
+fn main() {}
+#+END_SRC
+#+END_QUOTE
+
+* DONE Last
+");
+        assert!(doc.warnings().is_empty());
+        assert_eq!(doc.headlines()[0].property("ID"), Some("fix"));
+        assert_eq!(doc.headlines()[1].priority(), Some('B'));
+        assert!(doc.repair().is_empty());
+    }
+}
@@ -0,0 +1,627 @@
+use keyword::{Affiliate, Affiliated};
+use span::{self, Shift, Span};
+use {Content, Document, DocumentParser, GreaterElement, Section};
+
+/// A plain list is a run of items sharing the same indentation.
+///
+/// ```ignore
+/// - unordered item
+/// + also unordered
+/// 1. ordered item
+/// 2) ordered item
+/// - term :: description
+/// - [X] checked item
+/// ```
+///
+/// The kind of the whole list is decided by its first item. A list ends at the
+/// first non-blank line indented no deeper than its bullets that isn't another
+/// item, or after two consecutive blank lines.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct PlainList {
+    kind: ListKind,
+    items: Vec<ListItem>,
+    /// How far its bullets are indented in the text of the element holding
+    /// it.
+    indent: usize,
+    affiliated: Affiliated,
+    span: Span,
+}
+
+impl PlainList {
+    pub fn kind(&self) -> ListKind {
+        self.kind
+    }
+
+    pub fn items(&self) -> &[ListItem] {
+        &self.items
+    }
+
+    /// The `#+NAME:`, `#+CAPTION:` and other keywords written above it.
+    pub fn affiliated(&self) -> &Affiliated {
+        &self.affiliated
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    /// The style of the bullet of the first item, which the others follow.
+    pub fn bullet_style(&self) -> Option<BulletStyle> {
+        let bullet = &self.items.first()?.bullet;
+        Some(match bullet.as_bytes()[bullet.len() - 1] {
+            b'-' => BulletStyle::Minus,
+            b'+' => BulletStyle::Plus,
+            b'*' => BulletStyle::Star,
+            b'.' => BulletStyle::Period,
+            _ => BulletStyle::Parenthesis,
+        })
+    }
+
+    /// Fix the bullets after the items were edited, like org does: every
+    /// item takes the bullet of the first one, and ordered items are
+    /// numbered from 1, or from the `[@N]` counter of an item on. Nested
+    /// lists are lists of their own and aren't changed.
+    pub fn renumber(&mut self) {
+        let first = match self.items.first() {
+            Some(item) => item.bullet.clone(),
+            None => return,
+        };
+        let digits = first.len() - first.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        let mut number = 0;
+        for item in &mut self.items {
+            item.bullet = if digits == 0 {
+                first.clone()
+            } else {
+                number = item.counter.unwrap_or(number + 1);
+                format!("{}{}", number, &first[digits..])
+            };
+        }
+    }
+
+    /// Change the bullets of the items to `style`, renumbering them if they
+    /// are numbered. Returns false, without changing anything, if the list
+    /// can't take the style: descriptive lists can't be numbered, and `*`
+    /// bullets in the first column would be headlines.
+    pub fn set_bullet_style(&mut self, style: BulletStyle) -> bool {
+        let ordered = style == BulletStyle::Period || style == BulletStyle::Parenthesis;
+        if self.items.is_empty() || ordered && self.kind == ListKind::Descriptive
+            || style == BulletStyle::Star && self.indent == 0 {
+            return false;
+        }
+        self.items[0].bullet = style.bullet().into();
+        if ordered {
+            self.kind = ListKind::Ordered;
+        } else if self.kind == ListKind::Ordered {
+            self.kind = ListKind::Unordered;
+        }
+        self.renumber();
+        true
+    }
+}
+
+/// The bullets org cycles through for a list: `-`, `+`, `*`, `1.` and `1)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BulletStyle {
+    Minus,
+    Plus,
+    Star,
+    Period,
+    Parenthesis,
+}
+
+impl BulletStyle {
+    /// The bullet of the first item in this style.
+    fn bullet(self) -> &'static str {
+        match self {
+            BulletStyle::Minus => "-",
+            BulletStyle::Plus => "+",
+            BulletStyle::Star => "*",
+            BulletStyle::Period => "1.",
+            BulletStyle::Parenthesis => "1)",
+        }
+    }
+}
+
+impl Document {
+    /// Renumber every plain list of the document with `PlainList::renumber`,
+    /// writing the fixed bullets into the text.
+    pub fn renumber_lists(&mut self) {
+        self.edit_lists(&mut |list| list.renumber());
+    }
+
+    /// Change the bullets of the innermost plain list around the byte
+    /// `offset` with `PlainList::set_bullet_style`, writing them into the
+    /// text. Returns false if there is no list there or it can't take the
+    /// style.
+    pub fn set_bullet_style(&mut self, offset: usize, style: BulletStyle) -> bool {
+        let innermost = self.descendants().filter_map(|node| match node {
+            ::Node::Content(&Content::Greater(GreaterElement::PlainList(ref list)))
+                if list.span.contains(offset) => Some(list.span),
+            _ => None,
+        }).last();
+        let target = match innermost {
+            Some(span) => span,
+            None => return false,
+        };
+        let mut changed = false;
+        self.edit_lists(&mut |list| if list.span == target {
+            changed = list.set_bullet_style(style);
+        });
+        changed
+    }
+
+    /// Call `edit` on a copy of every plain list, and write the bullets it
+    /// changed into the text of the sections, which are then parsed again.
+    fn edit_lists(&mut self, edit: &mut dyn FnMut(&mut PlainList)) {
+        if let Some(ref mut section) = self.first_section {
+            edit_section_lists(&self.parser, section, edit);
+        }
+        ::visit_headlines_mut(&mut self.headlines, &mut |headline| {
+            if let Some(ref mut section) = headline.section {
+                edit_section_lists(&headline.parser, section, edit);
+            }
+        });
+    }
+}
+
+fn edit_section_lists(parser: &DocumentParser, section: &mut Section,
+                      edit: &mut dyn FnMut(&mut PlainList)) {
+    fn collect(contents: &[Content], edit: &mut dyn FnMut(&mut PlainList),
+               edits: &mut Vec<(usize, usize, String)>) {
+        for content in contents {
+            if let Content::Greater(GreaterElement::PlainList(ref list)) = *content {
+                let mut edited = list.clone();
+                edit(&mut edited);
+                for (old, new) in list.items.iter().zip(&edited.items) {
+                    if old.bullet != new.bullet {
+                        edits.push((old.span.start, old.bullet.len(), new.bullet.clone()));
+                    }
+                }
+            }
+            let (children, items) = content.children();
+            collect(children, edit, edits);
+            for item in items {
+                collect(item.contents(), edit, edits);
+            }
+        }
+    }
+
+    let mut edits = Vec::new();
+    collect(&section.contents, edit, &mut edits);
+    if edits.is_empty() {
+        return;
+    }
+    let base = section.span.start;
+    let mut text = section.text.clone();
+    edits.sort_by_key(|&(start, _, _)| start);
+    for &(start, len, ref bullet) in edits.iter().rev() {
+        let start = start - base;
+        let start = start + indentation(&text[start..]);
+        text.replace_range(start..start + len, bullet);
+    }
+    if let Some(updated) = Section::from_text(parser, &text, base) {
+        *section = updated;
+    }
+}
+
+impl Affiliate for PlainList {
+    fn affiliated_mut(&mut self) -> &mut Affiliated {
+        &mut self.affiliated
+    }
+}
+
+impl Shift for PlainList {
+    fn shift(&mut self, delta: isize) {
+        self.affiliated.shift(delta);
+        self.span.shift(delta);
+        self.items.shift(delta);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum ListKind {
+    /// Items with `-`, `+` or `*` bullets.
+    Unordered,
+    /// Items with `1.` or `1)` bullets.
+    Ordered,
+    /// Unordered items with a `term :: description` tag.
+    Descriptive,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum Checkbox {
+    /// `[ ]`
+    Unchecked,
+    /// `[-]`, some but not all sub-items are checked.
+    Partial,
+    /// `[X]`
+    Checked,
+}
+
+/// A single list item:
+///
+/// ```ignore
+/// BULLET COUNTER-SET CHECK-BOX TAG :: CONTENTS
+/// ```
+///
+/// Everything but the bullet is optional. The item extends over all following
+/// lines indented deeper than its bullet, which is how nested lists are
+/// written.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct ListItem {
+    bullet: String,
+    counter: Option<u32>,
+    checkbox: Option<Checkbox>,
+    tag: Option<String>,
+    text: String,
+    contents: Vec<Content>,
+    span: Span,
+}
+
+impl ListItem {
+    /// The bullet as written, e.g. `-` or `3.`.
+    pub fn bullet(&self) -> &str {
+        &self.bullet
+    }
+
+    /// The value of a `[@N]` counter cookie, which restarts numbering.
+    pub fn counter(&self) -> Option<u32> {
+        self.counter
+    }
+
+    pub fn checkbox(&self) -> Option<Checkbox> {
+        self.checkbox
+    }
+
+    /// The term of a descriptive item.
+    pub fn tag(&self) -> Option<&str> {
+        self.tag.as_ref().map(|s| &s[..])
+    }
+
+    /// The raw text of the item after its bullet, checkbox and tag, including
+    /// any nested lists, with continuation lines stripped of the indentation
+    /// up to the item's content column.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// The elements parsed from the item's text, such as nested lists.
+    pub fn contents(&self) -> &[Content] {
+        &self.contents
+    }
+
+    /// Where the item is in the text, from its bullet line to its last
+    /// non-blank line.
+    pub fn span(&self) -> Span {
+        self.span
+    }
+}
+
+impl Shift for ListItem {
+    fn shift(&mut self, delta: isize) {
+        self.span.shift(delta);
+        self.contents.shift(delta);
+    }
+}
+
+/// The parts of a line that starts a list item.
+struct ItemLine<'a> {
+    indent: usize,
+    bullet: &'a str,
+    counter: Option<u32>,
+    checkbox: Option<Checkbox>,
+    tag: Option<&'a str>,
+    rest: &'a str,
+    /// The column where the item's contents start, following the bullet.
+    content_column: usize,
+}
+
+fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+/// Split a `[...]` cookie off the front of `text`, returning its inside and
+/// whatever follows it.
+fn cookie(text: &str) -> Option<(&str, &str)> {
+    if !text.starts_with('[') {
+        return None;
+    }
+    let close = text.find(']')?;
+    Some((&text[1..close], &text[close + 1..]))
+}
+
+/// Accept `text` only if it is empty or continues with whitespace.
+fn followed_by_space(text: &str) -> bool {
+    text.is_empty() || text.starts_with(char::is_whitespace)
+}
+
+/// Whether `line` starts a list item.
+pub fn is_item_line(line: &str) -> bool {
+    parse_item_line(line).is_some()
+}
+
+fn parse_item_line(line: &str) -> Option<ItemLine<'_>> {
+    let indent = indentation(line);
+    let trimmed = line[indent..].trim_end();
+    let bullet_len = match trimmed.chars().next()? {
+        '-' | '+' => 1,
+        // A star in the first column would be a headline.
+        '*' if indent > 0 => 1,
+        c if c.is_ascii_digit() => {
+            let digits = trimmed.find(|c: char| !c.is_ascii_digit())?;
+            match trimmed[digits..].chars().next() {
+                Some('.') | Some(')') => digits + 1,
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+    let bullet = &trimmed[..bullet_len];
+    let mut rest = &trimmed[bullet_len..];
+    if !followed_by_space(rest) {
+        return None;
+    }
+    let content_column = indent + bullet_len + 1;
+    rest = rest.trim_start();
+
+    let mut counter = None;
+    if let Some((inside, after)) = cookie(rest) {
+        if let Some(Ok(n)) = inside.strip_prefix('@').map(str::parse) {
+            counter = Some(n);
+            rest = after.trim_start();
+        }
+    }
+
+    let mut checkbox = None;
+    if let Some((inside, after)) = cookie(rest) {
+        let state = match inside {
+            " " => Some(Checkbox::Unchecked),
+            "-" => Some(Checkbox::Partial),
+            "X" | "x" => Some(Checkbox::Checked),
+            _ => None,
+        };
+        if state.is_some() && followed_by_space(after) {
+            checkbox = state;
+            rest = after.trim_start();
+        }
+    }
+
+    let mut tag = None;
+    let ordered = bullet.ends_with('.') || bullet.ends_with(')');
+    if !ordered {
+        if let Some(separator) = rest.find(" ::") {
+            if followed_by_space(&rest[separator + 3..]) {
+                tag = Some(rest[..separator].trim());
+                rest = rest[separator + 3..].trim_start();
+            }
+        }
+    }
+
+    Some(ItemLine {
+        indent,
+        bullet,
+        counter,
+        checkbox,
+        tag,
+        rest,
+        content_column,
+    })
+}
+
+/// Try to parse a plain list starting at `lines[start]`. Returns the list and
+/// the index of the first line after it.
+pub fn parse_list(parser: &DocumentParser, lines: &[&str], offsets: &[usize], start: usize)
+                  -> Option<(PlainList, usize)> {
+    let first = parse_item_line(lines[start])?;
+    let list_indent = first.indent;
+    let kind = if first.bullet.ends_with('.') || first.bullet.ends_with(')') {
+        ListKind::Ordered
+    } else if first.tag.is_some() {
+        ListKind::Descriptive
+    } else {
+        ListKind::Unordered
+    };
+
+    let mut items = Vec::new();
+    let mut current = Some(first);
+    let mut i = start + 1;
+    while let Some(item) = current.take() {
+        let item_start = i - 1;
+        let mut body = vec![item.rest];
+        let rest_offset = item.rest.as_ptr() as usize - lines[item_start].as_ptr() as usize;
+        let mut body_offsets = vec![offsets[item_start] + rest_offset];
+        let mut blank_run = 0;
+        while i < lines.len() {
+            let line = lines[i];
+            if line.trim().is_empty() {
+                blank_run += 1;
+                if blank_run == 2 {
+                    break;
+                }
+                body.push("");
+                body_offsets.push(offsets[i]);
+                i += 1;
+                continue;
+            }
+            let indent = indentation(line);
+            if indent <= list_indent {
+                break;
+            }
+            blank_run = 0;
+            // Whitespace like U+3000 takes more than a byte, so count it by
+            // characters to stop at one of their boundaries.
+            let stripped = line.char_indices()
+                .take_while(|&(_, c)| c.is_whitespace())
+                .take(item.content_column)
+                .last()
+                .map_or(0, |(i, c)| i + c.len_utf8());
+            body.push(&line[stripped..]);
+            body_offsets.push(offsets[i] + stripped);
+            i += 1;
+        }
+        while body.len() > 1 && body.last() == Some(&"") {
+            body.pop();
+            body_offsets.pop();
+        }
+
+        items.push(ListItem {
+            bullet: item.bullet.into(),
+            counter: item.counter,
+            checkbox: item.checkbox,
+            tag: item.tag.map(String::from),
+            text: body.join("\n"),
+            contents: ::parse_contents(parser, &body, &body_offsets),
+            span: span::lines_span(lines, offsets, item_start, item_start + body.len()),
+        });
+
+        if blank_run < 2 && i < lines.len() {
+            current = parse_item_line(lines[i]).filter(|next| next.indent == list_indent);
+            if current.is_some() {
+                i += 1;
+            }
+        }
+    }
+
+    // Blank lines after the last item separate the list from what follows,
+    // they aren't part of it.
+    while i > start + 1 && lines[i - 1].trim().is_empty() {
+        i -= 1;
+    }
+    let span = span::lines_span(lines, offsets, start, i);
+    let affiliated = Affiliated::default();
+    Some((PlainList { kind, items, indent: list_indent, affiliated, span }, i))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {Element, GreaterElement};
+
+    fn list(text: &str) -> PlainList {
+        let parser = DocumentParser::new();
+        let lines: Vec<_> = text.lines().collect();
+        parse_list(&parser, &lines, &span::line_offsets(text, 0), 0).unwrap().0
+    }
+
+    #[test]
+    fn test_parse_list() {
+        let parsed = list("- [X] Buy milk
+- [ ] Write
+  a letter
+  1. Draft
+  2) [@5] [-] Send
++ Last");
+        assert_eq!(parsed.kind(), ListKind::Unordered);
+        let items = parsed.items();
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[0].checkbox(), Some(Checkbox::Checked));
+        assert_eq!(items[0].text(), "Buy milk");
+        assert_eq!(items[1].checkbox(), Some(Checkbox::Unchecked));
+        assert_eq!(items[1].text(), "Write\na letter\n1. Draft\n2) [@5] [-] Send");
+        assert_eq!(items[2].bullet(), "+");
+        assert_eq!(items[1].span(), Span::new(15, 67));
+
+        let nested = match items[1].contents() {
+            [Content::Element(Element::Paragraph(text)),
+             Content::Greater(GreaterElement::PlainList(nested))] => {
+                assert_eq!(text.text(), "Write\na letter");
+                nested
+            }
+            other => panic!("expected text and a nested list, got {:?}", other),
+        };
+        assert_eq!(nested.kind(), ListKind::Ordered);
+        assert_eq!(nested.items()[1].bullet(), "2)");
+        assert_eq!(nested.items()[1].counter(), Some(5));
+        assert_eq!(nested.items()[1].checkbox(), Some(Checkbox::Partial));
+        assert_eq!(nested.items()[1].text(), "Send");
+        assert_eq!(nested.span(), Span::new(40, 67));
+    }
+
+    #[test]
+    fn test_list_boundaries() {
+        let parser = DocumentParser::new();
+        let lines: Vec<_> = "- Term :: Definition
+- Other ::
+
+  still the same item
+
+
+- a new list
+-----
+not a list".lines().collect();
+        let offsets = span::line_offsets(&lines.join("\n"), 0);
+        let (first, next) = parse_list(&parser, &lines, &offsets, 0).unwrap();
+        assert_eq!(first.kind(), ListKind::Descriptive);
+        assert_eq!(first.items()[0].tag(), Some("Term"));
+        assert_eq!(first.items()[0].text(), "Definition");
+        assert_eq!(first.items()[1].tag(), Some("Other"));
+        assert_eq!(first.items()[1].text(), "\n\nstill the same item");
+        assert_eq!(next, 4);
+
+        let (second, next) = parse_list(&parser, &lines, &offsets, 6).unwrap();
+        assert_eq!(second.items().len(), 1);
+        assert_eq!(next, 7);
+        assert!(parse_list(&parser, &lines, &offsets, 7).is_none());
+        assert!(parse_list(&parser, &["*bold* text"], &[0], 0).is_none());
+
+        let doc = parser.parse("- x\n \u{3000}#+BEGIN_SRC mermaid").unwrap();
+        assert_eq!(doc.to_org_string(), "- x\n \u{3000}#+BEGIN_SRC mermaid");
+    }
+
+    #[test]
+    fn test_renumber() {
+        let mut parsed = list("3. one
+1. two
+7. [@10] ten
+2. eleven");
+        parsed.renumber();
+        let bullets: Vec<_> = parsed.items().iter().map(ListItem::bullet).collect();
+        assert_eq!(bullets, ["1.", "2.", "10.", "11."]);
+
+        assert!(parsed.set_bullet_style(BulletStyle::Parenthesis));
+        assert_eq!(parsed.items()[3].bullet(), "11)");
+        assert!(parsed.set_bullet_style(BulletStyle::Plus));
+        assert_eq!(parsed.kind(), ListKind::Unordered);
+        assert_eq!(parsed.bullet_style(), Some(BulletStyle::Plus));
+        assert!(parsed.items().iter().all(|item| item.bullet() == "+"));
+        assert!(!parsed.set_bullet_style(BulletStyle::Star));
+        let mut indented = list("  - a\n  - b");
+        assert!(indented.set_bullet_style(BulletStyle::Star));
+        assert_eq!(indented.items()[1].bullet(), "*");
+
+        let mut described = list("- Term :: Definition\n- Other :: More");
+        assert!(!described.set_bullet_style(BulletStyle::Period));
+        assert_eq!(described.items()[0].bullet(), "-");
+
+        let mut doc = DocumentParser::new().parse("* Steps
+1. First
+  - [ ] sub
+  + [X] sub
+3. Second
+4) Third
+").unwrap();
+        doc.renumber_lists();
+        assert_eq!(doc.to_org_string(), "* Steps
+1. First
+  - [ ] sub
+  - [X] sub
+2. Second
+3. Third
+");
+        let offset = doc.to_org_string().find("sub").unwrap();
+        assert!(doc.set_bullet_style(offset, BulletStyle::Plus));
+        assert!(!doc.set_bullet_style(0, BulletStyle::Minus));
+        assert!(doc.set_bullet_style(9, BulletStyle::Parenthesis));
+        assert_eq!(doc.to_org_string(), "* Steps
+1) First
+  + [ ] sub
+  + [X] sub
+2) Second
+3) Third
+");
+    }
+}
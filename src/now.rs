@@ -0,0 +1,59 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use {Date, Time};
+
+/// Where the current date and time come from, for the functions that work
+/// relative to now, like `Headline::clock_in` and `TodoConfig::at`. Give
+/// them a fixed `(Date, Time)` or a closure in tests, and `SystemNow`
+/// otherwise.
+pub trait Now {
+    fn now(&self) -> (Date, Time);
+
+    fn today(&self) -> Date {
+        self.now().0
+    }
+}
+
+impl Now for (Date, Time) {
+    fn now(&self) -> (Date, Time) {
+        *self
+    }
+}
+
+impl<F: Fn() -> (Date, Time)> Now for F {
+    fn now(&self) -> (Date, Time) {
+        self()
+    }
+}
+
+/// The system clock, at a fixed offset from UTC, since there is no time
+/// zone database to find the local one in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SystemNow {
+    offset_minutes: i32,
+}
+
+impl SystemNow {
+    pub fn utc() -> SystemNow {
+        SystemNow { offset_minutes: 0 }
+    }
+
+    /// The time `minutes` ahead of UTC, or behind it for a negative count,
+    /// like 120 for UTC+2.
+    pub fn with_offset(minutes: i32) -> SystemNow {
+        SystemNow { offset_minutes: minutes }
+    }
+}
+
+impl Now for SystemNow {
+    fn now(&self) -> (Date, Time) {
+        let seconds = match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(since) => since.as_secs() as i64,
+            Err(before) => -(before.duration().as_secs() as i64),
+        };
+        let minutes = seconds.div_euclid(60) + self.offset_minutes as i64;
+        let (days, minute_of_day) = (minutes.div_euclid(24 * 60), minutes.rem_euclid(24 * 60));
+        let date = Date { year: 1970, month: 1, day: 1 }.add_days(days);
+        (date, Time { hour: (minute_of_day / 60) as u8, minute: (minute_of_day % 60) as u8 })
+    }
+}
@@ -0,0 +1,821 @@
+use footnote::{self, FootnoteReference};
+use raw::Raw;
+use span::Span;
+use statistics::{self, StatisticsCookie};
+use timestamp::{self, Timestamp};
+
+/// Inline content of titles and paragraphs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum Object {
+    /// Plain text without any markup.
+    Text(String),
+    /// `*bold*`
+    Bold(Vec<Object>),
+    /// `/italic/`
+    Italic(Vec<Object>),
+    /// `_underline_`
+    Underline(Vec<Object>),
+    /// `+strike-through+`
+    StrikeThrough(Vec<Object>),
+    /// `=verbatim=`, never parsed further.
+    Verbatim(String),
+    /// `~code~`, never parsed further.
+    Code(String),
+    /// `[[destination][description]]`, `<type:path>` or a plain
+    /// `type:path` link.
+    Link(Link),
+    /// `<<target>>`, the destination of internal links.
+    Target(String),
+    /// `<<<radio target>>>`, a target that the same text anywhere else in
+    /// the document links to, see `link_radio_targets`.
+    RadioTarget(String),
+    /// `\alpha`, `\to` and the like.
+    Entity(Entity),
+    /// `H_{2}` or `x_{i+1}`, right after other text, or `x_i` after a
+    /// one-character word.
+    Subscript(Script),
+    /// `x^2` or `e^{i\pi}`, right after other text.
+    Superscript(Script),
+    /// `$x^2$`, `$$\sum x$$`, `\(x\)` or `\[x\]`, kept as written, delimiters
+    /// included.
+    LatexFragment(String),
+    FootnoteReference(FootnoteReference),
+    /// `[2/5]` or `[40%]`.
+    StatisticsCookie(StatisticsCookie),
+    Timestamp(Timestamp),
+    /// A timestamp that can't be parsed, kept as written, see
+    /// `DocumentParser::lenient`.
+    Raw(Raw),
+}
+
+/// What a link points to, determined from its destination.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum LinkType {
+    /// `file:notes.org`, or a bare path like `./notes.org` or `/tmp/x`.
+    File,
+    /// `http:` and `https:` URLs.
+    Http,
+    /// `id:`, matching the `ID` property of a headline.
+    Id,
+    /// `#custom-id`, matching the `CUSTOM_ID` property of a headline.
+    CustomId,
+    /// `*Title`, matching the title of a headline.
+    Headline,
+    /// `(ref)`, a coderef label in a source block.
+    Coderef,
+    /// Text without a type, matching a target or a headline title.
+    Fuzzy,
+    /// Text matching a radio target, made by `link_radio_targets`.
+    Radio,
+    /// Any other `type:path` link, holding the type, like `mailto` or `doi`.
+    Other(String),
+}
+
+/// A link, see `Object::Link`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Link {
+    destination: String,
+    description: Option<Vec<Object>>,
+    radio: bool,
+}
+
+impl Link {
+    /// What the link points to, as written, e.g. `https://orgmode.org` or
+    /// `file:notes.org::*Heading`.
+    pub fn destination(&self) -> &str {
+        &self.destination
+    }
+
+    /// The description of a bracket link, if it has one.
+    pub fn description(&self) -> Option<&[Object]> {
+        self.description.as_ref().map(|d| &d[..])
+    }
+
+    /// The link type prefix of the destination, if it has one.
+    fn protocol(&self) -> Option<&str> {
+        let colon = self.destination.find(':')?;
+        let protocol = &self.destination[..colon];
+        let valid = protocol.starts_with(|c: char| c.is_ascii_alphabetic())
+            && protocol.chars().all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-');
+        if valid { Some(protocol) } else { None }
+    }
+
+    pub fn link_type(&self) -> LinkType {
+        let destination = &self.destination[..];
+        if self.radio {
+            return LinkType::Radio;
+        }
+        match self.protocol() {
+            Some("file") => LinkType::File,
+            Some("http") | Some("https") => LinkType::Http,
+            Some("id") => LinkType::Id,
+            Some(protocol) => LinkType::Other(protocol.into()),
+            None if destination.starts_with('#') => LinkType::CustomId,
+            None if destination.starts_with('*') => LinkType::Headline,
+            None if destination.starts_with('(') && destination.ends_with(')') => LinkType::Coderef,
+            None if destination.starts_with(&['/', '.', '~'][..]) => LinkType::File,
+            None => LinkType::Fuzzy,
+        }
+    }
+
+    /// The destination without its type prefix or search option: the file
+    /// name of a file link, the id of an `id:` link, the title of a `*Title`
+    /// link, and so on.
+    pub fn path(&self) -> &str {
+        let destination = &self.destination[..];
+        match self.link_type() {
+            LinkType::File => {
+                let path = destination.strip_prefix("file:").unwrap_or(destination);
+                path.find("::").map_or(path, |i| &path[..i])
+            }
+            LinkType::CustomId | LinkType::Headline => &destination[1..],
+            LinkType::Coderef => &destination[1..destination.len() - 1],
+            LinkType::Fuzzy | LinkType::Radio => destination,
+            _ => &destination[self.protocol().unwrap().len() + 1..],
+        }
+    }
+
+    /// The search option of a file link, after `::`: a line number, a
+    /// `*Title`, a `#custom-id`, or text to search for.
+    pub fn search_option(&self) -> Option<&str> {
+        if self.link_type() != LinkType::File {
+            return None;
+        }
+        self.destination.find("::").map(|i| &self.destination[i + 2..])
+    }
+}
+
+/// What a subscript or superscript holds, see `Object::Subscript`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Script {
+    braced: bool,
+    contents: Vec<Object>,
+}
+
+impl Script {
+    /// Whether it was written in braces, like `x^{2}`, which is the only
+    /// form `#+OPTIONS: ^:{}` allows.
+    pub fn is_braced(&self) -> bool {
+        self.braced
+    }
+
+    pub fn contents(&self) -> &[Object] {
+        &self.contents
+    }
+}
+
+/// Which subscripts and superscripts are exported as such, set with the `^`
+/// item of `#+OPTIONS:`, see `Document::scripts`. The others are exported as
+/// they were written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Scripts {
+    /// `^:t`, the default.
+    All,
+    /// `^:{}`, only those in braces.
+    Braced,
+    /// `^:nil`.
+    None,
+}
+
+impl Scripts {
+    pub fn allows(self, script: &Script) -> bool {
+        match self {
+            Scripts::All => true,
+            Scripts::Braced => script.braced,
+            Scripts::None => false,
+        }
+    }
+}
+
+/// A named entity, see `Object::Entity`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Entity {
+    name: String,
+}
+
+impl Entity {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The character the entity stands for. Parsed entities always have
+    /// one; this is empty only for unknown names from deserialized data.
+    pub fn utf8(&self) -> &'static str {
+        ENTITIES.iter()
+            .find(|&&(name, _)| name == self.name)
+            .map_or("", |&(_, utf8)| utf8)
+    }
+}
+
+/// The entities recognized by the parser, with the text they stand for.
+const ENTITIES: &[(&str, &str)] = &[
+    ("alpha", "α"), ("beta", "β"), ("gamma", "γ"), ("delta", "δ"),
+    ("epsilon", "ε"), ("zeta", "ζ"), ("eta", "η"), ("theta", "θ"),
+    ("iota", "ι"), ("kappa", "κ"), ("lambda", "λ"), ("mu", "μ"),
+    ("nu", "ν"), ("xi", "ξ"), ("pi", "π"), ("rho", "ρ"), ("sigma", "σ"),
+    ("tau", "τ"), ("upsilon", "υ"), ("phi", "φ"), ("chi", "χ"),
+    ("psi", "ψ"), ("omega", "ω"), ("Gamma", "Γ"), ("Delta", "Δ"),
+    ("Theta", "Θ"), ("Lambda", "Λ"), ("Pi", "Π"), ("Sigma", "Σ"),
+    ("Phi", "Φ"), ("Psi", "Ψ"), ("Omega", "Ω"),
+    ("to", "→"), ("rarr", "→"), ("larr", "←"), ("harr", "↔"),
+    ("rArr", "⇒"), ("lArr", "⇐"), ("hArr", "⇔"),
+    ("times", "×"), ("div", "÷"), ("pm", "±"), ("neq", "≠"), ("ne", "≠"),
+    ("le", "≤"), ("ge", "≥"), ("approx", "≈"), ("infin", "∞"), ("deg", "°"),
+    ("nbsp", "\u{a0}"), ("ndash", "–"), ("mdash", "—"), ("hellip", "…"),
+    ("dots", "…"), ("laquo", "«"), ("raquo", "»"), ("copy", "©"),
+    ("reg", "®"), ("trade", "™"), ("sect", "§"), ("para", "¶"),
+    ("euro", "€"), ("pound", "£"), ("yen", "¥"), ("cent", "¢"),
+    ("checkmark", "✓"), ("star", "⋆"), ("vert", "|"), ("amp", "&"),
+    ("lt", "<"), ("gt", ">"), ("backslash", "\\"),
+];
+
+/// Link types recognized in plain and angle links.
+const LINK_TYPES: &[&str] = &[
+    "attachment", "doi", "elisp", "file", "ftp", "help", "http", "https", "id",
+    "info", "mailto", "news", "shell",
+];
+
+/// Characters allowed right before an emphasis marker.
+fn is_pre(c: char) -> bool {
+    c.is_whitespace() || "-({'\"".contains(c)
+}
+
+/// Characters allowed right after an emphasis marker.
+fn is_post(c: char) -> bool {
+    c.is_whitespace() || "-.,;:!?'\")}[\\".contains(c)
+}
+
+fn marker_object(marker: char, inner: &str) -> Object {
+    match marker {
+        '*' => Object::Bold(parse_objects(inner)),
+        '/' => Object::Italic(parse_objects(inner)),
+        '_' => Object::Underline(parse_objects(inner)),
+        '+' => Object::StrikeThrough(parse_objects(inner)),
+        '=' => Object::Verbatim(inner.into()),
+        '~' => Object::Code(inner.into()),
+        _ => unreachable!(),
+    }
+}
+
+/// Parse emphasis at the start of `text`, returning it and its length. The
+/// caller checks what precedes the opening marker.
+fn parse_emphasis(text: &str) -> Option<(Object, usize)> {
+    let marker = text.chars().next()?;
+    if !"*/_+=~".contains(marker) {
+        return None;
+    }
+    let body = &text[1..];
+    if body.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let mut newlines = 0;
+    for (i, c) in body.char_indices() {
+        if c == '\n' {
+            newlines += 1;
+            if newlines > 1 {
+                return None;
+            }
+        }
+        if c != marker || i == 0 || body[..i].ends_with(char::is_whitespace) {
+            continue;
+        }
+        let after = &body[i + 1..];
+        if after.chars().next().is_none_or(is_post) {
+            return Some((marker_object(marker, &body[..i]), i + 2));
+        }
+    }
+    None
+}
+
+/// Parse a `[[destination][description]]` link at the start of `text`.
+fn parse_bracket_link(text: &str) -> Option<(Object, usize)> {
+    let rest = text.strip_prefix("[[")?;
+    let mut destination = String::new();
+    let mut chars = rest.char_indices();
+    let dest_end = loop {
+        match chars.next()? {
+            (_, '\\') => destination.push(chars.next()?.1),
+            (i, ']') => break i,
+            (_, '[') => return None,
+            (_, c) => destination.push(c),
+        }
+    };
+    if destination.trim().is_empty() {
+        return None;
+    }
+    let after = &rest[dest_end + 1..];
+    if after.starts_with(']') {
+        let link = Link { destination, description: None, radio: false };
+        return Some((Object::Link(link), 2 + dest_end + 2));
+    }
+    let description = after.strip_prefix('[')?;
+    let desc_end = description.find("]]")?;
+    let link = Link {
+        destination,
+        description: Some(parse_objects(&description[..desc_end])),
+        radio: false,
+    };
+    Some((Object::Link(link), 2 + dest_end + 2 + desc_end + 2))
+}
+
+/// The length of a plain `type:path` link at the start of `text`.
+fn plain_link_len(text: &str) -> Option<usize> {
+    let colon = text.find(':')?;
+    if !LINK_TYPES.contains(&&text[..colon]) {
+        return None;
+    }
+    let path_len = text[colon + 1..]
+        .find(|c: char| c.is_whitespace() || "()<>[]".contains(c))
+        .unwrap_or(text.len() - colon - 1);
+    let path = &text[colon + 1..colon + 1 + path_len];
+    // Trailing punctuation is more likely to belong to the sentence.
+    let path = path.trim_end_matches(|c: char| c.is_ascii_punctuation() && c != '/');
+    if path.is_empty() {
+        return None;
+    }
+    Some(colon + 1 + path.len())
+}
+
+fn parse_angle_link(text: &str) -> Option<(Object, usize)> {
+    let rest = text.strip_prefix('<')?;
+    let close = rest.find('>')?;
+    let inner = &rest[..close];
+    let colon = inner.find(':')?;
+    if !LINK_TYPES.contains(&&inner[..colon]) || inner.contains('\n') {
+        return None;
+    }
+    let link = Link { destination: inner.into(), description: None, radio: false };
+    Some((Object::Link(link), close + 2))
+}
+
+fn parse_target(text: &str) -> Option<(Object, usize)> {
+    let rest = text.strip_prefix("<<")?;
+    if rest.starts_with('<') {
+        return None;
+    }
+    let close = rest.find(">>")?;
+    let inner = &rest[..close];
+    if inner.is_empty() || inner.contains(&['<', '>', '\n'][..])
+        || inner.starts_with(char::is_whitespace) || inner.ends_with(char::is_whitespace) {
+        return None;
+    }
+    Some((Object::Target(inner.into()), close + 4))
+}
+
+fn parse_radio_target(text: &str) -> Option<(Object, usize)> {
+    let rest = text.strip_prefix("<<<")?;
+    let close = rest.find(">>>")?;
+    let inner = &rest[..close];
+    if inner.is_empty() || inner.contains(&['<', '>', '\n'][..])
+        || inner.starts_with(char::is_whitespace) || inner.ends_with(char::is_whitespace) {
+        return None;
+    }
+    Some((Object::RadioTarget(inner.into()), close + 6))
+}
+
+/// Parse a subscript or superscript at the start of `text`, which starts
+/// with its `_` or `^`.
+fn parse_script(text: &str) -> Option<(Object, usize)> {
+    let rest = &text[1..];
+    let (braced, contents, len) = if rest.starts_with('{') {
+        let mut depth = 0;
+        let close = rest.char_indices().find(|&(_, c)| {
+            match c {
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                _ => {}
+            }
+            depth == 0
+        })?.0;
+        (true, parse_objects(&rest[1..close]), close + 2)
+    } else if rest.starts_with('*') {
+        (false, vec![Object::Text("*".into())], 2)
+    } else {
+        let sign = if rest.starts_with(['+', '-']) { 1 } else { 0 };
+        let body = &rest[sign..];
+        let end = body.find(|c: char| !c.is_alphanumeric() && !",.\\".contains(c))
+            .unwrap_or(body.len());
+        let end = body[..end].rfind(char::is_alphanumeric)?;
+        let end = sign + end + body[end..].chars().next()?.len_utf8();
+        (false, vec![Object::Text(rest[..end].into())], end + 1)
+    };
+    let script = Script { braced, contents };
+    match text.as_bytes()[0] {
+        b'_' => Some((Object::Subscript(script), len)),
+        _ => Some((Object::Superscript(script), len)),
+    }
+}
+
+/// Whether `before` ends with a one-character word, like the `x` of `x_i`.
+/// Unbraced subscripts only follow those, so that names like `snake_case`
+/// and `BEGIN_SRC` stay text.
+fn is_script_base(before: &str) -> bool {
+    let mut chars = before.chars().rev();
+    chars.next().is_some_and(char::is_alphanumeric)
+        && chars.next().is_none_or(|c| !c.is_alphanumeric() && c != '_')
+}
+
+/// Parse a LaTeX fragment at the start of `text`. `prev` is the character
+/// before it.
+fn parse_latex_fragment(text: &str, prev: Option<char>) -> Option<(Object, usize)> {
+    let fragment = |len: usize| Some((Object::LatexFragment(text[..len].into()), len));
+    for &(open, close) in &[("\\(", "\\)"), ("\\[", "\\]"), ("$$", "$$")] {
+        if let Some(rest) = text.strip_prefix(open) {
+            return fragment(open.len() + rest.find(close)? + close.len());
+        }
+    }
+    // A single `$` can't be right after another or start a price like `$5
+    // and $6`: the text inside must not start or end with a space.
+    let rest = text.strip_prefix('$')?;
+    if prev == Some('$') || rest.starts_with(|c: char| c.is_whitespace() || ".,;$".contains(c)) {
+        return None;
+    }
+    let mut from = 0;
+    loop {
+        let close = from + rest[from..].find(['$', '\n'])?;
+        if rest[close..].starts_with('\n') {
+            return None;
+        }
+        let inside = &rest[..close];
+        let after = rest[close + 1..].chars().next();
+        if !inside.ends_with(|c: char| c.is_whitespace() || ".,;$".contains(c))
+            && after.is_none_or(|c| !c.is_alphanumeric()) {
+            return fragment(close + 2);
+        }
+        from = close + 1;
+    }
+}
+
+fn parse_entity(text: &str) -> Option<(Object, usize)> {
+    let rest = text.strip_prefix('\\')?;
+    let name_len = rest.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(rest.len());
+    let name = &rest[..name_len];
+    if !ENTITIES.iter().any(|&(entity, _)| entity == name) {
+        return None;
+    }
+    // `\name{}` separates an entity from following letters.
+    let len = if rest[name_len..].starts_with("{}") { name_len + 3 } else { name_len + 1 };
+    Some((Object::Entity(Entity { name: name.into() }), len))
+}
+
+fn collect_links(objects: &[Object], links: &mut Vec<Link>) {
+    for object in objects {
+        match *object {
+            Object::Link(ref link) => links.push(link.clone()),
+            Object::Bold(ref inner) | Object::Italic(ref inner)
+                | Object::Underline(ref inner) | Object::StrikeThrough(ref inner) => {
+                collect_links(inner, links);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Find every link in `text`, in order, including those nested in emphasis.
+pub fn find_links(text: &str) -> Vec<Link> {
+    let mut links = Vec::new();
+    collect_links(&parse_objects(text), &mut links);
+    links
+}
+
+/// Parse the inline objects in `text`, such as the title of a headline or the
+/// text of a paragraph.
+pub fn parse_objects(text: &str) -> Vec<Object> {
+    parse_objects_with_spans(text).into_iter().map(|(object, _)| object).collect()
+}
+
+/// Parse the inline objects in `text` like `parse_objects`, along with where
+/// each of them is in `text`.
+pub fn parse_objects_with_spans(text: &str) -> Vec<(Object, Span)> {
+    let mut objects = Vec::new();
+    let mut plain_start = 0;
+    let mut i = 0;
+    let mut prev: Option<char> = None;
+    while i < text.len() {
+        let rest = &text[i..];
+        let c = rest.chars().next().unwrap();
+        let at_word_start = prev.is_none_or(|p| !p.is_alphanumeric());
+        let found = match c {
+            '*' | '/' | '_' | '+' | '=' | '~' if prev.is_none_or(is_pre) => parse_emphasis(rest)
+                .or_else(|| match prev {
+                    Some(p) if c == '_' && !p.is_whitespace() && rest[1..].starts_with('{') => {
+                        parse_script(rest)
+                    }
+                    _ => None,
+                }),
+            '^' if prev.is_some_and(|p| !p.is_whitespace()) => parse_script(rest),
+            '_' if prev.is_some_and(|p| !p.is_whitespace()) => {
+                if rest[1..].starts_with('{') {
+                    parse_script(rest)
+                } else if is_script_base(&text[..i]) {
+                    parse_script(rest).filter(|&(_, len)| !rest[len..].starts_with('_'))
+                } else {
+                    None
+                }
+            }
+            '$' => parse_latex_fragment(rest, prev),
+            '[' => parse_bracket_link(rest)
+                .or_else(|| footnote::parse_reference(rest)
+                         .map(|(r, len)| (Object::FootnoteReference(r), len)))
+                .or_else(|| statistics::parse_cookie(rest)
+                         .map(|(c, len)| (Object::StatisticsCookie(c), len)))
+                .or_else(|| timestamp::parse_prefix(rest)
+                         .map(|(t, len)| (Object::Timestamp(t), len))),
+            '<' => parse_radio_target(rest)
+                .or_else(|| parse_target(rest))
+                .or_else(|| timestamp::parse_prefix(rest)
+                         .map(|(t, len)| (Object::Timestamp(t), len)))
+                .or_else(|| parse_angle_link(rest)),
+            '\\' => parse_latex_fragment(rest, prev).or_else(|| parse_entity(rest)),
+            _ if at_word_start && c.is_ascii_alphabetic() => plain_link_len(rest).map(|len| {
+                let destination = rest[..len].into();
+                let link = Link { destination, description: None, radio: false };
+                (Object::Link(link), len)
+            }),
+            _ => None,
+        };
+        match found {
+            Some((object, len)) => {
+                if plain_start < i {
+                    objects.push((Object::Text(text[plain_start..i].into()),
+                                  Span::new(plain_start, i)));
+                }
+                objects.push((object, Span::new(i, i + len)));
+                i += len;
+                plain_start = i;
+                prev = text[..i].chars().next_back();
+            }
+            None => {
+                i += c.len_utf8();
+                prev = Some(c);
+            }
+        }
+    }
+    if plain_start < text.len() {
+        objects.push((Object::Text(text[plain_start..].into()),
+                      Span::new(plain_start, text.len())));
+    }
+    objects
+}
+
+/// The innermost object in `text` around the byte `offset`, looking inside
+/// emphasis and link descriptions, and its span in `text`.
+pub fn object_at(text: &str, offset: usize) -> Option<(Object, Span)> {
+    let (object, span) = parse_objects_with_spans(text).into_iter()
+        .find(|&(_, span)| span.contains(offset))?;
+    let inner = match object {
+        Object::Bold(_) | Object::Italic(_) | Object::Underline(_) | Object::StrikeThrough(_) => {
+            Some(Span::new(span.start + 1, span.end - 1))
+        }
+        Object::Link(Link { description: Some(_), .. }) if text[span.start..].starts_with("[[") => {
+            description_start(span.slice(text))
+                .map(|start| Span::new(span.start + start, span.end - 2))
+        }
+        _ => None,
+    };
+    let inner = inner.filter(|inner| inner.contains(offset)).and_then(|inner| {
+        let (object, found) = object_at(inner.slice(text), offset - inner.start)?;
+        Some((object, Span::new(inner.start + found.start, inner.start + found.end)))
+    });
+    inner.or(Some((object, span)))
+}
+
+/// Where the description starts in the `[[destination][description]]` link
+/// `text`.
+fn description_start(text: &str) -> Option<usize> {
+    let mut chars = text.char_indices().skip(2);
+    loop {
+        match chars.next()? {
+            (_, '\\') => {
+                chars.next()?;
+            }
+            (i, ']') => return Some(i + 2),
+            _ => {}
+        }
+    }
+}
+
+/// The text of `objects` without any markup.
+pub fn plain_text(objects: &[Object]) -> String {
+    let mut text = String::new();
+    for object in objects {
+        match *object {
+            Object::Text(ref s) | Object::Verbatim(ref s) | Object::Code(ref s) => text.push_str(s),
+            Object::Bold(ref inner) | Object::Italic(ref inner)
+                | Object::Underline(ref inner) | Object::StrikeThrough(ref inner) => {
+                text.push_str(&plain_text(inner));
+            }
+            Object::Link(ref link) => match link.description() {
+                Some(description) => text.push_str(&plain_text(description)),
+                None => text.push_str(link.destination()),
+            },
+            Object::Entity(ref entity) => text.push_str(entity.utf8()),
+            Object::StatisticsCookie(cookie) => text.push_str(&cookie.to_string()),
+            Object::Timestamp(ref timestamp) => text.push_str(&timestamp.to_string()),
+            Object::RadioTarget(ref s) | Object::LatexFragment(ref s) => text.push_str(s),
+            Object::Raw(ref raw) => text.push_str(raw.text()),
+            Object::Subscript(ref script) | Object::Superscript(ref script) => {
+                text.push_str(&plain_text(script.contents()));
+            }
+            Object::Target(_) | Object::FootnoteReference(_) => {}
+        }
+    }
+    text
+}
+
+/// Turn the text in `objects` that matches one of the radio `targets`,
+/// ignoring case, into links of type `LinkType::Radio` to the target, like
+/// org does for a document with `<<<radio targets>>>`. Matches must be whole
+/// words, and the longest target wins. Text in links, code and verbatim
+/// stays as it is.
+pub fn link_radio_targets(objects: Vec<Object>, targets: &[String]) -> Vec<Object> {
+    let mut targets: Vec<&str> = targets.iter().map(|target| &target[..]).collect();
+    targets.sort_by_key(|target| ::std::cmp::Reverse(target.len()));
+    link_objects(objects, &targets)
+}
+
+fn link_objects(objects: Vec<Object>, targets: &[&str]) -> Vec<Object> {
+    if targets.is_empty() {
+        return objects;
+    }
+    let mut linked = Vec::new();
+    for object in objects {
+        match object {
+            Object::Text(text) => link_text(&text, targets, &mut linked),
+            Object::Bold(inner) => linked.push(Object::Bold(link_objects(inner, targets))),
+            Object::Italic(inner) => linked.push(Object::Italic(link_objects(inner, targets))),
+            Object::Underline(inner) => {
+                linked.push(Object::Underline(link_objects(inner, targets)));
+            }
+            Object::StrikeThrough(inner) => {
+                linked.push(Object::StrikeThrough(link_objects(inner, targets)));
+            }
+            object => linked.push(object),
+        }
+    }
+    linked
+}
+
+/// Push `text` to `linked` as text and radio links to `targets`, which are
+/// sorted longest first.
+fn link_text(text: &str, targets: &[&str], linked: &mut Vec<Object>) {
+    let mut plain_start = 0;
+    let mut i = 0;
+    let mut prev: Option<char> = None;
+    while i < text.len() {
+        let rest = &text[i..];
+        let c = rest.chars().next().unwrap();
+        let found = if prev.is_some_and(char::is_alphanumeric) {
+            None
+        } else {
+            targets.iter().find(|target| {
+                rest.get(..target.len()).is_some_and(|candidate| {
+                    candidate.to_lowercase() == target.to_lowercase()
+                        && !rest[target.len()..].starts_with(char::is_alphanumeric)
+                })
+            })
+        };
+        match found {
+            Some(target) => {
+                if plain_start < i {
+                    linked.push(Object::Text(text[plain_start..i].into()));
+                }
+                let matched = &rest[..target.len()];
+                linked.push(Object::Link(Link {
+                    destination: target.to_string(),
+                    description: Some(vec![Object::Text(matched.into())]),
+                    radio: true,
+                }));
+                i += target.len();
+                plain_start = i;
+                prev = matched.chars().next_back();
+            }
+            None => {
+                i += c.len_utf8();
+                prev = Some(c);
+            }
+        }
+    }
+    if plain_start < text.len() {
+        linked.push(Object::Text(text[plain_start..].into()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(s: &str) -> Object {
+        Object::Text(s.into())
+    }
+
+    #[test]
+    fn test_emphasis() {
+        assert_eq!(parse_objects("a *bold /and italic/* word"), [
+            text("a "),
+            Object::Bold(vec![text("bold "), Object::Italic(vec![text("and italic")])]),
+            text(" word"),
+        ]);
+        assert_eq!(parse_objects("=*verb*= and ~code~."), [
+            Object::Verbatim("*verb*".into()),
+            text(" and "),
+            Object::Code("code".into()),
+            text("."),
+        ]);
+        // Boundary rules: markers inside words or next to spaces don't count.
+        assert_eq!(parse_objects("2*3*4 and * not bold * and a_b_c"),
+                   [text("2*3*4 and * not bold * and a_b_c")]);
+        assert_eq!(parse_objects("+one\ntwo+"),
+                   [Object::StrikeThrough(vec![text("one\ntwo")])]);
+        assert_eq!(parse_objects("_one\ntwo\nthree_"), [text("_one\ntwo\nthree_")]);
+    }
+
+    #[test]
+    fn test_links_and_others() {
+        let objects = parse_objects(
+            "See [[https://orgmode.org][the *site*]], [[file:a.org]], https://x.org/a. \
+             or <mailto:me@x.org> <<here>> \\alpha\\to{}x[fn:1] <2024-01-01 Mon>");
+        assert_eq!(objects[0], text("See "));
+        match objects[1] {
+            Object::Link(ref link) => {
+                assert_eq!(link.destination(), "https://orgmode.org");
+                assert_eq!(link.description().unwrap()[1],
+                           Object::Bold(vec![text("site")]));
+            }
+            ref other => panic!("expected a link, got {:?}", other),
+        }
+        let links: Vec<_> = objects.iter()
+            .filter_map(|o| match *o {
+                Object::Link(ref link) => Some(link.destination()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(links, [
+            "https://orgmode.org", "file:a.org", "https://x.org/a", "mailto:me@x.org",
+        ]);
+        assert!(objects.contains(&Object::Target("here".into())));
+        let entities: Vec<_> = objects.iter()
+            .filter_map(|o| match *o {
+                Object::Entity(ref e) => Some(e.utf8()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(entities, ["α", "→"]);
+        assert!(objects.iter().any(|o| match *o {
+            Object::FootnoteReference(ref r) => r.label() == Some("1"),
+            _ => false,
+        }));
+        assert!(matches!(objects.last(), Some(&Object::Timestamp(_))));
+        assert_eq!(parse_objects("\\unknown"), [text("\\unknown")]);
+    }
+
+    #[test]
+    fn test_scripts_and_latex() {
+        let script = |braced, s: &str| Script { braced, contents: vec![text(s)] };
+        assert_eq!(parse_objects("x^2, H_{2}O and x_i"), [
+            text("x"),
+            Object::Superscript(script(false, "2")),
+            text(", H"),
+            Object::Subscript(script(true, "2")),
+            text("O and x"),
+            Object::Subscript(script(false, "i")),
+        ]);
+        assert_eq!(parse_objects("snake_case and a_b_c"), [text("snake_case and a_b_c")]);
+        assert_eq!(parse_objects("If $x$, \\(y\\) and $$z$$."), [
+            text("If "),
+            Object::LatexFragment("$x$".into()),
+            text(", "),
+            Object::LatexFragment("\\(y\\)".into()),
+            text(" and "),
+            Object::LatexFragment("$$z$$".into()),
+            text("."),
+        ]);
+        assert_eq!(parse_objects("$5 and $6"), [text("$5 and $6")]);
+        assert!(matches!(parse_objects("\\alpha")[..], [Object::Entity(_)]));
+    }
+
+    #[test]
+    fn test_link_types() {
+        let links = find_links("[[file:notes.org::*Tasks]] /[[./a.org]]/ [[#intro]] [[id:42]]
+[[*A headline]] [[(ref)]] [[some text]] [[doi:10.1/x]] https://x.org =https://no=");
+        let described: Vec<_> = links.iter()
+            .map(|l| (l.link_type(), l.path(), l.search_option()))
+            .collect();
+        assert_eq!(described, [
+            (LinkType::File, "notes.org", Some("*Tasks")),
+            (LinkType::File, "./a.org", None),
+            (LinkType::CustomId, "intro", None),
+            (LinkType::Id, "42", None),
+            (LinkType::Headline, "A headline", None),
+            (LinkType::Coderef, "ref", None),
+            (LinkType::Fuzzy, "some text", None),
+            (LinkType::Other("doi".into()), "10.1/x", None),
+            (LinkType::Http, "//x.org", None),
+        ]);
+    }
+}
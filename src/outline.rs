@@ -0,0 +1,188 @@
+use std::ptr;
+
+use {Document, Headline};
+
+/// The indices, from the top level down, of the headline reached by following
+/// `titles`, taking the first sibling with each title.
+pub fn find_path(headlines: &[Headline], titles: &[&str]) -> Option<Vec<usize>> {
+    let mut path = Vec::new();
+    let mut siblings = headlines;
+    for &title in titles {
+        let i = siblings.iter().position(|headline| headline.title() == title)?;
+        path.push(i);
+        siblings = &siblings[i].headlines;
+    }
+    Some(path)
+}
+
+/// The indices, from the top level down, of `target` among `headlines` and
+/// their descendants, found by address so that it must be one of them
+/// rather than a copy.
+pub fn path_of(headlines: &[Headline], target: &Headline) -> Option<Vec<usize>> {
+    fn find(headlines: &[Headline], target: &Headline, path: &mut Vec<usize>) -> bool {
+        for (i, headline) in headlines.iter().enumerate() {
+            path.push(i);
+            if ptr::eq(headline, target) || find(&headline.headlines, target, path) {
+                return true;
+            }
+            path.pop();
+        }
+        false
+    }
+
+    let mut path = Vec::new();
+    if find(headlines, target, &mut path) { Some(path) } else { None }
+}
+
+/// A headline of a document along with where it is, for moving to its
+/// parent and siblings, which headlines themselves don't know.
+#[derive(Debug, Clone)]
+pub struct Cursor<'a> {
+    document: &'a Document,
+    path: Vec<usize>,
+    headline: &'a Headline,
+}
+
+impl<'a> Cursor<'a> {
+    fn at(document: &'a Document, path: Vec<usize>) -> Option<Cursor<'a>> {
+        let (&first, rest) = path.split_first()?;
+        let mut headline = document.headlines.get(first)?;
+        for &i in rest {
+            headline = headline.headlines.get(i)?;
+        }
+        Some(Cursor { document, path, headline })
+    }
+
+    pub fn headline(&self) -> &'a Headline {
+        self.headline
+    }
+
+    /// The indices of the headline among its siblings, from the top level
+    /// down, like `kanban::Card::path`.
+    pub fn path(&self) -> &[usize] {
+        &self.path
+    }
+
+    /// The titles of the headline's ancestors from the top level down, and
+    /// its own title last, as `Document::find_olp` takes them.
+    pub fn olp(&self) -> Vec<&'a str> {
+        let mut titles = Vec::new();
+        let mut siblings = &self.document.headlines;
+        for &i in &self.path {
+            titles.push(siblings[i].title());
+            siblings = &siblings[i].headlines;
+        }
+        titles
+    }
+
+    /// The headline this one is a child of, or `None` at the top level.
+    pub fn parent(&self) -> Option<Cursor<'a>> {
+        let (_, parent) = self.path.split_last()?;
+        Cursor::at(self.document, parent.to_vec())
+    }
+
+    pub fn next_sibling(&self) -> Option<Cursor<'a>> {
+        let mut path = self.path.clone();
+        *path.last_mut()? += 1;
+        Cursor::at(self.document, path)
+    }
+
+    pub fn prev_sibling(&self) -> Option<Cursor<'a>> {
+        let mut path = self.path.clone();
+        let last = path.last_mut()?;
+        *last = last.checked_sub(1)?;
+        Cursor::at(self.document, path)
+    }
+
+    pub fn first_child(&self) -> Option<Cursor<'a>> {
+        let mut path = self.path.clone();
+        path.push(0);
+        Cursor::at(self.document, path)
+    }
+}
+
+impl Document {
+    /// The headline at the outline path `titles`, like `["Projects",
+    /// "org-rs", "Parser"]`, each title picking the first sibling with it.
+    pub fn find_olp(&self, titles: &[&str]) -> Option<&Headline> {
+        self.cursor_at(&find_path(&self.headlines, titles)?).map(|cursor| cursor.headline())
+    }
+
+    /// A cursor on the headline at `path`, its indices among its siblings
+    /// from the top level down.
+    pub fn cursor_at(&self, path: &[usize]) -> Option<Cursor<'_>> {
+        Cursor::at(self, path.to_vec())
+    }
+
+    /// The headline at `path`, to change it in place.
+    pub fn headline_at_mut(&mut self, path: &[usize]) -> Option<&mut Headline> {
+        let (&first, rest) = path.split_first()?;
+        let mut headline = self.headlines.get_mut(first)?;
+        for &i in rest {
+            headline = headline.headlines.get_mut(i)?;
+        }
+        Some(headline)
+    }
+
+    /// A cursor on `headline`, which must be one of this document's own
+    /// headlines rather than a copy.
+    pub fn cursor<'a>(&'a self, headline: &Headline) -> Option<Cursor<'a>> {
+        Cursor::at(self, path_of(&self.headlines, headline)?)
+    }
+
+    /// The outline path of `headline`, see `Cursor::olp`.
+    pub fn olp(&self, headline: &Headline) -> Option<Vec<&str>> {
+        self.cursor(headline).map(|cursor| cursor.olp())
+    }
+
+    /// The subtree of the headline at `path` as a document of its own, with
+    /// this document's TODO keywords and priorities, parsed again from its
+    /// text so that its spans are offsets into `to_org_string` of the new
+    /// document. The headline keeps its level.
+    pub fn subtree_at(&self, path: &[usize]) -> Option<Document> {
+        let text = self.cursor_at(path)?.headline().to_org_string();
+        self.parser.parse(&text).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use DocumentParser;
+
+    #[test]
+    fn test_outline_navigation() {
+        let doc = DocumentParser::new().parse("#+TODO: NEXT | DONE
+* Inbox
+* Projects
+** org-rs
+*** NEXT Parser
+Lexing first.
+*** Exporter
+** Garden
+").unwrap();
+        let parser = doc.find_olp(&["Projects", "org-rs", "Parser"]).unwrap();
+        assert_eq!(doc.olp(parser).unwrap(), ["Projects", "org-rs", "Parser"]);
+        assert!(doc.find_olp(&["Projects", "Parser"]).is_none());
+        assert!(doc.olp(&parser.clone()).is_none());
+
+        let cursor = doc.cursor(parser).unwrap();
+        assert_eq!(cursor.path(), [1, 0, 0]);
+        assert!(cursor.prev_sibling().is_none());
+        assert_eq!(cursor.next_sibling().unwrap().headline().title(), "Exporter");
+        let project = cursor.parent().unwrap();
+        assert_eq!(project.next_sibling().unwrap().headline().title(), "Garden");
+        assert_eq!(project.parent().unwrap().prev_sibling().unwrap().headline().title(), "Inbox");
+        assert!(project.parent().unwrap().parent().is_none());
+        assert_eq!(project.first_child().unwrap().headline().keyword(), Some("NEXT"));
+
+        let subtree = doc.subtree_at(&[1, 0]).unwrap();
+        assert_eq!(subtree.to_org_string(), "** org-rs
+*** NEXT Parser
+Lexing first.
+*** Exporter
+");
+        assert_eq!(subtree.headlines()[0].children()[0].keyword(), Some("NEXT"));
+        assert!(doc.subtree_at(&[5]).is_none());
+        assert!(doc.subtree_at(&[]).is_none());
+    }
+}
@@ -0,0 +1,186 @@
+use keyword::{Affiliate, Affiliated};
+use object::{self, Object};
+use raw;
+use span::{self, Shift, Span};
+use DocumentParser;
+
+/// A run of text lines that don't belong to any other element. It ends at a
+/// blank line or at the first line starting another element.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Paragraph {
+    text: String,
+    affiliated: Affiliated,
+    span: Span,
+    /// Where each line of the text starts in the parsed text, which differs
+    /// from `span.start` plus its offset in the text for the lines of a list
+    /// item, whose indentation isn't part of the text.
+    line_offsets: Vec<usize>,
+    lenient: bool,
+}
+
+impl Paragraph {
+    /// The lines of the paragraph as written.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// The text parsed into inline objects. With `DocumentParser::lenient`,
+    /// timestamps that can't be parsed are `Object::Raw`.
+    pub fn objects(&self) -> Vec<Object> {
+        self.objects_with_spans().into_iter().map(|(object, _)| object).collect()
+    }
+
+    /// The objects of `objects`, each with where it is in the parsed text.
+    pub fn objects_with_spans(&self) -> Vec<(Object, Span)> {
+        let mut objects = Vec::new();
+        let mut rest = 0;
+        for (raw, span) in self.raw_timestamps_in_text() {
+            self.extend_objects(&mut objects, rest, span.start);
+            rest = span.end;
+            objects.push((Object::Raw(raw), self.source_span(span)));
+        }
+        self.extend_objects(&mut objects, rest, self.text.len());
+        objects
+    }
+
+    /// The innermost object around the byte `offset` of the parsed text,
+    /// looking inside emphasis and link descriptions, and its span.
+    pub fn object_at(&self, offset: usize) -> Option<(Object, Span)> {
+        let offset = self.text_offset(offset)?;
+        let mut rest = 0;
+        let mut segments = Vec::new();
+        for (raw, span) in self.raw_timestamps_in_text() {
+            segments.push((rest, span.start));
+            if span.contains(offset) {
+                return Some((Object::Raw(raw), self.source_span(span)));
+            }
+            rest = span.end;
+        }
+        segments.push((rest, self.text.len()));
+        let &(start, end) = segments.iter().find(|&&(start, end)| start <= offset && offset < end)?;
+        let (object, span) = object::object_at(&self.text[start..end], offset - start)?;
+        Some((object, self.source_span(Span::new(start + span.start, start + span.end))))
+    }
+
+    /// The timestamps in the text that can't be parsed, when parsed with
+    /// `DocumentParser::lenient`.
+    pub fn raw_timestamps(&self) -> Vec<raw::Raw> {
+        self.raw_timestamps_in_text().into_iter().map(|(raw, _)| raw).collect()
+    }
+
+    /// The timestamps of `raw_timestamps` with where each is in the text.
+    fn raw_timestamps_in_text(&self) -> Vec<(raw::Raw, Span)> {
+        if !self.lenient {
+            return Vec::new();
+        }
+        raw::find_bad_timestamps(&self.text, 0).into_iter()
+            .map(|mut raw| {
+                let span = raw.span();
+                raw.shift(self.source_offset(span.start) as isize - span.start as isize);
+                (raw, span)
+            })
+            .collect()
+    }
+
+    fn extend_objects(&self, objects: &mut Vec<(Object, Span)>, start: usize, end: usize) {
+        for (object, span) in object::parse_objects_with_spans(&self.text[start..end]) {
+            let span = Span::new(start + span.start, start + span.end);
+            objects.push((object, self.source_span(span)));
+        }
+    }
+
+    /// Where the byte `offset` of the text is in the parsed text.
+    fn source_offset(&self, offset: usize) -> usize {
+        let line = self.text[..offset].matches('\n').count();
+        let line_start = self.text[..offset].rfind('\n').map_or(0, |newline| newline + 1);
+        self.line_offsets.get(line).map_or(self.span.start + offset, |start| {
+            start + offset - line_start
+        })
+    }
+
+    fn source_span(&self, span: Span) -> Span {
+        Span::new(self.source_offset(span.start), self.source_offset(span.end))
+    }
+
+    /// Where the byte `offset` of the parsed text is in the text, if it is in
+    /// it rather than in the indentation of a line.
+    fn text_offset(&self, offset: usize) -> Option<usize> {
+        let line = self.line_offsets.iter().rposition(|&start| start <= offset)?;
+        let line_start: usize = self.text.split('\n').take(line).map(|l| l.len() + 1).sum();
+        let line_len = self.text[line_start..].find('\n').unwrap_or(self.text.len() - line_start);
+        let column = offset - self.line_offsets[line];
+        if column <= line_len { Some(line_start + column) } else { None }
+    }
+
+    /// The `#+NAME:`, `#+CAPTION:` and other keywords written above it.
+    pub fn affiliated(&self) -> &Affiliated {
+        &self.affiliated
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+}
+
+impl Affiliate for Paragraph {
+    fn affiliated_mut(&mut self) -> &mut Affiliated {
+        &mut self.affiliated
+    }
+}
+
+impl Shift for Paragraph {
+    fn shift(&mut self, delta: isize) {
+        self.affiliated.shift(delta);
+        self.span.shift(delta);
+        for offset in &mut self.line_offsets {
+            *offset = (*offset as isize + delta) as usize;
+        }
+    }
+}
+
+/// Parse a paragraph starting at `lines[start]`, which must not be blank.
+/// Returns the paragraph and the index of the first line after it.
+pub fn parse_paragraph(parser: &DocumentParser, lines: &[&str], offsets: &[usize], start: usize)
+                       -> (Paragraph, usize) {
+    let mut end = start + 1;
+    while end < lines.len() && !lines[end].trim().is_empty()
+        && ::parse_element(parser, lines, offsets, end).is_none() {
+        end += 1;
+    }
+    (Paragraph {
+        text: lines[start..end].join("\n"),
+        affiliated: Affiliated::default(),
+        span: span::lines_span(lines, offsets, start, end),
+        line_offsets: offsets[start..end].to_vec(),
+        lenient: parser.lenient,
+    }, end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_paragraph() {
+        let lines: Vec<_> = "First line
+second line
+
+After a blank line
+| table |
+#+BEGIN_SRC
+unclosed blocks are text
+- item".lines().collect();
+        let offsets = span::line_offsets(&lines.join("\n"), 0);
+        let parser = DocumentParser::new();
+        let (paragraph, next) = parse_paragraph(&parser, &lines, &offsets, 0);
+        assert_eq!(next, 2);
+        assert_eq!(paragraph.text(), "First line\nsecond line");
+        assert_eq!(paragraph.span(), Span::new(0, 22));
+        assert_eq!(parse_paragraph(&parser, &lines, &offsets, 3).1, 4);
+        let (paragraph, next) = parse_paragraph(&parser, &lines, &offsets, 5);
+        assert_eq!(next, 7);
+        let text = "#+BEGIN_SRC\nunclosed blocks are text";
+        assert_eq!(paragraph.objects(), [Object::Text(text.into())]);
+    }
+}
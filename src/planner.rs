@@ -0,0 +1,211 @@
+//! Day planning: a first proposal of when to work on the tasks of today's
+//! agenda, fitting them by their `EFFORT` into the free time of the day.
+//!
+//! Entries with a time of day stay at that time and take up their effort, or
+//! the time range of their timestamp. The others go into the free blocks
+//! first fit, the highest priority and most urgent first and among those the
+//! longest first, so that what is left over are the small and unimportant
+//! tasks.
+
+use std::ptr;
+
+use agenda::{Entry, EntryKind};
+use {Date, Priorities, Time, parse_duration};
+
+/// A stretch of free time in a day.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Block {
+    pub start: Time,
+    pub end: Time,
+}
+
+impl Block {
+    pub fn new(start: Time, end: Time) -> Block {
+        Block { start, end }
+    }
+
+    pub fn minutes(&self) -> u32 {
+        minutes(self.end).saturating_sub(minutes(self.start))
+    }
+}
+
+/// An entry given a time in the plan.
+#[derive(Debug, Clone)]
+pub struct Slot<'a> {
+    pub start: Time,
+    pub end: Time,
+    pub entry: Entry<'a>,
+    /// Whether it stays at the time its timestamp gives it.
+    pub fixed: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Plan<'a> {
+    /// The planned entries, in order of time.
+    pub slots: Vec<Slot<'a>>,
+    /// The tasks without an `EFFORT`, and those that didn't fit, in the
+    /// order they would have been planned.
+    pub unplanned: Vec<Entry<'a>>,
+}
+
+/// Plan the entries `entries` has on `today` that aren't done into the
+/// `free` blocks. A headline on the agenda more than once is planned once,
+/// as its most urgent entry. Headlines without a priority cookie count as
+/// the default priority, `B`.
+pub fn plan<'a>(entries: &[Entry<'a>], today: Date, free: &[Block]) -> Plan<'a> {
+    let mut candidates: Vec<&Entry<'a>> = entries.iter()
+        .filter(|entry| entry.date == today && !entry.done)
+        .collect();
+    let default = Priorities::default().default;
+    candidates.sort_by_key(|entry| {
+        (entry.headline.priority().unwrap_or(default), urgency(entry.kind),
+         !effort(entry).unwrap_or(0))
+    });
+    let mut seen = Vec::new();
+    candidates.retain(|entry| {
+        let first = !seen.iter().any(|&headline| ptr::eq(headline, entry.headline));
+        seen.push(entry.headline);
+        first
+    });
+
+    let mut blocks: Vec<(u32, u32)> = free.iter()
+        .map(|block| (minutes(block.start), minutes(block.end)))
+        .filter(|&(start, end)| start < end)
+        .collect();
+    let mut slots = Vec::new();
+    let mut unplanned = Vec::new();
+    for entry in candidates.iter().filter(|entry| entry.time.is_some()) {
+        let start = entry.time.map_or(0, minutes);
+        let same_day = entry.timestamp.end_date() == Some(entry.timestamp.date());
+        let end = match (effort(entry), entry.timestamp.end_time()) {
+            (Some(effort), _) => start + effort,
+            (None, Some(end)) if same_day => minutes(end).max(start),
+            _ => start,
+        };
+        blocks = blocks.into_iter()
+            .flat_map(|(from, to)| vec![(from, to.min(start)), (from.max(end), to)])
+            .filter(|&(from, to)| from < to)
+            .collect();
+        slots.push(Slot {
+            start: time(start),
+            end: time(end),
+            entry: (*entry).clone(),
+            fixed: true,
+        });
+    }
+    for entry in candidates.iter().filter(|entry| entry.time.is_none()) {
+        let effort = match effort(entry) {
+            Some(effort) => effort,
+            None => {
+                unplanned.push((*entry).clone());
+                continue;
+            }
+        };
+        match blocks.iter_mut().find(|&&mut (from, to)| to - from >= effort) {
+            Some(block) => {
+                let start = block.0;
+                block.0 += effort;
+                slots.push(Slot {
+                    start: time(start),
+                    end: time(start + effort),
+                    entry: (*entry).clone(),
+                    fixed: false,
+                });
+            }
+            None => unplanned.push((*entry).clone()),
+        }
+    }
+    slots.sort_by_key(|slot| (slot.start, slot.end));
+    Plan { slots, unplanned }
+}
+
+/// How soon an entry needs doing, the most urgent first.
+fn urgency(kind: EntryKind) -> u8 {
+    match kind {
+        EntryKind::DeadlineOverdue(_) => 0,
+        EntryKind::Deadline => 1,
+        EntryKind::ScheduledLate(_) => 2,
+        EntryKind::DeadlineWarning(_) => 3,
+        EntryKind::Scheduled => 4,
+        EntryKind::Timestamp => 5,
+    }
+}
+
+/// The entry's `EFFORT` in minutes.
+fn effort(entry: &Entry) -> Option<u32> {
+    entry.headline.property("EFFORT").and_then(parse_duration)
+}
+
+fn minutes(time: Time) -> u32 {
+    time.hour as u32 * 60 + time.minute as u32
+}
+
+/// The time `minutes` after midnight, stopping at the end of the day.
+fn time(minutes: u32) -> Time {
+    let minutes = minutes.min(24 * 60 - 1);
+    Time { hour: (minutes / 60) as u8, minute: (minutes % 60) as u8 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use agenda::agenda;
+    use DocumentParser;
+
+    fn at(hour: u8, minute: u8) -> Time {
+        Time { hour, minute }
+    }
+
+    #[test]
+    fn test_plan() {
+        let parser = DocumentParser::new().todo_keywords(vec!["TODO", "DONE"]);
+        let doc = parser.parse("* TODO Meeting
+  SCHEDULED: <2024-05-07 Tue 10:00-11:00>
+* TODO [#C] Email
+  SCHEDULED: <2024-05-07 Tue>
+  :PROPERTIES:
+  :EFFORT: 0:30
+  :END:
+* TODO Report
+  DEADLINE: <2024-05-07 Tue> SCHEDULED: <2024-05-06 Mon>
+  :PROPERTIES:
+  :EFFORT: 1:30
+  :END:
+* TODO [#A] Review
+  SCHEDULED: <2024-05-07 Tue>
+  :PROPERTIES:
+  :EFFORT: 0:45
+  :END:
+* TODO Big
+  SCHEDULED: <2024-05-07 Tue>
+  :PROPERTIES:
+  :EFFORT: 4:00
+  :END:
+* TODO Vague
+  SCHEDULED: <2024-05-07 Tue>
+* DONE Finished
+  SCHEDULED: <2024-05-07 Tue>
+  :PROPERTIES:
+  :EFFORT: 0:10
+  :END:").unwrap();
+        let today = Date { year: 2024, month: 5, day: 7 };
+        let entries = agenda(&[&doc], today, today, today);
+        let plan = plan(&entries, today, &[Block::new(at(9, 0), at(12, 0)),
+                                           Block::new(at(13, 0), at(15, 0))]);
+        let slots: Vec<_> = plan.slots.iter()
+            .map(|slot| format!("{}-{} {}{}", slot.start, slot.end, slot.entry.headline.title(),
+                                if slot.fixed { " (fixed)" } else { "" }))
+            .collect();
+        assert_eq!(slots, [
+            "09:00-09:45 Review",
+            "10:00-11:00 Meeting (fixed)",
+            "11:00-11:30 Email",
+            "13:00-14:30 Report",
+        ]);
+        let unplanned: Vec<_> = plan.unplanned.iter()
+            .map(|entry| entry.headline.title())
+            .collect();
+        assert_eq!(unplanned, ["Big", "Vague"]);
+        assert_eq!(Block::new(at(9, 0), at(8, 0)).minutes(), 0);
+    }
+}
@@ -0,0 +1,107 @@
+use span::{Shift, Span};
+use timestamp::{self, Timestamp};
+
+/// The planning line directly below a headline, holding any of its
+/// SCHEDULED, DEADLINE and CLOSED timestamps in any order:
+///
+/// ```ignore
+/// * TODO Write report
+///   DEADLINE: <2024-05-10 Fri> SCHEDULED: <2024-05-06 Mon>
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Planning {
+    scheduled: Option<Timestamp>,
+    deadline: Option<Timestamp>,
+    closed: Option<Timestamp>,
+    span: Span,
+}
+
+impl Planning {
+    pub fn scheduled(&self) -> Option<&Timestamp> {
+        self.scheduled.as_ref()
+    }
+
+    pub fn deadline(&self) -> Option<&Timestamp> {
+        self.deadline.as_ref()
+    }
+
+    pub fn closed(&self) -> Option<&Timestamp> {
+        self.closed.as_ref()
+    }
+
+    /// Where the planning line is in the text, or an empty span for a
+    /// headline without one.
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scheduled.is_none() && self.deadline.is_none() && self.closed.is_none()
+    }
+
+    pub fn set_scheduled(&mut self, scheduled: Option<Timestamp>) {
+        self.scheduled = scheduled;
+    }
+
+    pub fn set_deadline(&mut self, deadline: Option<Timestamp>) {
+        self.deadline = deadline;
+    }
+
+    pub fn set_closed(&mut self, closed: Option<Timestamp>) {
+        self.closed = closed;
+    }
+}
+
+impl Shift for Planning {
+    fn shift(&mut self, delta: isize) {
+        self.span.shift(delta);
+    }
+}
+
+/// Parse `line`, starting at `offset` in the text, as a planning line, which
+/// must contain nothing but planning keywords each followed by a timestamp.
+pub fn parse_planning(line: &str, offset: usize) -> Option<Planning> {
+    let mut planning = Planning::default();
+    let mut rest = line.trim();
+    if rest.is_empty() {
+        return None;
+    }
+    while !rest.is_empty() {
+        let colon = rest.find(':')?;
+        let slot = match &rest[..colon] {
+            "SCHEDULED" => &mut planning.scheduled,
+            "DEADLINE" => &mut planning.deadline,
+            "CLOSED" => &mut planning.closed,
+            _ => return None,
+        };
+        let after = rest[colon + 1..].trim_start();
+        let (timestamp, len) = timestamp::parse_prefix(after)?;
+        *slot = Some(timestamp);
+        rest = after[len..].trim_start();
+    }
+    planning.span = Span::new(offset, offset + line.len());
+    Some(planning)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_planning() {
+        let planning = parse_planning(
+            "  DEADLINE: <2024-05-10 Fri -2d> SCHEDULED: <2024-05-06 Mon 9:00>", 5).unwrap();
+        assert_eq!(planning.span(), Span::new(5, 70));
+        assert_eq!(planning.deadline().unwrap().date().day, 10);
+        assert_eq!(planning.scheduled().unwrap().time().unwrap().hour, 9);
+        assert!(planning.closed().is_none());
+
+        let planning = parse_planning("CLOSED: [2024-05-07 Tue 17:12]", 0).unwrap();
+        assert!(!planning.closed().unwrap().is_active());
+
+        assert!(parse_planning("SCHEDULED: <2024-05-06 Mon> and then", 0).is_none());
+        assert!(parse_planning("Note: <2024-05-06 Mon>", 0).is_none());
+        assert!(parse_planning("", 0).is_none());
+    }
+}
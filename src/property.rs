@@ -0,0 +1,420 @@
+use std::error::Error;
+use std::fmt;
+
+use {Document, Headline, Timestamp};
+
+/// Why a property couldn't be read as the type asked for, or can't have its
+/// value because a `KEY_ALL` property doesn't allow it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PropertyError {
+    key: String,
+    value: String,
+    expected: Option<PropertyType>,
+    allowed: Vec<String>,
+}
+
+impl PropertyError {
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// The value as written.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// The type the value should have had, or `None` if it isn't one of
+    /// the `allowed` values.
+    pub fn expected(&self) -> Option<PropertyType> {
+        self.expected
+    }
+
+    pub fn allowed(&self) -> &[String] {
+        &self.allowed
+    }
+}
+
+impl fmt::Display for PropertyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "property {} is {:?}, not ", self.key, self.value)?;
+        match self.expected {
+            Some(kind) => write!(f, "{}", kind.name()),
+            None => write!(f, "one of {}", self.allowed.join(", ")),
+        }
+    }
+}
+
+impl Error for PropertyError {}
+
+/// The types `PropertySchema` can require of a property.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PropertyType {
+    /// A whole number, like `-3`.
+    Int,
+    /// A duration read by `parse_duration`, like `1:30` or `2d 4h`.
+    Duration,
+    /// A timestamp, like `<2024-05-01 Wed>`.
+    Timestamp,
+    /// `t` or `nil`, or `yes`, `no`, `true`, `false`, `on` or `off`, in
+    /// any case.
+    Bool,
+}
+
+impl PropertyType {
+    fn name(self) -> &'static str {
+        match self {
+            PropertyType::Int => "an integer",
+            PropertyType::Duration => "a duration",
+            PropertyType::Timestamp => "a timestamp",
+            PropertyType::Bool => "a boolean",
+        }
+    }
+
+    fn accepts(self, value: &str) -> bool {
+        match self {
+            PropertyType::Int => value.trim().parse::<i64>().is_ok(),
+            PropertyType::Duration => parse_duration(value).is_some(),
+            PropertyType::Timestamp => Timestamp::parse(value).is_some(),
+            PropertyType::Bool => parse_bool(value).is_some(),
+        }
+    }
+}
+
+/// The types properties must have, for `lint::lint_properties` to check a
+/// document against. Keys are matched case-insensitively.
+///
+/// ```ignore
+/// let schema = PropertySchema::new()
+///     .property("Effort", PropertyType::Duration)
+///     .property("Billable", PropertyType::Bool);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct PropertySchema {
+    types: Vec<(String, PropertyType)>,
+}
+
+impl PropertySchema {
+    pub fn new() -> Self {
+        PropertySchema::default()
+    }
+
+    /// Require `key` to have values of type `kind`, replacing any type given
+    /// for it before.
+    pub fn property<S: Into<String>>(mut self, key: S, kind: PropertyType) -> Self {
+        let key = key.into();
+        self.types.retain(|(k, _)| !k.eq_ignore_ascii_case(&key));
+        self.types.push((key, kind));
+        self
+    }
+
+    /// The type required of `key`, if the schema has one.
+    pub fn get(&self, key: &str) -> Option<PropertyType> {
+        self.types.iter().find(|(k, _)| k.eq_ignore_ascii_case(key)).map(|&(_, kind)| kind)
+    }
+
+    /// Check `value` against the type of `key`. Keys the schema doesn't know
+    /// take any value.
+    pub fn check(&self, key: &str, value: &str) -> Result<(), PropertyError> {
+        match self.get(key) {
+            Some(kind) if !kind.accepts(value) => Err(PropertyError {
+                key: key.into(),
+                value: value.into(),
+                expected: Some(kind),
+                allowed: Vec::new(),
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Parse a duration like org's `org-duration-to-minutes` into minutes:
+/// either `H:MM` or `H:MM:SS`, or numbers with the units `min`, `h`, `d`,
+/// `w`, `m` for months of 30 days and `y` for years of 365.25 days, like
+/// `2d 4h` or `90min`.
+pub fn parse_duration(text: &str) -> Option<u32> {
+    let text = text.trim();
+    if text.contains(':') {
+        let parts: Vec<u32> = text.split(':')
+            .map(|part| part.parse().ok())
+            .collect::<Option<_>>()?;
+        return match parts[..] {
+            [hours, minutes] => hours.checked_mul(60)?.checked_add(minutes),
+            [hours, minutes, seconds] => {
+                let rounded = seconds / 60 + u32::from(seconds % 60 >= 30);
+                hours.checked_mul(60)?.checked_add(minutes)?.checked_add(rounded)
+            }
+            _ => None,
+        };
+    }
+    if text.is_empty() {
+        return None;
+    }
+    let mut total: u32 = 0;
+    let mut rest = text;
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        let value: u32 = rest[..digits].parse().ok()?;
+        rest = rest[digits..].trim_start();
+        let unit_len = rest.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(rest.len());
+        let unit = match &rest[..unit_len] {
+            "min" => 1,
+            "h" => 60,
+            "d" => 24 * 60,
+            "w" => 7 * 24 * 60,
+            "m" => 30 * 24 * 60,
+            "y" => 525_960,
+            _ => return None,
+        };
+        total = total.checked_add(value.checked_mul(unit)?)?;
+        rest = rest[unit_len..].trim_start();
+    }
+    Some(total)
+}
+
+fn parse_bool(text: &str) -> Option<bool> {
+    match &text.trim().to_ascii_lowercase()[..] {
+        "t" | "yes" | "true" | "on" => Some(true),
+        "nil" | "no" | "false" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+impl Headline {
+    /// Read the property `key` with `parse`, which failing makes an error
+    /// about `kind`. A headline without the property gives `Ok(None)`.
+    fn typed_property<T, F>(&self, key: &str, kind: PropertyType, parse: F)
+                            -> Result<Option<T>, PropertyError>
+        where F: FnOnce(&str) -> Option<T> {
+        let value = match self.property(key) {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        parse(value).map(Some).ok_or_else(|| PropertyError {
+            key: key.into(),
+            value: value.into(),
+            expected: Some(kind),
+            allowed: Vec::new(),
+        })
+    }
+
+    pub fn property_as_int(&self, key: &str) -> Result<Option<i64>, PropertyError> {
+        self.typed_property(key, PropertyType::Int, |value| value.trim().parse().ok())
+    }
+
+    /// The property `key` in minutes, see `parse_duration`.
+    pub fn property_as_duration(&self, key: &str) -> Result<Option<u32>, PropertyError> {
+        self.typed_property(key, PropertyType::Duration, parse_duration)
+    }
+
+    pub fn property_as_timestamp(&self, key: &str) -> Result<Option<Timestamp>, PropertyError> {
+        self.typed_property(key, PropertyType::Timestamp, Timestamp::parse)
+    }
+
+    /// The property `key` as a boolean, see `PropertyType::Bool`.
+    pub fn property_as_bool(&self, key: &str) -> Result<Option<bool>, PropertyError> {
+        self.typed_property(key, PropertyType::Bool, parse_bool)
+    }
+}
+
+/// The values of a `KEY_ALL` property, separated by whitespace.
+fn allowed_values(text: &str) -> Vec<String> {
+    text.split_whitespace().map(String::from).collect()
+}
+
+/// The key of the property listing the values allowed for `key`, if `key`
+/// isn't one itself.
+fn all_key(key: &str) -> Option<String> {
+    if key.len() > 4 && key.is_char_boundary(key.len() - 4)
+        && key[key.len() - 4..].eq_ignore_ascii_case("_ALL") {
+        None
+    } else {
+        Some(format!("{}_ALL", key))
+    }
+}
+
+impl Document {
+    /// The values a `#+PROPERTY: KEY_ALL value...` line allows for `key`
+    /// anywhere in the document.
+    pub fn allowed_property_values(&self, key: &str) -> Option<Vec<String>> {
+        let all = all_key(key)?;
+        self.file_properties().into_iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(&all))
+            .map(|(_, values)| allowed_values(&values))
+    }
+
+    /// The values allowed for `key` on the headline at `path`, its indices
+    /// among its siblings from the top level down: those of the `KEY_ALL`
+    /// property of the headline or of its nearest ancestor with one, or
+    /// else those of `allowed_property_values`.
+    pub fn allowed_property_values_at(&self, path: &[usize], key: &str) -> Option<Vec<String>> {
+        self.inherited_property(path, &all_key(key)?).map(|values| allowed_values(&values))
+    }
+
+    /// The property `key` of the headline at `path`, or else of its nearest
+    /// ancestor with it, or else of a `#+PROPERTY:` line, like the
+    /// properties org lets headlines inherit.
+    pub fn inherited_property(&self, path: &[usize], key: &str) -> Option<String> {
+        let mut value = None;
+        let mut siblings = &self.headlines;
+        for &i in path {
+            let headline = siblings.get(i)?;
+            value = headline.property(key).or(value);
+            siblings = &headline.headlines;
+        }
+        match value {
+            Some(value) => Some(value.into()),
+            None => self.file_properties().into_iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(key))
+                .map(|(_, value)| value),
+        }
+    }
+
+    /// Set the property `key` of the headline at `path` to `value`, like
+    /// `Headline::set_property`, unless `allowed_property_values_at` rules
+    /// it out. Returns false if there is no headline at `path`.
+    pub fn set_property_at(&mut self, path: &[usize], key: &str, value: &str)
+                           -> Result<bool, PropertyError> {
+        if let Some(allowed) = self.allowed_property_values_at(path, key) {
+            if !allowed.iter().any(|allowed| allowed == value) {
+                return Err(PropertyError {
+                    key: key.into(),
+                    value: value.into(),
+                    expected: None,
+                    allowed,
+                });
+            }
+        }
+        match self.headline_at_mut(path) {
+            Some(headline) => {
+                headline.set_property(key, value);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// The properties of every headline, depth-first, whose values aren't
+    /// among those allowed for them there, see `allowed_property_values_at`.
+    pub fn disallowed_properties(&self) -> Vec<(&Headline, PropertyError)> {
+        fn visit<'a>(headlines: &'a [Headline], inherited: &mut Vec<(String, Vec<String>)>,
+                     found: &mut Vec<(&'a Headline, PropertyError)>) {
+            for headline in headlines {
+                let before = inherited.len();
+                for (key, values) in headline.properties().iter() {
+                    if all_key(key).is_none() {
+                        inherited.push((key[..key.len() - 4].into(), allowed_values(values)));
+                    }
+                }
+                for (key, value) in headline.properties().iter() {
+                    let allowed = inherited.iter().rev().find(|(k, _)| k.eq_ignore_ascii_case(key));
+                    match allowed {
+                        Some((_, allowed)) if !allowed.iter().any(|allowed| allowed == value) => {
+                            found.push((headline, PropertyError {
+                                key: key.into(),
+                                value: value.into(),
+                                expected: None,
+                                allowed: allowed.clone(),
+                            }));
+                        }
+                        _ => {}
+                    }
+                }
+                visit(&headline.headlines, inherited, found);
+                inherited.truncate(before);
+            }
+        }
+
+        let mut inherited: Vec<_> = self.file_properties().into_iter()
+            .filter(|(key, _)| all_key(key).is_none())
+            .map(|(key, values)| (key[..key.len() - 4].to_string(), allowed_values(&values)))
+            .collect();
+        let mut found = Vec::new();
+        visit(&self.headlines, &mut inherited, &mut found);
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use DocumentParser;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("1:30"), Some(90));
+        assert_eq!(parse_duration("0:01:40"), Some(2));
+        assert_eq!(parse_duration("2d 4h"), Some(3120));
+        assert_eq!(parse_duration("90min"), Some(90));
+        assert_eq!(parse_duration("1w1d"), Some(11520));
+        assert_eq!(parse_duration("2 hours"), None);
+        assert_eq!(parse_duration("1:x"), None);
+        assert_eq!(parse_duration(""), None);
+        assert_eq!(parse_duration("99999999y"), None);
+    }
+
+    #[test]
+    fn test_typed_properties() {
+        let doc = DocumentParser::new().parse("* Task
+:PROPERTIES:
+:Estimate: 1:30
+:Count: 12
+:Billable: Yes
+:Due: <2024-05-01 Wed>
+:Bad: twelve
+:END:
+").unwrap();
+        let task = &doc.headlines()[0];
+        assert_eq!(task.property_as_duration("estimate"), Ok(Some(90)));
+        assert_eq!(task.property_as_int("Count"), Ok(Some(12)));
+        assert_eq!(task.property_as_bool("Billable"), Ok(Some(true)));
+        assert_eq!(task.property_as_timestamp("Due").unwrap().unwrap().date().day, 1);
+        assert_eq!(task.property_as_int("Missing"), Ok(None));
+        let error = task.property_as_int("Bad").unwrap_err();
+        assert_eq!(error.to_string(), "property Bad is \"twelve\", not an integer");
+
+        let schema = PropertySchema::new()
+            .property("count", PropertyType::Bool)
+            .property("Count", PropertyType::Int);
+        assert_eq!(schema.get("COUNT"), Some(PropertyType::Int));
+        assert!(schema.check("Count", "12").is_ok());
+        assert_eq!(schema.check("Count", "1.5").unwrap_err().expected(), Some(PropertyType::Int));
+        assert!(schema.check("Other", "anything").is_ok());
+    }
+
+    #[test]
+    fn test_allowed_property_values() {
+        let mut doc = DocumentParser::new().parse("#+PROPERTY: Owner_ALL alice bob
+* Team
+:PROPERTIES:
+:Owner: carol
+:END:
+** Ops
+:PROPERTIES:
+:OWNER_ALL: dave erin
+:Owner: dave
+:END:
+*** Pager
+:PROPERTIES:
+:Owner: alice
+:END:
+").unwrap();
+        assert_eq!(doc.allowed_property_values("owner").unwrap(), ["alice", "bob"]);
+        assert!(doc.allowed_property_values("Owner_ALL").is_none());
+        assert!(doc.allowed_property_values("Effort").is_none());
+        assert_eq!(doc.allowed_property_values_at(&[0, 0, 0], "Owner").unwrap(), ["dave", "erin"]);
+        assert_eq!(doc.allowed_property_values_at(&[0], "Owner").unwrap(), ["alice", "bob"]);
+
+        let disallowed: Vec<_> = doc.disallowed_properties().iter()
+            .map(|(headline, error)| format!("{}: {}", headline.title(), error))
+            .collect();
+        assert_eq!(disallowed, ["Team: property Owner is \"carol\", not one of alice, bob",
+                                "Pager: property Owner is \"alice\", not one of dave, erin"]);
+
+        let error = doc.set_property_at(&[0, 0, 0], "Owner", "bob").unwrap_err();
+        assert_eq!(error.allowed(), ["dave", "erin"]);
+        assert_eq!(doc.set_property_at(&[0, 0, 0], "Owner", "erin"), Ok(true));
+        assert_eq!(doc.headlines()[0].children()[0].children()[0].property("Owner"), Some("erin"));
+        assert_eq!(doc.set_property_at(&[0], "Effort", "1:00"), Ok(true));
+        assert_eq!(doc.set_property_at(&[3], "Effort", "1:00"), Ok(false));
+    }
+}
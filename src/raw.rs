@@ -0,0 +1,88 @@
+use block;
+use drawer;
+use error::Diagnostic;
+use span::{Shift, Span};
+use DocumentParser;
+
+/// Text that `DocumentParser::lenient` keeps as written because it couldn't
+/// be parsed, like a `#+BEGIN_SRC` line nothing closes, a stray `:END:` or a
+/// timestamp with a date that doesn't exist, along with what is wrong with
+/// it. Without lenient mode the same text is read as plain text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Raw {
+    text: String,
+    diagnostic: Diagnostic,
+    span: Span,
+}
+
+impl Raw {
+    /// The text exactly as written.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// What is wrong with the text, as listed in `Document::warnings`.
+    pub fn diagnostic(&self) -> &Diagnostic {
+        &self.diagnostic
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+}
+
+impl Shift for Raw {
+    fn shift(&mut self, delta: isize) {
+        self.diagnostic.shift(delta);
+        self.span.shift(delta);
+    }
+}
+
+/// Parse `line`, starting at `offset` in the text, as a block or drawer
+/// delimiter left over because nothing closes or opens it, if the parser is
+/// lenient. Drawer lines are only text when drawers are restricted.
+pub fn parse_raw_line(parser: &DocumentParser, line: &str, offset: usize) -> Option<Raw> {
+    if !parser.lenient {
+        return None;
+    }
+    let message = block::stray_delimiter(line).or_else(|| {
+        drawer::stray_delimiter(line).filter(|_| parser.drawers.is_none())
+    })?;
+    let start = offset + line.len() - line.trim_start().len();
+    let end = offset + line.trim_end().len();
+    Some(Raw {
+        text: line.into(),
+        diagnostic: Diagnostic::new(message, Span::new(start, end)),
+        span: Span::new(offset, offset + line.len()),
+    })
+}
+
+/// The timestamps in `text`, starting at `offset`, that can't be parsed:
+/// `<` or `[` followed by something that starts like a date, up to the
+/// closing bracket on the same line.
+pub fn find_bad_timestamps(text: &str, offset: usize) -> Vec<Raw> {
+    let mut found = Vec::new();
+    let mut rest = 0;
+    while let Some(start) = text[rest..].find(['<', '[']).map(|start| rest + start) {
+        let close = if text[start..].starts_with('<') { '>' } else { ']' };
+        let candidate = &text[start..];
+        let bytes = candidate.as_bytes();
+        let dated = bytes.get(1..5).is_some_and(|year| year.iter().all(u8::is_ascii_digit))
+            && bytes.get(5) == Some(&b'-');
+        let end = candidate.find(close).filter(|&end| !candidate[..end].contains('\n'));
+        match end {
+            Some(end) if dated && ::timestamp::parse_prefix(candidate).is_none() => {
+                let span = Span::new(offset + start, offset + start + end + 1);
+                found.push(Raw {
+                    text: candidate[..end + 1].into(),
+                    diagnostic: Diagnostic::new("timestamp can't be parsed", span),
+                    span,
+                });
+                rest = start + end + 1;
+            }
+            _ => rest = start + 1,
+        }
+    }
+    found
+}
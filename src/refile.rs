@@ -0,0 +1,188 @@
+use std::path::Path;
+
+use outline::find_path as find;
+use {Date, Document, Headline, Time, Timestamp};
+
+fn get_mut<'a>(headlines: &'a mut Vec<Headline>, path: &[usize]) -> &'a mut Vec<Headline> {
+    path.iter().fold(headlines, |siblings, &i| &mut siblings[i].headlines)
+}
+
+/// Remove the headline at the non-empty `path`.
+fn take(headlines: &mut Vec<Headline>, path: &[usize]) -> Headline {
+    let (&last, parent) = path.split_last().unwrap();
+    get_mut(headlines, parent).remove(last)
+}
+
+/// Add `headline` as the last child of the headline at `path`, or as the last
+/// top-level headline if `path` is empty.
+fn append(document: &mut Document, path: &[usize], headline: Headline) {
+    match path.split_last() {
+        Some((&last, parent)) => {
+            let target = &mut get_mut(&mut document.headlines, parent)[last];
+            let index = target.headlines.len();
+            target.insert_child(index, headline);
+        }
+        None => {
+            let index = document.headlines.len();
+            document.insert_headline(index, headline);
+        }
+    }
+}
+
+impl Document {
+    /// Move the headline at the outline path `headline`, like `["Inbox",
+    /// "Call Sam"]`, to be the last child of the headline at `target`, or the
+    /// last top-level headline if `target` is empty, changing the levels of
+    /// its subtree to fit. Like `org-refile`.
+    ///
+    /// Each title in a path picks the first sibling with that title. Returns
+    /// false, changing nothing, if either headline doesn't exist or `target`
+    /// is inside the subtree being moved.
+    pub fn refile(&mut self, headline: &[&str], target: &[&str]) -> bool {
+        let found = (find(&self.headlines, headline), find(&self.headlines, target));
+        let (from, mut to) = match found {
+            (Some(from), Some(to)) if !from.is_empty() && !to.starts_with(&from) => (from, to),
+            _ => return false,
+        };
+        // Removing the headline moves up the later siblings, which may
+        // include the target or one of its ancestors.
+        let depth = from.len() - 1;
+        if to.len() > depth && to[..depth] == from[..depth] && to[depth] > from[depth] {
+            to[depth] -= 1;
+        }
+        let subtree = take(&mut self.headlines, &from);
+        append(self, &to, subtree);
+        true
+    }
+
+    /// Move the headline at the outline path `headline` into `other`, as the
+    /// last child of the headline at `target` there, or its last top-level
+    /// headline if `target` is empty. See `refile`.
+    pub fn refile_to(&mut self, headline: &[&str], other: &mut Document, target: &[&str])
+        -> bool
+    {
+        let (from, to) = match (find(&self.headlines, headline), find(&other.headlines, target)) {
+            (Some(from), Some(to)) if !from.is_empty() => (from, to),
+            _ => return false,
+        };
+        let subtree = take(&mut self.headlines, &from);
+        append(other, &to, subtree);
+        true
+    }
+
+    /// Move the headline at the outline path `headline` to the end of
+    /// `archive`, as a top-level headline, like `org-archive-subtree`. `file`
+    /// is the name of this document's file, and `date` and `time` are when it
+    /// was archived. Org's archive for `notes.org` is `notes.org_archive`.
+    ///
+    /// The headline gets the properties org records to say where it came
+    /// from: `ARCHIVE_TIME`, `ARCHIVE_FILE`, `ARCHIVE_OLPATH` with the titles
+    /// of its ancestors, `ARCHIVE_CATEGORY` with the nearest `CATEGORY`
+    /// property, `#+CATEGORY:` or the file's name, and `ARCHIVE_TODO` with
+    /// its keyword. Returns false, changing nothing, if there is no such
+    /// headline.
+    pub fn archive(&mut self, headline: &[&str], archive: &mut Document, file: &str, date: Date,
+                   time: Time) -> bool
+    {
+        let path = match find(&self.headlines, headline) {
+            Some(ref path) if path.is_empty() => return false,
+            Some(path) => path,
+            None => return false,
+        };
+        let mut category = None;
+        let mut siblings = &self.headlines;
+        for &i in &path {
+            category = siblings[i].property("CATEGORY").map(String::from).or(category);
+            siblings = &siblings[i].headlines;
+        }
+        let category = category
+            .or_else(|| self.keywords().remove("CATEGORY"))
+            .unwrap_or_else(|| {
+                let stem = Path::new(file).file_stem().map(|stem| stem.to_string_lossy());
+                stem.map_or_else(|| file.to_string(), |stem| stem.into_owned())
+            });
+
+        let mut subtree = take(&mut self.headlines, &path);
+        let stamp = Timestamp::new(false, date, Some(time)).to_string();
+        subtree.set_property("ARCHIVE_TIME", &stamp[1..stamp.len() - 1]);
+        subtree.set_property("ARCHIVE_FILE", file);
+        let ancestors = &headline[..headline.len() - 1];
+        if !ancestors.is_empty() {
+            subtree.set_property("ARCHIVE_OLPATH", &ancestors.join("/"));
+        }
+        subtree.set_property("ARCHIVE_CATEGORY", &category);
+        if let Some(keyword) = subtree.keyword().map(String::from) {
+            subtree.set_property("ARCHIVE_TODO", &keyword);
+        }
+        append(archive, &[], subtree);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {Date, DocumentParser, Time};
+
+    #[test]
+    fn test_refile() {
+        let parser = DocumentParser::new();
+        let mut doc = parser.parse("* Inbox
+** Call Sam
+Ask about the roof.
+*** Number
+* Projects
+** House
+").unwrap();
+        assert!(doc.refile(&["Inbox", "Call Sam"], &["Projects", "House"]));
+        assert_eq!(doc.to_org_string(), "* Inbox
+* Projects
+** House
+*** Call Sam
+Ask about the roof.
+**** Number
+");
+        assert!(!doc.refile(&["Projects"], &["Projects", "House"]));
+        assert!(!doc.refile(&["Missing"], &[]));
+        assert!(doc.refile(&["Projects", "House"], &[]));
+        assert!(doc.refile(&["Inbox"], &["House"]));
+        assert_eq!(doc.to_org_string(), "* Projects
+* House
+** Call Sam
+Ask about the roof.
+*** Number
+** Inbox
+");
+
+        let mut other = parser.parse("* Someday\n").unwrap();
+        assert!(doc.refile_to(&["House", "Inbox"], &mut other, &["Someday"]));
+        assert_eq!(other.to_org_string(), "* Someday\n** Inbox\n");
+    }
+
+    #[test]
+    fn test_archive() {
+        let mut doc = DocumentParser::new().parse("#+TODO: TODO | DONE
+#+CATEGORY: home
+* Projects
+** DONE Fix the roof
+:PROPERTIES:
+:ID: roof
+:END:
+Done.
+").unwrap();
+        let mut archive = DocumentParser::new().parse("").unwrap();
+        let (date, time) = (Date { year: 2024, month: 5, day: 8 }, Time { hour: 10, minute: 30 });
+        assert!(doc.archive(&["Projects", "Fix the roof"], &mut archive, "notes.org", date, time));
+        assert!(doc.headlines()[0].children().is_empty());
+        assert_eq!(archive.to_org_string(), "* DONE Fix the roof
+:PROPERTIES:
+:ID: roof
+:ARCHIVE_TIME: 2024-05-08 Wed 10:30
+:ARCHIVE_FILE: notes.org
+:ARCHIVE_OLPATH: Projects
+:ARCHIVE_CATEGORY: home
+:ARCHIVE_TODO: DONE
+:END:
+Done.
+");
+    }
+}
@@ -0,0 +1,57 @@
+use keyword::{Affiliate, Affiliated};
+use span::{Shift, Span};
+
+/// A horizontal rule, a line of at least five dashes: `-----`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct HorizontalRule {
+    affiliated: Affiliated,
+    span: Span,
+}
+
+impl HorizontalRule {
+    pub fn affiliated(&self) -> &Affiliated {
+        &self.affiliated
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+}
+
+impl Affiliate for HorizontalRule {
+    fn affiliated_mut(&mut self) -> &mut Affiliated {
+        &mut self.affiliated
+    }
+}
+
+impl Shift for HorizontalRule {
+    fn shift(&mut self, delta: isize) {
+        self.affiliated.shift(delta);
+        self.span.shift(delta);
+    }
+}
+
+/// Parse `line`, starting at `offset` in the text, as a horizontal rule.
+pub fn parse_rule(line: &str, offset: usize) -> Option<HorizontalRule> {
+    let dashes = line.trim();
+    if dashes.len() < 5 || !dashes.bytes().all(|b| b == b'-') {
+        return None;
+    }
+    Some(HorizontalRule {
+        affiliated: Affiliated::default(),
+        span: Span::new(offset, offset + line.len()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rule() {
+        assert_eq!(parse_rule("  ------", 3).unwrap().span(), Span::new(3, 11));
+        assert!(parse_rule("----", 0).is_none());
+        assert!(parse_rule("----- x", 0).is_none());
+    }
+}
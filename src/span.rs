@@ -0,0 +1,145 @@
+/// A range of byte offsets into the parsed text, from `start` up to but not
+/// including `end`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Span {
+        Span { start, end }
+    }
+
+    pub fn contains(&self, offset: usize) -> bool {
+        self.start <= offset && offset < self.end
+    }
+
+    /// The text the span covers in `text`, which must be the parsed text.
+    pub fn slice<'a>(&self, text: &'a str) -> &'a str {
+        &text[self.start..self.end]
+    }
+}
+
+/// A change to a document's text: the `range` of the old text is replaced
+/// with `text`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub range: Span,
+    pub text: String,
+}
+
+impl TextEdit {
+    pub fn new<S: Into<String>>(range: Span, text: S) -> TextEdit {
+        TextEdit { range, text: text.into() }
+    }
+
+    /// The text after the edit, where `text` is the text before it.
+    pub fn apply(&self, text: &str) -> String {
+        format!("{}{}{}", &text[..self.range.start], self.text, &text[self.range.end..])
+    }
+
+    /// How much the edit moves the text after it.
+    pub fn delta(&self) -> isize {
+        self.text.len() as isize - (self.range.end - self.range.start) as isize
+    }
+}
+
+/// Moving a node, with every node inside it, by `delta` bytes. Used to reuse
+/// the nodes after an edit without parsing them again.
+pub trait Shift {
+    fn shift(&mut self, delta: isize);
+}
+
+impl Shift for Span {
+    fn shift(&mut self, delta: isize) {
+        // Nodes that weren't parsed from the text, like a headline's missing
+        // planning line, keep their empty span.
+        if *self == Span::default() {
+            return;
+        }
+        self.start = (self.start as isize + delta) as usize;
+        self.end = (self.end as isize + delta) as usize;
+    }
+}
+
+impl<T: Shift> Shift for [T] {
+    fn shift(&mut self, delta: isize) {
+        for node in self {
+            node.shift(delta);
+        }
+    }
+}
+
+impl<T: Shift> Shift for Option<T> {
+    fn shift(&mut self, delta: isize) {
+        if let Some(ref mut node) = *self {
+            node.shift(delta);
+        }
+    }
+}
+
+/// The line and column of the byte `offset` in `text`, both counted from 1.
+/// Columns count characters, not bytes.
+pub fn line_column(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    (before.matches('\n').count() + 1, before[line_start..].chars().count() + 1)
+}
+
+/// The offset of each line of `text`, as split by `str::lines`, when `text`
+/// itself starts at `base`.
+pub fn line_offsets(text: &str, base: usize) -> Vec<usize> {
+    let mut offsets = Vec::new();
+    let mut pos = 0;
+    for line in text.lines() {
+        offsets.push(base + pos);
+        pos += line.len();
+        if text[pos..].starts_with("\r\n") {
+            pos += 2;
+        } else if text[pos..].starts_with('\n') {
+            pos += 1;
+        }
+    }
+    offsets
+}
+
+/// The span from the start of `lines[start]` to the end of `lines[end - 1]`,
+/// where `offsets` holds the offset of each line.
+pub fn lines_span(lines: &[&str], offsets: &[usize], start: usize, end: usize) -> Span {
+    Span::new(offsets[start], offsets[end - 1] + lines[end - 1].len())
+}
+
+/// The text of `lines[start..end]` as it was before it was split into lines,
+/// with the `\n` or `\r\n` between each two, but not after the last one.
+pub fn lines_text(lines: &[&str], offsets: &[usize], start: usize, end: usize) -> String {
+    let mut text = String::new();
+    for i in start..end {
+        if i > start {
+            let ending = offsets[i] - offsets[i - 1] - lines[i - 1].len();
+            text.push_str(if ending == 2 { "\r\n" } else { "\n" });
+        }
+        text.push_str(lines[i]);
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offsets() {
+        let text = "one\r\ntwo\n\nfünf";
+        assert_eq!(line_offsets(text, 10), [10, 15, 19, 20]);
+        let lines: Vec<_> = text.lines().collect();
+        let offsets = line_offsets(text, 0);
+        let span = lines_span(&lines, &offsets, 1, 4);
+        assert_eq!(span.slice(text), "two\n\nfünf");
+        assert_eq!(lines_text(&lines, &offsets, 0, 3), "one\r\ntwo\n");
+        assert_eq!(line_column(text, 5), (2, 1));
+        assert_eq!(line_column(text, text.len()), (4, 5));
+        assert!(span.contains(5) && !span.contains(span.end));
+    }
+}
@@ -0,0 +1,255 @@
+use std::fmt;
+
+use {Checkbox, Content, Document, DocumentParser, GreaterElement, Headline, ListItem, Section};
+
+/// A statistics cookie, counting the done children of a headline or the
+/// checked sub-items of a list item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum StatisticsCookie {
+    /// `[40%]`, or `[%]` before it was first computed.
+    Percent(Option<u32>),
+    /// `[2/5]`, done and total, or `[/]` before it was first computed.
+    Fraction(Option<(u32, u32)>),
+}
+
+impl StatisticsCookie {
+    /// The same kind of cookie, showing `done` out of `total`.
+    pub fn with_counts(self, done: u32, total: u32) -> StatisticsCookie {
+        match self {
+            StatisticsCookie::Percent(_) => {
+                StatisticsCookie::Percent(Some((done * 100).checked_div(total).unwrap_or(0)))
+            }
+            StatisticsCookie::Fraction(_) => StatisticsCookie::Fraction(Some((done, total))),
+        }
+    }
+}
+
+impl fmt::Display for StatisticsCookie {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            StatisticsCookie::Percent(Some(percent)) => write!(f, "[{}%]", percent),
+            StatisticsCookie::Percent(None) => write!(f, "[%]"),
+            StatisticsCookie::Fraction(Some((done, total))) => write!(f, "[{}/{}]", done, total),
+            StatisticsCookie::Fraction(None) => write!(f, "[/]"),
+        }
+    }
+}
+
+/// Parse a cookie at the start of `text`, returning it and its length.
+pub fn parse_cookie(text: &str) -> Option<(StatisticsCookie, usize)> {
+    let rest = text.strip_prefix('[')?;
+    let close = rest.find(']')?;
+    let inner = &rest[..close];
+    let number = |digits: &str| -> Option<Option<u32>> {
+        if digits.is_empty() {
+            Some(None)
+        } else if digits.bytes().all(|b| b.is_ascii_digit()) {
+            digits.parse().ok().map(Some)
+        } else {
+            None
+        }
+    };
+    let cookie = if let Some(percent) = inner.strip_suffix('%') {
+        StatisticsCookie::Percent(number(percent)?)
+    } else {
+        let slash = inner.find('/')?;
+        match (number(&inner[..slash])?, number(&inner[slash + 1..])?) {
+            (Some(done), Some(total)) => StatisticsCookie::Fraction(Some((done, total))),
+            (None, None) => StatisticsCookie::Fraction(None),
+            _ => return None,
+        }
+    };
+    Some((cookie, close + 2))
+}
+
+/// The first cookie in `text`, with where it starts and its length.
+fn find_cookie(text: &str) -> Option<(usize, StatisticsCookie, usize)> {
+    text.match_indices('[')
+        .filter_map(|(start, _)| parse_cookie(&text[start..]).map(|(c, len)| (start, c, len)))
+        .next()
+}
+
+/// Replace the first cookie in `text` with the counts, if it has one.
+fn update_cookie(text: &mut String, done: u32, total: u32) {
+    if let Some((start, cookie, len)) = find_cookie(text) {
+        let updated = cookie.with_counts(done, total).to_string();
+        text.replace_range(start..start + len, &updated);
+    }
+}
+
+impl Headline {
+    /// The first statistics cookie in the title.
+    pub fn statistics_cookie(&self) -> Option<StatisticsCookie> {
+        find_cookie(&self.title).map(|(_, cookie, _)| cookie)
+    }
+}
+
+impl ListItem {
+    /// The first statistics cookie on the item's first line.
+    pub fn statistics_cookie(&self) -> Option<StatisticsCookie> {
+        find_cookie(self.text().lines().next().unwrap_or("")).map(|(_, cookie, _)| cookie)
+    }
+}
+
+impl Document {
+    /// Recompute every statistics cookie from the current state of the tree.
+    ///
+    /// A headline's cookie counts its children with a TODO keyword and how
+    /// many of those are done. Without such children it counts the checkboxes
+    /// of the top-level list items in its section instead. A list item's
+    /// cookie counts the checkboxes of its direct sub-items. Done keywords
+    /// are those of `Document::done_keywords`.
+    pub fn update_statistics(&mut self) {
+        let done_keywords = self.done_keywords.clone();
+        if let Some(ref mut section) = self.first_section {
+            update_section(&self.parser, section);
+        }
+        ::visit_headlines_mut(&mut self.headlines, &mut |headline| {
+            if let Some(ref mut section) = headline.section {
+                update_section(&headline.parser, section);
+            }
+            if headline.statistics_cookie().is_none() {
+                return;
+            }
+            let todo: Vec<_> = headline.headlines.iter()
+                .filter_map(|child| child.keyword())
+                .collect();
+            let (done, total) = if todo.is_empty() {
+                let items = headline.section.iter()
+                    .flat_map(|section| section.contents())
+                    .filter_map(|content| match *content {
+                        Content::Greater(GreaterElement::PlainList(ref list)) => Some(list.items()),
+                        _ => None,
+                    })
+                    .flatten();
+                checkbox_counts(items)
+            } else {
+                let done = todo.iter().filter(|kwd| done_keywords.iter().any(|d| d == *kwd));
+                (done.count() as u32, todo.len() as u32)
+            };
+            update_cookie(&mut headline.title, done, total);
+        });
+    }
+}
+
+/// How many of `items` have a checkbox, and how many of those are checked.
+fn checkbox_counts<'a, I: Iterator<Item = &'a ListItem>>(items: I) -> (u32, u32) {
+    let (mut done, mut total) = (0, 0);
+    for item in items {
+        match item.checkbox() {
+            Some(Checkbox::Checked) => {
+                done += 1;
+                total += 1;
+            }
+            Some(_) => total += 1,
+            None => {}
+        }
+    }
+    (done, total)
+}
+
+/// Update the cookies of the list items in `section`, parsing it again with
+/// `parser` if any changed.
+fn update_section(parser: &DocumentParser, section: &mut Section) {
+    fn collect(contents: &[Content], edits: &mut Vec<(usize, u32, u32)>) {
+        for content in contents {
+            let (children, items) = content.children();
+            collect(children, edits);
+            for item in items {
+                if item.statistics_cookie().is_some() {
+                    let sub_items = item.contents().iter()
+                        .filter_map(|content| match *content {
+                            Content::Greater(GreaterElement::PlainList(ref list)) => {
+                                Some(list.items())
+                            }
+                            _ => None,
+                        })
+                        .flatten();
+                    let (done, total) = checkbox_counts(sub_items);
+                    edits.push((item.span().start, done, total));
+                }
+                collect(item.contents(), edits);
+            }
+        }
+    }
+
+    let mut edits = Vec::new();
+    collect(&section.contents, &mut edits);
+    if edits.is_empty() {
+        return;
+    }
+    let base = section.span.start;
+    let mut text = section.text.clone();
+    edits.sort_by_key(|&(start, _, _)| start);
+    for &(start, done, total) in edits.iter().rev() {
+        let start = start - base;
+        let end = text[start..].find('\n').map_or(text.len(), |newline| start + newline);
+        let mut line = text[start..end].to_string();
+        update_cookie(&mut line, done, total);
+        text.replace_range(start..end, &line);
+    }
+    if text != section.text {
+        if let Some(updated) = Section::from_text(parser, &text, base) {
+            *section = updated;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cookie() {
+        assert_eq!(parse_cookie("[2/5] x"), Some((StatisticsCookie::Fraction(Some((2, 5))), 5)));
+        assert_eq!(parse_cookie("[40%]"), Some((StatisticsCookie::Percent(Some(40)), 5)));
+        assert_eq!(parse_cookie("[/]"), Some((StatisticsCookie::Fraction(None), 3)));
+        assert_eq!(parse_cookie("[%]"), Some((StatisticsCookie::Percent(None), 3)));
+        assert!(parse_cookie("[2/]").is_none());
+        assert!(parse_cookie("[ ]").is_none());
+        let objects = ::parse_objects("Tasks [1/2]");
+        let cookie = StatisticsCookie::Fraction(Some((1, 2)));
+        assert_eq!(objects[1], ::Object::StatisticsCookie(cookie));
+    }
+
+    #[test]
+    fn test_update_statistics() {
+        let mut doc = DocumentParser::new().parse("#+TODO: TODO NEXT | DONE CANCELLED
+* Release [/]
+** DONE Tag it
+** CANCELLED Announce
+** NEXT Publish
+** Notes
+* Shopping [%]
+- [X] Bread
+- [ ] Milk [1/2]
+  - [X] Whole
+  - [ ] Oat
+- [X] Eggs
+").unwrap();
+        doc.update_statistics();
+        assert_eq!(doc.headlines()[0].title(), "Release [2/3]");
+        let shopping = &doc.headlines()[1];
+        assert_eq!(shopping.title(), "Shopping [66%]");
+        assert!(shopping.section().unwrap().text().contains("- [ ] Milk [1/2]\n"));
+
+        let mut doc = DocumentParser::new().parse("* List
+- Milk [/]
+  - [X] Whole
+  - [X] Oat
+").unwrap();
+        doc.update_statistics();
+        assert_eq!(doc.headlines()[0].section().unwrap().text(), "- Milk [2/2]
+  - [X] Whole
+  - [X] Oat
+");
+        match doc.headlines()[0].section().unwrap().contents()[0] {
+            Content::Greater(GreaterElement::PlainList(ref list)) => {
+                let cookie = list.items()[0].statistics_cookie();
+                assert_eq!(cookie, Some(StatisticsCookie::Fraction(Some((2, 2)))));
+            }
+            ref content => panic!("{:?}", content),
+        }
+    }
+}
@@ -0,0 +1,740 @@
+//! A set of documents loaded together, like the files of a notes directory,
+//! with their headlines indexed by `ID` and `CUSTOM_ID` so that `id:` links
+//! can be followed from one file to another, and searched and counted
+//! without the content configured in its `Exclusions`.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::future::Future;
+use std::io;
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+
+use {Content, Document, DocumentParser, GreaterElement, Headline, Link, LinkType, Section};
+
+/// Documents by name, usually their file path, with an index of the
+/// headlines that have an `ID` or `CUSTOM_ID` property.
+#[derive(Debug, Clone, Default)]
+pub struct DocumentSet {
+    documents: Vec<(String, Document)>,
+    ids: HashMap<String, Location>,
+    custom_ids: HashMap<String, Location>,
+    exclusions: Exclusions,
+}
+
+/// What `DocumentSet::search`, `tag_counts` and `word_count` leave out, so
+/// that they only see the real content of the documents.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Exclusions {
+    tags: Vec<String>,
+    drawers: Vec<String>,
+    properties: Vec<(String, String)>,
+}
+
+impl Exclusions {
+    pub fn new() -> Exclusions {
+        Exclusions::default()
+    }
+
+    /// Leave out the headlines with `tag`, inherited ones included, like
+    /// `noexport` or `ARCHIVE`.
+    pub fn tag<S: Into<String>>(mut self, tag: S) -> Self {
+        self.tags.push(tag.into());
+        self
+    }
+
+    /// Leave out the contents of drawers named `name`, like `LOGBOOK`. Names
+    /// are compared ignoring case.
+    pub fn drawer<S: Into<String>>(mut self, name: S) -> Self {
+        self.drawers.push(name.into());
+        self
+    }
+
+    /// Leave out the headlines whose `key` property is `value`, along with
+    /// their subtrees.
+    pub fn property<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        self.properties.push((key.into(), value.into()));
+        self
+    }
+
+    fn excludes(&self, headline: &Headline, tags: &[String]) -> bool {
+        tags.iter().any(|tag| self.tags.contains(tag))
+            || self.properties.iter().any(|(key, value)| {
+                headline.property(key) == Some(&value[..])
+            })
+    }
+
+    /// The text of `section` without the drawers that are left out.
+    fn text(&self, section: &Section) -> String {
+        let base = section.span.start;
+        let mut text = String::new();
+        let mut from = 0;
+        for content in &section.contents {
+            if let Content::Greater(GreaterElement::Drawer(ref drawer)) = *content {
+                if self.drawers.iter().any(|name| name.eq_ignore_ascii_case(drawer.name())) {
+                    let span = drawer.span();
+                    text.push_str(&section.text[from..span.start - base]);
+                    from = span.end - base;
+                }
+            }
+        }
+        text.push_str(&section.text[from..]);
+        text
+    }
+}
+
+/// A headline as the index of its document and its index among its siblings
+/// from the top level down, like `kanban::Card::path`.
+#[derive(Debug, Clone)]
+struct Location {
+    document: usize,
+    path: Vec<usize>,
+}
+
+impl DocumentSet {
+    pub fn new() -> DocumentSet {
+        DocumentSet::default()
+    }
+
+    /// Add `document` as `name`, returning the document it replaces.
+    pub fn insert<S: Into<String>>(&mut self, name: S, document: Document) -> Option<Document> {
+        let name = name.into();
+        let replaced = match self.documents.iter_mut().find(|&&mut (ref n, _)| *n == name) {
+            Some(&mut (_, ref mut old)) => Some(mem::replace(old, document)),
+            None => {
+                self.documents.push((name, document));
+                None
+            }
+        };
+        self.reindex();
+        replaced
+    }
+
+    /// Read the file at `path` with `parser` and add it by its path.
+    pub fn load<P: AsRef<Path>>(&mut self, parser: &DocumentParser, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let document = parser.parse_file(path)?;
+        self.insert(path.to_string_lossy(), document);
+        Ok(())
+    }
+
+    /// Read every `.org` file under `directory` with `parser`, skipping
+    /// hidden files and directories, and add them by their paths. Returns
+    /// how many there were.
+    pub fn load_dir<P: AsRef<Path>>(&mut self, parser: &DocumentParser, directory: P)
+        -> io::Result<usize>
+    {
+        let mut files = Vec::new();
+        org_files(directory.as_ref(), &mut files)?;
+        let documents = files.iter()
+            .map(|path| Ok((path.to_string_lossy().into_owned(), parser.parse_file(path)?)))
+            .collect::<io::Result<Vec<_>>>()?;
+        Ok(self.insert_all(documents))
+    }
+
+    /// Read the files at `paths` with `parser` and add them by their paths,
+    /// parsing them on as many threads as the machine has cores. Nothing is
+    /// added unless every file loads. Returns how many there were.
+    pub fn parse_files_parallel<P: AsRef<Path>>(&mut self, parser: &DocumentParser, paths: &[P])
+                                                -> io::Result<usize> {
+        let files: Vec<PathBuf> = paths.iter().map(|path| path.as_ref().to_path_buf()).collect();
+        let limit = thread::available_parallelism().map_or(1, |cores| cores.get());
+        let parsed = Mutex::new(Vec::with_capacity(files.len()));
+        let parse = |path: &Path| parser.parse_file(path);
+        parse_files(&parse, files, limit.min(paths.len()), &|| false, &|index, name, document| {
+            if let Ok(mut parsed) = parsed.lock() {
+                parsed.push((index, name, document));
+            }
+        })?;
+        let mut parsed = parsed.into_inner().unwrap_or_else(|poisoned| poisoned.into_inner());
+        parsed.sort_by_key(|&(index, _, _)| index);
+        let documents = parsed.into_iter().map(|(_, name, document)| (name, document)).collect();
+        Ok(self.insert_all(documents))
+    }
+
+    /// Like `load_dir`, but reading and parsing on up to `limit` threads of
+    /// its own, so that an async runtime can wait for the returned future
+    /// without blocking its threads. It works with any runtime.
+    ///
+    /// Nothing is added unless every file loads. Cancelling `cancel`, or
+    /// dropping the future, stops the loading after the files that are
+    /// already being parsed; a cancelled future finishes with an
+    /// `Interrupted` error.
+    pub fn load_dir_async<P: AsRef<Path>>(&mut self, parser: &DocumentParser, directory: P,
+                                          limit: usize, cancel: &Cancel) -> LoadDir<'_> {
+        let parser = parser.clone();
+        let parse = move |path: &Path| parser.parse_file(path);
+        let loading = spawn_loading(parse, directory.as_ref().to_path_buf(), limit, cancel.clone());
+        LoadDir { set: self, loading }
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Document> {
+        let i = self.documents.iter().position(|(n, _)| n == name)?;
+        let (_, document) = self.documents.remove(i);
+        self.reindex();
+        Some(document)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Document> {
+        self.documents.iter().find(|(n, _)| n == name).map(|(_, document)| document)
+    }
+
+    /// The names and documents, in the order they were added.
+    pub fn documents(&self) -> impl Iterator<Item = (&str, &Document)> {
+        self.documents.iter().map(|(name, document)| (&name[..], document))
+    }
+
+    pub fn len(&self) -> usize {
+        self.documents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.documents.is_empty()
+    }
+
+    /// The headline whose `ID` property is `id`, with the name of its
+    /// document. If several have it, the first one added wins.
+    pub fn by_id(&self, id: &str) -> Option<(&str, &Headline)> {
+        self.ids.get(id).and_then(|location| self.headline_at(location))
+    }
+
+    /// The headline whose `CUSTOM_ID` property is `id`, with the name of its
+    /// document. Custom ids are usually only unique within a file, so prefer
+    /// `resolve_link` for links.
+    pub fn by_custom_id(&self, id: &str) -> Option<(&str, &Headline)> {
+        self.custom_ids.get(id).and_then(|location| self.headline_at(location))
+    }
+
+    /// Find the headline `link`, found in the document named `from`, points
+    /// to, with the name of its document. `id:` links are looked up in every
+    /// document; file links with a `::#custom-id` or `::*Title` search option
+    /// in the document with that file name, relative to `from`. Other links
+    /// resolve as `Document::resolve_link` does in `from`.
+    pub fn resolve_link<'a>(&'a self, from: &str, link: &Link) -> Option<(&'a str, &'a Headline)> {
+        match link.link_type() {
+            LinkType::Id => self.by_id(link.path()),
+            LinkType::File => {
+                let (name, document) = self.documents()
+                    .find(|&(name, _)| is_file_link_to(from, link, name))?;
+                let search = link.search_option()?;
+                let link = ::parse_objects(&format!("[[{}]]", search)).into_iter()
+                    .filter_map(|object| match object {
+                        ::Object::Link(link) => Some(link),
+                        _ => None,
+                    })
+                    .next()?;
+                match link.link_type() {
+                    LinkType::CustomId | LinkType::Headline => {
+                        document.resolve_link(&link).map(|headline| (name, headline))
+                    }
+                    _ => None,
+                }
+            }
+            _ => {
+                let (name, document) = self.documents().find(|&(name, _)| name == from)?;
+                document.resolve_link(link).map(|headline| (name, headline))
+            }
+        }
+    }
+
+    /// The links in other documents that point to the document named
+    /// `name`, with the names of the documents they are in: file links to
+    /// it, and `id:` links to its headlines.
+    pub fn backlinks(&self, name: &str) -> Vec<(&str, Link)> {
+        let mut backlinks = Vec::new();
+        for (from, document) in self.documents().filter(|&(from, _)| from != name) {
+            for link in document.links() {
+                let points_here = match link.link_type() {
+                    LinkType::File => is_file_link_to(from, &link, name),
+                    LinkType::Id => self.by_id(link.path()).is_some_and(|(to, _)| to == name),
+                    _ => false,
+                };
+                if points_here {
+                    backlinks.push((from, link));
+                }
+            }
+        }
+        backlinks
+    }
+
+    /// Write the document named `name` to the file of that name, in the
+    /// encoding it was read in.
+    pub fn save(&self, name: &str) -> io::Result<()> {
+        let document = self.get(name).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, format!("no document named {}", name))
+        })?;
+        fs::write(name, document.to_encoded_bytes())
+    }
+
+    /// Set what `search`, `tag_counts` and `word_count` leave out.
+    pub fn set_exclusions(&mut self, exclusions: Exclusions) {
+        self.exclusions = exclusions;
+    }
+
+    pub fn exclusions(&self) -> &Exclusions {
+        &self.exclusions
+    }
+
+    /// The headlines whose title or text contains `query`, ignoring case,
+    /// with the names of their documents, leaving out the exclusions.
+    pub fn search(&self, query: &str) -> Vec<(&str, &Headline)> {
+        let query = query.to_lowercase();
+        self.included().into_iter()
+            .filter(|&(_, headline)| {
+                headline.title().to_lowercase().contains(&query)
+                    || headline.section().is_some_and(|section| {
+                        self.exclusions.text(section).to_lowercase().contains(&query)
+                    })
+            })
+            .collect()
+    }
+
+    /// How many headlines have each tag, counting only their own tags and
+    /// leaving out the exclusions.
+    pub fn tag_counts(&self) -> BTreeMap<String, usize> {
+        let mut counts = BTreeMap::new();
+        for (_, headline) in self.included() {
+            for tag in headline.tags() {
+                *counts.entry(tag.to_string()).or_insert(0) += 1;
+            }
+        }
+        counts
+    }
+
+    /// The number of words in the headline titles and text of every
+    /// document, leaving out the exclusions.
+    pub fn word_count(&self) -> usize {
+        let words = |text: &str| text.split_whitespace().count();
+        let first_sections = self.documents.iter()
+            .filter(|(_, document)| {
+                !document.file_tags().iter().any(|tag| self.exclusions.tags.contains(tag))
+            })
+            .filter_map(|(_, document)| document.first_section())
+            .map(|section| words(&self.exclusions.text(section)))
+            .sum::<usize>();
+        first_sections + self.included().into_iter()
+            .map(|(_, headline)| {
+                words(headline.title())
+                    + headline.section().map_or(0, |section| words(&self.exclusions.text(section)))
+            })
+            .sum::<usize>()
+    }
+
+    /// The headlines of every document, in order, leaving out the excluded
+    /// ones and their subtrees.
+    fn included(&self) -> Vec<(&str, &Headline)> {
+        fn add<'a>(name: &'a str, headlines: &'a [Headline], inherited: &[String],
+                   exclusions: &Exclusions, included: &mut Vec<(&'a str, &'a Headline)>) {
+            for headline in headlines {
+                let mut tags = inherited.to_vec();
+                tags.extend(headline.tags().iter().map(|tag| tag.to_string()));
+                if !exclusions.excludes(headline, &tags) {
+                    included.push((name, headline));
+                    add(name, headline.children(), &tags, exclusions, included);
+                }
+            }
+        }
+
+        let mut included = Vec::new();
+        for (name, document) in &self.documents {
+            let tags = document.file_tags();
+            add(name, document.headlines(), &tags, &self.exclusions, &mut included);
+        }
+        included
+    }
+
+    /// Add or replace each of `documents`, indexing them once at the end.
+    /// Returns how many there were.
+    fn insert_all(&mut self, documents: Vec<(String, Document)>) -> usize {
+        let count = documents.len();
+        for (name, document) in documents {
+            match self.documents.iter_mut().find(|&&mut (ref n, _)| *n == name) {
+                Some(&mut (_, ref mut old)) => *old = document,
+                None => self.documents.push((name, document)),
+            }
+        }
+        self.reindex();
+        count
+    }
+
+    fn headline_at(&self, location: &Location) -> Option<(&str, &Headline)> {
+        let (ref name, ref document) = *self.documents.get(location.document)?;
+        let (&first, rest) = location.path.split_first()?;
+        let mut headline = document.headlines().get(first)?;
+        for &i in rest {
+            headline = headline.children().get(i)?;
+        }
+        Some((name, headline))
+    }
+
+    fn reindex(&mut self) {
+        fn add(headlines: &[Headline], location: &mut Location,
+               ids: &mut HashMap<String, Location>, custom_ids: &mut HashMap<String, Location>) {
+            for (i, headline) in headlines.iter().enumerate() {
+                location.path.push(i);
+                if let Some(id) = headline.property("ID") {
+                    ids.entry(id.into()).or_insert_with(|| location.clone());
+                }
+                if let Some(id) = headline.property("CUSTOM_ID") {
+                    custom_ids.entry(id.into()).or_insert_with(|| location.clone());
+                }
+                add(headline.children(), location, ids, custom_ids);
+                location.path.pop();
+            }
+        }
+
+        self.ids.clear();
+        self.custom_ids.clear();
+        for (i, (_, document)) in self.documents.iter().enumerate() {
+            let mut location = Location { document: i, path: Vec::new() };
+            add(document.headlines(), &mut location, &mut self.ids, &mut self.custom_ids);
+        }
+    }
+}
+
+/// A handle for stopping a `DocumentSet::load_dir_async` early, which can be
+/// cloned and cancelled from anywhere.
+#[derive(Debug, Clone, Default)]
+pub struct Cancel(Arc<AtomicBool>);
+
+impl Cancel {
+    pub fn new() -> Cancel {
+        Cancel::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// The future returned by `DocumentSet::load_dir_async`, finishing with how
+/// many documents were added.
+#[derive(Debug)]
+pub struct LoadDir<'a> {
+    set: &'a mut DocumentSet,
+    loading: Arc<Mutex<Loading>>,
+}
+
+#[derive(Debug, Default)]
+struct Loading {
+    documents: Vec<(String, Document)>,
+    /// Set once the loading threads are done.
+    result: Option<io::Result<()>>,
+    waker: Option<Waker>,
+    dropped: bool,
+}
+
+impl<'a> Future for LoadDir<'a> {
+    type Output = io::Result<usize>;
+
+    fn poll(mut self: Pin<&mut Self>, context: &mut Context) -> Poll<io::Result<usize>> {
+        let mut loading = self.loading.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let result = match loading.result.take() {
+            Some(result) => result,
+            None => {
+                loading.waker = Some(context.waker().clone());
+                return Poll::Pending;
+            }
+        };
+        let mut documents = mem::take(&mut loading.documents);
+        drop(loading);
+        documents.sort_by(|a, b| a.0.cmp(&b.0));
+        Poll::Ready(result.map(|()| self.set.insert_all(documents)))
+    }
+}
+
+impl<'a> Drop for LoadDir<'a> {
+    fn drop(&mut self) {
+        self.loading.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).dropped = true;
+    }
+}
+
+/// Start loading the `.org` files under `directory` with `parse` on a thread
+/// of its own for `DocumentSet::load_dir_async`. The result is always set,
+/// even if the loading panics, so that the future finishes.
+fn spawn_loading<F>(parse: F, directory: PathBuf, limit: usize, cancel: Cancel)
+                    -> Arc<Mutex<Loading>>
+    where F: Fn(&Path) -> io::Result<Document> + Send + Sync + 'static {
+    let loading = Arc::new(Mutex::new(Loading::default()));
+    let shared = loading.clone();
+    thread::spawn(move || {
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            load_files(&parse, &directory, limit.max(1), &cancel, &shared)
+        }));
+        let result = result.unwrap_or_else(|_| Err(io::Error::other("loading panicked")));
+        let mut loading = shared.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        loading.result = Some(result);
+        if let Some(waker) = loading.waker.take() {
+            waker.wake();
+        }
+    });
+    loading
+}
+
+/// List the `.org` files under `directory` and parse them on `limit`
+/// threads, collecting the documents in `shared`.
+fn load_files(parse: &(dyn Fn(&Path) -> io::Result<Document> + Sync), directory: &Path,
+              limit: usize, cancel: &Cancel, shared: &Mutex<Loading>) -> io::Result<()> {
+    let mut files = Vec::new();
+    org_files(directory, &mut files)?;
+    let stopped = || cancel.is_cancelled() || shared.lock().map_or(true, |loading| loading.dropped);
+    parse_files(parse, files, limit, &stopped, &|_, name, document| {
+        if let Ok(mut loading) = shared.lock() {
+            loading.documents.push((name, document));
+        }
+    })?;
+    if cancel.is_cancelled() {
+        return Err(io::Error::new(io::ErrorKind::Interrupted, "loading was cancelled"));
+    }
+    Ok(())
+}
+
+/// Parse `files` with `parse` on `limit` threads, handing each document to
+/// `add` along with its index in `files` and its name, until `stopped` says
+/// to stop or a file fails to load. A file whose parsing panics fails to
+/// load.
+fn parse_files(parse: &(dyn Fn(&Path) -> io::Result<Document> + Sync), files: Vec<PathBuf>,
+               limit: usize, stopped: &(dyn Fn() -> bool + Sync),
+               add: &(dyn Fn(usize, String, Document) + Sync)) -> io::Result<()> {
+    let queue = Mutex::new(files.into_iter().enumerate().rev().collect::<Vec<_>>());
+    let error = Mutex::new(None);
+    let failed = || error.lock().map_or(true, |error| error.is_some());
+    thread::scope(|scope| {
+        for _ in 0..limit {
+            scope.spawn(|| {
+                while !stopped() && !failed() {
+                    let (index, path) = match queue.lock().ok().and_then(|mut queue| queue.pop()) {
+                        Some(file) => file,
+                        None => break,
+                    };
+                    let parsed = panic::catch_unwind(AssertUnwindSafe(|| parse(&path)))
+                        .unwrap_or_else(|_| {
+                            Err(io::Error::other(format!("parsing {} panicked", path.display())))
+                        });
+                    match parsed {
+                        Ok(document) => add(index, path.to_string_lossy().into_owned(), document),
+                        Err(e) => {
+                            if let Ok(mut error) = error.lock() {
+                                error.get_or_insert(e);
+                            }
+                        }
+                    }
+                }
+            });
+        }
+    });
+    match error.into_inner().unwrap_or_else(|poisoned| poisoned.into_inner()) {
+        Some(error) => Err(error),
+        None => Ok(()),
+    }
+}
+
+/// Add the `.org` files under `directory` to `files`, in order, skipping
+/// hidden files and directories.
+fn org_files(directory: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    let mut entries = fs::read_dir(directory)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort();
+    for path in entries {
+        if path.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.')) {
+            continue;
+        }
+        if path.is_dir() {
+            org_files(&path, files)?;
+        } else if path.extension().is_some_and(|extension| extension == "org") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Whether `link`, a file link in the document named `from`, points to the
+/// document named `name`, taking its path as relative to `from`.
+fn is_file_link_to(from: &str, link: &Link, name: &str) -> bool {
+    let target = Path::new(from).parent().unwrap_or(Path::new("")).join(link.path());
+    link.link_type() == LinkType::File && (Path::new(name) == target || name == link.path())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_document_set() {
+        let parser = DocumentParser::new();
+        let mut set = DocumentSet::new();
+        set.insert("notes/index.org", parser.parse("* Index
+:PROPERTIES:
+:CUSTOM_ID: top
+:END:
+See [[id:b-1][the project]], [[file:projects.org::#plan]] and [[#top]].").unwrap());
+        set.insert("notes/projects.org", parser.parse("* Projects
+** Build
+:PROPERTIES:
+:ID: b-1
+:CUSTOM_ID: plan
+:END:").unwrap());
+        assert_eq!(set.len(), 2);
+        assert_eq!(set.by_id("b-1").map(|(name, h)| (name, h.title())),
+                   Some(("notes/projects.org", "Build")));
+        assert_eq!(set.by_custom_id("plan").unwrap().1.title(), "Build");
+
+        let links = set.get("notes/index.org").unwrap().links();
+        let resolved: Vec<_> = links.iter()
+            .map(|link| set.resolve_link("notes/index.org", link))
+            .map(|resolved| resolved.map(|(name, h)| (name, h.title())))
+            .collect();
+        assert_eq!(resolved, [
+            Some(("notes/projects.org", "Build")),
+            Some(("notes/projects.org", "Build")),
+            Some(("notes/index.org", "Index")),
+        ]);
+
+        let backlinks = set.backlinks("notes/projects.org");
+        let destinations: Vec<_> = backlinks.iter()
+            .map(|&(from, ref link)| (from, link.destination()))
+            .collect();
+        assert_eq!(destinations, [
+            ("notes/index.org", "id:b-1"),
+            ("notes/index.org", "file:projects.org::#plan"),
+        ]);
+
+        set.remove("notes/projects.org");
+        assert!(set.by_id("b-1").is_none());
+        assert!(set.resolve_link("notes/index.org", &links[0]).is_none());
+    }
+
+    /// Run `future` to completion on this thread.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        struct Unpark(thread::Thread);
+
+        impl ::std::task::Wake for Unpark {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        let mut future = Box::pin(future);
+        let waker = Waker::from(Arc::new(Unpark(thread::current())));
+        let mut context = Context::from_waker(&waker);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+                return output;
+            }
+            thread::park();
+        }
+    }
+
+    #[test]
+    fn test_load_dir_async() {
+        let name = format!("org-load-dir-{}", ::std::process::id());
+        let directory = ::std::env::temp_dir().join(name);
+        fs::create_dir_all(directory.join("sub")).unwrap();
+        fs::create_dir_all(directory.join(".git")).unwrap();
+        let names = ["a.org", "sub/b.org", "sub/c.org", ".git/d.org", "e.txt"];
+        for (i, name) in names.iter().enumerate() {
+            fs::write(directory.join(name), format!("* Note {}", i)).unwrap();
+        }
+
+        let parser = DocumentParser::new();
+        let mut set = DocumentSet::new();
+        let loaded = block_on(set.load_dir_async(&parser, &directory, 2, &Cancel::new()));
+        assert_eq!(loaded.unwrap(), 3);
+        let titles: Vec<_> = set.documents().map(|(_, doc)| doc.headlines()[0].title()).collect();
+        assert_eq!(titles, ["Note 0", "Note 1", "Note 2"]);
+
+        let cancel = Cancel::new();
+        cancel.cancel();
+        let mut other = DocumentSet::new();
+        let error = block_on(other.load_dir_async(&parser, &directory, 2, &cancel)).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::Interrupted);
+        assert!(other.is_empty());
+        assert_eq!(other.load_dir(&parser, &directory).unwrap(), 3);
+
+        let parse = move |path: &Path| {
+            assert!(!path.ends_with("b.org"), "can't parse {}", path.display());
+            parser.parse_file(path)
+        };
+        let mut failing = DocumentSet::new();
+        let loading = spawn_loading(parse, directory.clone(), 2, Cancel::new());
+        let error = block_on(LoadDir { set: &mut failing, loading }).unwrap_err();
+        assert!(error.to_string().ends_with("b.org panicked"));
+        assert!(failing.is_empty());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_parse_files_parallel() {
+        fn send_sync<T: Send + Sync>() {}
+        send_sync::<DocumentParser>();
+        send_sync::<Document>();
+        send_sync::<DocumentSet>();
+
+        let name = format!("org-parallel-{}", ::std::process::id());
+        let directory = ::std::env::temp_dir().join(name);
+        fs::create_dir_all(&directory).unwrap();
+        let paths: Vec<_> = (0..20).map(|i| directory.join(format!("{:02}.org", 19 - i))).collect();
+        for (i, path) in paths.iter().enumerate() {
+            fs::write(path, format!("* Note {}", i)).unwrap();
+        }
+
+        let parser = DocumentParser::new();
+        let mut set = DocumentSet::new();
+        assert_eq!(set.parse_files_parallel(&parser, &paths).unwrap(), 20);
+        let titles: Vec<_> = set.documents().map(|(_, doc)| doc.headlines()[0].title()).collect();
+        let expected: Vec<_> = (0..20).map(|i| format!("Note {}", i)).collect();
+        assert_eq!(titles, expected);
+
+        let mut other = DocumentSet::new();
+        let missing = [directory.join("00.org"), directory.join("missing.org")];
+        let error = other.parse_files_parallel(&parser, &missing).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+        assert!(other.is_empty());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_exclusions() {
+        let parser = DocumentParser::new();
+        let mut set = DocumentSet::new();
+        set.insert("a.org", parser.parse("Some notes.
+* Garden :home:
+Plant the beans.
+:LOGBOOK:
+Watered the beans.
+:END:
+** Draft :noexport:
+Beans, maybe.
+* Private
+:PROPERTIES:
+:VISIBILITY: private
+:END:
+More beans.").unwrap());
+        set.insert("b.org", parser.parse("#+FILETAGS: noexport\n* Beans :home:").unwrap());
+        assert_eq!(set.search("beans").len(), 4);
+        assert_eq!(set.tag_counts().get("home"), Some(&2));
+
+        set.set_exclusions(Exclusions::new()
+            .tag("noexport")
+            .drawer("logbook")
+            .property("VISIBILITY", "private"));
+        let found: Vec<_> = set.search("beans").iter().map(|&(_, h)| h.title()).collect();
+        assert_eq!(found, ["Garden"]);
+        assert!(set.search("watered").is_empty());
+        assert_eq!(set.tag_counts().get("home"), Some(&1));
+        assert_eq!(set.tag_counts().get("noexport"), None);
+        // "Some notes." and "Garden Plant the beans."
+        assert_eq!(set.word_count(), 6);
+    }
+}
@@ -0,0 +1,170 @@
+use std::borrow::Borrow;
+use std::collections::HashSet;
+use std::fmt;
+use std::ops::Deref;
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// An interned string, used for tags, TODO keywords and property keys,
+/// which repeat across every headline of a large set of files. Symbols with
+/// the same text share one allocation, and clone without copying it.
+///
+/// A symbol derefs to `str` and compares equal to string types, so it can
+/// mostly be used as one.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Symbol(Arc<str>);
+
+struct Interner {
+    symbols: HashSet<Arc<str>>,
+    /// How many symbols there were after the last sweep.
+    swept: usize,
+}
+
+fn interner() -> &'static Mutex<Interner> {
+    static INTERNER: OnceLock<Mutex<Interner>> = OnceLock::new();
+    INTERNER.get_or_init(|| Mutex::new(Interner { symbols: HashSet::new(), swept: 0 }))
+}
+
+impl Symbol {
+    /// The symbol for `text`, shared with every other symbol for it.
+    pub fn new(text: &str) -> Symbol {
+        let mut interner = interner().lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(symbol) = interner.symbols.get(text) {
+            return Symbol(symbol.clone());
+        }
+        // Drop the strings no symbol uses anymore once the set has doubled.
+        if interner.symbols.len() >= 2 * interner.swept.max(512) {
+            interner.symbols.retain(|symbol| Arc::strong_count(symbol) > 1);
+            interner.swept = interner.symbols.len();
+        }
+        let symbol: Arc<str> = Arc::from(text);
+        interner.symbols.insert(symbol.clone());
+        Symbol(symbol)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for Symbol {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for Symbol {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(text: &str) -> Symbol {
+        Symbol::new(text)
+    }
+}
+
+impl From<String> for Symbol {
+    fn from(text: String) -> Symbol {
+        Symbol::new(&text)
+    }
+}
+
+impl From<&String> for Symbol {
+    fn from(text: &String) -> Symbol {
+        Symbol::new(text)
+    }
+}
+
+impl From<Symbol> for String {
+    fn from(symbol: Symbol) -> String {
+        symbol.as_str().into()
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl PartialEq<String> for Symbol {
+    fn eq(&self, other: &String) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<Symbol> for str {
+    fn eq(&self, other: &Symbol) -> bool {
+        self == other.as_str()
+    }
+}
+
+impl PartialEq<Symbol> for &str {
+    fn eq(&self, other: &Symbol) -> bool {
+        *self == other.as_str()
+    }
+}
+
+impl PartialEq<Symbol> for String {
+    fn eq(&self, other: &Symbol) -> bool {
+        self == other.as_str()
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl ::serde::Serialize for Symbol {
+    fn serialize<S: ::serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> ::serde::Deserialize<'de> for Symbol {
+    fn deserialize<D: ::serde::Deserializer<'de>>(deserializer: D) -> Result<Symbol, D::Error> {
+        String::deserialize(deserializer).map(Symbol::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_symbols_are_shared() {
+        let a = Symbol::new("work");
+        let b = Symbol::from(String::from("work"));
+        assert!(Arc::ptr_eq(&a.0, &b.0));
+        assert_eq!(a, "work");
+        assert_eq!("work", b);
+        assert_eq!(a.len(), 4);
+        assert_eq!(format!("{} {:?}", a, a), "work \"work\"");
+        assert_ne!(a, Symbol::new("home"));
+    }
+}
@@ -0,0 +1,246 @@
+use formula::FormulaError;
+use keyword::{Affiliate, Affiliated};
+use span::{self, Shift, Span};
+use Now;
+
+/// An org table, made of rows of cells and horizontal rules:
+///
+/// ```ignore
+/// | Name  | Qty |
+/// |-------+-----|
+/// | Apple |   3 |
+/// #+TBLFM: $2=$1*2
+/// ```
+///
+/// Any `#+TBLFM:` lines directly after the table belong to it.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Table {
+    rows: Vec<TableRow>,
+    formulas: Vec<String>,
+    affiliated: Affiliated,
+    span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum TableRow {
+    /// A horizontal rule such as `|---+---|`.
+    Rule,
+    /// A row of cells, with surrounding whitespace removed from each cell.
+    Standard(Vec<String>),
+}
+
+impl Table {
+    /// All rows, including horizontal rules.
+    pub fn rows(&self) -> &[TableRow] {
+        &self.rows
+    }
+
+    /// The rows holding cells, skipping horizontal rules.
+    pub fn data_rows(&self) -> impl Iterator<Item = &[String]> {
+        self.rows.iter().filter_map(|row| match *row {
+            TableRow::Standard(ref cells) => Some(&cells[..]),
+            TableRow::Rule => None,
+        })
+    }
+
+    /// The cell at `col` in the `row`th data row, both counted from zero.
+    /// Horizontal rules don't count as rows.
+    pub fn cell(&self, row: usize, col: usize) -> Option<&str> {
+        self.data_rows().nth(row)?.get(col).map(|s| &s[..])
+    }
+
+    /// The individual formulas from the `#+TBLFM:` lines, which may each hold
+    /// several formulas separated by `::`.
+    pub fn formulas(&self) -> &[String] {
+        &self.formulas
+    }
+
+    /// The `#+NAME:`, `#+CAPTION:` and other keywords written above it.
+    pub fn affiliated(&self) -> &Affiliated {
+        &self.affiliated
+    }
+
+    /// Where the table is in the text, including its `#+TBLFM:` lines.
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    /// Set the cell at `col` in the `row`th data row, adding empty cells
+    /// before it if the row is too short. Does nothing if there is no such
+    /// row.
+    pub fn set_cell<S: Into<String>>(&mut self, row: usize, col: usize, value: S) {
+        let cells = self.rows.iter_mut()
+            .filter_map(|row| match *row {
+                TableRow::Standard(ref mut cells) => Some(cells),
+                TableRow::Rule => None,
+            })
+            .nth(row);
+        if let Some(cells) = cells {
+            if cells.len() <= col {
+                cells.resize(col + 1, String::new());
+            }
+            cells[col] = value.into();
+        }
+    }
+
+    /// Evaluate the formulas and write their results into the cells.
+    ///
+    /// Column formulas like `$3=$1*$2` apply to every data row below the
+    /// header, and then field formulas like `@>$3=vsum(@I..@II)` to a single
+    /// cell. Formulas can use `+`, `-`, `*`, `/`, parentheses, references to
+    /// rows (`@2`, `@<`, `@>`, `@-1`, `@I` for the row after the first rule)
+    /// and columns (`$1`, `$<`, `$>`, `$+1`), and `vsum`, `vmean`, `vmin`,
+    /// `vmax` and `vcount` over ranges like `@2$1..@>$2`, comparisons, and
+    /// dates and durations: `$3=$2-$1` counts the days between two
+    /// timestamps, and `$2=$1+"2d"` or `$2=now()+"1h"` gives one. A mode
+    /// after a `;` like `%.2f`, `N`, `E` or `T` changes how fields are read
+    /// and results written, and simple Lisp formulas like
+    /// `'(concat $1 " " $2)` work on the fields' text.
+    ///
+    /// Each cell is computed after the cells it reads, and formulas that
+    /// depend on their own results are an error. Empty cells count as zero.
+    /// On an error, the cells set by earlier formulas keep their new values.
+    pub fn recalculate(&mut self) -> Result<(), FormulaError> {
+        ::formula::evaluate(&mut self.rows, &self.formulas)
+    }
+
+    /// `recalculate`, with `now()` and `today()` taken from `now`.
+    pub fn recalculate_at(&mut self, now: &dyn Now) -> Result<(), FormulaError> {
+        ::formula::evaluate_at(&mut self.rows, &self.formulas, now)
+    }
+
+    /// The rows as org text, with the columns aligned and those holding
+    /// mostly numbers aligned to the right, like `org-table-align`. The
+    /// `#+TBLFM:` lines aren't included.
+    pub fn to_org_string(&self) -> String {
+        let columns = self.data_rows().map(|cells| cells.len()).max().unwrap_or(0).max(1);
+        let mut widths = vec![1; columns];
+        let mut numbers = vec![(0, 0); columns];
+        for cells in self.data_rows() {
+            for (i, cell) in cells.iter().enumerate() {
+                widths[i] = widths[i].max(cell.chars().count());
+                if !cell.is_empty() {
+                    numbers[i].1 += 1;
+                    if cell.parse::<f64>().is_ok() {
+                        numbers[i].0 += 1;
+                    }
+                }
+            }
+        }
+        let mut out = String::new();
+        for row in &self.rows {
+            match *row {
+                TableRow::Rule => {
+                    let dashes: Vec<_> = widths.iter()
+                        .map(|&width| "-".repeat(width + 2))
+                        .collect();
+                    out.push_str(&format!("|{}|\n", dashes.join("+")));
+                }
+                TableRow::Standard(ref cells) => {
+                    out.push('|');
+                    for (i, &width) in widths.iter().enumerate() {
+                        let cell = cells.get(i).map_or("", |cell| &cell[..]);
+                        let (numeric, filled) = numbers[i];
+                        if numeric * 2 >= filled && numeric > 0 {
+                            out.push_str(&format!(" {:>1$} |", cell, width));
+                        } else {
+                            out.push_str(&format!(" {:1$} |", cell, width));
+                        }
+                    }
+                    out.push('\n');
+                }
+            }
+        }
+        out
+    }
+}
+
+impl Affiliate for Table {
+    fn affiliated_mut(&mut self) -> &mut Affiliated {
+        &mut self.affiliated
+    }
+}
+
+impl Shift for Table {
+    fn shift(&mut self, delta: isize) {
+        self.affiliated.shift(delta);
+        self.span.shift(delta);
+    }
+}
+
+fn parse_row(line: &str) -> TableRow {
+    let line = line.trim();
+    if line.starts_with("|-") {
+        return TableRow::Rule;
+    }
+    let inner = &line[1..];
+    let inner = inner.strip_suffix('|').unwrap_or(inner);
+    TableRow::Standard(inner.split('|').map(|cell| cell.trim().into()).collect())
+}
+
+/// The value of a `#+TBLFM:` line, matched case-insensitively.
+fn formula_line(line: &str) -> Option<&str> {
+    let line = line.trim();
+    let prefix = "#+tblfm:";
+    if line.len() >= prefix.len()
+        && line.is_char_boundary(prefix.len())
+        && line[..prefix.len()].eq_ignore_ascii_case(prefix) {
+        Some(line[prefix.len()..].trim())
+    } else {
+        None
+    }
+}
+
+/// Try to parse a table starting at `lines[start]`. Returns the table and the
+/// index of the first line after it.
+pub fn parse_table(lines: &[&str], offsets: &[usize], start: usize) -> Option<(Table, usize)> {
+    let mut i = start;
+    let mut rows = Vec::new();
+    while i < lines.len() && lines[i].trim_start().starts_with('|') {
+        rows.push(parse_row(lines[i]));
+        i += 1;
+    }
+    if rows.is_empty() {
+        return None;
+    }
+    let mut formulas = Vec::new();
+    while let Some(value) = lines.get(i).and_then(|line| formula_line(line)) {
+        formulas.extend(value.split("::")
+                        .map(str::trim)
+                        .filter(|formula| !formula.is_empty())
+                        .map(String::from));
+        i += 1;
+    }
+    let span = span::lines_span(lines, offsets, start, i);
+    Some((Table { rows, formulas, affiliated: Affiliated::default(), span }, i))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_table() {
+        let lines: Vec<_> = "  | Name  | Qty |
+  |-------+-----|
+  | Apple |   3 |
+  | Pear  |
+#+TBLFM: $2=$1*2::@2$1=vsum(@I..@II)
+#+tblfm: $3=1
+after".lines().collect();
+        let offsets = span::line_offsets(&lines.join("\n"), 0);
+        let (table, next) = parse_table(&lines, &offsets, 0).unwrap();
+        assert_eq!(next, 6);
+        assert_eq!(table.span(), Span::new(0, offsets[6] - 1));
+        assert_eq!(table.rows().len(), 4);
+        assert_eq!(table.rows()[1], TableRow::Rule);
+        assert_eq!(table.cell(0, 0), Some("Name"));
+        assert_eq!(table.cell(1, 1), Some("3"));
+        assert_eq!(table.cell(2, 0), Some("Pear"));
+        assert_eq!(table.cell(2, 1), None);
+        assert_eq!(table.formulas(), ["$2=$1*2", "@2$1=vsum(@I..@II)", "$3=1"]);
+        assert!(parse_table(&lines, &offsets, 6).is_none());
+    }
+}
@@ -0,0 +1,145 @@
+use std::ptr;
+
+use {Document, Headline, Symbol};
+
+/// A tag match like org's agenda tag searches: `work&!someday`,
+/// `+work-someday` or `home|errand`.
+///
+/// `|` separates alternatives, at least one of which must match. Within an
+/// alternative every tag must be present, except for those negated with `!`
+/// or `-`. Tags are joined with `&`, `+` or nothing before a `-`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagQuery {
+    /// For each alternative, the tags and whether each must be present.
+    alternatives: Vec<Vec<(String, bool)>>,
+}
+
+impl TagQuery {
+    pub fn parse(query: &str) -> TagQuery {
+        let alternatives = query.split('|').map(|alternative| {
+            let mut terms = Vec::new();
+            let mut present = true;
+            let mut tag = String::new();
+            for c in alternative.chars().chain(Some('&')) {
+                match c {
+                    '&' | '+' | '-' | '!' => {
+                        if !tag.is_empty() {
+                            terms.push((tag.clone(), present));
+                            tag.clear();
+                            present = true;
+                        }
+                        if c == '-' || c == '!' {
+                            present = false;
+                        }
+                    }
+                    _ if c.is_whitespace() => {}
+                    _ => tag.push(c),
+                }
+            }
+            terms
+        }).filter(|terms| !terms.is_empty()).collect();
+        TagQuery { alternatives }
+    }
+
+    /// Whether a headline with `tags` matches. An empty query matches
+    /// everything.
+    pub fn matches(&self, tags: &[String]) -> bool {
+        self.alternatives.is_empty() || self.alternatives.iter().any(|terms| {
+            terms.iter().all(|&(ref tag, present)| tags.contains(tag) == present)
+        })
+    }
+}
+
+impl Document {
+    /// The tags in `#+FILETAGS:`, which every headline inherits.
+    pub fn file_tags(&self) -> Vec<String> {
+        let mut tags: Vec<String> = Vec::new();
+        if let Some(value) = self.keywords().get("FILETAGS") {
+            for tag in value.split(|c: char| c == ':' || c.is_whitespace()) {
+                if !tag.is_empty() && !tags.iter().any(|t| t == tag) {
+                    tags.push(tag.into());
+                }
+            }
+        }
+        tags
+    }
+
+    /// The tags of `headline`, which must be in this document, with those
+    /// it inherits from the file and from its ancestors, outermost first.
+    pub fn all_tags(&self, headline: &Headline) -> Vec<String> {
+        let mut tags = self.file_tags();
+        let mut path = Vec::new();
+        if find_path(&self.headlines, headline, &mut path) {
+            for ancestor in path {
+                add_tags(&mut tags, ancestor.tags());
+            }
+        }
+        tags
+    }
+
+    /// The headlines whose tags, inherited ones included, match the `query`,
+    /// in document order. See `TagQuery` for the syntax.
+    pub fn find_by_tags(&self, query: &str) -> Vec<&Headline> {
+        fn search<'a>(headlines: &'a [Headline], inherited: &[String], query: &TagQuery,
+                      found: &mut Vec<&'a Headline>) {
+            for headline in headlines {
+                let mut tags = inherited.to_vec();
+                add_tags(&mut tags, headline.tags());
+                if query.matches(&tags) {
+                    found.push(headline);
+                }
+                search(headline.children(), &tags, query, found);
+            }
+        }
+
+        let mut found = Vec::new();
+        search(&self.headlines, &self.file_tags(), &TagQuery::parse(query), &mut found);
+        found
+    }
+}
+
+fn add_tags(tags: &mut Vec<String>, new: &[Symbol]) {
+    for tag in new {
+        if !tags.iter().any(|t| t == tag) {
+            tags.push(tag.to_string());
+        }
+    }
+}
+
+/// Fill `path` with the headlines from the top level down to `target`,
+/// returning whether it was found.
+fn find_path<'a>(headlines: &'a [Headline], target: &Headline, path: &mut Vec<&'a Headline>)
+                 -> bool {
+    for headline in headlines {
+        path.push(headline);
+        if ptr::eq(headline, target) || find_path(headline.children(), target, path) {
+            return true;
+        }
+        path.pop();
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use DocumentParser;
+
+    #[test]
+    fn test_tags() {
+        let doc = DocumentParser::new().parse("#+FILETAGS: :notes:
+* Projects :work:
+** Website
+** Garden :someday:
+* Errands :home:").unwrap();
+        let website = &doc.headlines()[0].children()[0];
+        assert_eq!(doc.all_tags(website), ["notes", "work"]);
+        let titles = |query| -> Vec<&str> {
+            doc.find_by_tags(query).iter().map(|h| h.title()).collect()
+        };
+        assert_eq!(titles("work&!someday"), ["Projects", "Website"]);
+        assert_eq!(titles("+work-someday"), ["Projects", "Website"]);
+        assert_eq!(titles("someday|home"), ["Garden", "Errands"]);
+        assert_eq!(titles("notes").len(), 4);
+        assert!(titles("missing").is_empty());
+    }
+}
@@ -0,0 +1,273 @@
+//! Writing the src blocks of a document out to the files their `:tangle`
+//! header arguments name, like `org-babel-tangle`, so that a build script
+//! can tangle literate sources without Emacs.
+//!
+//! Blocks are concatenated per file in document order, with a blank line
+//! between them unless they have `:padline no`, and start with the
+//! `:shebang` of the first block that has one. Blocks whose `:noweb` says to
+//! expand references when tangling have each `<<name>>` replaced by the
+//! blocks named `name`, either with `#+NAME:` or with `:noweb-ref`. Text
+//! before a reference on its line is repeated on every line of what it
+//! expands to, so that indentation and comment markers carry over.
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+use {Block, Document, HeaderArgs, Tangle};
+
+/// The file extensions of the languages whose extension isn't the same as
+/// their name, for `:tangle yes`, like `org-babel-tangle-lang-exts`.
+const LANGUAGE_EXTENSIONS: &[(&str, &str)] = &[
+    ("rust", "rs"), ("python", "py"), ("emacs-lisp", "el"), ("elisp", "el"), ("shell", "sh"),
+    ("bash", "sh"), ("ruby", "rb"), ("perl", "pl"), ("haskell", "hs"), ("javascript", "js"),
+    ("typescript", "ts"), ("ocaml", "ml"), ("clojure", "clj"), ("scheme", "scm"),
+    ("latex", "tex"), ("C", "c"), ("C++", "cpp"), ("cpp", "cpp"), ("kotlin", "kt"),
+    ("markdown", "md"), ("julia", "jl"), ("elixir", "ex"), ("erlang", "erl"),
+];
+
+/// Why a document couldn't be tangled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TangleError {
+    reference: String,
+    message: String,
+}
+
+impl TangleError {
+    fn new<S: Into<String>>(reference: &str, message: S) -> TangleError {
+        TangleError { reference: reference.into(), message: message.into() }
+    }
+
+    /// The noweb reference that couldn't be expanded, without its `<<` and
+    /// `>>`.
+    pub fn reference(&self) -> &str {
+        &self.reference
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for TangleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} in noweb reference <<{}>>", self.message, self.reference)
+    }
+}
+
+impl Error for TangleError {}
+
+/// The files the src blocks of `document` tangle to, as paths and contents,
+/// in the order their first block appears. `file_name` is the path of the
+/// document, which `:tangle yes` names its files after and the paths of
+/// `:tangle path` are relative to.
+pub fn tangle(document: &Document, file_name: &str)
+              -> Result<Vec<(String, String)>, TangleError> {
+    let tangler = Tangler { blocks: document.src_blocks() };
+    let mut files: Vec<(String, Vec<usize>)> = Vec::new();
+    for (index, (block, args)) in tangler.blocks.iter().enumerate() {
+        let path = match target(block, args, file_name) {
+            Some(path) => path,
+            None => continue,
+        };
+        match files.iter_mut().find(|(existing, _)| *existing == path) {
+            Some((_, blocks)) => blocks.push(index),
+            None => files.push((path, vec![index])),
+        }
+    }
+
+    let mut tangled = Vec::new();
+    for (path, blocks) in files {
+        let mut contents = String::new();
+        let shebang = blocks.iter().find_map(|&index| tangler.blocks[index].1.get("shebang"));
+        if let Some(shebang) = shebang {
+            contents.push_str(unquote(shebang));
+            contents.push('\n');
+        }
+        for (i, &index) in blocks.iter().enumerate() {
+            if i > 0 && tangler.blocks[index].1.get("padline") != Some("no") {
+                contents.push('\n');
+            }
+            contents.push_str(&tangler.body(index, &mut Vec::new())?);
+            contents.push('\n');
+        }
+        tangled.push((path, contents));
+    }
+    Ok(tangled)
+}
+
+/// Tangle `document` like `tangle`, passing each file's path and contents
+/// to `write`, which could write them to disk with `fs::write`. Stops at the
+/// first error, writing nothing if a noweb reference can't be expanded.
+pub fn tangle_with<F>(document: &Document, file_name: &str, mut write: F) -> io::Result<()>
+    where F: FnMut(&str, &str) -> io::Result<()> {
+    let files = tangle(document, file_name)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+    for (path, contents) in files {
+        write(&path, &contents)?;
+    }
+    Ok(())
+}
+
+/// The path the src `block` tangles to, if it does.
+fn target(block: &Block, args: &HeaderArgs, file_name: &str) -> Option<String> {
+    let path = match args.tangle() {
+        Tangle::No => return None,
+        Tangle::Yes => {
+            let language = block.language()?;
+            let extension = LANGUAGE_EXTENSIONS.iter()
+                .find(|&&(name, _)| name == language)
+                .map_or(language, |&(_, extension)| extension);
+            return Some(Path::new(file_name).with_extension(extension).to_string_lossy().into());
+        }
+        Tangle::File(path) => path,
+    };
+    let path = unquote(&path);
+    match Path::new(file_name).parent() {
+        Some(directory) if !Path::new(path).is_absolute() => {
+            Some(directory.join(path).to_string_lossy().into())
+        }
+        _ => Some(path.into()),
+    }
+}
+
+fn unquote(value: &str) -> &str {
+    value.strip_prefix('"').and_then(|value| value.strip_suffix('"')).unwrap_or(value)
+}
+
+struct Tangler<'a> {
+    blocks: Vec<(&'a Block, HeaderArgs)>,
+}
+
+impl<'a> Tangler<'a> {
+    /// The text of the block at `index`, with its references expanded if
+    /// its `:noweb` says to. `expanding` holds the references being expanded
+    /// around it, to catch blocks that include themselves.
+    fn body(&self, index: usize, expanding: &mut Vec<String>) -> Result<String, TangleError> {
+        let (block, ref args) = self.blocks[index];
+        if !args.noweb().expands_on_tangle() {
+            return Ok(block.text().into());
+        }
+        let mut expanded = Vec::new();
+        for line in block.text().split('\n') {
+            expanded.push(self.expand_line(line, expanding)?);
+        }
+        Ok(expanded.join("\n"))
+    }
+
+    fn expand_line(&self, line: &str, expanding: &mut Vec<String>)
+                   -> Result<String, TangleError> {
+        let mut expanded = String::new();
+        let mut rest = 0;
+        while let Some((start, end)) = find_reference(&line[rest..]) {
+            let (start, end) = (rest + start, rest + end);
+            let name = line[start + 2..end - 2].trim();
+            let prefix = format!("\n{}", &line[..start]);
+            expanded.push_str(&line[rest..start]);
+            expanded.push_str(&self.reference(name, expanding)?.replace('\n', &prefix));
+            rest = end;
+        }
+        expanded.push_str(&line[rest..]);
+        Ok(expanded)
+    }
+
+    /// The blocks named `name`, one after another, with their own references
+    /// expanded.
+    fn reference(&self, name: &str, expanding: &mut Vec<String>)
+                 -> Result<String, TangleError> {
+        if name.ends_with(')') {
+            return Err(TangleError::new(name, "can't evaluate a block for its results"));
+        }
+        if expanding.iter().any(|outer| outer == name) {
+            return Err(TangleError::new(name, "block includes itself"));
+        }
+        let named: Vec<_> = (0..self.blocks.len())
+            .filter(|&index| {
+                let (block, ref args) = self.blocks[index];
+                block.affiliated().name() == Some(name) || args.get("noweb-ref") == Some(name)
+            })
+            .collect();
+        if named.is_empty() {
+            return Err(TangleError::new(name, "no block named"));
+        }
+        expanding.push(name.into());
+        let mut bodies = Vec::new();
+        for index in named {
+            bodies.push(self.body(index, expanding)?);
+        }
+        expanding.pop();
+        Ok(bodies.join("\n"))
+    }
+}
+
+/// The start and end of the first `<<reference>>` in `text`.
+fn find_reference(text: &str) -> Option<(usize, usize)> {
+    let start = text.find("<<")?;
+    let end = start + 2 + text[start + 2..].find(">>")? + 2;
+    if text[start + 2..end - 2].trim().is_empty() {
+        return None;
+    }
+    Some((start, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use DocumentParser;
+
+    #[test]
+    fn test_tangle() {
+        let doc = DocumentParser::new().parse("#+PROPERTY: header-args :noweb yes
+* Main
+#+BEGIN_SRC rust :tangle src/main.rs
+fn main() {
+    // <<greeting>>
+}
+#+END_SRC
+#+BEGIN_SRC rust :tangle src/main.rs :padline no
+fn helper() {}
+#+END_SRC
+* Pieces
+#+NAME: greeting
+#+BEGIN_SRC rust
+println!(\"hello\");
+<<name>>
+#+END_SRC
+#+BEGIN_SRC rust :noweb-ref name
+println!(\"world\");
+#+END_SRC
+#+BEGIN_SRC sh :tangle yes :shebang \"#!/bin/sh\"
+echo <<no-such-block>>
+#+END_SRC
+").unwrap();
+        let error = tangle(&doc, "notes/build.org").unwrap_err();
+        assert_eq!(error.reference(), "no-such-block");
+
+        let doc = DocumentParser::new().parse(&doc.to_org_string()
+            .replace("echo <<no-such-block>>", "echo '<<name>>'")).unwrap();
+        let files = tangle(&doc, "notes/build.org").unwrap();
+        assert_eq!(files, [
+            ("notes/src/main.rs".to_string(), "fn main() {
+    // println!(\"hello\");
+    // println!(\"world\");
+}
+fn helper() {}
+".to_string()),
+            ("notes/build.sh".to_string(), "#!/bin/sh\necho 'println!(\"world\");'\n".to_string()),
+        ]);
+
+        let looped = DocumentParser::new().parse("#+NAME: a
+#+BEGIN_SRC sh :noweb yes :tangle a.sh
+<<a>>
+#+END_SRC
+").unwrap();
+        let mut written = Vec::new();
+        let error = tangle_with(&looped, "a.org", |path, _| {
+            written.push(path.to_string());
+            Ok(())
+        });
+        assert_eq!(error.unwrap_err().kind(), io::ErrorKind::InvalidData);
+        assert!(written.is_empty());
+    }
+}
@@ -0,0 +1,242 @@
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+
+use {Content, Document, Element, Headline, Link, LinkType, Object};
+
+/// What an internal link points to, found by `Document::link_target`.
+#[derive(Debug, Clone, Copy)]
+pub enum LinkTarget<'a> {
+    /// A `<<target>>` or `<<<radio target>>>`, in the title or section of
+    /// the headline, or before the first headline for `None`.
+    Target(Option<&'a Headline>),
+    /// An element whose `#+NAME:` is the text of the link.
+    Element(&'a Content),
+    Headline(&'a Headline),
+}
+
+/// Everything internal links can point to, collected in one pass over the
+/// document so that resolving each link is a lookup, made by
+/// `Document::link_index`. Exporters build one up front rather than calling
+/// `Document::link_target` for every link.
+#[derive(Debug, Clone)]
+pub struct LinkIndex<'a> {
+    /// The first `<<target>>` with each name, with the position in the
+    /// document of the headline holding it.
+    targets: HashMap<String, (usize, Option<&'a Headline>)>,
+    /// The same for radio targets, by their text in lowercase.
+    radios: HashMap<String, (usize, Option<&'a Headline>)>,
+    radio_targets: Vec<String>,
+    names: HashMap<&'a str, &'a Content>,
+    ids: HashMap<&'a str, &'a Headline>,
+    custom_ids: HashMap<&'a str, &'a Headline>,
+    titles: HashMap<&'a str, &'a Headline>,
+}
+
+impl<'a> LinkIndex<'a> {
+    fn new(document: &'a Document) -> LinkIndex<'a> {
+        let mut index = LinkIndex {
+            targets: HashMap::new(),
+            radios: HashMap::new(),
+            radio_targets: Vec::new(),
+            names: HashMap::new(),
+            ids: HashMap::new(),
+            custom_ids: HashMap::new(),
+            titles: HashMap::new(),
+        };
+        for (position, (headline, objects)) in document.objects_by_headline().into_iter()
+            .enumerate() {
+            index.add_targets(&objects, position, headline);
+        }
+        if let Some(section) = document.first_section() {
+            index.add_names(section.contents());
+        }
+        for headline in document.all_headlines() {
+            if let Some(section) = headline.section() {
+                index.add_names(section.contents());
+            }
+        }
+        for headline in document.all_headlines() {
+            if let Some(id) = headline.property("ID") {
+                index.ids.entry(id).or_insert(headline);
+            }
+            if let Some(id) = headline.property("CUSTOM_ID") {
+                index.custom_ids.entry(id).or_insert(headline);
+            }
+            index.titles.entry(&headline.title).or_insert(headline);
+        }
+        index
+    }
+
+    fn add_targets(&mut self, objects: &[Object], position: usize, headline: Option<&'a Headline>) {
+        for object in objects {
+            match *object {
+                Object::Target(ref name) => {
+                    self.targets.entry(name.clone()).or_insert((position, headline));
+                }
+                Object::RadioTarget(ref text) => {
+                    if let Entry::Vacant(entry) = self.radios.entry(text.to_lowercase()) {
+                        entry.insert((position, headline));
+                        self.radio_targets.push(text.clone());
+                    }
+                }
+                Object::Bold(ref inner) | Object::Italic(ref inner)
+                | Object::Underline(ref inner) | Object::StrikeThrough(ref inner) => {
+                    self.add_targets(inner, position, headline);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Add the elements among `contents`, and inside them, named with
+    /// `#+NAME:`, keeping the first of each name.
+    fn add_names(&mut self, contents: &'a [Content]) {
+        for content in contents {
+            if let Some(name) = content.affiliated().and_then(|affiliated| affiliated.name()) {
+                self.names.entry(name).or_insert(content);
+            }
+            let (children, items) = content.children();
+            self.add_names(children);
+            for item in items {
+                self.add_names(item.contents());
+            }
+        }
+    }
+
+    /// What `link` points to, as `Document::link_target` finds it.
+    pub fn target(&self, link: &Link) -> Option<LinkTarget<'a>> {
+        let path = link.path();
+        match link.link_type() {
+            LinkType::Fuzzy | LinkType::Radio => {}
+            _ => return self.headline(link).map(LinkTarget::Headline),
+        }
+        let target = self.targets.get(path);
+        let radio = self.radios.get(&path.to_lowercase());
+        let first = match (target, radio) {
+            (Some(target), Some(radio)) => Some(if radio.0 < target.0 { radio } else { target }),
+            (target, radio) => target.or(radio),
+        };
+        if let Some(&(_, headline)) = first {
+            return Some(LinkTarget::Target(headline));
+        }
+        if let Some(&content) = self.names.get(path) {
+            return Some(LinkTarget::Element(content));
+        }
+        self.headline(link).map(LinkTarget::Headline)
+    }
+
+    /// The headline `link` points to, as `Document::resolve_link` finds it.
+    pub fn headline(&self, link: &Link) -> Option<&'a Headline> {
+        let headlines = match link.link_type() {
+            LinkType::Id => &self.ids,
+            LinkType::CustomId => &self.custom_ids,
+            LinkType::Headline | LinkType::Fuzzy => &self.titles,
+            _ => return None,
+        };
+        headlines.get(link.path()).cloned()
+    }
+
+    /// The text of every radio target, as `Document::radio_targets` gives
+    /// them.
+    pub fn radio_targets(&self) -> &[String] {
+        &self.radio_targets
+    }
+}
+
+impl Document {
+    /// The text of every `<<<radio target>>>` in the document, in order,
+    /// leaving out those that repeat one before them, ignoring case. Pass
+    /// them to `link_radio_targets` to find the text that links to them.
+    pub fn radio_targets(&self) -> Vec<String> {
+        self.link_index().radio_targets
+    }
+
+    /// Find what an internal link points to. Fuzzy links and radio links
+    /// match, like they do in org, first a target with their text, then an
+    /// element with it as its `#+NAME:`, then a headline with it as its
+    /// title. Other links resolve as they do with `resolve_link`.
+    ///
+    /// This searches the whole document; to resolve many links, make a
+    /// `link_index` once and look each of them up in it.
+    pub fn link_target(&self, link: &Link) -> Option<LinkTarget<'_>> {
+        self.link_index().target(link)
+    }
+
+    pub fn link_index(&self) -> LinkIndex<'_> {
+        LinkIndex::new(self)
+    }
+
+    /// The objects of the title and paragraphs of each headline, and of the
+    /// paragraphs before the first one, in order.
+    fn objects_by_headline(&self) -> Vec<(Option<&Headline>, Vec<Object>)> {
+        let mut objects = Vec::new();
+        if let Some(section) = self.first_section() {
+            let mut found = Vec::new();
+            paragraph_objects(section.contents(), &mut found);
+            objects.push((None, found));
+        }
+        for headline in self.all_headlines() {
+            let mut found = headline.title_objects();
+            if let Some(section) = headline.section() {
+                paragraph_objects(section.contents(), &mut found);
+            }
+            objects.push((Some(headline), found));
+        }
+        objects
+    }
+}
+
+fn paragraph_objects(contents: &[Content], objects: &mut Vec<Object>) {
+    for content in contents {
+        if let Content::Element(Element::Paragraph(ref paragraph)) = *content {
+            objects.extend(paragraph.objects());
+        }
+        let (children, items) = content.children();
+        paragraph_objects(children, objects);
+        for item in items {
+            paragraph_objects(item.contents(), objects);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {DocumentParser, link_radio_targets, parse_objects};
+
+    #[test]
+    fn test_link_target() {
+        let doc = DocumentParser::new().parse("Intro with a <<<Radio Link>>>.
+* Data
+#+NAME: numbers
+| 1 | 2 |
+* Other
+See <<here>> and *radio links*.
+* here
+").unwrap();
+        let targets = doc.radio_targets();
+        assert_eq!(targets, ["Radio Link"]);
+        let objects = link_radio_targets(parse_objects("Two radio link uses, not radio linked"),
+                                         &targets);
+        let link = match objects[1] {
+            Object::Link(ref link) => link.clone(),
+            ref other => panic!("expected a link, got {:?}", other),
+        };
+        assert_eq!(link.link_type(), LinkType::Radio);
+        assert_eq!(link.path(), "Radio Link");
+        assert_eq!(objects.len(), 3);
+        assert!(matches!(doc.link_target(&link), Some(LinkTarget::Target(None))));
+
+        let fuzzy = |text: &str| ::object::find_links(&format!("[[{}]]", text)).remove(0);
+        match doc.link_target(&fuzzy("here")) {
+            Some(LinkTarget::Target(Some(headline))) => assert_eq!(headline.title(), "Other"),
+            other => panic!("expected a target, got {:?}", other),
+        }
+        assert!(matches!(doc.link_target(&fuzzy("numbers")), Some(LinkTarget::Element(_))));
+        match doc.link_target(&fuzzy("Data")) {
+            Some(LinkTarget::Headline(headline)) => assert_eq!(headline.title(), "Data"),
+            other => panic!("expected a headline, got {:?}", other),
+        }
+        assert!(doc.link_target(&fuzzy("nowhere")).is_none());
+    }
+}
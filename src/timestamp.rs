@@ -0,0 +1,679 @@
+use std::fmt;
+
+/// A calendar date, as written in `2024-05-01`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Date {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+}
+
+/// A time of day, as written in `9:30` or `18:00`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Time {
+    pub hour: u8,
+    pub minute: u8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum TimeUnit {
+    Hour,
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum RepeaterKind {
+    /// `+1w`, shift the timestamp by one interval.
+    Cumulative,
+    /// `++1w`, shift by as many intervals as needed to get into the future.
+    CatchUp,
+    /// `.+1w`, shift to one interval after today.
+    Restart,
+}
+
+/// A repeater like `+1w`, making the timestamp recur.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Repeater {
+    pub kind: RepeaterKind,
+    pub value: u32,
+    pub unit: TimeUnit,
+}
+
+/// A warning delay like `-2d`, which changes how early a deadline shows up
+/// (or how late a scheduled item does). `--2d` only affects the first
+/// occurrence of a repeated timestamp.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Delay {
+    pub first_only: bool,
+    pub value: u32,
+    pub unit: TimeUnit,
+}
+
+/// An org timestamp:
+///
+/// ```ignore
+/// <2024-05-01 Wed>                      active
+/// [2024-05-01 Wed 10:30]                inactive
+/// <2024-05-01 Wed 10:00-12:00>          time range
+/// <2024-05-01 Wed>--<2024-05-03 Fri>    date range
+/// <2024-05-01 Wed +1w -2d>              repeater and warning delay
+/// ```
+///
+/// Day names are ignored when parsing, so timestamps written with localized
+/// day names are accepted too.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct Timestamp {
+    active: bool,
+    date: Date,
+    time: Option<Time>,
+    end: Option<(Date, Option<Time>)>,
+    repeater: Option<Repeater>,
+    delay: Option<Delay>,
+}
+
+impl Timestamp {
+    /// A timestamp for a single day, at `time` if given.
+    pub fn new(active: bool, date: Date, time: Option<Time>) -> Timestamp {
+        Timestamp { active, date, time, end: None, repeater: None, delay: None }
+    }
+
+    /// Parse a single timestamp (or range), ignoring surrounding whitespace.
+    pub fn parse(text: &str) -> Option<Timestamp> {
+        let text = text.trim();
+        match parse_prefix(text) {
+            Some((timestamp, len)) if len == text.len() => Some(timestamp),
+            _ => None,
+        }
+    }
+
+    /// Active timestamps (`<...>`) show up in the agenda, inactive ones
+    /// (`[...]`) don't.
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    pub fn set_active(&mut self, active: bool) {
+        self.active = active;
+    }
+
+    pub fn date(&self) -> Date {
+        self.date
+    }
+
+    pub fn time(&self) -> Option<Time> {
+        self.time
+    }
+
+    /// Whether this is a date range or a time range.
+    pub fn is_range(&self) -> bool {
+        self.end.is_some()
+    }
+
+    /// The date a range ends on; for a time range this is the start date.
+    pub fn end_date(&self) -> Option<Date> {
+        self.end.map(|(date, _)| date)
+    }
+
+    /// The time a range ends at, if it has one.
+    pub fn end_time(&self) -> Option<Time> {
+        self.end.and_then(|(_, time)| time)
+    }
+
+    pub fn repeater(&self) -> Option<Repeater> {
+        self.repeater
+    }
+
+    pub fn delay(&self) -> Option<Delay> {
+        self.delay
+    }
+
+    /// The timestamp moved to its next occurrence, as org moves it when a
+    /// repeated task is done on `today`, or `None` without a repeater. Hourly
+    /// repeaters always count from the timestamp's own time.
+    pub fn repeat(&self, today: Date) -> Option<Timestamp> {
+        let repeater = self.repeater.filter(|repeater| repeater.value > 0)?;
+        let step = |date: Date, count: i64| -> (Date, Option<Time>) {
+            let value = repeater.value as i64 * count;
+            match repeater.unit {
+                TimeUnit::Hour => {
+                    let time = self.time.unwrap_or(Time { hour: 0, minute: 0 });
+                    let hours = time.hour as i64 + value;
+                    let time = Time { hour: hours.rem_euclid(24) as u8, minute: time.minute };
+                    (date.add_days(hours.div_euclid(24)), Some(time))
+                }
+                TimeUnit::Day => (date.add_days(value), self.time),
+                TimeUnit::Week => (date.add_days(value * 7), self.time),
+                TimeUnit::Month => (date.add_months(value), self.time),
+                TimeUnit::Year => (date.add_months(value * 12), self.time),
+            }
+        };
+        let (date, time) = match repeater.kind {
+            RepeaterKind::Restart if repeater.unit != TimeUnit::Hour => step(today, 1),
+            RepeaterKind::CatchUp => {
+                let mut count = 1;
+                while step(self.date, count).0 <= today {
+                    count += 1;
+                }
+                step(self.date, count)
+            }
+            _ => step(self.date, 1),
+        };
+        let end = self.end.map(|(end, end_time)| {
+            (date.add_days(end.days_since(self.date)), end_time)
+        });
+        Some(Timestamp { date, time, end, ..self.clone() })
+    }
+}
+
+impl Date {
+    /// Parse a date written like `2024-05-01`.
+    pub fn parse(text: &str) -> Option<Date> {
+        parse_date(text.trim())
+    }
+
+    /// The same day and month in `year`. February 29 becomes February 28 in
+    /// years that aren't leap years.
+    pub fn with_year(self, year: u16) -> Date {
+        Date { year, month: self.month, day: self.day.min(days_in_month(year, self.month)) }
+    }
+
+    /// The date `months` months later, or earlier for a negative count. Days
+    /// past the end of the new month become its last day.
+    pub fn add_months(self, months: i64) -> Date {
+        let index = self.year as i64 * 12 + self.month as i64 - 1 + months;
+        let (year, month) = (index.div_euclid(12) as u16, (index.rem_euclid(12) + 1) as u8);
+        Date { year, month, day: self.day.min(days_in_month(year, month)) }
+    }
+
+    /// The date `days` days later, or earlier for a negative count.
+    pub fn add_days(self, days: i64) -> Date {
+        // Converting from days since 1970-01-01, after Howard Hinnant's
+        // civil_from_days.
+        let days = days_from_epoch(self) + days + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days.rem_euclid(146_097);
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524
+                           - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month_index = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+        let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+        let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+        Date { year: year as u16, month: month as u8, day: day as u8 }
+    }
+
+    /// How many days after `other` this date is; negative if it is before.
+    pub fn days_since(self, other: Date) -> i64 {
+        days_from_epoch(self) - days_from_epoch(other)
+    }
+}
+
+/// The number of days from 1970-01-01 to `date`, after Howard Hinnant's
+/// days_from_civil.
+fn days_from_epoch(date: Date) -> i64 {
+    let month = date.month as i64;
+    let year = date.year as i64 - if month <= 2 { 1 } else { 0 };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5
+        + date.day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// The English abbreviation of the day of the week `date` falls on.
+pub fn day_name(date: Date) -> &'static str {
+    // 1970-01-01 was a Thursday.
+    let day = (days_from_epoch(date) + 4).rem_euclid(7);
+    ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"][day as usize]
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+impl fmt::Display for Time {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:02}:{:02}", self.hour, self.minute)
+    }
+}
+
+impl fmt::Display for TimeUnit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            TimeUnit::Hour => "h",
+            TimeUnit::Day => "d",
+            TimeUnit::Week => "w",
+            TimeUnit::Month => "m",
+            TimeUnit::Year => "y",
+        })
+    }
+}
+
+impl fmt::Display for Repeater {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mark = match self.kind {
+            RepeaterKind::Cumulative => "+",
+            RepeaterKind::CatchUp => "++",
+            RepeaterKind::Restart => ".+",
+        };
+        write!(f, "{}{}{}", mark, self.value, self.unit)
+    }
+}
+
+impl fmt::Display for Delay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mark = if self.first_only { "--" } else { "-" };
+        write!(f, "{}{}{}", mark, self.value, self.unit)
+    }
+}
+
+/// Formats the timestamp the way org writes it, with English day names.
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.format(TimestampStyle::org()))
+    }
+}
+
+/// How `Timestamp::format` writes timestamps, for text read by people or by
+/// other tools rather than by org.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TimestampStyle {
+    iso: bool,
+    twelve_hour: bool,
+    day_names: bool,
+}
+
+impl Default for TimestampStyle {
+    fn default() -> Self {
+        TimestampStyle::org()
+    }
+}
+
+impl TimestampStyle {
+    /// Org's own format, `<2024-05-01 Wed 14:30 +1w>`, as `Display` writes
+    /// it.
+    pub fn org() -> Self {
+        TimestampStyle { iso: false, twelve_hour: false, day_names: true }
+    }
+
+    /// ISO 8601, `2024-05-01T14:30`, with ranges written as intervals like
+    /// `2024-05-01T10:00/2024-05-01T12:00`. Brackets, day names, repeaters
+    /// and delays are left out, and times are always 24-hour.
+    pub fn iso() -> Self {
+        TimestampStyle { iso: true, twelve_hour: false, day_names: false }
+    }
+
+    /// Write times like `2:30pm` instead of `14:30`.
+    pub fn twelve_hour(mut self, twelve_hour: bool) -> Self {
+        self.twelve_hour = twelve_hour;
+        self
+    }
+
+    /// Whether to write the day of the week after the date.
+    pub fn day_names(mut self, day_names: bool) -> Self {
+        self.day_names = day_names;
+        self
+    }
+
+    fn time(&self, time: Time) -> String {
+        if !self.twelve_hour {
+            return time.to_string();
+        }
+        let hour = match time.hour % 12 {
+            0 => 12,
+            hour => hour,
+        };
+        let suffix = if time.hour < 12 { "am" } else { "pm" };
+        format!("{}:{:02}{}", hour, time.minute, suffix)
+    }
+
+    fn date(&self, date: Date) -> String {
+        if self.day_names {
+            format!("{} {}", date, day_name(date))
+        } else {
+            date.to_string()
+        }
+    }
+}
+
+impl Timestamp {
+    /// The timestamp written in `style`.
+    pub fn format(&self, style: TimestampStyle) -> String {
+        if style.iso {
+            let start = self.iso_start();
+            return match self.end {
+                Some((date, time)) => format!("{}/{}", start, iso(date, time)),
+                None => start,
+            };
+        }
+        let (open, close) = if self.active { ('<', '>') } else { ('[', ']') };
+        let mut out = format!("{}{}", open, style.date(self.date));
+        if let Some(time) = self.time {
+            out.push(' ');
+            out.push_str(&style.time(time));
+            if let Some((date, Some(end))) = self.end {
+                if date == self.date {
+                    out.push('-');
+                    out.push_str(&style.time(end));
+                }
+            }
+        }
+        if let Some(repeater) = self.repeater {
+            out.push_str(&format!(" {}", repeater));
+        }
+        if let Some(delay) = self.delay {
+            out.push_str(&format!(" {}", delay));
+        }
+        out.push(close);
+        match self.end {
+            Some((date, end_time)) if date != self.date || self.time.is_none() => {
+                out.push_str(&format!("--{}{}", open, style.date(date)));
+                if let Some(time) = end_time {
+                    out.push(' ');
+                    out.push_str(&style.time(time));
+                }
+                out.push(close);
+            }
+            _ => {}
+        }
+        out
+    }
+
+    /// The start of the timestamp in ISO 8601, `2024-05-01` or
+    /// `2024-05-01T14:30`, as HTML wants it for the `datetime` of a
+    /// `<time>`.
+    pub fn iso_start(&self) -> String {
+        iso(self.date, self.time)
+    }
+}
+
+fn iso(date: Date, time: Option<Time>) -> String {
+    match time {
+        Some(time) => format!("{}T{}", date, time),
+        None => date.to_string(),
+    }
+}
+
+/// Find every timestamp in `text`, in order.
+pub fn find_timestamps(text: &str) -> Vec<Timestamp> {
+    let mut timestamps = Vec::new();
+    let mut offset = 0;
+    while let Some(open) = text[offset..].find(&['<', '['][..]) {
+        let start = offset + open;
+        match parse_prefix(&text[start..]) {
+            Some((timestamp, len)) => {
+                timestamps.push(timestamp);
+                offset = start + len;
+            }
+            None => offset = start + 1,
+        }
+    }
+    timestamps
+}
+
+/// Parse a timestamp at the very start of `text`, returning it along with the
+/// number of bytes it spans.
+pub fn parse_prefix(text: &str) -> Option<(Timestamp, usize)> {
+    let (mut timestamp, mut len, end_time) = parse_single(text)?;
+    if let Some(end_time) = end_time {
+        timestamp.end = Some((timestamp.date, Some(end_time)));
+    } else if text[len..].starts_with("--") {
+        if let Some((end, end_len, None)) = parse_single(&text[len + 2..]) {
+            if end.active == timestamp.active {
+                timestamp.end = Some((end.date, end.time));
+                len += 2 + end_len;
+            }
+        }
+    }
+    Some((timestamp, len))
+}
+
+/// Parse one bracketed timestamp, also returning its length and the end of
+/// a time range written inside it.
+fn parse_single(text: &str) -> Option<(Timestamp, usize, Option<Time>)> {
+    let (active, close) = match text.chars().next()? {
+        '<' => (true, '>'),
+        '[' => (false, ']'),
+        _ => return None,
+    };
+    let close_at = text.find(close)?;
+    let inner = &text[1..close_at];
+    if inner.contains('\n') {
+        return None;
+    }
+
+    let mut words = inner.split_whitespace();
+    let date = parse_date(words.next()?)?;
+    let mut timestamp = Timestamp {
+        active,
+        date,
+        time: None,
+        end: None,
+        repeater: None,
+        delay: None,
+    };
+    let mut end_time = None;
+    let mut seen_day_name = false;
+    for word in words {
+        if !seen_day_name && timestamp.time.is_none()
+            && word.chars().next().is_some_and(char::is_alphabetic) {
+            seen_day_name = true;
+        } else if timestamp.time.is_none() && word.contains(':') {
+            let mut times = word.splitn(2, '-');
+            timestamp.time = Some(parse_time(times.next()?)?);
+            if let Some(end) = times.next() {
+                end_time = Some(parse_time(end)?);
+            }
+        } else if let Some(repeater) = parse_repeater(word) {
+            timestamp.repeater = Some(repeater);
+        } else if let Some(delay) = parse_delay(word) {
+            timestamp.delay = Some(delay);
+        } else {
+            return None;
+        }
+    }
+    Some((timestamp, close_at + 1, end_time))
+}
+
+fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        _ if year.is_multiple_of(4)
+            && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => 29,
+        _ => 28,
+    }
+}
+
+fn parse_date(text: &str) -> Option<Date> {
+    let mut parts = text.split('-');
+    let (year, month, day) = (parts.next()?, parts.next()?, parts.next()?);
+    if parts.next().is_some() || year.len() != 4 || month.len() != 2 || day.len() != 2
+        || !text.bytes().all(|b| b == b'-' || b.is_ascii_digit()) {
+        return None;
+    }
+    let date = Date {
+        year: year.parse().ok()?,
+        month: month.parse().ok()?,
+        day: day.parse().ok()?,
+    };
+    if date.month < 1 || date.month > 12
+        || date.day < 1 || date.day > days_in_month(date.year, date.month) {
+        return None;
+    }
+    Some(date)
+}
+
+fn parse_time(text: &str) -> Option<Time> {
+    let colon = text.find(':')?;
+    let (hour, minute) = (&text[..colon], &text[colon + 1..]);
+    if hour.is_empty() || hour.len() > 2 || minute.len() != 2
+        || !hour.bytes().chain(minute.bytes()).all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let time = Time {
+        hour: hour.parse().ok()?,
+        minute: minute.parse().ok()?,
+    };
+    if time.hour > 24 || time.minute > 59 {
+        return None;
+    }
+    Some(time)
+}
+
+/// Parse the `3d` in a repeater or delay.
+fn parse_interval(text: &str) -> Option<(u32, TimeUnit)> {
+    let unit = match text.chars().last()? {
+        'h' => TimeUnit::Hour,
+        'd' => TimeUnit::Day,
+        'w' => TimeUnit::Week,
+        'm' => TimeUnit::Month,
+        'y' => TimeUnit::Year,
+        _ => return None,
+    };
+    let value = &text[..text.len() - 1];
+    if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    Some((value.parse().ok()?, unit))
+}
+
+fn parse_repeater(text: &str) -> Option<Repeater> {
+    let (kind, rest) = if let Some(rest) = text.strip_prefix("++") {
+        (RepeaterKind::CatchUp, rest)
+    } else if let Some(rest) = text.strip_prefix(".+") {
+        (RepeaterKind::Restart, rest)
+    } else {
+        (RepeaterKind::Cumulative, text.strip_prefix('+')?)
+    };
+    let (value, unit) = parse_interval(rest)?;
+    Some(Repeater { kind, value, unit })
+}
+
+fn parse_delay(text: &str) -> Option<Delay> {
+    let (first_only, rest) = match text.strip_prefix("--") {
+        Some(rest) => (true, rest),
+        None => (false, text.strip_prefix('-')?),
+    };
+    let (value, unit) = parse_interval(rest)?;
+    Some(Delay { first_only, value, unit })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_timestamp() {
+        let ts = Timestamp::parse("<2024-05-01 Wed>").unwrap();
+        assert!(ts.is_active());
+        assert_eq!(ts.date(), Date { year: 2024, month: 5, day: 1 });
+        assert_eq!(ts.time(), None);
+        assert!(!ts.is_range());
+
+        let ts = Timestamp::parse("[2024-02-29 Do 9:05-10:30 .+2d --1w]").unwrap();
+        assert!(!ts.is_active());
+        assert_eq!(ts.time(), Some(Time { hour: 9, minute: 5 }));
+        assert_eq!(ts.end_date(), Some(ts.date()));
+        assert_eq!(ts.end_time(), Some(Time { hour: 10, minute: 30 }));
+        assert_eq!(ts.repeater(), Some(Repeater {
+            kind: RepeaterKind::Restart,
+            value: 2,
+            unit: TimeUnit::Day,
+        }));
+        assert_eq!(ts.delay(), Some(Delay {
+            first_only: true,
+            value: 1,
+            unit: TimeUnit::Week,
+        }));
+
+        let ts = Timestamp::parse("<2024-05-01 Wed 10:00>--<2024-05-03 Fri>").unwrap();
+        assert_eq!(ts.end_date(), Some(Date { year: 2024, month: 5, day: 3 }));
+        assert_eq!(ts.end_time(), None);
+
+        assert!(Timestamp::parse("<2023-02-29 Wed>").is_none());
+        assert!(Timestamp::parse("<2024-05-01 Wed>--[2024-05-02 Thu]").is_none());
+        assert!(Timestamp::parse("[fn:1]").is_none());
+        assert!(Timestamp::parse("<2024-05-01 Wed +1x>").is_none());
+        assert!(Timestamp::parse("<2024-+5-01 Wed>").is_none());
+        assert!(Timestamp::parse("<2024-05-01 Wed +1:00>").is_none());
+    }
+
+    #[test]
+    fn test_display_timestamp() {
+        for text in &["<2024-05-01 Wed>", "[2024-02-29 Thu 09:05-10:30 .+2d --1w]",
+                      "<2024-05-01 Wed 10:00>--<2024-05-03 Fri>", "<2000-01-01 Sat ++1y>"] {
+            assert_eq!(Timestamp::parse(text).unwrap().to_string(), *text);
+        }
+        let year_zero = Timestamp::parse("<0000-01-05 +1d>").unwrap();
+        assert_eq!(year_zero.to_string(), "<0000-01-05 Wed +1d>");
+        let localized = Timestamp::parse("<2024-05-06 Mo 9:00>").unwrap();
+        assert_eq!(localized.to_string(), "<2024-05-06 Mon 09:00>");
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        let range = Timestamp::parse("[2024-02-29 Thu 09:05-13:30 +1w]").unwrap();
+        let days = Timestamp::parse("<2024-05-01 Wed 00:15>--<2024-05-03 Fri>").unwrap();
+        let style = TimestampStyle::org().twelve_hour(true).day_names(false);
+        assert_eq!(range.format(style), "[2024-02-29 9:05am-1:30pm +1w]");
+        assert_eq!(days.format(style), "<2024-05-01 12:15am>--<2024-05-03>");
+        assert_eq!(range.format(TimestampStyle::iso()), "2024-02-29T09:05/2024-02-29T13:30");
+        assert_eq!(days.format(TimestampStyle::iso().twelve_hour(true)),
+                   "2024-05-01T00:15/2024-05-03");
+        assert_eq!(days.iso_start(), "2024-05-01T00:15");
+    }
+
+    #[test]
+    fn test_find_timestamps() {
+        let found = find_timestamps("Met [2024-01-02 Tue] and <not one> then
+<2024-01-05 Fri ++1m>");
+        assert_eq!(found.len(), 2);
+        assert!(!found[0].is_active());
+        assert_eq!(found[1].repeater().unwrap().kind, RepeaterKind::CatchUp);
+    }
+
+    #[test]
+    fn test_repeat_timestamp() {
+        let today = Date { year: 2024, month: 5, day: 20 };
+        let repeat = |text: &str| {
+            Timestamp::parse(text).unwrap().repeat(today).map(|timestamp| timestamp.to_string())
+        };
+        assert_eq!(repeat("<2024-05-01 Wed +1w>").unwrap(), "<2024-05-08 Wed +1w>");
+        assert_eq!(repeat("<2024-05-01 Wed ++1w>").unwrap(), "<2024-05-22 Wed ++1w>");
+        assert_eq!(repeat("<2024-05-01 Wed 9:00 .+2d>").unwrap(), "<2024-05-22 Wed 09:00 .+2d>");
+        assert_eq!(repeat("<2024-01-31 Wed +1m -3d>").unwrap(), "<2024-02-29 Thu +1m -3d>");
+        assert_eq!(repeat("<2024-05-01 Wed 22:00 +3h>").unwrap(), "<2024-05-02 Thu 01:00 +3h>");
+        assert_eq!(repeat("<2024-05-01 Wed>"), None);
+    }
+
+    #[test]
+    fn test_date_arithmetic() {
+        let date = Date { year: 2024, month: 2, day: 27 };
+        assert_eq!(date.add_days(3), Date { year: 2024, month: 3, day: 1 });
+        assert_eq!(date.add_days(-58), Date { year: 2023, month: 12, day: 31 });
+        assert_eq!(date.add_days(365).days_since(date), 365);
+        assert_eq!(Date { year: 1970, month: 1, day: 1 }.days_since(date), -19_780);
+        let leap_day = Date::parse(" 2024-02-29").unwrap();
+        assert_eq!(leap_day.with_year(2025), Date { year: 2025, month: 2, day: 28 });
+        let end_of_month = Date { year: 2024, month: 1, day: 31 };
+        assert_eq!(end_of_month.add_months(1), Date { year: 2024, month: 2, day: 29 });
+        assert_eq!(end_of_month.add_months(-13), Date { year: 2022, month: 12, day: 31 });
+        assert!(Date::parse("2023-02-29").is_none());
+        let timestamp = Timestamp::new(false, date, Some(Time { hour: 9, minute: 5 }));
+        assert_eq!(timestamp.to_string(), "[2024-02-27 Tue 09:05]");
+    }
+}
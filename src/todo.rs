@@ -0,0 +1,506 @@
+use std::error::Error;
+use std::fmt;
+
+use planning::{self, Planning};
+use {Date, Document, Headline, Now, Section, Time, Timestamp};
+
+/// What `Headline::set_todo_state` writes down about a change, like org's
+/// `org-log-done` and the `!` in `#+TODO: TODO(t!) | DONE(d!)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TodoLogging {
+    /// Only change the keyword.
+    Off,
+    /// Add a `CLOSED:` timestamp when a task is done, and a `LAST_REPEAT`
+    /// property and state note when a repeated task comes round again.
+    Time,
+    /// Like `Time`, and also add a state note for every change.
+    States,
+}
+
+/// How `Headline::set_todo_state` changes a headline: when, which keywords
+/// are done states, and what it logs where.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TodoConfig {
+    date: Date,
+    time: Time,
+    done_keywords: Vec<String>,
+    logging: TodoLogging,
+    drawer: Option<String>,
+    /// The keywords with logging specs, with what they log on entering and
+    /// on leaving their state: `@` for a note, `!` for a timestamp.
+    specs: Vec<(String, Option<char>, Option<char>)>,
+}
+
+impl TodoConfig {
+    /// Changes made on `date` at `time`, with `DONE` as the only done state,
+    /// logging with `TodoLogging::Time` into a `LOGBOOK` drawer.
+    pub fn new(date: Date, time: Time) -> TodoConfig {
+        TodoConfig {
+            date,
+            time,
+            done_keywords: vec!["DONE".into()],
+            logging: TodoLogging::Time,
+            drawer: Some("LOGBOOK".into()),
+            specs: Vec::new(),
+        }
+    }
+
+    /// Changes made at the time `now` gives, see `new`.
+    pub fn at(now: &dyn Now) -> TodoConfig {
+        let (date, time) = now.now();
+        TodoConfig::new(date, time)
+    }
+
+    /// The keywords for done states, replacing `DONE`, usually those of
+    /// `Document::done_keywords`.
+    pub fn done_keywords<S: Into<String>>(mut self, keywords: Vec<S>) -> Self {
+        self.done_keywords = keywords.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn logging(mut self, logging: TodoLogging) -> Self {
+        self.logging = logging;
+        self
+    }
+
+    /// The drawer state notes go in, like `org-log-into-drawer`, or `None` to
+    /// write them straight into the section after the planning line and
+    /// properties.
+    pub fn drawer(mut self, drawer: Option<&str>) -> Self {
+        self.drawer = drawer.map(String::from);
+        self
+    }
+
+    /// Log changes to and from the keywords as their logging specs say, for
+    /// keywords written like in `#+TODO:` lines, such as `WAIT(w@/!)`: `@`
+    /// asks for a note and `!` notes the time on entering the state, and
+    /// after a `/` on leaving it. Keywords without a spec are skipped.
+    pub fn keyword_logging<S: AsRef<str>>(mut self, keywords: &[S]) -> Self {
+        for word in keywords {
+            let word = word.as_ref();
+            let (keyword, spec) = match word.find('(') {
+                Some(paren) if word.ends_with(')') => {
+                    (&word[..paren], &word[paren + 1..word.len() - 1])
+                }
+                _ => continue,
+            };
+            let (enter, leave) = match spec.find('/') {
+                Some(slash) => (&spec[..slash], &spec[slash + 1..]),
+                None => (spec, ""),
+            };
+            let flag = |part: &str| part.chars().find(|&c| c == '@' || c == '!');
+            self.specs.retain(|(k, _, _)| k != keyword);
+            self.specs.push((keyword.into(), flag(enter), flag(leave)));
+        }
+        self
+    }
+
+    /// What the spec of `keyword` logs on entering its state, or with
+    /// `leaving` on leaving it.
+    fn spec(&self, keyword: Option<&str>, leaving: bool) -> Option<char> {
+        let keyword = keyword?;
+        self.specs.iter()
+            .find(|(k, _, _)| k == keyword)
+            .and_then(|&(_, enter, leave)| if leaving { leave } else { enter })
+    }
+
+    fn is_done(&self, keyword: Option<&str>) -> bool {
+        keyword.is_some_and(|keyword| self.done_keywords.iter().any(|done| done == keyword))
+    }
+
+    fn timestamp(&self) -> Timestamp {
+        Timestamp::new(false, self.date, Some(self.time))
+    }
+}
+
+impl Headline {
+    /// Change the TODO keyword to `state`, logging the change as `config`
+    /// says, like `org-todo`.
+    ///
+    /// Finishing a task adds a `CLOSED:` timestamp and reopening it removes
+    /// it. A task with a repeater on its `SCHEDULED:` or `DEADLINE:`
+    /// timestamp, like a habit, isn't finished: the timestamps move to their
+    /// next occurrence and the keyword stays as it was. Notes are written
+    /// like org writes them, newest first:
+    ///
+    /// ```ignore
+    /// - State "WAIT"       from "TODO"       [2024-05-08 Wed 10:30] \\
+    ///   Waiting for Bob to reply.
+    /// ```
+    ///
+    /// `note` is added below the state note, which is then written even if
+    /// `config` doesn't log states. Fails, changing nothing, without a
+    /// `note` if the logging spec of the new or old keyword asks for one,
+    /// see `TodoConfig::keyword_logging`.
+    pub fn set_todo_state(&mut self, state: Option<&str>, note: Option<&str>,
+                          config: &TodoConfig) -> Result<(), TodoError> {
+        let old = self.keyword().map(String::from);
+        if old.as_deref() == state {
+            return Ok(());
+        }
+        let entering = config.spec(state, false);
+        let leaving = config.spec(old.as_deref(), true);
+        let wanted = if entering == Some('@') { state } else { old.as_deref() };
+        if note.is_none() && (entering == Some('@') || leaving == Some('@')) {
+            return Err(TodoError::NoteRequired(wanted.unwrap_or_default().into()));
+        }
+        let mut planning = self.planning.clone();
+        let done = config.is_done(state);
+        let scheduled = planning.scheduled().and_then(|t| t.repeat(config.date));
+        let deadline = planning.deadline().and_then(|t| t.repeat(config.date));
+        let repeated = done && (scheduled.is_some() || deadline.is_some());
+        let logged = config.logging != TodoLogging::Off;
+        if repeated {
+            if scheduled.is_some() {
+                planning.set_scheduled(scheduled);
+            }
+            if deadline.is_some() {
+                planning.set_deadline(deadline);
+            }
+        } else {
+            self.set_keyword(state);
+            if !done {
+                planning.set_closed(None);
+            } else if logged && !config.is_done(old.as_deref()) {
+                planning.set_closed(Some(config.timestamp()));
+            }
+        }
+
+        let noted = config.logging == TodoLogging::States || (logged && repeated)
+            || entering.is_some() || leaving.is_some() || note.is_some();
+        let entry = if noted {
+            let quote = |keyword: Option<&str>| {
+                keyword.map_or(String::new(), |keyword| format!("\"{}\"", keyword))
+            };
+            let mut entry = format!("- State {:<12} from {:<12} {}", quote(state),
+                                    quote(old.as_deref()), config.timestamp());
+            if let Some(note) = note {
+                entry.push_str(&format_note(note));
+            }
+            Some(entry)
+        } else {
+            None
+        };
+        self.write_log(&planning, entry, config);
+        if logged && repeated {
+            self.set_property("LAST_REPEAT", &config.timestamp().to_string());
+        }
+        Ok(())
+    }
+
+    /// Rewrite the planning line in the section text, and add `note` to the
+    /// configured drawer.
+    fn write_log(&mut self, planning: &Planning, note: Option<String>, config: &TodoConfig) {
+        let (old, offset) = match self.section.take() {
+            Some(section) => (section.text, section.span.start),
+            None => (String::new(), self.body_start()),
+        };
+        let mut lines: Vec<String> = old.lines().map(String::from).collect();
+        let line = if planning.is_empty() {
+            None
+        } else {
+            let slots = [("CLOSED", planning.closed()), ("DEADLINE", planning.deadline()),
+                         ("SCHEDULED", planning.scheduled())];
+            let parts: Vec<_> = slots.iter()
+                .filter_map(|&(name, timestamp)| Some(format!("{}: {}", name, timestamp?)))
+                .collect();
+            Some(parts.join(" "))
+        };
+        let had_planning = lines.first()
+            .is_some_and(|line| planning::parse_planning(line, offset).is_some());
+        match (had_planning, line) {
+            (true, Some(line)) => {
+                let indent = lines[0].len() - lines[0].trim_start().len();
+                lines[0] = format!("{}{}", &lines[0][..indent], line);
+            }
+            (true, None) => {
+                lines.remove(0);
+            }
+            (false, Some(line)) => lines.insert(0, line),
+            (false, None) => {}
+        }
+
+        if let Some(note) = note {
+            insert_log_line(&mut lines, note, config.drawer.as_deref());
+        }
+
+        let mut text = lines.join("\n");
+        if !text.is_empty() && (old.is_empty() || old.ends_with('\n')) {
+            text.push('\n');
+        }
+        self.planning = lines.first()
+            .and_then(|line| planning::parse_planning(line, offset))
+            .unwrap_or_default();
+        self.section = Section::from_text(&self.parser, &text, offset);
+    }
+}
+
+/// Why `Headline::set_todo_state` refused to change a keyword.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TodoError {
+    /// The logging spec of the keyword, like the `@` of `WAIT(w@)`, asks
+    /// for a note and none was given.
+    NoteRequired(String),
+}
+
+impl fmt::Display for TodoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TodoError::NoteRequired(ref keyword) => {
+                write!(f, "changing the state to or from {} needs a note", keyword)
+            }
+        }
+    }
+}
+
+impl Error for TodoError {}
+
+/// The lines of `note` as org writes them below a state note: after a `\\`
+/// ending the state note's line, each indented by two spaces, without the
+/// blank lines at either end.
+pub fn format_note(note: &str) -> String {
+    let lines: Vec<&str> = note.lines().map(str::trim_end).collect();
+    let start = lines.iter().position(|line| !line.is_empty()).unwrap_or(lines.len());
+    let end = lines.iter().rposition(|line| !line.is_empty()).map_or(start, |end| end + 1);
+    let mut text = String::new();
+    if start < end {
+        text.push_str(" \\\\");
+    }
+    for line in &lines[start..end] {
+        text.push('\n');
+        if !line.is_empty() {
+            text.push_str("  ");
+            text.push_str(line);
+        }
+    }
+    text
+}
+
+/// The drawer a `LOG_INTO_DRAWER` value names: none for `nil`, `LOGBOOK`
+/// for `t`, and otherwise the one it names.
+fn drawer_setting(value: &str) -> Option<String> {
+    match value.trim() {
+        "nil" => None,
+        "t" | "" => Some("LOGBOOK".into()),
+        name => Some(name.into()),
+    }
+}
+
+impl Document {
+    /// The drawer notes about the headline at `path` go in, as org decides
+    /// it: from the `LOG_INTO_DRAWER` property the headline has or inherits,
+    /// or else from `logdrawer` or `nologdrawer` in `#+STARTUP:`, or else
+    /// `default`.
+    pub fn log_drawer_at(&self, path: &[usize], default: Option<&str>) -> Option<String> {
+        if let Some(value) = self.inherited_property(path, "LOG_INTO_DRAWER") {
+            return drawer_setting(&value);
+        }
+        let startup = self.keywords().remove("STARTUP").unwrap_or_default();
+        let option = startup.split_whitespace()
+            .rfind(|&option| option == "logdrawer" || option == "nologdrawer");
+        match option {
+            Some("logdrawer") => Some("LOGBOOK".into()),
+            Some(_) => None,
+            None => default.map(String::from),
+        }
+    }
+
+    /// A `TodoConfig` for changes made on `date` at `time` to this document,
+    /// with its done keywords and the logging specs of its `#+TODO:` lines.
+    pub fn todo_config(&self, date: Date, time: Time) -> TodoConfig {
+        let mut keywords = self.keywords();
+        let words: Vec<String> = ["TODO", "SEQ_TODO", "TYP_TODO"].iter()
+            .filter_map(|key| keywords.remove(*key))
+            .flat_map(|value| value.split_whitespace().map(String::from).collect::<Vec<_>>())
+            .collect();
+        TodoConfig::new(date, time)
+            .done_keywords(self.done_keywords().to_vec())
+            .keyword_logging(&words)
+    }
+
+    /// Change the TODO keyword of the headline at `path` with
+    /// `Headline::set_todo_state`, writing notes into the drawer
+    /// `log_drawer_at` gives, with the drawer of `config` as its default.
+    /// Returns false if there is no headline at `path`.
+    pub fn set_todo_state_at(&mut self, path: &[usize], state: Option<&str>, note: Option<&str>,
+                             config: &TodoConfig) -> Result<bool, TodoError> {
+        let drawer = self.log_drawer_at(path, config.drawer.as_deref());
+        let config = config.clone().drawer(drawer.as_deref());
+        match self.headline_at_mut(path) {
+            Some(headline) => headline.set_todo_state(state, note, &config).map(|()| true),
+            None => Ok(false),
+        }
+    }
+}
+
+/// Add `line` to the top of the drawer `drawer` in the lines of a section,
+/// after its planning line and properties, making the drawer if there is
+/// none, or straight into the section for `None`.
+pub fn insert_log_line(lines: &mut Vec<String>, line: String, drawer: Option<&str>) {
+    let mut at = match lines.first() {
+        Some(first) if planning::parse_planning(first, 0).is_some() => 1,
+        _ => 0,
+    };
+    if lines.get(at).is_some_and(|line| line.trim() == ":PROPERTIES:") {
+        if let Some(end) = lines[at..].iter().position(|line| {
+            line.trim().eq_ignore_ascii_case(":END:")
+        }) {
+            at += end + 1;
+        }
+    }
+    match drawer {
+        Some(name) => {
+            let open = format!(":{}:", name);
+            if lines.get(at).is_some_and(|line| line.trim().eq_ignore_ascii_case(&open)) {
+                lines.insert(at + 1, line);
+            } else {
+                lines.splice(at..at, vec![open, line, ":END:".into()]);
+            }
+        }
+        None => lines.insert(at, line),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use DocumentParser;
+
+    #[test]
+    fn test_set_todo_state() {
+        let mut doc = DocumentParser::new().parse("#+TODO: TODO | DONE
+* TODO Write report
+SCHEDULED: <2024-05-06 Mon>
+* TODO Water plants
+SCHEDULED: <2024-05-01 Wed .+2d>
+:PROPERTIES:
+:STYLE: habit
+:END:
+").unwrap();
+        let date = Date { year: 2024, month: 5, day: 8 };
+        let config = TodoConfig::new(date, Time { hour: 10, minute: 30 })
+            .done_keywords(doc.done_keywords().to_vec())
+            .logging(TodoLogging::States);
+
+        let report = &mut doc.headlines_mut()[0];
+        report.set_todo_state(Some("DONE"), None, &config).unwrap();
+        assert_eq!(report.closed().map(|t| t.date()), Some(date));
+        assert_eq!(report.to_org_string(), "* DONE Write report
+CLOSED: [2024-05-08 Wed 10:30] SCHEDULED: <2024-05-06 Mon>
+:LOGBOOK:
+- State \"DONE\"       from \"TODO\"       [2024-05-08 Wed 10:30]
+:END:
+");
+        report.set_todo_state(Some("TODO"), None, &config.clone().logging(TodoLogging::Time))
+            .unwrap();
+        assert!(report.closed().is_none());
+        report.set_todo_state(None, None, &config.clone().drawer(None)).unwrap();
+        assert_eq!(report.to_org_string(), "* Write report
+SCHEDULED: <2024-05-06 Mon>
+- State              from \"TODO\"       [2024-05-08 Wed 10:30]
+:LOGBOOK:
+- State \"DONE\"       from \"TODO\"       [2024-05-08 Wed 10:30]
+:END:
+");
+
+        let habit = &mut doc.headlines_mut()[1];
+        habit.set_todo_state(Some("DONE"), None, &config).unwrap();
+        assert_eq!(habit.to_org_string(), "* TODO Water plants
+SCHEDULED: <2024-05-10 Fri .+2d>
+:PROPERTIES:
+:STYLE: habit
+:LAST_REPEAT: [2024-05-08 Wed 10:30]
+:END:
+:LOGBOOK:
+- State \"DONE\"       from \"TODO\"       [2024-05-08 Wed 10:30]
+:END:
+");
+    }
+
+    #[test]
+    fn test_log_into_drawer() {
+        let mut doc = DocumentParser::new().parse("#+TODO: TODO | DONE
+#+STARTUP: overview nologdrawer
+* Notes
+:PROPERTIES:
+:LOG_INTO_DRAWER: HISTORY
+:END:
+** TODO Call
+* TODO Shop
+* Projects
+:PROPERTIES:
+:LOG_INTO_DRAWER: t
+:END:
+** TODO Plan
+").unwrap();
+        assert_eq!(doc.log_drawer_at(&[0, 0], None).as_deref(), Some("HISTORY"));
+        assert_eq!(doc.log_drawer_at(&[1], Some("LOGBOOK")), None);
+        assert_eq!(doc.log_drawer_at(&[2, 0], None).as_deref(), Some("LOGBOOK"));
+
+        let date = Date { year: 2024, month: 5, day: 8 };
+        let config = TodoConfig::new(date, Time { hour: 10, minute: 30 })
+            .logging(TodoLogging::States);
+        assert_eq!(doc.set_todo_state_at(&[0, 0], Some("DONE"), None, &config), Ok(true));
+        assert_eq!(doc.set_todo_state_at(&[1], Some("DONE"), None, &config), Ok(true));
+        assert_eq!(doc.set_todo_state_at(&[5], Some("DONE"), None, &config), Ok(false));
+        let note = "- State \"DONE\"       from \"TODO\"       [2024-05-08 Wed 10:30]";
+        assert_eq!(doc.headlines()[0].children()[0].to_org_string(), format!("** DONE Call
+CLOSED: [2024-05-08 Wed 10:30]
+:HISTORY:
+{}
+:END:
+", note));
+        assert_eq!(doc.headlines()[1].to_org_string(), format!("* DONE Shop
+CLOSED: [2024-05-08 Wed 10:30]
+{}
+", note));
+
+        let now = (date, Time { hour: 11, minute: 0 });
+        assert!(doc.clock_in_at(&[0, 0], &now));
+        assert!(doc.clock_in_at(&[1], &now));
+        assert_eq!(doc.clock_drawer_at(&[2, 0]).as_deref(), Some("LOGBOOK"));
+        let sections: Vec<_> = [&doc.headlines()[0].children()[0], &doc.headlines()[1]].iter()
+            .map(|headline| headline.section().unwrap().text().to_string())
+            .collect();
+        assert_eq!(sections[0], format!("CLOSED: [2024-05-08 Wed 10:30]
+:HISTORY:
+CLOCK: [2024-05-08 Wed 11:00]
+{}
+:END:
+", note));
+        assert_eq!(sections[1], format!("CLOSED: [2024-05-08 Wed 10:30]
+:LOGBOOK:
+CLOCK: [2024-05-08 Wed 11:00]
+:END:
+{}
+", note));
+    }
+
+    #[test]
+    fn test_state_notes() {
+        let mut doc = DocumentParser::new().parse("#+TODO: TODO(t) WAIT(w@/!) | DONE(d!)
+* TODO Reply
+").unwrap();
+        let date = Date { year: 2024, month: 5, day: 8 };
+        let config = doc.todo_config(date, Time { hour: 10, minute: 30 })
+            .logging(TodoLogging::Off);
+        let reply = &mut doc.headlines_mut()[0];
+        assert_eq!(reply.set_todo_state(Some("WAIT"), None, &config),
+                   Err(TodoError::NoteRequired("WAIT".into())));
+        assert_eq!(reply.keyword(), Some("TODO"));
+        reply.set_todo_state(Some("WAIT"), Some("\nWaiting for Bob.\n\nHe is away.\n"), &config)
+            .unwrap();
+        reply.set_todo_state(Some("DONE"), None, &config).unwrap();
+        assert_eq!(reply.to_org_string(), "* DONE Reply
+:LOGBOOK:
+- State \"DONE\"       from \"WAIT\"       [2024-05-08 Wed 10:30]
+- State \"WAIT\"       from \"TODO\"       [2024-05-08 Wed 10:30] \\\\
+  Waiting for Bob.
+
+  He is away.
+:END:
+");
+        assert_eq!(TodoError::NoteRequired("WAIT".into()).to_string(),
+                   "changing the state to or from WAIT needs a note");
+    }
+}
@@ -0,0 +1,29 @@
+//! Instrumentation through the `tracing` crate, with the `tracing` feature.
+//! Without it the macros expand to nothing and their arguments are never
+//! evaluated, so counting nodes for an event costs nothing.
+
+/// Enter a debug span until the returned guard is dropped.
+#[cfg(feature = "tracing")]
+macro_rules! trace_span {
+    ($($args:tt)*) => { ::tracing::debug_span!($($args)*).entered() };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! trace_span {
+    ($($args:tt)*) => { ::trace::Entered };
+}
+
+/// Record a debug event in the current span.
+#[cfg(feature = "tracing")]
+macro_rules! trace_event {
+    ($($args:tt)*) => { ::tracing::debug!($($args)*) };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! trace_event {
+    ($($args:tt)*) => {};
+}
+
+/// Stands in for a span guard without the `tracing` feature.
+#[cfg(not(feature = "tracing"))]
+pub struct Entered;
@@ -0,0 +1,188 @@
+use {Content, Document, Headline, ListItem, Node, Object};
+
+/// Callbacks for `Document::walk`, which visits the tree depth-first in
+/// document order. Every method does nothing by default, so implementations
+/// only override the nodes they care about.
+///
+/// Objects are parsed from headline titles and paragraphs as they are
+/// visited, so the references only live for the call.
+#[allow(unused_variables)]
+pub trait Visitor {
+    fn enter_headline(&mut self, headline: &Headline) {}
+    /// Called after the headline's title, section and children.
+    fn exit_headline(&mut self, headline: &Headline) {}
+    fn enter_content(&mut self, content: &Content) {}
+    fn exit_content(&mut self, content: &Content) {}
+    fn enter_list_item(&mut self, item: &ListItem) {}
+    fn exit_list_item(&mut self, item: &ListItem) {}
+    fn enter_object(&mut self, object: &Object) {}
+    /// Called after the objects inside markup or a link description.
+    fn exit_object(&mut self, object: &Object) {}
+}
+
+impl Document {
+    /// Visit every headline, element, list item and object in the document.
+    pub fn walk<V: Visitor>(&self, visitor: &mut V) {
+        if let Some(section) = self.first_section() {
+            walk_contents(section.contents(), visitor);
+        }
+        for headline in self.headlines() {
+            walk_headline(headline, visitor);
+        }
+    }
+
+    /// Iterate over every headline, element and list item in the document,
+    /// depth-first, in the order they appear in the text.
+    pub fn descendants(&self) -> Descendants<'_> {
+        let mut stack = Vec::new();
+        push_headlines(&mut stack, self.headlines());
+        if let Some(section) = self.first_section() {
+            push_contents(&mut stack, section.contents());
+        }
+        Descendants { stack }
+    }
+}
+
+fn walk_headline<V: Visitor>(headline: &Headline, visitor: &mut V) {
+    visitor.enter_headline(headline);
+    walk_objects(&headline.title_objects(), visitor);
+    if let Some(section) = headline.section() {
+        walk_contents(section.contents(), visitor);
+    }
+    for child in headline.children() {
+        walk_headline(child, visitor);
+    }
+    visitor.exit_headline(headline);
+}
+
+fn walk_contents<V: Visitor>(contents: &[Content], visitor: &mut V) {
+    for content in contents {
+        visitor.enter_content(content);
+        if let Content::Element(::Element::Paragraph(ref paragraph)) = *content {
+            walk_objects(&paragraph.objects(), visitor);
+        }
+        let (children, items) = content.children();
+        walk_contents(children, visitor);
+        for item in items {
+            visitor.enter_list_item(item);
+            walk_contents(item.contents(), visitor);
+            visitor.exit_list_item(item);
+        }
+        visitor.exit_content(content);
+    }
+}
+
+fn walk_objects<V: Visitor>(objects: &[Object], visitor: &mut V) {
+    for object in objects {
+        visitor.enter_object(object);
+        match *object {
+            Object::Bold(ref inner) | Object::Italic(ref inner)
+            | Object::Underline(ref inner) | Object::StrikeThrough(ref inner) => {
+                walk_objects(inner, visitor);
+            }
+            Object::Link(ref link) => walk_objects(link.description().unwrap_or(&[]), visitor),
+            Object::Subscript(ref script) | Object::Superscript(ref script) => {
+                walk_objects(script.contents(), visitor);
+            }
+            _ => {}
+        }
+        visitor.exit_object(object);
+    }
+}
+
+/// The iterator returned by `Document::descendants`.
+pub struct Descendants<'a> {
+    /// The nodes left to visit, the next one last.
+    stack: Vec<Node<'a>>,
+}
+
+fn push_headlines<'a>(stack: &mut Vec<Node<'a>>, headlines: &'a [Headline]) {
+    stack.extend(headlines.iter().rev().map(Node::Headline));
+}
+
+fn push_contents<'a>(stack: &mut Vec<Node<'a>>, contents: &'a [Content]) {
+    stack.extend(contents.iter().rev().map(Node::Content));
+}
+
+impl<'a> Iterator for Descendants<'a> {
+    type Item = Node<'a>;
+
+    fn next(&mut self) -> Option<Node<'a>> {
+        let node = self.stack.pop()?;
+        match node {
+            Node::Headline(headline) => {
+                push_headlines(&mut self.stack, headline.children());
+                if let Some(section) = headline.section() {
+                    push_contents(&mut self.stack, section.contents());
+                }
+            }
+            Node::Content(content) => {
+                let (children, items) = content.children();
+                self.stack.extend(items.iter().rev().map(Node::ListItem));
+                push_contents(&mut self.stack, children);
+            }
+            Node::ListItem(item) => push_contents(&mut self.stack, item.contents()),
+        }
+        Some(node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use DocumentParser;
+
+    #[derive(Default)]
+    struct Outline {
+        events: Vec<String>,
+    }
+
+    impl Visitor for Outline {
+        fn enter_headline(&mut self, headline: &Headline) {
+            self.events.push(format!("<{}", headline.title()));
+        }
+
+        fn exit_headline(&mut self, headline: &Headline) {
+            self.events.push(format!("{}>", headline.title()));
+        }
+
+        fn enter_list_item(&mut self, _: &ListItem) {
+            self.events.push("item".into());
+        }
+
+        fn enter_object(&mut self, object: &Object) {
+            match *object {
+                Object::Text(ref text) => self.events.push(text.clone()),
+                Object::Bold(_) => self.events.push("<b".into()),
+                _ => {}
+            }
+        }
+
+        fn exit_object(&mut self, object: &Object) {
+            if let Object::Bold(_) = *object {
+                self.events.push("b>".into());
+            }
+        }
+    }
+
+    #[test]
+    fn test_walk() {
+        let doc = DocumentParser::new().parse("Intro
+* A *bold*
+- one
+** B
+* C").unwrap();
+        let mut outline = Outline::default();
+        doc.walk(&mut outline);
+        assert_eq!(outline.events, ["Intro", "<A *bold*", "A ", "<b", "bold", "b>",
+                                    "item", "one", "<B", "B", "B>", "A *bold*>",
+                                    "<C", "C", "C>"]);
+
+        let kinds: Vec<_> = doc.descendants().map(|node| match node {
+            Node::Headline(headline) => headline.title().to_string(),
+            Node::Content(_) => "content".into(),
+            Node::ListItem(_) => "item".into(),
+        }).collect();
+        assert_eq!(kinds, ["content", "A *bold*", "content", "item", "content", "B", "C"]);
+    }
+}
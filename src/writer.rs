@@ -0,0 +1,360 @@
+use {Document, Headline, TimestampStyle};
+
+/// How `to_org_string_with` writes timestamps on planning lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimestampFormat {
+    /// Keep planning lines as they were written.
+    AsWritten,
+    /// Rewrite planning lines the way org writes them, with English day
+    /// names, in the order CLOSED, DEADLINE, SCHEDULED.
+    Normalized,
+    /// Rewrite planning lines like `Normalized`, with timestamps in the
+    /// given style. Org only reads back org styles, with or without day
+    /// names.
+    Styled(TimestampStyle),
+}
+
+/// Formatting options for writing documents back out as org text, see
+/// `Document::to_org_string_with`. The default writes the same text as
+/// `Document::to_org_string`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WriterConfig {
+    tag_column: i32,
+    blank_lines: Option<usize>,
+    property_order: Vec<String>,
+    sort_properties: bool,
+    sort_tags: bool,
+    timestamp_format: TimestampFormat,
+}
+
+impl Default for WriterConfig {
+    fn default() -> Self {
+        WriterConfig::new()
+    }
+}
+
+impl WriterConfig {
+    pub fn new() -> Self {
+        WriterConfig {
+            tag_column: 0,
+            blank_lines: None,
+            property_order: Vec::new(),
+            sort_properties: false,
+            sort_tags: false,
+            timestamp_format: TimestampFormat::AsWritten,
+        }
+    }
+
+    /// A preset by name:
+    ///
+    /// - `emacs-default`: tags aligned to end at column 77, like org's default
+    ///   `org-tags-column`, with everything else kept as written.
+    /// - `compact`: tags a space after the title, no blank lines before
+    ///   headlines, and normalized timestamps.
+    /// - `doom`: tags aligned to end at column 80, one blank line before each
+    ///   headline, `ID` as the first property, and normalized timestamps.
+    pub fn profile(name: &str) -> Option<WriterConfig> {
+        let config = WriterConfig::new();
+        Some(match name {
+            "emacs-default" => config.tag_column(-77),
+            "compact" => config.blank_lines(Some(0))
+                .timestamp_format(TimestampFormat::Normalized),
+            "doom" => config.tag_column(-80)
+                .blank_lines(Some(1))
+                .property_order(vec!["ID"])
+                .timestamp_format(TimestampFormat::Normalized),
+            _ => return None,
+        })
+    }
+
+    /// Where tags go on headline lines, like `org-tags-column`: a positive
+    /// column starts them there, a negative one aligns them to end there,
+    /// and for 0 (the default) they follow the title after a space. Tags
+    /// that don't fit also follow after a space.
+    pub fn tag_column(mut self, column: i32) -> Self {
+        self.tag_column = column;
+        self
+    }
+
+    /// How many blank lines to leave before each headline, or `None` to keep
+    /// those in the text.
+    pub fn blank_lines(mut self, lines: Option<usize>) -> Self {
+        self.blank_lines = lines;
+        self
+    }
+
+    /// Properties to write first in property drawers, in this order. The
+    /// others follow as written.
+    pub fn property_order<S: Into<String>>(mut self, keys: Vec<S>) -> Self {
+        self.property_order = keys.into_iter().map(|key| key.into()).collect();
+        self
+    }
+
+    /// Write the properties that `property_order` doesn't place sorted by
+    /// key, ignoring case, rather than as written, so that drawers come out
+    /// the same whatever order their properties were set in.
+    pub fn sort_properties(mut self, sort: bool) -> Self {
+        self.sort_properties = sort;
+        self
+    }
+
+    /// Write tags sorted rather than in the order they were added.
+    pub fn sort_tags(mut self, sort: bool) -> Self {
+        self.sort_tags = sort;
+        self
+    }
+
+    pub fn timestamp_format(mut self, format: TimestampFormat) -> Self {
+        self.timestamp_format = format;
+        self
+    }
+
+    /// The headline line of `headline`, without a line ending.
+    fn headline_line(&self, headline: &Headline) -> String {
+        let mut line = "*".repeat(headline.level as usize);
+        if let Some(ref keyword) = headline.keyword {
+            line.push(' ');
+            line.push_str(keyword);
+        }
+        if let Some(priority) = headline.priority {
+            line.push_str(&format!(" [#{}]", priority));
+        }
+        if headline.commented {
+            line.push_str(" COMMENT");
+        }
+        if !headline.title.is_empty() {
+            line.push(' ');
+            line.push_str(&headline.title);
+        }
+        if !headline.tags.is_empty() {
+            let mut tags: Vec<&str> = headline.tags.iter().map(|tag| &tag[..]).collect();
+            if self.sort_tags {
+                tags.sort_unstable();
+            }
+            let tags = format!(":{}:", tags.join(":"));
+            let width = line.chars().count();
+            let start = if self.tag_column < 0 {
+                self.tag_column.unsigned_abs() as usize
+                    - tags.chars().count().min(self.tag_column.unsigned_abs() as usize)
+            } else {
+                self.tag_column as usize
+            };
+            line.push_str(&" ".repeat(start.saturating_sub(width).max(1)));
+            line.push_str(&tags);
+        }
+        line
+    }
+
+    /// The section text of `headline`, with its planning line and property
+    /// drawer rewritten if the options ask for it.
+    fn section_text(&self, headline: &Headline) -> String {
+        let section = match headline.section {
+            Some(ref section) => section,
+            None => return String::new(),
+        };
+        let text = &section.text;
+        let reorder = !self.property_order.is_empty() || self.sort_properties;
+        if self.timestamp_format == TimestampFormat::AsWritten && !reorder {
+            return text.clone();
+        }
+        let mut lines: Vec<String> = text.lines().map(String::from).collect();
+        let mut start = 0;
+        let planning = &headline.planning;
+        if !planning.is_empty() && planning.span().start == section.span.start {
+            let style = match self.timestamp_format {
+                TimestampFormat::AsWritten => None,
+                TimestampFormat::Normalized => Some(TimestampStyle::org()),
+                TimestampFormat::Styled(style) => Some(style),
+            };
+            if let Some(style) = style {
+                let indent = lines[0].len() - lines[0].trim_start().len();
+                let mut line = lines[0][..indent].to_string();
+                let slots = [("CLOSED", planning.closed()), ("DEADLINE", planning.deadline()),
+                             ("SCHEDULED", planning.scheduled())];
+                let parts: Vec<_> = slots.iter()
+                    .filter_map(|&(name, timestamp)| {
+                        let timestamp = timestamp?.format(style);
+                        Some(format!("{}: {}", name, timestamp))
+                    })
+                    .collect();
+                line.push_str(&parts.join(" "));
+                lines[0] = line;
+            }
+            start = 1;
+        }
+        let properties = &headline.properties;
+        if reorder && !properties.is_empty()
+            && lines.get(start).is_some_and(|line| line.trim() == ":PROPERTIES:") {
+            let end = lines[start..].iter()
+                .position(|line| line.trim().eq_ignore_ascii_case(":END:"))
+                .map_or(start, |end| start + end);
+            let mut entries: Vec<_> = lines[start + 1..end].to_vec();
+            // Sorting is stable, so `KEY+` lines stay after their `KEY`.
+            let rank = |line: &String| {
+                let key = line.trim().trim_start_matches(':').split(':').next().unwrap_or("");
+                let key = key.trim_end_matches('+');
+                let first = self.property_order.iter()
+                    .position(|first| first.eq_ignore_ascii_case(key))
+                    .unwrap_or(self.property_order.len());
+                (first, if self.sort_properties { key.to_lowercase() } else { String::new() })
+            };
+            entries.sort_by_key(rank);
+            lines.splice(start + 1..end, entries);
+        }
+        let mut out = lines.join("\n");
+        if text.ends_with('\n') {
+            out.push('\n');
+        }
+        out
+    }
+
+    fn write_headline(&self, headline: &Headline, out: &mut String) {
+        self.before_headline(out);
+        out.push_str(&self.headline_line(headline));
+        out.push('\n');
+        out.push_str(&self.section_text(headline));
+        for child in &headline.headlines {
+            self.write_headline(child, out);
+        }
+    }
+
+    /// End `out` with the configured number of blank lines, unless a
+    /// headline would start the text.
+    fn before_headline(&self, out: &mut String) {
+        if let Some(lines) = self.blank_lines {
+            if out.is_empty() {
+                return;
+            }
+            let trimmed = out.trim_end().len();
+            out.truncate(trimmed);
+            if !out.is_empty() {
+                out.push_str(&"\n".repeat(lines + 1));
+            }
+        }
+    }
+}
+
+impl Document {
+    /// Write the document back out as org text, formatted according to
+    /// `config`.
+    pub fn to_org_string_with(&self, config: &WriterConfig) -> String {
+        let mut out = String::new();
+        if let Some(ref section) = self.first_section {
+            out.push_str(&section.text);
+        }
+        for headline in &self.headlines {
+            config.write_headline(headline, &mut out);
+        }
+        out
+    }
+}
+
+impl Headline {
+    /// Write the headline and its subtree back out as org text, formatted
+    /// according to `config`.
+    pub fn to_org_string_with(&self, config: &WriterConfig) -> String {
+        let mut out = String::new();
+        config.write_headline(self, &mut out);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::{env, fs};
+
+    use super::*;
+    use DocumentParser;
+
+    #[test]
+    fn test_writer_profiles() {
+        let doc = DocumentParser::new().todo_keywords(vec!["TODO"]).parse("#+TITLE: Styles
+* TODO Plan :work:
+SCHEDULED: <2024-03-01>  DEADLINE: <2024-03-08 Fri>
+:PROPERTIES:
+:EFFORT: 1:00
+:ID: abc
+:END:
+Body.
+
+
+** Child
+* Next
+").unwrap();
+        let compact = WriterConfig::profile("compact").unwrap();
+        assert_eq!(doc.to_org_string_with(&compact), "#+TITLE: Styles
+* TODO Plan :work:
+DEADLINE: <2024-03-08 Fri> SCHEDULED: <2024-03-01 Fri>
+:PROPERTIES:
+:EFFORT: 1:00
+:ID: abc
+:END:
+Body.
+** Child
+* Next
+");
+        let doom = WriterConfig::profile("doom").unwrap();
+        let text = doc.headlines()[0].to_org_string_with(&doom);
+        assert_eq!(text, format!("* TODO Plan{}:work:
+DEADLINE: <2024-03-08 Fri> SCHEDULED: <2024-03-01 Fri>
+:PROPERTIES:
+:ID: abc
+:EFFORT: 1:00
+:END:
+Body.
+
+** Child
+", " ".repeat(63)));
+        assert_eq!(text.lines().next().unwrap().len(), 80);
+        assert_eq!(doc.to_org_string_with(&WriterConfig::new()), doc.to_org_string());
+        assert!(WriterConfig::profile("spacemacs").is_none());
+
+        let style = TimestampStyle::org().day_names(false);
+        let config = WriterConfig::new().timestamp_format(TimestampFormat::Styled(style));
+        let text = doc.headlines()[0].to_org_string_with(&config);
+        assert_eq!(text.lines().nth(1), Some("DEADLINE: <2024-03-08> SCHEDULED: <2024-03-01>"));
+    }
+
+    /// Write every `tests/golden/NAME.org` with each configuration and
+    /// compare the text with `NAME.CONFIG.org` next to it. Run with
+    /// `UPDATE_GOLDEN=1` set to write those files instead.
+    #[test]
+    fn test_golden() {
+        let configs = [
+            ("default", WriterConfig::new()),
+            ("emacs-default", WriterConfig::profile("emacs-default").unwrap()),
+            ("compact", WriterConfig::profile("compact").unwrap()),
+            ("doom", WriterConfig::profile("doom").unwrap()),
+            ("sorted", WriterConfig::new().sort_tags(true).sort_properties(true)),
+        ];
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+        let mut inputs: Vec<_> = fs::read_dir(&dir).unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| {
+                let name = path.file_name().unwrap().to_string_lossy();
+                name.ends_with(".org") && name.matches('.').count() == 1
+            })
+            .collect();
+        inputs.sort();
+        assert!(!inputs.is_empty());
+        let update = env::var_os("UPDATE_GOLDEN").is_some();
+        for input in inputs {
+            let text = fs::read_to_string(&input).unwrap();
+            let doc = DocumentParser::new().parse(&text).unwrap();
+            for &(name, ref config) in &configs {
+                let written = doc.to_org_string_with(config);
+                let expected = input.with_extension(format!("{}.org", name));
+                // Writing the text again must not change it any further.
+                let again = DocumentParser::new().parse(&written).unwrap();
+                assert_eq!(again.to_org_string_with(config), written, "{}", expected.display());
+                if update {
+                    fs::write(&expected, &written).unwrap();
+                } else {
+                    let golden = fs::read_to_string(&expected).unwrap();
+                    assert_eq!(written, golden, "{}", expected.display());
+                }
+            }
+        }
+    }
+}